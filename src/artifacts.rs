@@ -0,0 +1,220 @@
+//! Saving what is needed to reproduce failed runs.
+
+use crate::{
+    compare::{self, DiffOptions},
+    units, RunObserver, RunResult, RunStatus,
+};
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// An observer writing the generated input of every failed run to
+/// `failing_input_<index>.txt` in a directory, along with `failing_output_<index>.txt`,
+/// `failing_expected_<index>.txt` and `failing_diff_<index>.txt` for wrong answers, and
+/// `timeout_output_<index>.txt` with what timed out runs printed until they were killed.
+pub struct FailureSaver {
+    dir: PathBuf,
+    /// Also save inputs the validator rejected, as `invalid_input_<index>.txt`.
+    save_invalid: bool,
+    /// Unchanged lines around every change in saved diffs.
+    diff_context: usize,
+    /// With deduplication, the run whose input was saved for every failure signature seen.
+    saved: Option<Mutex<HashMap<String, u64>>>,
+}
+
+impl FailureSaver {
+    /// Save into `dir`, creating it if needed. With `dedup`, only the first input of every
+    /// [`signature`] is saved.
+    pub fn new(dir: &Path, dedup: bool) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(FailureSaver {
+            dir: dir.to_path_buf(),
+            save_invalid: false,
+            diff_context: DiffOptions::default().context,
+            saved: dedup.then(Default::default),
+        })
+    }
+
+    /// Whether to also save the inputs the validator rejected.
+    pub fn save_invalid(mut self, save_invalid: bool) -> Self {
+        self.save_invalid = save_invalid;
+        self
+    }
+
+    /// Show `context` unchanged lines around every change in saved diffs.
+    pub fn diff_context(mut self, context: usize) -> Self {
+        self.diff_context = context;
+        self
+    }
+
+    /// The path the input of run `index` is saved to.
+    pub fn input_path(&self, index: u64) -> PathBuf {
+        self.dir.join(format!("failing_input_{}.txt", index))
+    }
+
+    /// Save what the timed out run of `result` printed until it was killed, as far as it was
+    /// kept, after a line saying when that was.
+    fn save_partial_output(&self, result: &RunResult) {
+        let mut content = format!(
+            "#tester run {} {} after {}, its output until then (partial)\n",
+            result.index,
+            result.status,
+            units::format_duration(result.duration)
+        )
+        .into_bytes();
+        for (stream, output) in [("stdout", &result.stdout), ("stderr", &result.stderr)] {
+            content.extend_from_slice(format!("#tester {}:\n", stream).as_bytes());
+            content.extend_from_slice(output);
+            if !output.is_empty() && !output.ends_with(b"\n") {
+                content.push(b'\n');
+            }
+        }
+        let path = self
+            .dir
+            .join(format!("timeout_output_{}.txt", result.index));
+        match fs::write(&path, content) {
+            Ok(()) if result.output_suppressed => {}
+            Ok(()) => notice!(
+                "#tester run {}: output until the timeout saved to {}",
+                result.index,
+                path.display()
+            ),
+            Err(err) => warn!(
+                "#tester run {}: cannot save the output to {}: {}",
+                result.index,
+                path.display(),
+                err
+            ),
+        }
+    }
+}
+
+/// What tells failures apart for `--dedup-failures`: where and why the run failed, and the
+/// last line it wrote to stderr, which usually names the crash.
+pub fn signature(result: &RunResult) -> String {
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    let last_line = stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default();
+    format!(
+        "{} | {}",
+        result
+            .failure
+            .as_ref()
+            .map(|failure| failure.to_string())
+            .unwrap_or_default(),
+        last_line.trim()
+    )
+}
+
+impl RunObserver for FailureSaver {
+    fn on_run(&self, result: &RunResult) {
+        let (Some(_), Some(input)) = (&result.failure, &result.input) else {
+            return;
+        };
+        if result.status == RunStatus::Skipped {
+            return;
+        }
+        if result.status == RunStatus::Invalid {
+            if self.save_invalid {
+                let path = self.dir.join(format!("invalid_input_{}.txt", result.index));
+                match fs::write(&path, input) {
+                    Ok(()) => notice!(
+                        "#tester run {}: invalid input saved to {}",
+                        result.index,
+                        path.display()
+                    ),
+                    Err(err) => warn!(
+                        "#tester run {}: cannot save invalid input to {}: {}",
+                        result.index,
+                        path.display(),
+                        err
+                    ),
+                }
+            }
+            return;
+        }
+        if let Some(saved) = &self.saved {
+            let mut saved = saved.lock().unwrap();
+            let first = *saved.entry(signature(result)).or_insert(result.index);
+            if first != result.index {
+                if result.output_suppressed {
+                    return;
+                }
+                notice!(
+                    "#tester run {}: same failure as run {}, input not saved",
+                    result.index,
+                    first
+                );
+                return;
+            }
+        }
+        let path = self.input_path(result.index);
+        let truncated = if result.input_truncated {
+            ", only the start of a longer input"
+        } else {
+            ""
+        };
+        match fs::write(&path, input) {
+            // The count of suppressed failures says where they are.
+            Ok(()) if result.output_suppressed => {}
+            Ok(()) => notice!(
+                "#tester run {}: failing input saved to {} ({} bytes{})",
+                result.index,
+                path.display(),
+                input.len(),
+                truncated
+            ),
+            Err(err) => warn!(
+                "#tester run {}: cannot save failing input to {}: {}",
+                result.index,
+                path.display(),
+                err
+            ),
+        }
+        if matches!(
+            result.status,
+            RunStatus::TimedOut | RunStatus::RelativeTimeout
+        ) {
+            self.save_partial_output(result);
+        }
+        let Some(comparison) = result.comparison.as_ref().filter(|c| !c.matches()) else {
+            return;
+        };
+        let options = DiffOptions {
+            context: self.diff_context,
+            max_hunks: None,
+        };
+        let diff = compare::unified_diff(&comparison.actual, &comparison.expected, options)
+            + &compare::tolerance_notes(
+                &comparison.actual,
+                &comparison.expected,
+                &comparison.tolerance,
+                usize::MAX,
+            );
+        let files = [
+            ("output", &comparison.actual[..]),
+            ("expected", &comparison.expected[..]),
+            ("diff", diff.as_bytes()),
+        ];
+        for (what, content) in files {
+            let path = self
+                .dir
+                .join(format!("failing_{}_{}.txt", what, result.index));
+            if let Err(err) = fs::write(&path, content) {
+                warn!(
+                    "#tester run {}: cannot save {} to {}: {}",
+                    result.index,
+                    what,
+                    path.display(),
+                    err
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Comparing a session with the summary of an earlier one, to catch regressions.
+
+use crate::{objective::Objective, SessionReport};
+use serde::{Deserialize, Serialize};
+
+/// What a session is compared with its baseline on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// Failed runs divided by runs. Its change is in absolute terms.
+    FailureRate,
+    /// The average score of the passed runs. Its change is relative.
+    MeanScore,
+    /// The average wall-clock seconds of a run. Its change is relative.
+    MeanDuration,
+}
+
+impl Metric {
+    /// How the metric is called in the summary.
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::FailureRate => "failure rate",
+            Metric::MeanScore => "mean score",
+            Metric::MeanDuration => "mean duration",
+        }
+    }
+}
+
+/// How one metric changed from the baseline.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Delta {
+    /// The metric compared.
+    pub metric: Metric,
+    /// Its value in the baseline.
+    pub baseline: f64,
+    /// Its value in this session.
+    pub current: f64,
+    /// The change, negative when it went down: the difference for the failure rate, relative
+    /// to the baseline for the others, `None` when the baseline is zero.
+    pub change: Option<f64>,
+    /// Whether the change is worse than the threshold allows.
+    pub regression: bool,
+}
+
+/// How a session compares with its baseline, metric by metric.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BaselineComparison {
+    /// The metrics both sessions have, in the order of [`Metric`].
+    pub deltas: Vec<Delta>,
+}
+
+impl BaselineComparison {
+    /// Compare `current` with `baseline`. A metric regressed when it got worse by more than
+    /// `threshold`, e.g. `0.05` for 5% slower runs or 5 more failures in 100 runs. Higher
+    /// scores are better as `objective` says.
+    pub fn new(
+        baseline: &SessionReport,
+        current: &SessionReport,
+        threshold: f64,
+        objective: Objective,
+    ) -> Self {
+        let failure_rate = |report: &SessionReport| {
+            (report.run_times > 0).then(|| report.fail_times as f64 / report.run_times as f64)
+        };
+        let metrics = [
+            (
+                Metric::FailureRate,
+                failure_rate(baseline),
+                failure_rate(current),
+            ),
+            (
+                Metric::MeanScore,
+                baseline.scores.mean(),
+                current.scores.mean(),
+            ),
+            (
+                Metric::MeanDuration,
+                baseline.times.mean(),
+                current.times.mean(),
+            ),
+        ];
+        let deltas = metrics
+            .into_iter()
+            .filter_map(|(metric, baseline, current)| {
+                let (baseline, current) = (baseline?, current?);
+                let change = match metric {
+                    Metric::FailureRate => Some(current - baseline),
+                    _ if baseline == 0.0 => None,
+                    _ => Some((current - baseline) / baseline.abs()),
+                };
+                let worsening = match metric {
+                    Metric::MeanScore if objective == Objective::Maximize => {
+                        change.map(|change| -change)
+                    }
+                    // Anything is infinitely worse than zero failures or zero seconds.
+                    _ => change.or((current > baseline).then_some(f64::INFINITY)),
+                };
+                Some(Delta {
+                    metric,
+                    baseline,
+                    current,
+                    change,
+                    regression: worsening.is_some_and(|worsening| worsening > threshold),
+                })
+            })
+            .collect();
+        BaselineComparison { deltas }
+    }
+
+    /// Whether any metric regressed.
+    pub fn regressed(&self) -> bool {
+        self.deltas.iter().any(|delta| delta.regression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A report of `runs` runs of which `failed` failed, the others scoring `score` in 1s.
+    fn report(runs: u64, failed: u64, score: f64) -> SessionReport {
+        let mut report = SessionReport::new(1);
+        report.run_times = runs;
+        report.fail_times = failed;
+        for _ in failed..runs {
+            report.scores.add(score);
+        }
+        for _ in 0..runs {
+            report.times.add(1.0);
+        }
+        report
+    }
+
+    fn regressions(baseline: &SessionReport, current: &SessionReport) -> Vec<Metric> {
+        BaselineComparison::new(baseline, current, 0.05, Objective::Maximize)
+            .deltas
+            .into_iter()
+            .filter(|delta| delta.regression)
+            .map(|delta| delta.metric)
+            .collect()
+    }
+
+    #[test]
+    fn equal() {
+        let comparison = BaselineComparison::new(
+            &report(100, 10, 50.0),
+            &report(100, 10, 50.0),
+            0.05,
+            Objective::Maximize,
+        );
+        assert_eq!(comparison.deltas.len(), 3);
+        assert!(comparison
+            .deltas
+            .iter()
+            .all(|delta| delta.change == Some(0.0) && !delta.regression));
+        assert!(!comparison.regressed());
+    }
+
+    #[test]
+    fn worse_within_threshold() {
+        // 4 more failures in 100 runs, and a 4% lower score.
+        assert_eq!(
+            regressions(&report(100, 10, 50.0), &report(100, 14, 48.0)),
+            []
+        );
+        let lower = BaselineComparison::new(
+            &report(10, 0, 50.0),
+            &report(10, 0, 52.0),
+            0.05,
+            Objective::Minimize,
+        );
+        assert!(!lower.regressed());
+    }
+
+    #[test]
+    fn worse_beyond_threshold() {
+        assert_eq!(
+            regressions(&report(100, 10, 50.0), &report(100, 20, 50.0)),
+            [Metric::FailureRate]
+        );
+        assert_eq!(
+            regressions(&report(100, 10, 50.0), &report(100, 10, 45.0)),
+            [Metric::MeanScore]
+        );
+        let higher = BaselineComparison::new(
+            &report(10, 0, 50.0),
+            &report(10, 0, 55.0),
+            0.05,
+            Objective::Minimize,
+        );
+        assert!(higher.regressed());
+        // Anything is worse than a score of zero when lower is better.
+        let from_zero = BaselineComparison::new(
+            &report(10, 0, 0.0),
+            &report(10, 0, 0.1),
+            0.05,
+            Objective::Minimize,
+        );
+        assert!(from_zero.regressed());
+    }
+}
@@ -0,0 +1,111 @@
+//! Judging the output of a run with a separate checker command, for problems with many correct
+//! answers.
+
+use crate::command::Step;
+use std::{env, fs, io, path::PathBuf, process};
+
+/// The placeholder replaced by the path of the run's input in the checker's arguments.
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+/// The placeholder replaced by the path of the last step's stdout.
+pub const OUTPUT_PLACEHOLDER: &str = "{output}";
+/// The placeholder replaced by the path of the reference solution's stdout.
+pub const ANSWER_PLACEHOLDER: &str = "{answer}";
+
+/// A command judging the output of every iteration, given its input, output and, with a
+/// reference solution, the reference's output as files. It accepts the output by exiting
+/// successfully.
+#[derive(Clone, Debug)]
+pub struct Checker {
+    /// The checker command.
+    pub step: Step,
+    /// Leave the files of runs the checker rejected behind instead of removing them.
+    pub keep_failed: bool,
+}
+
+impl Checker {
+    /// Whether the checker's arguments need the reference's output.
+    pub fn uses_answer(&self) -> bool {
+        self.step
+            .args
+            .iter()
+            .any(|arg| arg.contains(ANSWER_PLACEHOLDER))
+    }
+
+    /// The checker command for `files`: with the paths substituted for the placeholders, or,
+    /// when there are none, appended as input, output and answer, testlib style.
+    pub fn step_for(&self, files: &CheckFiles) -> Step {
+        let mut step = self.step.clone();
+        let placeholders = [INPUT_PLACEHOLDER, OUTPUT_PLACEHOLDER, ANSWER_PLACEHOLDER];
+        let has_placeholder = step
+            .args
+            .iter()
+            .any(|arg| placeholders.iter().any(|p| arg.contains(p)));
+        let paths = [
+            Some(&files.input),
+            Some(&files.output),
+            files.answer.as_ref(),
+        ];
+        if has_placeholder {
+            for arg in &mut step.args {
+                for (placeholder, path) in placeholders.iter().zip(&paths) {
+                    if let Some(path) = path {
+                        *arg = arg.replace(placeholder, &path.display().to_string());
+                    }
+                }
+            }
+        } else {
+            step.args.extend(
+                paths
+                    .iter()
+                    .flatten()
+                    .map(|path| path.display().to_string()),
+            );
+        }
+        step
+    }
+}
+
+/// The files a checker reads, in a directory of their own under the system's temporary
+/// directory.
+#[derive(Debug)]
+pub struct CheckFiles {
+    /// The directory holding the files.
+    pub dir: PathBuf,
+    /// The run's input.
+    pub input: PathBuf,
+    /// The last step's stdout.
+    pub output: PathBuf,
+    /// The reference's stdout, if there is a reference.
+    pub answer: Option<PathBuf>,
+}
+
+impl CheckFiles {
+    /// Write the files of run `index`.
+    pub fn write(
+        index: u64,
+        input: &[u8],
+        output: &[u8],
+        answer: Option<&[u8]>,
+    ) -> io::Result<Self> {
+        let dir = env::temp_dir().join(format!("tester-check-{}-{}", process::id(), index));
+        fs::create_dir_all(&dir)?;
+        let write = |name: &str, content: &[u8]| -> io::Result<PathBuf> {
+            let path = dir.join(name);
+            fs::write(&path, content)?;
+            Ok(path)
+        };
+        Ok(CheckFiles {
+            input: write("input.txt", input)?,
+            output: write("output.txt", output)?,
+            answer: answer
+                .map(|answer| write("answer.txt", answer))
+                .transpose()?,
+            dir,
+        })
+    }
+
+    /// Remove the files and their directory.
+    pub fn remove(self) -> io::Result<()> {
+        fs::remove_dir_all(&self.dir)
+    }
+}
@@ -0,0 +1,157 @@
+//! The commands run in every iteration: parsing them from `--exec`, finding their
+//! executables and printing them back.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// One command of an iteration. An iteration runs its steps in order and fails at the first
+/// one that fails.
+#[derive(Clone, Debug)]
+pub struct Step {
+    /// The program to run, a path or a name looked up in `PATH`.
+    pub exec: String,
+    /// Arguments passed to the program as they are.
+    pub args: Vec<String>,
+}
+
+impl Step {
+    /// The program followed by its arguments.
+    pub fn argv(&self) -> Vec<String> {
+        std::iter::once(&self.exec)
+            .chain(&self.args)
+            .cloned()
+            .collect()
+    }
+
+    /// A [`Command`] running this step.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(&self.exec);
+        command.args(&self.args);
+        command
+    }
+
+    /// This step run by `wrapper`: the wrapper's program and arguments, then this step's.
+    pub fn wrapped_in(&self, wrapper: &Step) -> Step {
+        Step {
+            exec: wrapper.exec.clone(),
+            args: wrapper
+                .args
+                .iter()
+                .chain(self.argv().iter())
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// The step as a line that can be pasted into a shell.
+    pub fn command_line(&self) -> String {
+        std::iter::once(&self.exec)
+            .chain(&self.args)
+            .map(|arg| shell_quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Parse an `--exec` value into a step, splitting words the way a POSIX shell would for
+/// quotes and backslashes (no expansions are performed).
+pub fn parse_step(s: &str) -> Result<Step, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated single quote in `{}`", s)),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(format!("unterminated double quote in `{}`", s)),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(format!("unterminated double quote in `{}`", s)),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(format!("trailing backslash in `{}`", s)),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+
+    let mut words = words.into_iter();
+    let exec = words.next().ok_or("empty command")?;
+    Ok(Step {
+        exec,
+        args: words.collect(),
+    })
+}
+
+/// All steps of an iteration as one shell-like line.
+pub fn pipeline_line(steps: &[Step]) -> String {
+    steps
+        .iter()
+        .map(Step::command_line)
+        .collect::<Vec<_>>()
+        .join(" && ")
+}
+
+/// Quote `arg` for display so the printed command line can be pasted into a shell.
+pub fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Look up `exec` the way the shell would: paths containing a separator are taken as is,
+/// bare names are searched in `PATH`.
+pub fn resolve_executable(exec: &str) -> Option<PathBuf> {
+    let is_executable = |path: &Path| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            path.metadata()
+                .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        }
+        #[cfg(not(unix))]
+        {
+            path.is_file()
+        }
+    };
+
+    if exec.contains(std::path::MAIN_SEPARATOR) || exec.contains('/') {
+        let path = PathBuf::from(exec);
+        return is_executable(&path).then_some(path);
+    }
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(exec))
+        .find(|candidate| is_executable(candidate))
+}
@@ -0,0 +1,773 @@
+//! Comparing the output of a run with the expected output and showing the difference.
+
+/// Where two outputs first differ.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// Line of the first differing byte, counting from 1.
+    pub line: usize,
+    /// Column of the first differing byte in its line, counting from 1.
+    pub column: usize,
+    /// Offset of the first differing byte.
+    pub offset: usize,
+}
+
+/// Where `actual` first differs from `expected`, byte by byte, `None` if they are equal.
+pub fn first_difference(actual: &[u8], expected: &[u8]) -> Option<Difference> {
+    if actual == expected {
+        return None;
+    }
+    let offset = actual
+        .iter()
+        .zip(expected)
+        .position(|(a, e)| a != e)
+        .unwrap_or(actual.len().min(expected.len()));
+    let before = &actual[..offset];
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    Some(Difference {
+        line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+        column: offset - line_start + 1,
+        offset,
+    })
+}
+
+/// How much of a difference [`unified_diff`] shows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Unchanged lines shown around every change.
+    pub context: usize,
+    /// Show only the first this many hunks.
+    pub max_hunks: Option<usize>,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            context: 3,
+            max_hunks: Some(3),
+        }
+    }
+}
+
+/// The most edits [`unified_diff`] searches for a shortest edit script before giving up and
+/// replacing the whole differing middle.
+const MAX_EDITS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// A shortest edit script turning `old` into `new`, found with Myers' algorithm, or `None` if
+/// it takes more than `max_edits` edits.
+fn shortest_edits(old: &[&[u8]], new: &[&[u8]], max_edits: usize) -> Option<Vec<Edit>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // The furthest x of every diagonal k in -d..=d after d edits, for backtracking.
+    let mut trace: Vec<Vec<isize>> = vec![];
+    for d in 0..=(n + m).min(max_edits as isize) {
+        for k in (-d..=d).step_by(2) {
+            let i = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+    // `trace[d][k + d]` is the furthest x on diagonal k after d edits.
+    let furthest = |d: isize, k: isize| trace[d as usize][(k + d) as usize];
+    let mut edits = vec![];
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && furthest(d - 1, k - 1) < furthest(d - 1, k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = furthest(d - 1, prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if x == prev_x {
+            edits.push(Edit::Insert);
+            y -= 1;
+        } else {
+            edits.push(Edit::Delete);
+            x -= 1;
+        }
+    }
+    edits.extend((0..x).map(|_| Edit::Keep));
+    edits.reverse();
+    edits
+}
+
+/// The edits turning the lines of `old` into those of `new`: the common first and last lines
+/// are kept, and the middle is diffed, or replaced as a whole if it differs too much.
+fn line_edits(old: &[&[u8]], new: &[&[u8]]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(o, n)| o == n).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(o, n)| o == n)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+    let middle = shortest_edits(old_middle, new_middle, MAX_EDITS).unwrap_or_else(|| {
+        let deleted = old_middle.iter().map(|_| Edit::Delete);
+        deleted
+            .chain(new_middle.iter().map(|_| Edit::Insert))
+            .collect()
+    });
+    let kept = |count| (0..count).map(|_| Edit::Keep);
+    kept(prefix).chain(middle).chain(kept(suffix)).collect()
+}
+
+/// A hunk range, `start,count` with `start` counting from 1, as in `diff -u`.
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", start - 1),
+        1 => start.to_string(),
+        count => format!("{},{}", start, count),
+    }
+}
+
+fn push_line(out: &mut String, sign: char, line: &[u8]) {
+    let text = String::from_utf8_lossy(line);
+    match text.strip_suffix('\n') {
+        Some(text) => *out += &format!("{}{}\n", sign, text),
+        None => *out += &format!("{}{}\n\\ No newline at end of file\n", sign, text),
+    }
+}
+
+/// Whether `bytes` looks like binary data rather than text.
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.contains(&0) || std::str::from_utf8(bytes).is_err()
+}
+
+/// The bytes of `bytes` around `offset`, in hex.
+fn hex_around(bytes: &[u8], offset: usize) -> String {
+    const SHOWN_BYTES: usize = 16;
+    let start = offset.saturating_sub(SHOWN_BYTES / 2).min(bytes.len());
+    let end = (start + SHOWN_BYTES).min(bytes.len());
+    let hex = bytes[start..end]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("[{}..{}] {}", start, end, hex)
+}
+
+/// A unified diff from `expected` to `actual`, the lines of `expected` prefixed with `-` and
+/// those of `actual` with `+`, empty if they are equal.
+///
+/// Binary outputs, which do not split into lines, are shown as the bytes around the first
+/// differing byte instead.
+pub fn unified_diff(actual: &[u8], expected: &[u8], options: DiffOptions) -> String {
+    let Some(difference) = first_difference(actual, expected) else {
+        return String::new();
+    };
+    if is_binary(actual) || is_binary(expected) {
+        return format!(
+            "binary outputs differ at byte offset {} (expected {} bytes, actual {} bytes)\n\
+             -{}\n+{}\n",
+            difference.offset,
+            expected.len(),
+            actual.len(),
+            hex_around(expected, difference.offset),
+            hex_around(actual, difference.offset)
+        );
+    }
+    let old = expected
+        .split_inclusive(|&b| b == b'\n')
+        .collect::<Vec<_>>();
+    let new = actual.split_inclusive(|&b| b == b'\n').collect::<Vec<_>>();
+    let edits = line_edits(&old, &new);
+
+    // Hunks as ranges of edits: every change with its context, merged when they touch.
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (index, _) in edits.iter().enumerate().filter(|(_, e)| **e != Edit::Keep) {
+        let start = index.saturating_sub(options.context);
+        let end = (index + 1 + options.context).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    // Line indices in `old` and `new` before every edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_line, mut new_line) = (0, 0);
+    for edit in &edits {
+        positions.push((old_line, new_line));
+        old_line += usize::from(*edit != Edit::Insert);
+        new_line += usize::from(*edit != Edit::Delete);
+    }
+    positions.push((old_line, new_line));
+
+    let mut out = String::from("--- expected\n+++ actual\n");
+    let shown = options.max_hunks.unwrap_or(hunks.len()).min(hunks.len());
+    for &(start, end) in &hunks[..shown] {
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        out += &format!(
+            "@@ -{} +{} @@\n",
+            hunk_range(old_start + 1, old_end - old_start),
+            hunk_range(new_start + 1, new_end - new_start)
+        );
+        for (edit, &(old_line, new_line)) in edits[start..end].iter().zip(&positions[start..end]) {
+            match edit {
+                Edit::Keep => push_line(&mut out, ' ', old[old_line]),
+                Edit::Delete => push_line(&mut out, '-', old[old_line]),
+                Edit::Insert => push_line(&mut out, '+', new[new_line]),
+            }
+        }
+    }
+    if hunks.len() > shown {
+        out += &format!("... {} more hunks\n", hunks.len() - shown);
+    }
+    out
+}
+
+/// The first `max_lines` lines of `bytes`, indented, for printing a whole output.
+pub fn excerpt(bytes: &[u8], max_lines: usize) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let lines = text.lines().collect::<Vec<_>>();
+    let mut out = String::new();
+    for line in lines.iter().take(max_lines) {
+        out += &format!("  {}\n", line);
+    }
+    if lines.len() > max_lines {
+        out += &format!("  ... {} more lines\n", lines.len() - max_lines);
+    }
+    out
+}
+
+/// The last `max_lines` lines of `bytes`, indented, for printing the end of an output.
+pub fn tail_excerpt(bytes: &[u8], max_lines: usize) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let lines = text.lines().collect::<Vec<_>>();
+    let skipped = lines.len().saturating_sub(max_lines);
+    let mut out = String::new();
+    if skipped > 0 {
+        out += &format!("  ... {} earlier lines\n", skipped);
+    }
+    for line in &lines[skipped..] {
+        out += &format!("  {}\n", line);
+    }
+    out
+}
+
+/// How loosely outputs are compared, byte for byte by default. See `--compare-ignore-ws`,
+/// `--compare-ignore-case` and `--compare-float-epsilon`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Tolerance {
+    /// Ignore whitespace at the start and end of lines, how much of it separates words, and
+    /// blank lines at the end.
+    pub ignore_whitespace: bool,
+    /// Compare letters regardless of case.
+    pub ignore_case: bool,
+    /// Let numbers differ by this much, absolutely or relative to the larger of the two.
+    pub float_epsilon: Option<f64>,
+}
+
+impl Tolerance {
+    /// Whether outputs are compared byte for byte.
+    pub fn is_exact(&self) -> bool {
+        *self == Tolerance::default()
+    }
+
+    /// Whether `actual` matches `expected`.
+    pub fn matches(&self, actual: &[u8], expected: &[u8]) -> bool {
+        actual == expected || (!self.is_exact() && first_mismatch(actual, expected, self).is_none())
+    }
+
+    /// The lines of `bytes` to compare.
+    fn lines<'a>(&self, bytes: &'a str) -> Vec<&'a str> {
+        let mut lines = bytes.split('\n').collect::<Vec<_>>();
+        if self.ignore_whitespace {
+            while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                lines.pop();
+            }
+        } else if lines.last() == Some(&"") {
+            // The end of the last line rather than a line.
+            lines.pop();
+        }
+        lines
+    }
+
+    fn normalize<'a>(&self, line: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.ignore_whitespace {
+            line.split_whitespace().collect::<Vec<_>>().join(" ").into()
+        } else {
+            line.into()
+        }
+    }
+
+    fn texts_match(&self, actual: &str, expected: &str) -> bool {
+        match self.ignore_case {
+            true => actual.to_lowercase() == expected.to_lowercase(),
+            false => actual == expected,
+        }
+    }
+
+    /// How the line `actual` compares with the line `expected`: the tokens that differ, each
+    /// with whether it is allowed and why.
+    fn compare_lines(&self, actual: &str, expected: &str) -> Vec<TokenVerdict> {
+        let (actual_line, expected_line) = (self.normalize(actual), self.normalize(expected));
+        let Some(epsilon) = self.float_epsilon else {
+            if actual_line == expected_line {
+                return vec![TokenVerdict::allowed(
+                    actual,
+                    expected,
+                    "whitespace is ignored",
+                )];
+            }
+            let why = if !self.texts_match(&actual_line, &expected_line) {
+                return vec![TokenVerdict::rejected(actual, expected, "the line differs")];
+            } else if actual.to_lowercase() == expected.to_lowercase() {
+                "case is ignored"
+            } else {
+                "whitespace and case are ignored"
+            };
+            return vec![TokenVerdict::allowed(actual, expected, why)];
+        };
+        let (actual_tokens, expected_tokens) = (tokenize(&actual_line), tokenize(&expected_line));
+        let mut verdicts = vec![];
+        for (a, e) in actual_tokens.iter().zip(&expected_tokens) {
+            let verdict = match (a, e) {
+                (Token::Number(a_value, a_text), Token::Number(e_value, e_text)) => {
+                    if a_text == e_text {
+                        continue;
+                    }
+                    let off = (a_value - e_value).abs();
+                    match numbers_match(*a_value, *e_value, epsilon) {
+                        true => TokenVerdict::allowed(
+                            a_text,
+                            e_text,
+                            &format!("off by {:.2e}, within {:e}", off, epsilon),
+                        ),
+                        false if off.is_finite() => TokenVerdict::rejected(
+                            a_text,
+                            e_text,
+                            &format!("off by {:.2e}, not within {:e}", off, epsilon),
+                        ),
+                        false => TokenVerdict::rejected(a_text, e_text, "not the same number"),
+                    }
+                }
+                (Token::Text(a), Token::Text(e)) => {
+                    if a == e {
+                        continue;
+                    }
+                    match self.texts_match(a, e) {
+                        true => TokenVerdict::allowed(a, e, "case is ignored"),
+                        false => TokenVerdict::rejected(a, e, "the text differs"),
+                    }
+                }
+                (a, e) => TokenVerdict::rejected(a.text(), e.text(), "a number and a text"),
+            };
+            verdicts.push(verdict);
+        }
+        let extra = if actual_tokens.len() > expected_tokens.len() {
+            let rest = &actual_tokens[expected_tokens.len()..];
+            Some((join_tokens(rest), String::new(), "only in the actual line"))
+        } else if expected_tokens.len() > actual_tokens.len() {
+            let rest = &expected_tokens[actual_tokens.len()..];
+            Some((
+                String::new(),
+                join_tokens(rest),
+                "only in the expected line",
+            ))
+        } else {
+            None
+        };
+        if let Some((a, e, why)) = extra {
+            verdicts.push(TokenVerdict::rejected(&a, &e, why));
+        }
+        if verdicts.is_empty() {
+            verdicts.push(TokenVerdict::allowed(
+                actual,
+                expected,
+                "whitespace is ignored",
+            ));
+        }
+        verdicts
+    }
+}
+
+/// What is ignored, e.g. `ignoring case, numbers within 1e-6`, or `byte for byte`.
+impl std::fmt::Display for Tolerance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![];
+        if self.ignore_whitespace {
+            parts.push("ignoring whitespace".to_string());
+        }
+        if self.ignore_case {
+            parts.push("ignoring case".to_string());
+        }
+        if let Some(epsilon) = self.float_epsilon {
+            parts.push(format!("numbers within {:e}", epsilon));
+        }
+        match parts.is_empty() {
+            true => write!(f, "byte for byte"),
+            false => write!(f, "{}", parts.join(", ")),
+        }
+    }
+}
+
+/// Whether the numbers `actual` and `expected` differ by at most `epsilon`, absolutely or
+/// relative to the larger one. NaN matches only NaN, and infinities only themselves.
+fn numbers_match(actual: f64, expected: f64, epsilon: f64) -> bool {
+    if actual.is_nan() || expected.is_nan() {
+        return actual.is_nan() && expected.is_nan();
+    }
+    if actual.is_infinite() || expected.is_infinite() {
+        return actual == expected;
+    }
+    let off = (actual - expected).abs();
+    off <= epsilon || off <= epsilon * actual.abs().max(expected.abs())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    Number(f64, &'a str),
+    Text(&'a str),
+}
+
+impl<'a> Token<'a> {
+    fn text(&self) -> &'a str {
+        match self {
+            Token::Number(_, text) | Token::Text(text) => text,
+        }
+    }
+}
+
+fn join_tokens(tokens: &[Token]) -> String {
+    tokens.iter().map(Token::text).collect()
+}
+
+/// Whether `byte` may be part of a word, so a number next to it is part of the word, e.g. in
+/// `x1` or `1.2.3`.
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'.' || !byte.is_ascii()
+}
+
+/// The end of the number starting at `start` of `bytes`, if one does: an optional sign, digits
+/// with an optional decimal point and exponent, or `nan`, `inf` or `infinity`.
+fn number_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let digits = |from: usize| {
+        bytes[from.min(bytes.len())..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+    };
+    let mut end = start;
+    if matches!(bytes.get(end), Some(b'+' | b'-')) {
+        end += 1;
+    }
+    for word in ["infinity", "inf", "nan"] {
+        if bytes.len() >= end + word.len()
+            && bytes[end..end + word.len()].eq_ignore_ascii_case(word.as_bytes())
+        {
+            return Some(end + word.len());
+        }
+    }
+    let integer = digits(end);
+    end += integer;
+    if bytes.get(end) == Some(&b'.') {
+        let fraction = digits(end + 1);
+        if integer + fraction == 0 {
+            return None;
+        }
+        end += 1 + fraction;
+    } else if integer == 0 {
+        return None;
+    }
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        let mut exponent = end + 1;
+        if matches!(bytes.get(exponent), Some(b'+' | b'-')) {
+            exponent += 1;
+        }
+        let exponent_digits = digits(exponent);
+        if exponent_digits > 0 {
+            end = exponent + exponent_digits;
+        }
+    }
+    Some(end)
+}
+
+/// `line` as numbers, standing alone rather than part of a word, and the texts between them.
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let bytes = line.as_bytes();
+    let mut tokens = vec![];
+    let (mut text_start, mut i) = (0, 0);
+    while i < bytes.len() {
+        let number = (i == 0 || !is_word_byte(bytes[i - 1]))
+            .then(|| number_end(bytes, i))
+            .flatten()
+            .filter(|&end| end == bytes.len() || !is_word_byte(bytes[end]))
+            .and_then(|end| Some((end, line[i..end].parse::<f64>().ok()?)));
+        match number {
+            Some((end, value)) => {
+                if text_start < i {
+                    tokens.push(Token::Text(&line[text_start..i]));
+                }
+                tokens.push(Token::Number(value, &line[i..end]));
+                (text_start, i) = (end, end);
+            }
+            None => i += 1,
+        }
+    }
+    if text_start < bytes.len() {
+        tokens.push(Token::Text(&line[text_start..]));
+    }
+    tokens
+}
+
+/// A token of an actual line differing from the expected one, for the notes under a diff.
+#[derive(Clone, Debug, PartialEq)]
+struct TokenVerdict {
+    actual: String,
+    expected: String,
+    allowed: bool,
+    why: String,
+}
+
+impl TokenVerdict {
+    fn allowed(actual: &str, expected: &str, why: &str) -> Self {
+        TokenVerdict {
+            actual: actual.to_string(),
+            expected: expected.to_string(),
+            allowed: true,
+            why: why.to_string(),
+        }
+    }
+
+    fn rejected(actual: &str, expected: &str, why: &str) -> Self {
+        TokenVerdict {
+            allowed: false,
+            ..TokenVerdict::allowed(actual, expected, why)
+        }
+    }
+}
+
+/// Where two outputs first differ beyond a [`Tolerance`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mismatch {
+    /// Line of the difference, counting from 1.
+    pub line: usize,
+    /// What differs there, e.g. `column 4`, or when comparing loosely
+    /// `` `1.6` for `1.5`, off by 1.00e-1, not within 1e-3 ``.
+    pub what: String,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "first difference at line {}, {}", self.line, self.what)
+    }
+}
+
+/// The pairs of lines of `actual` and `expected` with the same number that differ byte for
+/// byte, numbered from 1, a missing line being `None`.
+fn differing_lines<'a>(
+    actual: &'a str,
+    expected: &'a str,
+    tolerance: &Tolerance,
+) -> impl Iterator<Item = (usize, Option<&'a str>, Option<&'a str>)> {
+    let (actual, expected) = (tolerance.lines(actual), tolerance.lines(expected));
+    (0..actual.len().max(expected.len()))
+        .map(move |i| (i + 1, actual.get(i).copied(), expected.get(i).copied()))
+        .filter(|(_, a, e)| a != e)
+}
+
+/// The verdicts on the differing tokens of a line of `actual` and `expected`.
+fn line_verdicts(
+    actual: Option<&str>,
+    expected: Option<&str>,
+    tolerance: &Tolerance,
+) -> Vec<TokenVerdict> {
+    match (actual, expected) {
+        (Some(a), Some(e)) => tolerance.compare_lines(a, e),
+        (Some(a), None) => vec![TokenVerdict::rejected(a, "", "an extra line")],
+        (None, e) => vec![TokenVerdict::rejected(
+            "",
+            e.unwrap_or_default(),
+            "a missing line",
+        )],
+    }
+}
+
+/// Where `actual` first differs from `expected` beyond `tolerance`, `None` if they match.
+pub fn first_mismatch(actual: &[u8], expected: &[u8], tolerance: &Tolerance) -> Option<Mismatch> {
+    if tolerance.is_exact() {
+        return first_difference(actual, expected).map(|difference| Mismatch {
+            line: difference.line,
+            what: format!("column {}", difference.column),
+        });
+    }
+    let (actual, expected) = (
+        String::from_utf8_lossy(actual),
+        String::from_utf8_lossy(expected),
+    );
+    let mismatch = differing_lines(&actual, &expected, tolerance).find_map(|(line, a, e)| {
+        let verdict = line_verdicts(a, e, tolerance)
+            .into_iter()
+            .find(|verdict| !verdict.allowed)?;
+        Some(Mismatch {
+            line,
+            what: verdict.to_string(),
+        })
+    });
+    mismatch
+}
+
+impl std::fmt::Display for TokenVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.actual.is_empty(), self.expected.is_empty()) {
+            (false, false) => write!(f, "`{}` for `{}`, {}", self.actual, self.expected, self.why),
+            (false, true) => write!(f, "`{}`, {}", self.actual, self.why),
+            (true, _) => write!(f, "`{}` is missing, {}", self.expected, self.why),
+        }
+    }
+}
+
+/// Notes to append to a [`unified_diff`] of `actual` and `expected`: for every token on the
+/// lines that differ, whether `tolerance` allowed it and why, at most `max_notes` of them.
+/// Empty when comparing byte for byte.
+pub fn tolerance_notes(
+    actual: &[u8],
+    expected: &[u8],
+    tolerance: &Tolerance,
+    max_notes: usize,
+) -> String {
+    if tolerance.is_exact() || actual == expected {
+        return String::new();
+    }
+    let (actual, expected) = (
+        String::from_utf8_lossy(actual),
+        String::from_utf8_lossy(expected),
+    );
+    let notes = differing_lines(&actual, &expected, tolerance)
+        .flat_map(|(line, a, e)| {
+            line_verdicts(a, e, tolerance)
+                .into_iter()
+                .map(move |verdict| {
+                    let allowed = if verdict.allowed {
+                        "allowed"
+                    } else {
+                        "rejected"
+                    };
+                    format!("line {}: {}: {}\n", line, allowed, verdict)
+                })
+        })
+        .collect::<Vec<_>>();
+    let mut out = String::from("tolerances:\n");
+    for note in notes.iter().take(max_notes) {
+        out += note;
+    }
+    if notes.len() > max_notes {
+        out += &format!("... {} more\n", notes.len() - max_notes);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: Tolerance = Tolerance {
+        ignore_whitespace: false,
+        ignore_case: false,
+        float_epsilon: Some(1e-3),
+    };
+
+    #[test]
+    fn equal() {
+        for tolerance in [Tolerance::default(), EPSILON] {
+            assert!(tolerance.matches(b"1 0.5\nok\n", b"1 0.5\nok\n"));
+            assert_eq!(first_mismatch(b"1 0.5\n", b"1 0.5\n", &tolerance), None);
+            assert_eq!(tolerance_notes(b"1 0.5\n", b"1 0.5\n", &tolerance, 10), "");
+        }
+        let options = DiffOptions {
+            context: 3,
+            max_hunks: None,
+        };
+        assert_eq!(unified_diff(b"a\nb\n", b"a\nb\n", options), "");
+    }
+
+    #[test]
+    fn within_tolerance() {
+        assert!(EPSILON.matches(b"1 0.5004\nok\n", b"1 0.5\nok\n"));
+        assert!(EPSILON.matches(b"1000.5\n", b"1000\n"), "relatively");
+        assert_eq!(first_mismatch(b"0.5004\n", b"0.5\n", &EPSILON), None);
+        let notes = tolerance_notes(b"0.5004\n", b"0.5\n", &EPSILON, 10);
+        assert!(
+            notes.starts_with("tolerances:\nline 1: allowed: `0.5004` for `0.5`"),
+            "{}",
+            notes
+        );
+        let loose = Tolerance {
+            ignore_whitespace: true,
+            ignore_case: true,
+            float_epsilon: None,
+        };
+        assert!(loose.matches(b"  YES   no \n\n\n", b"yes no\n"));
+        // Exactly, they differ.
+        assert!(!Tolerance::default().matches(b"0.5004\n", b"0.5\n"));
+    }
+
+    #[test]
+    fn beyond_tolerance() {
+        assert!(!EPSILON.matches(b"1\n0.51\n", b"1\n0.5\n"));
+        let mismatch = first_mismatch(b"1\n0.51\n", b"1\n0.5\n", &EPSILON).unwrap();
+        assert_eq!(mismatch.line, 2);
+        assert!(
+            mismatch.what.starts_with("`0.51` for `0.5`"),
+            "{}",
+            mismatch
+        );
+        assert!(mismatch.what.contains("not within 1e-3"), "{}", mismatch);
+        // Words are compared exactly, and lines must be there.
+        assert!(!EPSILON.matches(b"1 no\n", b"1 yes\n"));
+        assert_eq!(first_mismatch(b"1\n", b"1\n2\n", &EPSILON).unwrap().line, 2);
+        assert_eq!(
+            first_mismatch(b"ab\ncd\n", b"ab\nce\n", &Tolerance::default()),
+            Some(Mismatch {
+                line: 2,
+                what: "column 2".to_string(),
+            })
+        );
+        let notes = tolerance_notes(b"0.51\n", b"0.5\n", &EPSILON, 10);
+        assert!(
+            notes.contains("line 1: rejected: `0.51` for `0.5`"),
+            "{}",
+            notes
+        );
+    }
+}
@@ -0,0 +1,247 @@
+//! A full-screen view of a running session on the terminal, see `--tui`.
+//!
+//! The screen is drawn on the alternate screen of the terminal, so whatever was on it before
+//! comes back once the [`Screen`] is dropped, as it does on a panic. What is drawn comes from
+//! the caller, gathered from the runner and its observers into a [`View`].
+
+use crate::{units, RunObserver, RunResult, SessionReport};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once,
+    },
+    time::Duration,
+};
+
+/// The smallest terminal the dashboard is drawn on, in columns and rows.
+pub const MIN_SIZE: (usize, usize) = (60, 16);
+
+/// Whether the alternate screen is shown, for the panic hook.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+fn leave() {
+    if ACTIVE.swap(false, Ordering::Relaxed) {
+        // Show the cursor again and switch back to the main screen.
+        eprint!("\x1b[?25h\x1b[?1049l");
+    }
+}
+
+/// The alternate screen of the terminal on stderr, taken over while it lives.
+pub struct Screen(());
+
+impl Screen {
+    /// Switch to the alternate screen and hide the cursor.
+    pub fn enter() -> io::Result<Self> {
+        let mut stderr = io::stderr().lock();
+        stderr.write_all(b"\x1b[?1049h\x1b[?25l")?;
+        stderr.flush()?;
+        ACTIVE.store(true, Ordering::Relaxed);
+        static HOOK: Once = Once::new();
+        HOOK.call_once(|| {
+            let hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                leave();
+                hook(info);
+            }));
+        });
+        Ok(Screen(()))
+    }
+
+    /// Replace what is on the screen with `lines`, each cut to `width` columns.
+    pub fn draw(&self, lines: &[String], width: usize) -> io::Result<()> {
+        let mut frame = String::from("\x1b[H");
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                frame += "\r\n";
+            }
+            frame += &console::truncate_str(line, width, "");
+            frame += "\x1b[K";
+        }
+        frame += "\x1b[J";
+        let mut stderr = io::stderr().lock();
+        stderr.write_all(frame.as_bytes())?;
+        stderr.flush()
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        leave();
+    }
+}
+
+/// An observer keeping the last failures of a session, one line each.
+pub struct FailureLog {
+    limit: usize,
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl FailureLog {
+    /// Keep the last `limit` failures.
+    pub fn new(limit: usize) -> Self {
+        FailureLog {
+            limit,
+            lines: Mutex::default(),
+        }
+    }
+
+    /// The failures kept, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl RunObserver for FailureLog {
+    fn on_run(&self, result: &RunResult) {
+        let Some(failure) = result
+            .failure
+            .as_ref()
+            .filter(|_| result.status.is_counted())
+        else {
+            return;
+        };
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == self.limit {
+            lines.pop_front();
+        }
+        lines.push_back(format!("run {}: {}", result.index, failure));
+    }
+}
+
+/// What the dashboard shows.
+pub struct View<'a> {
+    /// What every run executes.
+    pub title: &'a str,
+    /// The runs so far.
+    pub report: &'a SessionReport,
+    /// Runs finished, including resumed ones, of `total`.
+    pub completed: u64,
+    /// Runs of the session.
+    pub total: u64,
+    /// Since the session started.
+    pub elapsed: Duration,
+    /// About how long the rest takes, if known.
+    pub left: Option<Duration>,
+    /// Whether no new runs are started.
+    pub paused: bool,
+    /// The run durations charted in run order, see [`crate::sparkline`].
+    pub durations: &'a str,
+    /// One row per worker thread of how busy it was, see [`crate::timeline::render`].
+    pub threads: &'a [String],
+    /// The last failures, oldest first.
+    pub failures: &'a [String],
+    /// The last messages of tester, oldest first.
+    pub messages: &'a [String],
+    /// What the keys do.
+    pub keys: &'a str,
+}
+
+/// The lines of the dashboard for `view` on a terminal of `width` columns and `height` rows.
+/// The threads, failures and messages share the rows left, the oldest ones left out first.
+pub fn render(view: &View, width: usize, height: usize) -> Vec<String> {
+    let report = view.report;
+    let mut lines = vec![format!(
+        "tester: {}{}",
+        view.title,
+        if view.paused { " (paused)" } else { "" }
+    )];
+
+    let share = view.completed as f64 / view.total.max(1) as f64;
+    let counter = format!(
+        " {}/{} {}",
+        units::format_integer(view.completed),
+        units::format_integer(view.total),
+        units::format_duration(view.elapsed)
+    );
+    let left = view
+        .left
+        .map(|left| format!(", about {} left", units::format_duration(left)))
+        .unwrap_or_default();
+    let bar_width = width.saturating_sub(counter.len() + left.len() + 2).max(10);
+    let filled = ((share * bar_width as f64) as usize).min(bar_width);
+    lines.push(format!(
+        "[{}{}]{}{}",
+        "#".repeat(filled),
+        "-".repeat(bar_width - filled),
+        counter,
+        left
+    ));
+
+    let mut counts = format!(
+        "runs {}  passed {}  failed {}",
+        units::format_integer(report.run_times),
+        units::format_integer(report.pass_times()),
+        units::format_integer(report.fail_times)
+    );
+    if report.run_times > 0 {
+        counts += &format!(
+            " ({}%)",
+            units::format_number(report.fail_times as f64 * 100.0 / report.run_times as f64)
+        );
+    }
+    for (what, count) in [
+        ("timed out", report.timeout_times),
+        ("invalid", report.invalid_times),
+        ("skipped", report.skipped_times),
+    ] {
+        if count > 0 {
+            counts += &format!("  {} {}", what, units::format_integer(count));
+        }
+    }
+    lines.push(counts);
+    let seconds = |seconds: f64| units::format_duration(Duration::from_secs_f64(seconds.max(0.0)));
+    for (name, stats, format) in [
+        ("times ", &report.times, &seconds as &dyn Fn(f64) -> String),
+        ("scores", &report.scores, &units::format_score),
+    ] {
+        if let Some(mean) = stats.mean() {
+            lines.push(format!(
+                "{}  min {}  mean {}  max {}",
+                name,
+                format(stats.min),
+                format(mean),
+                format(stats.max)
+            ));
+        }
+    }
+    if !view.durations.trim().is_empty() {
+        lines.push(format!("trend   {}", view.durations));
+    }
+
+    // The keys go last; the rest of the rows are shared.
+    let rows = height.saturating_sub(lines.len() + 1);
+    let sections = [
+        ("threads", view.threads),
+        ("failures", view.failures),
+        ("messages", view.messages),
+    ];
+    let shown = sections
+        .iter()
+        .filter(|(_, items)| !items.is_empty())
+        .count();
+    let mut rows_left = rows;
+    let mut sections_left = shown;
+    for (name, items) in sections {
+        if items.is_empty() || rows_left < 2 {
+            continue;
+        }
+        let rows = (rows_left / sections_left.max(1)).max(2);
+        let kept = items.len().min(rows - 1);
+        lines.push(format!("{}:", name));
+        lines.extend(
+            items[items.len() - kept..]
+                .iter()
+                .map(|item| format!("  {}", item)),
+        );
+        rows_left -= kept + 1;
+        sections_left -= 1;
+    }
+    lines.truncate(height.saturating_sub(1));
+    while lines.len() + 1 < height {
+        lines.push(String::new());
+    }
+    lines.push(view.keys.to_string());
+    lines
+}
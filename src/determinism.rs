@@ -0,0 +1,125 @@
+//! Telling whether a supposedly deterministic command always writes the same output.
+
+use crate::{RunObserver, RunResult, RunStatus};
+use regex::bytes::Regex;
+use std::{
+    fs,
+    hash::{DefaultHasher, Hasher},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// One distinct output seen by a [`DeterminismCheck`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutputVariant {
+    /// Hash of the output, after normalization.
+    pub hash: u64,
+    /// Passed runs that wrote it.
+    pub count: u64,
+    /// The first run that wrote it.
+    pub first_run: u64,
+}
+
+/// An observer hashing the stdout of every passed run, and optionally its stderr, to count
+/// the distinct outputs. Only hashes are kept, never the outputs themselves.
+pub struct DeterminismCheck {
+    include_stderr: bool,
+    /// Matches are removed before hashing, e.g. timestamps.
+    ignore: Option<Regex>,
+    /// Where to save the output of the first run of every variant.
+    samples_dir: Option<PathBuf>,
+    variants: Mutex<Vec<OutputVariant>>,
+}
+
+impl DeterminismCheck {
+    /// Hash stdout, and stderr after it with `include_stderr`, leaving out what `ignore`
+    /// matches.
+    pub fn new(include_stderr: bool, ignore: Option<Regex>) -> Self {
+        DeterminismCheck {
+            include_stderr,
+            ignore,
+            samples_dir: None,
+            variants: Mutex::default(),
+        }
+    }
+
+    /// Save the output of the first run of every variant to `dir`, which must exist, as
+    /// `output_variant_<n>_run_<index>.txt`.
+    pub fn save_samples(mut self, dir: &Path) -> Self {
+        self.samples_dir = Some(dir.to_path_buf());
+        self
+    }
+
+    /// The distinct outputs seen so far, the most frequent first.
+    pub fn variants(&self) -> Vec<OutputVariant> {
+        let mut variants = self.variants.lock().unwrap().clone();
+        variants.sort_by_key(|variant| (std::cmp::Reverse(variant.count), variant.first_run));
+        variants
+    }
+
+    fn hash(&self, hasher: &mut DefaultHasher, output: &[u8]) {
+        match &self.ignore {
+            Some(ignore) => hasher.write(&ignore.replace_all(output, &b""[..])),
+            None => hasher.write(output),
+        }
+    }
+}
+
+/// Parse a `--deterministic-ignore` value, matched against the raw bytes of the output.
+pub fn parse_ignore(s: &str) -> Result<Regex, String> {
+    Regex::new(s).map_err(|err| format!("invalid regex `{}`: {}", s, err))
+}
+
+impl RunObserver for DeterminismCheck {
+    fn on_run(&self, result: &RunResult) {
+        if result.status != RunStatus::Passed {
+            return;
+        }
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher, &result.stdout);
+        if self.include_stderr {
+            hasher.write_u8(0xff);
+            self.hash(&mut hasher, &result.stderr);
+        }
+        let hash = hasher.finish();
+
+        let mut variants = self.variants.lock().unwrap();
+        if let Some(variant) = variants.iter_mut().find(|variant| variant.hash == hash) {
+            variant.count += 1;
+            return;
+        }
+        variants.push(OutputVariant {
+            hash,
+            count: 1,
+            first_run: result.index,
+        });
+        let number = variants.len();
+        drop(variants);
+        if number > 1 {
+            notice!(
+                "#tester run {}: output differs from every earlier run, variant {}",
+                result.index,
+                number
+            );
+        }
+        let Some(dir) = &self.samples_dir else {
+            return;
+        };
+        let path = dir.join(format!(
+            "output_variant_{}_run_{}.txt",
+            number, result.index
+        ));
+        let mut content = result.stdout.clone();
+        if self.include_stderr {
+            content.extend_from_slice(&result.stderr);
+        }
+        if let Err(err) = fs::write(&path, content) {
+            warn!(
+                "#tester run {}: cannot save its output to {}: {}",
+                result.index,
+                path.display(),
+                err
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! One character per finished run on stderr, like pytest prints them, see `--status dots`.
+//!
+//! The characters wrap at the width of the terminal, every line ending with the number of
+//! runs finished so far. Tester's own messages are printed around the unfinished line, see
+//! [`crate::logging::attach_dots`].
+
+use crate::{units, RunObserver, RunResult, RunStatus};
+use std::{
+    io::{self, Write},
+    sync::Mutex,
+};
+
+/// The character of a run that ended with `status`.
+pub fn symbol(status: RunStatus) -> char {
+    match status {
+        RunStatus::Passed => '.',
+        RunStatus::Failed => 'F',
+        RunStatus::TimedOut | RunStatus::RelativeTimeout => 'T',
+        RunStatus::CpuLimitExceeded => 'C',
+        RunStatus::Skipped => 's',
+        RunStatus::Invalid => 'i',
+        RunStatus::SetupFailed => 'E',
+    }
+}
+
+/// What the characters mean.
+pub fn legend() -> &'static str {
+    ". passed, F failed, T timed out, C over the CPU limit, s skipped, i invalid input, \
+     E setup failed"
+}
+
+/// The line being drawn.
+#[derive(Default)]
+struct Line {
+    /// What is on it so far, with colors.
+    shown: String,
+    /// Characters on it.
+    column: usize,
+    /// Runs finished, on every line so far.
+    finished: u64,
+}
+
+/// An observer printing the character of every run as soon as it finished, the whole line
+/// at once when it wraps so characters of runs finishing together never tear it.
+pub struct Dots {
+    total: u64,
+    /// Characters of a full line, without its count.
+    per_line: usize,
+    colors: bool,
+    /// Whether stderr is a terminal, where the unfinished line can be erased.
+    term: bool,
+    line: Mutex<Line>,
+}
+
+impl Dots {
+    /// Draw the `total` runs of a session on lines of `width` columns, in colors if `colors`.
+    pub fn new(total: u64, width: usize, colors: bool) -> Self {
+        let count_width = Self::count(total, total).len();
+        Dots {
+            total,
+            per_line: width.saturating_sub(count_width + 1).max(10),
+            colors,
+            term: console::Term::stderr().is_term(),
+            line: Mutex::default(),
+        }
+    }
+
+    /// The count ending a line after `finished` runs, as wide for every line.
+    fn count(finished: u64, total: u64) -> String {
+        let total = units::format_integer(total);
+        format!(
+            " {:>width$}/{}",
+            units::format_integer(finished),
+            total,
+            width = total.len()
+        )
+    }
+
+    /// `symbol` of a run that ended with `status`, colored if enabled.
+    fn styled(&self, status: RunStatus) -> String {
+        let symbol = console::style(symbol(status));
+        if !self.colors {
+            return symbol.to_string();
+        }
+        match status {
+            RunStatus::Passed => symbol.green(),
+            RunStatus::Failed | RunStatus::SetupFailed => symbol.red(),
+            RunStatus::TimedOut | RunStatus::RelativeTimeout | RunStatus::CpuLimitExceeded => {
+                symbol.yellow()
+            }
+            RunStatus::Skipped | RunStatus::Invalid => symbol.dim(),
+        }
+        .to_string()
+    }
+
+    /// The end of the unfinished `line`: padding up to the count, the count and a newline.
+    fn end_of(&self, line: &mut Line) -> String {
+        let end = format!(
+            "{}{}\n",
+            " ".repeat(self.per_line - line.column),
+            Self::count(line.finished, self.total)
+        );
+        line.shown.clear();
+        line.column = 0;
+        end
+    }
+
+    /// Run `print`, which prints something else on stderr, with the unfinished line out of
+    /// its way: erased on a terminal and drawn again afterwards, ended elsewhere.
+    pub fn suspend(&self, print: impl FnOnce()) {
+        let line = self.line.lock().unwrap();
+        if line.column == 0 {
+            print();
+            return;
+        }
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(if self.term { b"\r\x1b[2K" } else { b"\n" });
+        let _ = stderr.flush();
+        print();
+        let _ = stderr.write_all(line.shown.as_bytes());
+        let _ = stderr.flush();
+    }
+
+    /// End the unfinished line, if any, e.g. of a session that was interrupted.
+    pub fn finish(&self) {
+        let mut line = self.line.lock().unwrap();
+        if line.column > 0 {
+            let end = self.end_of(&mut line);
+            let _ = io::stderr().lock().write_all(end.as_bytes());
+        }
+    }
+}
+
+impl RunObserver for Dots {
+    fn on_run(&self, result: &RunResult) {
+        let mut line = self.line.lock().unwrap();
+        let symbol = self.styled(result.status);
+        line.shown += &symbol;
+        line.column += 1;
+        line.finished += 1;
+        let mut chunk = symbol;
+        if line.column == self.per_line || line.finished == self.total {
+            chunk += &self.end_of(&mut line);
+        }
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(chunk.as_bytes());
+        let _ = stderr.flush();
+    }
+}
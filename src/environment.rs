@@ -0,0 +1,145 @@
+//! The environment commands run in: inherited from tester, or cleared down to a few
+//! variables with `--clean-env`.
+
+use std::{env, process::Command};
+
+/// The prefix of the variables tester sets, which a clean environment keeps.
+pub const TESTER_PREFIX: &str = "TESTER_";
+
+/// Variables set in the environment of every command.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// Start from an empty environment instead of tester's own.
+    pub clear: bool,
+    /// Variables set on top, in order.
+    pub vars: Vec<(String, String)>,
+}
+
+impl Environment {
+    /// An environment with only `PATH`, set to `path` or else inherited, the inherited
+    /// `TESTER_*` variables and those whose name matches one of `passthrough`, then `vars`.
+    pub fn clean(path: Option<String>, passthrough: &[String], vars: &[(String, String)]) -> Self {
+        let mut kept = vec![];
+        if let Some(path) = path.or_else(|| env::var("PATH").ok()) {
+            kept.push(("PATH".to_string(), path));
+        }
+        for (name, value) in env::vars() {
+            let passed = name.starts_with(TESTER_PREFIX)
+                || passthrough.iter().any(|pattern| glob_match(pattern, &name));
+            if passed && name != "PATH" {
+                kept.push((name, value));
+            }
+        }
+        kept.sort();
+        kept.extend(vars.iter().cloned());
+        Environment {
+            clear: true,
+            vars: kept,
+        }
+    }
+
+    /// The value of `name` in this environment: the last one set on top, or else tester's own
+    /// unless cleared.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let set = self.vars.iter().rev().find(|(var, _)| var == name);
+        match set {
+            Some((_, value)) => Some(value.clone()),
+            None if self.clear => None,
+            None => env::var(name).ok(),
+        }
+    }
+
+    /// Set up `command` to run in this environment.
+    pub fn apply(&self, command: &mut Command) {
+        if self.clear {
+            command.env_clear();
+        }
+        command.envs(self.vars.iter().map(|(name, value)| (name, value)));
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of characters and `?` for
+/// a single one, e.g. `LC_*`.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    // The position after the last `*` seen, and where in `name` it started matching.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after_star, start)) => {
+                    p = after_star;
+                    n = start + 1;
+                    star = Some((after_star, start + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Expand the variables `$NAME` and `${NAME}` in `s` with their value in `env`, `$$` standing
+/// for a single `$`, as `--expand-env` does. A `$` followed by anything else is kept. An
+/// undefined variable is an error unless `allow_undefined`, then it expands to nothing.
+pub fn expand(s: &str, env: &Environment, allow_undefined: bool) -> Result<String, String> {
+    let is_name = |c: char| c == '_' || c.is_ascii_alphanumeric();
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(dollar) = rest.find('$') {
+        expanded += &rest[..dollar];
+        let after = &rest[dollar + 1..];
+        let (name, remainder) = if let Some(after) = after.strip_prefix('$') {
+            expanded.push('$');
+            rest = after;
+            continue;
+        } else if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("unterminated `${{` in `{}`", s))?;
+            (&braced[..end], &braced[end + 1..])
+        } else {
+            let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || !name.chars().all(is_name)
+        {
+            if after.starts_with('{') {
+                return Err(format!("invalid variable name `{}` in `{}`", name, s));
+            }
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        match env.get(name) {
+            Some(value) => expanded += &value,
+            None if allow_undefined => {}
+            None => return Err(format!("undefined variable `{}` in `{}`", name, s)),
+        }
+        rest = remainder;
+    }
+    expanded += rest;
+    Ok(expanded)
+}
+
+/// Parse an `--env NAME=VALUE` pair. The value may itself contain `=`.
+pub fn parse_var(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid variable `{}`: expected NAME=VALUE", s))?;
+    if name.is_empty() {
+        return Err(format!("invalid variable `{}`: the name is empty", s));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
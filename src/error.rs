@@ -0,0 +1,85 @@
+//! Errors that stop a session early.
+
+use std::{fmt, io};
+
+/// Something went wrong running the session itself, as opposed to a run failing.
+#[derive(Debug)]
+pub enum Error {
+    /// A step could not be started, e.g. because its executable does not exist.
+    Spawn {
+        /// The step, as a shell-like line.
+        step: String,
+        /// The error returned by the OS.
+        source: io::Error,
+    },
+    /// Waiting for a step or reading its output failed.
+    Wait {
+        /// The step, as a shell-like line.
+        step: String,
+        /// The error returned by the OS.
+        source: io::Error,
+    },
+    /// Copying a step's output to our stdout or stderr failed, e.g. because it was closed.
+    Output(io::Error),
+    /// Writing the files passed to the checker failed.
+    CheckFiles(io::Error),
+    /// Creating the temporary directory of an iteration failed.
+    TmpDir(io::Error),
+    /// More iterations than [`RunConfig::max_invalid`](crate::RunConfig::max_invalid) had
+    /// generated input the validator rejected.
+    TooManyInvalid {
+        /// Iterations with invalid input so far.
+        invalid: u64,
+        /// The most that were allowed.
+        max: u64,
+    },
+    /// More iterations than [`RunConfig::max_skipped`](crate::RunConfig::max_skipped) were
+    /// skipped.
+    TooManySkipped {
+        /// Iterations skipped so far.
+        skipped: u64,
+        /// The most that were allowed.
+        max: u64,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Spawn { step, source } => write!(f, "cannot start `{}`: {}", step, source),
+            Error::Wait { step, source } => write!(f, "cannot wait for `{}`: {}", step, source),
+            Error::Output(source) => write!(f, "cannot write the output of a run: {}", source),
+            Error::CheckFiles(source) => write!(f, "cannot write the checker's files: {}", source),
+            Error::TmpDir(source) => {
+                write!(
+                    f,
+                    "cannot create the temporary directory of a run: {}",
+                    source
+                )
+            }
+            Error::TooManyInvalid { invalid, max } => write!(
+                f,
+                "the validator rejected {} generated inputs, more than the {} allowed",
+                invalid, max
+            ),
+            Error::TooManySkipped { skipped, max } => write!(
+                f,
+                "{} runs were skipped by their exit code, more than the {} allowed",
+                skipped, max
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Spawn { source, .. }
+            | Error::Wait { source, .. }
+            | Error::Output(source)
+            | Error::CheckFiles(source)
+            | Error::TmpDir(source) => Some(source),
+            Error::TooManyInvalid { .. } | Error::TooManySkipped { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! Per-run records saved with `--csv` and `--jsonl`, and reading them back.
+
+use crate::{metadata::Tag, summary::SessionSummary, RunObserver, RunResult, RunStatus};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+/// The header line of CSV files, naming the fields of [`RunRecord`] in order. The keys of
+/// the session's tags may follow as more columns, with the same values on every line.
+pub const CSV_HEADER: &str = "index,thread,status,duration,score,variant";
+/// The header of files written before runs had a variant, still read.
+const LEGACY_CSV_HEADER: &str = "index,thread,status,duration,score";
+/// How many bytes of the end of the stderr of a run its JSONL record keeps.
+pub const STDERR_TAIL_BYTES: usize = 1000;
+
+/// What is saved of every run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RunRecord {
+    /// Run index.
+    pub index: u64,
+    /// Index of the worker thread that ran it.
+    pub thread: usize,
+    /// How the run ended.
+    pub status: RunStatus,
+    /// Wall-clock seconds the run took.
+    pub duration: f64,
+    /// The run's score, if it was scored.
+    pub score: Option<f64>,
+    /// The swept values the run used, see `--sweep`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    /// Exit code of the last step that ran, unless it was killed by a signal. Not in CSV.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// The last [`STDERR_TAIL_BYTES`] of the stderr of the run, if it printed anything and
+    /// it was captured. Not in CSV.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr_tail: Option<String>,
+}
+
+impl RunRecord {
+    /// The record of a finished run.
+    pub fn new(result: &RunResult) -> Self {
+        RunRecord {
+            index: result.index,
+            thread: result.thread,
+            status: result.status,
+            duration: result.duration.as_secs_f64(),
+            score: result.score,
+            variant: result.variant.clone(),
+            exit_code: result.exit_code,
+            stderr_tail: tail(&result.stderr),
+        }
+    }
+
+    /// The record as a CSV line without line terminator, fields as in [`CSV_HEADER`].
+    pub fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            self.index,
+            self.thread,
+            status_name(self.status),
+            self.duration,
+            self.score
+                .map(|score| score.to_string())
+                .unwrap_or_default(),
+            self.variant.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Parse a line written by [`to_csv`](Self::to_csv), or by older versions without the
+    /// variant field. Tag columns are skipped.
+    pub fn from_csv(line: &str) -> Result<Self, String> {
+        let mut fields = line.splitn(7, ',').collect::<Vec<_>>();
+        if fields.len() == 5 {
+            fields.push("");
+        }
+        let [index, thread, status, duration, score, variant, ..] = fields[..] else {
+            return Err(format!("expected 6 fields, found {}", fields.len()));
+        };
+        let field = |name: &str, value: &str| format!("invalid {} `{}`", name, value);
+        Ok(RunRecord {
+            index: index.parse().map_err(|_| field("index", index))?,
+            thread: thread.parse().map_err(|_| field("thread", thread))?,
+            status: parse_status(status).ok_or_else(|| field("status", status))?,
+            duration: duration.parse().map_err(|_| field("duration", duration))?,
+            score: match score {
+                "" => None,
+                score => Some(score.parse().map_err(|_| field("score", score))?),
+            },
+            variant: (!variant.is_empty()).then(|| variant.to_string()),
+            exit_code: None,
+            stderr_tail: None,
+        })
+    }
+}
+
+/// The last [`STDERR_TAIL_BYTES`] of `output`, starting at a character, if there are any.
+fn tail(output: &[u8]) -> Option<String> {
+    let mut start = output.len().saturating_sub(STDERR_TAIL_BYTES);
+    // Skip the rest of a UTF-8 character cut in the middle.
+    while start < output.len() && output[start] & 0xc0 == 0x80 {
+        start += 1;
+    }
+    (start < output.len()).then(|| String::from_utf8_lossy(&output[start..]).into_owned())
+}
+
+/// `field` as a CSV field, quoted if needed.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The name of `status` in saved records.
+pub(crate) fn status_name(status: RunStatus) -> &'static str {
+    match status {
+        RunStatus::Passed => "passed",
+        RunStatus::Failed => "failed",
+        RunStatus::TimedOut => "timed_out",
+        RunStatus::RelativeTimeout => "relative_timeout",
+        RunStatus::CpuLimitExceeded => "cpu_limit_exceeded",
+        RunStatus::Invalid => "invalid",
+        RunStatus::SetupFailed => "setup_failed",
+        RunStatus::Skipped => "skipped",
+    }
+}
+
+fn parse_status(s: &str) -> Option<RunStatus> {
+    [
+        RunStatus::Passed,
+        RunStatus::Failed,
+        RunStatus::TimedOut,
+        RunStatus::RelativeTimeout,
+        RunStatus::CpuLimitExceeded,
+        RunStatus::Invalid,
+        RunStatus::SetupFailed,
+        RunStatus::Skipped,
+    ]
+    .into_iter()
+    .find(|&status| status_name(status) == s)
+}
+
+/// How records are written to a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// A [`CSV_HEADER`] line, then one line per run.
+    Csv,
+    /// One JSON object per line.
+    Jsonl,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Format::Csv => "CSV",
+            Format::Jsonl => "JSONL",
+        })
+    }
+}
+
+/// The last line of a JSONL file: the summary of the session as saved with `--json`, told
+/// apart from the records by its `type`.
+#[derive(Serialize)]
+struct SummaryLine<'a> {
+    r#type: &'static str,
+    #[serde(flatten)]
+    summary: &'a SessionSummary,
+}
+
+/// Whether `line` of a JSONL file is a [`SummaryLine`] rather than a record.
+fn is_summary_line(line: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(line)
+        .is_ok_and(|value| value.get("type").and_then(|kind| kind.as_str()) == Some("summary"))
+}
+
+/// An observer appending the record of every run to a file as soon as it finished, so the
+/// file is complete up to the last finished run even if tester is killed. Every line is
+/// written at once, so lines of runs finishing together never interleave.
+pub struct RecordWriter {
+    format: Format,
+    /// The tag columns ending every CSV line, with their leading comma.
+    tag_fields: String,
+    file: Mutex<Box<dyn Write + Send>>,
+}
+
+impl RecordWriter {
+    /// Create or truncate `path`, writing the CSV header if needed. CSV lines end with the
+    /// values of `tags`.
+    pub fn create(path: &Path, format: Format, tags: &[Tag]) -> io::Result<Self> {
+        Self::file(fs::File::create(path)?, format, tags)
+    }
+
+    /// Append to `path`, e.g. when resuming a session, creating it if it does not exist.
+    pub fn append(path: &Path, format: Format, tags: &[Tag]) -> io::Result<Self> {
+        Self::file(
+            fs::File::options().append(true).create(true).open(path)?,
+            format,
+            tags,
+        )
+    }
+
+    /// Write to stdout instead, flushing every line, for another program reading as the runs
+    /// finish.
+    pub fn stdout(format: Format, tags: &[Tag]) -> io::Result<Self> {
+        Self::open(Box::new(io::stdout()), true, format, tags)
+    }
+
+    /// The file ends with the `summary` of the session: for JSONL, a line like the summary
+    /// saved with `--json` with a `type` of `summary`. CSV has no room for it.
+    pub fn finish(&self, summary: &SessionSummary) {
+        if self.format != Format::Jsonl {
+            return;
+        }
+        let line = SummaryLine {
+            r#type: "summary",
+            summary,
+        };
+        let line = serde_json::to_string(&line).expect("summaries serialize");
+        if let Err(err) = self.write_line(line) {
+            warn!("#tester failed to save the summary: {}", err);
+        }
+    }
+
+    fn write_line(&self, mut line: String) -> io::Result<()> {
+        line.push('\n');
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.flush()
+    }
+
+    fn file(file: fs::File, format: Format, tags: &[Tag]) -> io::Result<Self> {
+        let empty = file.metadata()?.len() == 0;
+        Self::open(Box::new(file), empty, format, tags)
+    }
+
+    fn open(
+        mut file: Box<dyn Write + Send>,
+        empty: bool,
+        format: Format,
+        tags: &[Tag],
+    ) -> io::Result<Self> {
+        let columns = |field: fn(&Tag) -> &str| {
+            tags.iter()
+                .map(|tag| format!(",{}", csv_field(field(tag))))
+                .collect::<String>()
+        };
+        if format == Format::Csv && empty {
+            writeln!(file, "{}{}", CSV_HEADER, columns(|tag| &tag.key))?;
+        }
+        Ok(RecordWriter {
+            format,
+            tag_fields: columns(|tag| &tag.value),
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl RunObserver for RecordWriter {
+    fn on_run(&self, result: &RunResult) {
+        let record = RunRecord::new(result);
+        let line = match self.format {
+            Format::Csv => record.to_csv() + &self.tag_fields,
+            Format::Jsonl => serde_json::to_string(&record).expect("records serialize"),
+        };
+        if let Err(err) = self.write_line(line) {
+            warn!("#tester failed to save run {}: {}", result.index, err);
+        }
+    }
+}
+
+/// Whether `line` is the header of a CSV file: [`CSV_HEADER`], maybe followed by tag columns,
+/// or the header of older versions.
+fn is_csv_header(line: &str) -> bool {
+    line == LEGACY_CSV_HEADER
+        || line
+            .strip_prefix(CSV_HEADER)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(','))
+}
+
+/// The fields of a CSV line, unquoting the quoted ones.
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// The tags saved in the columns of a CSV file written by a [`RecordWriter`], read from its
+/// header and first record. JSONL files and CSV files without runs have none.
+pub fn read_tags(path: &Path) -> Result<Vec<Tag>, String> {
+    let file =
+        fs::File::open(path).map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    let mut lines = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty());
+    let (Some(header), Some(record)) = (lines.next(), lines.next()) else {
+        return Ok(vec![]);
+    };
+    let header = header.trim_end();
+    let Some(keys) = header
+        .strip_prefix(CSV_HEADER)
+        .and_then(|rest| rest.strip_prefix(','))
+    else {
+        return Ok(vec![]);
+    };
+    let columns = CSV_HEADER.split(',').count();
+    Ok(split_csv(keys)
+        .into_iter()
+        .zip(split_csv(record.trim_end()).into_iter().skip(columns))
+        .map(|(key, value)| Tag { key, value })
+        .collect())
+}
+
+/// Read a file written by a [`RecordWriter`] of either format, telling them apart by their
+/// content. Returns the records in file order along with the format found.
+pub fn read_records(path: &Path) -> Result<(Format, Vec<RunRecord>), String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+    let mut lines = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let format = match lines.clone().next() {
+        None => return Err(format!("{} is empty", path.display())),
+        Some((_, line)) if line.trim_start().starts_with('{') => Format::Jsonl,
+        Some((_, line)) if is_csv_header(line.trim_end()) => {
+            lines.next();
+            Format::Csv
+        }
+        Some((_, line)) => {
+            return Err(format!(
+                "{} is not a tester CSV or JSONL file: unknown header `{}` (expected `{}`)",
+                path.display(),
+                line,
+                CSV_HEADER
+            ))
+        }
+    };
+    let records = lines
+        .filter(|(_, line)| format != Format::Jsonl || !is_summary_line(line))
+        .map(|(number, line)| {
+            let record = match format {
+                Format::Csv => RunRecord::from_csv(line.trim_end()),
+                Format::Jsonl => serde_json::from_str(line).map_err(|err| err.to_string()),
+            };
+            record.map_err(|err| {
+                format!(
+                    "{}:{}: not a tester {} record: {}",
+                    path.display(),
+                    number + 1,
+                    format,
+                    err
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((format, records))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn record(index: u64, status: RunStatus, score: Option<f64>) -> RunRecord {
+        RunRecord {
+            index,
+            thread: 1,
+            status,
+            duration: 0.25,
+            score,
+            variant: None,
+            exit_code: None,
+            stderr_tail: None,
+        }
+    }
+
+    /// A file in the temporary directory named after the test, removed when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str, content: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("tester-export-{}-{}", std::process::id(), name));
+            fs::write(&path, content).unwrap();
+            TempFile(path)
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut swept = record(2, RunStatus::TimedOut, None);
+        swept.variant = Some("threads=4".to_string());
+        for record in [
+            record(0, RunStatus::Passed, Some(1.5)),
+            record(1, RunStatus::CpuLimitExceeded, Some(-0.0)),
+            swept,
+        ] {
+            assert_eq!(RunRecord::from_csv(&record.to_csv()), Ok(record));
+        }
+        // Written before runs had a variant.
+        assert_eq!(
+            RunRecord::from_csv("3,1,skipped,0.25,"),
+            Ok(record(3, RunStatus::Skipped, None))
+        );
+        assert!(RunRecord::from_csv("3,0,lost,0.5,,").is_err());
+        assert!(RunRecord::from_csv("3,0,passed").is_err());
+    }
+
+    #[test]
+    fn read_csv() {
+        let file = TempFile::new(
+            "csv",
+            &format!(
+                "{},team,note\n0,1,passed,0.25,2,,ci,\"a,\"\"b\"\"\"\n1,1,failed,0.25,,,ci,\"a,\"\"b\"\"\"\n",
+                CSV_HEADER
+            ),
+        );
+        let (format, records) = read_records(&file.0).unwrap();
+        assert_eq!(format, Format::Csv);
+        assert_eq!(
+            records,
+            [
+                record(0, RunStatus::Passed, Some(2.0)),
+                record(1, RunStatus::Failed, None)
+            ]
+        );
+        assert_eq!(
+            read_tags(&file.0).unwrap(),
+            [
+                Tag {
+                    key: "team".to_string(),
+                    value: "ci".to_string()
+                },
+                Tag {
+                    key: "note".to_string(),
+                    value: "a,\"b\"".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn read_legacy_csv() {
+        let file = TempFile::new(
+            "legacy",
+            &format!("{}\n0,1,passed,0.25,\n", LEGACY_CSV_HEADER),
+        );
+        assert_eq!(
+            read_records(&file.0).unwrap(),
+            (Format::Csv, vec![record(0, RunStatus::Passed, None)])
+        );
+        assert_eq!(read_tags(&file.0).unwrap(), []);
+    }
+
+    #[test]
+    fn read_jsonl() {
+        let mut failed = record(1, RunStatus::Failed, None);
+        failed.exit_code = Some(3);
+        failed.stderr_tail = Some("boom\n".to_string());
+        let records = vec![record(0, RunStatus::Passed, Some(0.5)), failed];
+        let mut content = records
+            .iter()
+            .map(|record| serde_json::to_string(record).unwrap() + "\n")
+            .collect::<String>();
+        content += "{\"type\":\"summary\",\"command\":\"true\"}\n";
+        let file = TempFile::new("jsonl", &content);
+        assert_eq!(read_records(&file.0).unwrap(), (Format::Jsonl, records));
+    }
+
+    #[test]
+    fn read_unknown() {
+        let file = TempFile::new("unknown", "a,b,c\n1,2,3\n");
+        let err = read_records(&file.0).unwrap_err();
+        assert!(err.contains("unknown header `a,b,c`"), "{}", err);
+        let file = TempFile::new("empty", "\n");
+        assert!(read_records(&file.0).unwrap_err().ends_with("is empty"));
+        let file = TempFile::new("bad", "{\"index\":0}\n");
+        assert!(read_records(&file.0)
+            .unwrap_err()
+            .contains(":1: not a tester JSONL record"));
+    }
+
+    #[test]
+    fn stderr_tail() {
+        assert_eq!(tail(b""), None);
+        assert_eq!(tail(b"short").as_deref(), Some("short"));
+        let mut long = vec![b'x'; STDERR_TAIL_BYTES - 1];
+        long.splice(0..0, "é".bytes());
+        // The end of the `é` is cut off with it.
+        let kept = tail(&long).unwrap();
+        assert_eq!(kept.len(), STDERR_TAIL_BYTES - 1);
+        assert!(kept.bytes().all(|byte| byte == b'x'));
+    }
+}
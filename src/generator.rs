@@ -0,0 +1,42 @@
+//! Generating the input of every iteration with a separate command, for stress testing.
+
+use crate::command::Step;
+
+/// The placeholder replaced by the seed in the generator's arguments.
+pub const SEED_PLACEHOLDER: &str = "{seed}";
+
+/// A command run at the start of every iteration whose stdout becomes the stdin of every step.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    /// The generator command.
+    pub step: Step,
+    /// Pass run `i` the seed `seed_start + i`, so every run gets a distinct, replayable seed.
+    pub seed_start: Option<u64>,
+}
+
+impl Generator {
+    /// The seed of run `index`, if seeds are passed. Seeds follow the run indices, so they are
+    /// unique and dense across threads, and a resumed session passes the same seeds.
+    pub fn seed(&self, index: u64) -> Option<u64> {
+        self.seed_start
+            .map(|seed_start| seed_start.wrapping_add(index))
+    }
+
+    /// The generator command of run `index`: with the seed substituted for every
+    /// [`SEED_PLACEHOLDER`], or appended as the last argument when there is none.
+    pub fn step_for(&self, index: u64) -> Step {
+        let Some(seed) = self.seed(index) else {
+            return self.step.clone();
+        };
+        let seed = seed.to_string();
+        let mut step = self.step.clone();
+        if step.args.iter().any(|arg| arg.contains(SEED_PLACEHOLDER)) {
+            for arg in &mut step.args {
+                *arg = arg.replace(SEED_PLACEHOLDER, &seed);
+            }
+        } else {
+            step.args.push(seed);
+        }
+        step
+    }
+}
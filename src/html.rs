@@ -0,0 +1,327 @@
+//! A single-file HTML report of a session, with its charts as inline SVG, for sharing with
+//! people who will not run tester themselves.
+
+use crate::{
+    compare,
+    sparkline::{Measure, Series},
+    summary::SessionSummary,
+    units, RunObserver, RunResult,
+};
+use std::{fmt::Write, sync::Mutex, time::Duration};
+
+/// At most this many scores are kept for the histogram.
+const MAX_SCORES: usize = 1 << 16;
+/// The outputs of this many failed runs are included.
+const MAX_FAILURES: usize = 5;
+/// Lines of every output included.
+const OUTPUT_LINES: usize = 40;
+/// Bars of the score histogram.
+const HISTOGRAM_BINS: usize = 20;
+/// Points of the duration chart.
+const DURATION_POINTS: usize = 200;
+
+const WIDTH: f64 = 600.0;
+const HEIGHT: f64 = 160.0;
+
+/// Scores of every `stride`th run, the stride doubling whenever too many are kept, so they
+/// stay a sample spread evenly over the session.
+#[derive(Default)]
+struct Scores {
+    stride: u64,
+    values: Vec<(u64, f64)>,
+}
+
+impl Scores {
+    fn add(&mut self, index: u64, score: f64) {
+        self.stride = self.stride.max(1);
+        if !index.is_multiple_of(self.stride) {
+            return;
+        }
+        self.values.push((index, score));
+        if self.values.len() > MAX_SCORES {
+            self.stride *= 2;
+            let stride = self.stride;
+            self.values
+                .retain(|(index, _)| index.is_multiple_of(stride));
+        }
+    }
+}
+
+/// A failed run whose output is shown.
+struct FailedRun {
+    index: u64,
+    description: String,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+/// An observer collecting what the report shows beyond the [`SessionSummary`]: a sample of
+/// the scores, the durations in run order and the outputs of the first failed runs.
+pub struct ReportData {
+    scores: Mutex<Scores>,
+    durations: Series,
+    failures: Mutex<Vec<FailedRun>>,
+}
+
+impl ReportData {
+    /// Collect the data of the `times` runs of a session.
+    pub fn new(times: u64) -> Self {
+        ReportData {
+            scores: Mutex::default(),
+            durations: Series::new(Measure::Duration, times),
+            failures: Mutex::default(),
+        }
+    }
+}
+
+impl RunObserver for ReportData {
+    fn on_run(&self, result: &RunResult) {
+        self.durations.on_run(result);
+        if let Some(score) = result.score.filter(|score| score.is_finite()) {
+            self.scores.lock().unwrap().add(result.index, score);
+        }
+        let Some(failure) = &result.failure else {
+            return;
+        };
+        let mut failures = self.failures.lock().unwrap();
+        if failures.len() < MAX_FAILURES {
+            failures.push(FailedRun {
+                index: result.index,
+                description: format!("{}: {}", result.status, failure),
+                stdout: result.stdout.clone(),
+                stderr: result.stderr.clone(),
+            });
+        }
+    }
+}
+
+/// Escape `text` for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn seconds(value: f64) -> String {
+    units::format_duration(Duration::from_secs_f64(value.max(0.0)))
+}
+
+/// A bar chart of how many `values` fall in each of [`HISTOGRAM_BINS`] equal bins.
+fn histogram(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mut bins = [0u32; HISTOGRAM_BINS];
+    for value in values {
+        let bin = if max > min {
+            ((value - min) / (max - min) * HISTOGRAM_BINS as f64) as usize
+        } else {
+            HISTOGRAM_BINS / 2
+        };
+        bins[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+    let highest = bins.iter().copied().max().unwrap_or(1).max(1) as f64;
+    let bar_width = WIDTH / HISTOGRAM_BINS as f64;
+    let mut svg = svg_start();
+    for (bin, &count) in bins.iter().enumerate() {
+        let height = count as f64 / highest * (HEIGHT - 20.0);
+        let _ = write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\"><title>{}</title></rect>",
+            bin as f64 * bar_width + 1.0,
+            HEIGHT - 20.0 - height,
+            bar_width - 2.0,
+            height,
+            count
+        );
+    }
+    let _ = write!(
+        svg,
+        "<text x=\"0\" y=\"{}\">{}</text><text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text></svg>",
+        HEIGHT - 4.0,
+        escape(&units::format_score(min)),
+        WIDTH,
+        HEIGHT - 4.0,
+        escape(&units::format_score(max))
+    );
+    svg
+}
+
+/// A line chart of `values` in run order, with gaps where there is no value.
+fn line_chart(values: &[Option<f64>]) -> String {
+    let known = values.iter().flatten();
+    let min = known.clone().copied().fold(f64::INFINITY, f64::min);
+    let max = known.copied().fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1.0 };
+    let step = WIDTH / values.len().max(2).saturating_sub(1) as f64;
+    let mut svg = svg_start();
+    let mut points = String::new();
+    for (index, value) in values.iter().enumerate() {
+        match value {
+            Some(value) => {
+                let y = HEIGHT - 20.0 - (value - min) / range * (HEIGHT - 30.0);
+                let _ = write!(points, "{:.1},{:.1} ", index as f64 * step, y);
+            }
+            None if !points.is_empty() => {
+                let _ = write!(svg, "<polyline points=\"{}\"/>", points.trim_end());
+                points.clear();
+            }
+            None => {}
+        }
+    }
+    if !points.is_empty() {
+        let _ = write!(svg, "<polyline points=\"{}\"/>", points.trim_end());
+    }
+    let _ = write!(
+        svg,
+        "<text x=\"0\" y=\"{}\">{}</text><text x=\"0\" y=\"12\">{}</text></svg>",
+        HEIGHT - 4.0,
+        seconds(min),
+        seconds(max)
+    );
+    svg
+}
+
+fn svg_start() -> String {
+    format!(
+        "<svg viewBox=\"0 0 {} {}\" width=\"{}\" height=\"{}\">",
+        WIDTH, HEIGHT, WIDTH, HEIGHT
+    )
+}
+
+/// The whole report of the session summed up by `summary`.
+pub fn render(summary: &SessionSummary, data: &ReportData) -> String {
+    let report = &summary.report;
+    let title = match &summary.metadata.label {
+        Some(label) => format!("tester report: {}", label),
+        None => "tester report".to_string(),
+    };
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title><style>\
+         body{{font-family:sans-serif;max-width:50em;margin:2em auto;color:#222}}\
+         table{{border-collapse:collapse}}td,th{{padding:.2em .8em;text-align:right}}\
+         th{{border-bottom:1px solid #888}}code,pre{{background:#f4f4f4}}\
+         pre{{padding:.5em;overflow-x:auto}}svg rect{{fill:#4a7ab5}}\
+         svg polyline{{fill:none;stroke:#4a7ab5;stroke-width:1.5}}svg text{{font-size:11px}}\
+         .failed{{color:#b00}}</style></head><body>\n<h1>{}</h1>\n<p><code>{}</code></p>\n",
+        escape(&title),
+        escape(&title),
+        escape(&summary.command)
+    );
+    if !summary.metadata.tags.is_empty() {
+        let tags = summary
+            .metadata
+            .tags
+            .iter()
+            .map(|tag| format!("{}={}", tag.key, tag.value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let _ = writeln!(html, "<p>Tags: {}</p>", escape(&tags));
+    }
+
+    html += "<h2>Summary</h2>\n<table>\n";
+    let mut rows = vec![
+        ("runs", report.run_times.to_string()),
+        ("passed", report.pass_times().to_string()),
+        ("failed", report.fail_times.to_string()),
+    ];
+    let breakdown = [
+        ("of which timed out", report.timeout_times),
+        ("of which over the CPU limit", report.cpu_limit_times),
+        (
+            "of which over --timeout-factor",
+            report.relative_timeout_times,
+        ),
+        ("invalid input (not counted)", report.invalid_times),
+        ("setup failed (not counted)", report.setup_failed_times),
+        ("skipped (not counted)", report.skipped_times),
+    ];
+    for (what, count) in breakdown {
+        if count > 0 {
+            rows.push((what, count.to_string()));
+        }
+    }
+    if let Some(mean) = report.scores.mean() {
+        rows.push(("mean score", units::format_score(mean)));
+    }
+    if let Some(mean) = report.times.mean() {
+        rows.push(("mean duration", seconds(mean)));
+    }
+    for (what, value) in rows {
+        let _ = writeln!(
+            html,
+            "<tr><th>{}</th><td>{}</td></tr>",
+            what,
+            escape(&value)
+        );
+    }
+    html += "</table>\n";
+
+    let scores = data
+        .scores
+        .lock()
+        .unwrap()
+        .values
+        .iter()
+        .map(|&(_, score)| score)
+        .collect::<Vec<_>>();
+    if !scores.is_empty() {
+        html += "<h2>Scores</h2>\n";
+        html += &histogram(&scores);
+        html += "\n";
+    }
+    let durations = data.durations.values(DURATION_POINTS);
+    if durations.iter().any(Option::is_some) {
+        html += "<h2>Duration over the session</h2>\n";
+        html += &line_chart(&durations);
+        html += "\n";
+    }
+
+    if !summary.slowest.is_empty() {
+        html += "<h2>Slowest runs</h2>\n<table>\n\
+                 <tr><th>run</th><th>duration</th><th>status</th><th>score</th></tr>\n";
+        for run in &summary.slowest {
+            let status = match &run.reason {
+                Some(reason) => format!("{} ({})", run.status, reason),
+                None => run.status.to_string(),
+            };
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                run.index,
+                seconds(run.duration),
+                escape(&status),
+                escape(&run.score.map(units::format_score).unwrap_or_default())
+            );
+        }
+        html += "</table>\n";
+    }
+
+    let failures = data.failures.lock().unwrap();
+    if !failures.is_empty() {
+        let _ = writeln!(html, "<h2>First {} failed runs</h2>", failures.len());
+        for failure in failures.iter() {
+            let _ = writeln!(
+                html,
+                "<details><summary class=\"failed\">run {}: {}</summary>",
+                failure.index,
+                escape(&failure.description)
+            );
+            for (name, output) in [("stdout", &failure.stdout), ("stderr", &failure.stderr)] {
+                if !output.is_empty() {
+                    let _ = writeln!(
+                        html,
+                        "<p>{}</p><pre>{}</pre>",
+                        name,
+                        escape(&compare::excerpt(output, OUTPUT_LINES))
+                    );
+                }
+            }
+            html += "</details>\n";
+        }
+    }
+    html += "</body></html>\n";
+    html
+}
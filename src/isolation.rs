@@ -0,0 +1,74 @@
+//! Running the steps of every run in namespaces of their own, see `--isolate`. Only supported
+//! on Linux, where unprivileged users get them through a user namespace if the kernel allows.
+
+use std::{fmt, io};
+
+/// What the steps are cut off from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Isolation {
+    /// The network: the steps get a network namespace with nothing but a loopback device,
+    /// which is down.
+    pub net: bool,
+    /// The `/tmp` of everyone else: the steps get a mount namespace with an empty tmpfs on
+    /// `/tmp`, the directory of the run still where it was.
+    pub tmp: bool,
+}
+
+impl Isolation {
+    /// Whether the steps are not isolated at all.
+    pub fn is_empty(&self) -> bool {
+        *self == Isolation::default()
+    }
+}
+
+impl fmt::Display for Isolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts = [("net", self.net), ("tmp", self.tmp)]
+            .into_iter()
+            .filter_map(|(name, isolated)| isolated.then_some(name))
+            .collect::<Vec<_>>();
+        write!(f, "{}", parts.join(","))
+    }
+}
+
+/// Parse what to isolate for `--isolate`: a comma-separated list of `net` and `tmp`.
+pub fn parse_isolation(value: &str) -> Result<Isolation, String> {
+    if cfg!(not(target_os = "linux")) {
+        return Err("only supported on Linux".to_string());
+    }
+    let mut isolation = Isolation::default();
+    for part in value.split(',').map(str::trim) {
+        match part {
+            "net" => isolation.net = true,
+            "tmp" => isolation.tmp = true,
+            _ => return Err(format!("unknown `{}`, expected net or tmp", part)),
+        }
+    }
+    Ok(isolation)
+}
+
+/// Check that processes can be isolated like that here, by running one that does nothing.
+pub fn check(isolation: Isolation) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        let mut command = std::process::Command::new("true");
+        command
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null());
+        crate::process::isolate(&mut command, isolation, None);
+        let status = command.status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("`true` ended with {}", status)));
+        }
+        Ok(())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = isolation;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only supported on Linux",
+        ))
+    }
+}
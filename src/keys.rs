@@ -0,0 +1,125 @@
+//! Reading single key presses from the terminal while a session runs, see `--keys`.
+//!
+//! The terminal is switched out of line mode and echo only while a [`KeyReader`] lives; ctrl-c
+//! still interrupts as usual. Only supported on Unix.
+
+use std::{io, time::Duration};
+
+#[cfg(unix)]
+mod imp {
+    use std::{
+        io,
+        sync::{Mutex, Once},
+        time::Duration,
+    };
+
+    /// The terminal settings to restore, kept where the panic hook can find them.
+    static SAVED: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+    pub fn enable() -> io::Result<()> {
+        // SAFETY: termios is plain data that tcgetattr fills in.
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        // SAFETY: `termios` outlives the call, which only writes to it.
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let saved = termios;
+        termios.c_lflag &= !(libc::ICANON | libc::ECHO);
+        termios.c_cc[libc::VMIN] = 1;
+        termios.c_cc[libc::VTIME] = 0;
+        // SAFETY: `termios` was filled in by tcgetattr and outlives the call, which only reads
+        // it.
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        *SAVED.lock().unwrap_or_else(|err| err.into_inner()) = Some(saved);
+        static HOOK: Once = Once::new();
+        HOOK.call_once(|| {
+            let hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore();
+                hook(info);
+            }));
+        });
+        Ok(())
+    }
+
+    pub fn restore() {
+        let saved = SAVED.lock().unwrap_or_else(|err| err.into_inner()).take();
+        if let Some(saved) = saved {
+            // SAFETY: `saved` came from tcgetattr and outlives the call, which only reads it.
+            // Nothing can be done about a failure, also not from the panic hook.
+            unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &saved) };
+        }
+    }
+
+    pub fn read_key(timeout: Duration) -> io::Result<Option<u8>> {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(i32::MAX as u128) as i32;
+        // SAFETY: `fd` outlives the call and is the one pollfd the count says there is.
+        match unsafe { libc::poll(&mut fd, 1, millis) } {
+            0 => return Ok(None),
+            n if n < 0 => {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    io::ErrorKind::Interrupted => Ok(None),
+                    _ => Err(err),
+                };
+            }
+            _ => {}
+        }
+        let mut key = 0u8;
+        // SAFETY: `key` outlives the call, which writes at most the one byte it has.
+        match unsafe { libc::read(libc::STDIN_FILENO, (&mut key as *mut u8).cast(), 1) } {
+            1 => Ok(Some(key)),
+            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::{io, time::Duration};
+
+    pub fn enable() -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "only supported on Unix",
+        ))
+    }
+
+    pub fn restore() {}
+
+    pub fn read_key(_timeout: Duration) -> io::Result<Option<u8>> {
+        Ok(None)
+    }
+}
+
+/// Single key presses from the terminal on stdin. Dropping it restores the terminal, as does
+/// a panic.
+pub struct KeyReader(());
+
+impl KeyReader {
+    /// Take over the terminal on stdin, failing if stdin is not a terminal.
+    pub fn new() -> io::Result<Self> {
+        imp::enable()?;
+        Ok(KeyReader(()))
+    }
+
+    /// The next key pressed, or `None` if none was within `timeout`. Fails once stdin is
+    /// closed.
+    pub fn read_key(&self, timeout: Duration) -> io::Result<Option<u8>> {
+        imp::read_key(timeout)
+    }
+}
+
+impl Drop for KeyReader {
+    fn drop(&mut self) {
+        imp::restore();
+    }
+}
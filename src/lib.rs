@@ -0,0 +1,67 @@
+//! tester: run a command many times, possibly in parallel, and aggregate the results.
+//!
+//! The `tester` binary only parses its arguments and hands them to [`session::run`] or
+//! [`report::run`], which drive a [`TestRunner`]:
+//!
+//! ```no_run
+//! use tester::{command::Step, RunConfig, TestRunner};
+//!
+//! let step = Step { exec: "./my-test".to_string(), args: vec![] };
+//! let mut config = RunConfig::new(vec![step], 100);
+//! config.threads = 4;
+//! let runner = TestRunner::new(config);
+//! let report = runner.run()?;
+//! println!("{} of {} runs failed", report.fail_times, report.run_times);
+//! # Ok::<(), tester::Error>(())
+//! ```
+#![warn(missing_docs)]
+
+#[macro_use]
+pub mod logging;
+pub mod artifacts;
+pub mod baseline;
+pub mod checker;
+pub mod command;
+pub mod compare;
+pub mod dashboard;
+pub mod determinism;
+pub mod dots;
+pub mod environment;
+mod error;
+pub mod export;
+pub mod generator;
+pub mod html;
+pub mod isolation;
+pub mod keys;
+pub mod metadata;
+pub mod notify;
+pub mod objective;
+mod process;
+pub mod provenance;
+pub mod report;
+pub mod rerun;
+mod runner;
+pub mod runs;
+pub mod score;
+pub mod session;
+pub mod shrink;
+pub mod shuffle;
+pub mod signals;
+pub mod sink;
+pub mod slowest;
+pub mod sparkline;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
+pub mod summary;
+pub mod sweep;
+pub mod timeline;
+pub mod tmpdir;
+pub mod units;
+pub mod watch;
+
+pub use error::Error;
+pub use runner::{
+    Abort, CancellationToken, Comparison, Failure, RunConfig, RunObserver, RunResult, RunStatus,
+    Schedule, SessionReport, Snapshot, StdioChoice, TestRunner, TimeoutFactor, Variant,
+};
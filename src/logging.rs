@@ -0,0 +1,149 @@
+//! Tester's own messages (as opposed to the child's output), filtered by verbosity.
+//!
+//! Everything goes to stderr so stdout stays a clean passthrough of the child's output. The
+//! `error!`, `notice!`, `warn!`, `info!` and `debug!` macros format a message and [`emit`] it
+//! at the matching level.
+
+use crate::dots::Dots;
+use indicatif::ProgressBar;
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::{
+        atomic::{AtomicI8, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Always shown, even with `-q`.
+pub const ERROR: i8 = i8::MIN;
+/// The final summary. Shown by default, hidden by `-q`.
+pub const NOTICE: i8 = 0;
+/// Per-run result lines and warnings, shown from `-v`.
+pub const INFO: i8 = 1;
+/// Expanded commands and scheduling decisions, shown from `-vv`.
+pub const DEBUG: i8 = 2;
+
+static VERBOSITY: AtomicI8 = AtomicI8::new(NOTICE);
+static PROGRESS_BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+static DOTS: Mutex<Option<Arc<Dots>>> = Mutex::new(None);
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// The messages kept instead of printed, see [`start_capture`].
+struct Capture {
+    limit: usize,
+    lines: VecDeque<String>,
+    /// Lines that did not fit.
+    dropped: usize,
+}
+
+/// Show messages up to `verbosity`, one of the level constants or anything in between.
+pub fn set_verbosity(verbosity: i8) {
+    VERBOSITY.store(verbosity, Ordering::Relaxed);
+}
+
+/// Whether messages of `level` are shown.
+pub fn enabled(level: i8) -> bool {
+    level <= VERBOSITY.load(Ordering::Relaxed)
+}
+
+/// Route messages through `progress_bar` while it is drawn, so they don't tear its line.
+pub fn attach_progress_bar(progress_bar: Option<ProgressBar>) {
+    *PROGRESS_BAR.lock().unwrap() = progress_bar;
+}
+
+/// Likewise print messages around the unfinished line of `dots` while it is drawn.
+pub fn attach_dots(dots: Option<Arc<Dots>>) {
+    *DOTS.lock().unwrap() = dots;
+}
+
+/// Keep the lines of the messages shown from now on, the last `limit` of them, instead of
+/// printing them, e.g. while the screen is taken over by `--tui`.
+pub fn start_capture(limit: usize) {
+    *CAPTURE.lock().unwrap() = Some(Capture {
+        limit,
+        lines: VecDeque::new(),
+        dropped: 0,
+    });
+}
+
+/// The last `count` lines kept since [`start_capture`], oldest first.
+pub fn captured(count: usize) -> Vec<String> {
+    let capture = CAPTURE.lock().unwrap();
+    let Some(capture) = capture.as_ref() else {
+        return vec![];
+    };
+    let skipped = capture.lines.len().saturating_sub(count);
+    capture.lines.iter().skip(skipped).cloned().collect()
+}
+
+/// Print messages again, returning the lines kept since [`start_capture`], after one telling
+/// how many did not fit.
+pub fn stop_capture() -> Vec<String> {
+    let Some(capture) = CAPTURE.lock().unwrap().take() else {
+        return vec![];
+    };
+    let dropped = (capture.dropped > 0)
+        .then(|| format!("#tester ... {} earlier lines not kept", capture.dropped));
+    dropped.into_iter().chain(capture.lines).collect()
+}
+
+/// Print a message of `level` to stderr if that level is shown.
+pub fn emit(level: i8, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    if let Some(capture) = CAPTURE.lock().unwrap().as_mut() {
+        for line in args.to_string().lines() {
+            if capture.lines.len() == capture.limit {
+                capture.lines.pop_front();
+                capture.dropped += 1;
+            }
+            capture.lines.push_back(line.to_string());
+        }
+        return;
+    }
+    if let Some(progress_bar) = PROGRESS_BAR.lock().unwrap().as_ref() {
+        return progress_bar.suspend(|| eprintln!("{}", args));
+    }
+    match DOTS.lock().unwrap().as_ref() {
+        Some(dots) => dots.suspend(|| eprintln!("{}", args)),
+        None => eprintln!("{}", args),
+    }
+}
+
+/// Print a message that is always shown.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::logging::emit($crate::logging::ERROR, format_args!($($arg)*)) };
+}
+
+/// Print a message shown by default.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! notice {
+    ($($arg:tt)*) => { $crate::logging::emit($crate::logging::NOTICE, format_args!($($arg)*)) };
+}
+
+/// Print a warning, shown from `-v`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::logging::emit($crate::logging::INFO, format_args!($($arg)*)) };
+}
+
+/// Print a per-run message, shown from `-v`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::logging::emit($crate::logging::INFO, format_args!($($arg)*)) };
+}
+
+/// Print a message about commands and scheduling, shown from `-vv`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! debug {
+
+    ($($arg:tt)*) => { $crate::logging::emit($crate::logging::DEBUG, format_args!($($arg)*)) };
+}
@@ -1,198 +1,104 @@
+//! The `tester` command line: parses the arguments, runs the subcommand from the library and
+//! maps how it ended to an exit status.
+
 use clap::Parser;
-use ctrlc;
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
-use std::{
-    io::Write,
-    process::{Command, Stdio},
-    sync::{
-        atomic::{AtomicBool, AtomicU32, Ordering},
-        Arc, Mutex,
-    },
-    thread::{self, sleep},
-    time::Duration,
+use std::{env, ffi::OsString, process::ExitCode};
+use tester::{
+    error, logging, notice, report,
+    report::ReportArgs,
+    session::{self, Outcome, RunArgs},
 };
 
+/// Exit status when the session finished but some runs failed.
+const EXIT_RUNS_FAILED: u8 = 1;
+/// Exit status when the command line or the environment is unusable and nothing was run.
+/// Also what clap exits with on usage errors.
+const EXIT_CONFIG: u8 = 2;
+/// Exit status when running the session failed partway.
+const EXIT_RUNTIME: u8 = 3;
+/// Exit status when the session finished but `--after-all` failed.
+const EXIT_AFTER_ALL: u8 = 4;
+/// Exit status when the session regressed from its `--baseline`.
+const EXIT_REGRESSION: u8 = 5;
+/// Exit status when ctrl-c stopped the session before every run finished, that of a shell
+/// command killed by SIGINT.
+const EXIT_INTERRUPTED: u8 = 130;
+
 /// tester: A simple cli tool to help you run a test multi times
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    after_help = "Exit status: 0 if every run passed, 1 if some runs failed (with \
+                  --judge-by-target: more than --target-failure-rate allows), 2 if nothing could be \
+                  run (bad arguments or configuration), 3 if the session broke off (e.g. a \
+                  command could not be started), 4 if --after-all failed after a session that had not broken \
+                  off, 5 if the session regressed from its --baseline, 130 if ctrl-c stopped the \
+                  session early."
+)]
 struct Cli {
-    /// Disable the execution's stdout and stderr
-    #[arg(long, default_value_t = false)]
-    silent: bool,
-    /// Calculate the average score of every run
-    #[arg(short, long, default_value_t = false)]
-    score: bool,
-    /// Show progress bar
-    #[arg(long, default_value_t = false)]
-    progress: bool,
-    /// Number of times to run the commands
-    #[arg(short = 'n')]
-    times: u32,
-    /// Number of threads
-    #[arg(short = 'p', default_value_t = 1)]
-    threads: u8,
-    exec: String,
-    exec_args: Vec<String>,
+    #[command(subcommand)]
+    command: Subcommand,
 }
 
-struct TesterInfo {
-    fail_times: AtomicU32,
-    run_times: AtomicU32,
-    total_scores: Mutex<f64>,
-    cli_args: Cli,
-    ctrlc_signal: AtomicBool,
+#[derive(clap::Subcommand, Debug)]
+enum Subcommand {
+    /// Run a command many times and summarize the results
+    Run(Box<RunArgs>),
+    /// Analyze the results of earlier sessions
+    Report(ReportArgs),
 }
 
-impl TesterInfo {
-    fn print_summary(&self) {
-        let fail_times = self.fail_times.load(Ordering::Relaxed);
-        let run_times = self.run_times.load(Ordering::Relaxed);
-        let total_score = *self.total_scores.lock().unwrap();
-        if fail_times > 0 {
-            println!("#tester finished. Failed {} / {}", fail_times, run_times);
-            if self.cli_args.score {
-                let avg_score = total_score / ((run_times - fail_times) as f64);
-                println!("#tester average score(Ignore failed runs): {}.", avg_score);
-            }
-        } else {
-            println!("#tester finished. No failure in {} runs.", run_times);
-            if self.cli_args.score {
-                let avg_score = total_score / run_times as f64;
-                println!("#tester average score: {}.", avg_score);
-            }
-        }
-    }
-
-    fn do_test(&self, times: u32) {
-        let mut run_times = 0;
-        let mut fail_times = 0;
-        let mut total_scores = 0.0;
-        let mut program = Command::new(&self.cli_args.exec);
-        program
-            .args(&self.cli_args.exec_args)
-            .stderr(Stdio::piped())
-            .stdout(Stdio::piped());
-        for _ in 0..times {
-            if self.ctrlc_signaled() {
-                break;
-            }
-            let p_instance = program.spawn().expect("cmd failed to start");
-            let p_ret = p_instance.wait_with_output().expect("Unable to run cmd");
-
-            if !self.cli_args.silent {
-                let mut stdout = std::io::stdout();
-                let mut stderr = std::io::stderr();
-                stdout.write_all(&p_ret.stdout).unwrap();
-                stderr.write_all(&p_ret.stderr).unwrap();
-            }
-
-            if !p_ret.status.success() {
-                fail_times += 1;
-            } else if self.cli_args.score {
-                let std_out = String::from_utf8(p_ret.stdout).unwrap();
-                let score: f64 = std_out.trim().parse().unwrap();
-                total_scores += score;
-            }
-
-            run_times += 1;
-            if self.cli_args.progress {
-                self.append_run_times(1);
-            }
-        }
-        self.append_result(fail_times, total_scores);
-        if !self.cli_args.progress {
-            self.append_run_times(run_times);
-        }
-    }
-
-    fn append_result(&self, fail_times: u32, total_scores: f64) {
-        self.fail_times.fetch_add(fail_times, Ordering::Relaxed);
-        if self.cli_args.score {
-            *self.total_scores.lock().unwrap() += total_scores;
-        }
-    }
-
-    fn append_run_times(&self, run_times: u32) {
-        self.run_times.fetch_add(run_times, Ordering::Relaxed);
-    }
-
-    fn get_progress(&self) -> u32 {
-        return self.run_times.load(Ordering::Relaxed);
+/// Subcommand names, and the flags clap handles before one is needed.
+const SUBCOMMANDS: [&str; 7] = ["run", "report", "help", "-h", "--help", "-V", "--version"];
+
+/// The arguments with `run` inserted when no subcommand is given, so the `tester -n 10 cmd`
+/// form from before subcommands keeps working. Returns whether it was inserted.
+fn with_default_subcommand(mut args: Vec<OsString>) -> (Vec<OsString>, bool) {
+    let missing = args
+        .get(1)
+        .is_some_and(|arg| !SUBCOMMANDS.iter().any(|name| arg == *name));
+    if missing {
+        args.insert(1, "run".into());
     }
+    (args, missing)
+}
 
-    fn ctrlc_signaled(&self) -> bool {
-        return self.ctrlc_signal.load(Ordering::Relaxed);
+/// The exit status of a session that ended with `outcome`.
+fn exit_code(outcome: Outcome) -> ExitCode {
+    match outcome {
+        Outcome::Passed => ExitCode::SUCCESS,
+        Outcome::Failed => ExitCode::from(EXIT_RUNS_FAILED),
+        Outcome::Unusable => ExitCode::from(EXIT_CONFIG),
+        Outcome::Broken => ExitCode::from(EXIT_RUNTIME),
+        Outcome::AfterAllFailed => ExitCode::from(EXIT_AFTER_ALL),
+        Outcome::Regression => ExitCode::from(EXIT_REGRESSION),
+        Outcome::Interrupted => ExitCode::from(EXIT_INTERRUPTED),
     }
 }
 
-fn main() {
-    let test_info = Arc::new(TesterInfo {
-        fail_times: AtomicU32::new(0),
-        run_times: AtomicU32::new(0),
-        total_scores: Mutex::new(0.0),
-        ctrlc_signal: AtomicBool::new(false),
-        cli_args: Cli::parse(),
-    });
-
-    let mut handles = vec![];
-    let threads = std::cmp::max(test_info.cli_args.threads, 1);
-    let times_per_thread = test_info.cli_args.times / threads as u32;
-    let mut times_extra = test_info.cli_args.times % threads as u32;
-    for _ in 0..threads {
-        let mut times_this_thread = times_per_thread;
-        if times_extra > 0 {
-            times_this_thread += 1;
-            times_extra -= 1;
+fn main() -> ExitCode {
+    let (args, implicit_run) = with_default_subcommand(env::args_os().collect());
+    let result = match Cli::parse_from(args).command {
+        Subcommand::Run(args) => {
+            logging::set_verbosity(args.verbosity());
+            args.number_format().apply();
+            if implicit_run {
+                notice!("#tester running without a subcommand is deprecated, use `tester run`");
+            }
+            session::run(*args)
         }
-
-        if times_this_thread <= 0 {
-            break;
+        Subcommand::Report(args) => {
+            args.number_format().apply();
+            report::run(args).map(|()| Outcome::Passed)
         }
-
-        let test_info_share = test_info.clone();
-        let handle = thread::spawn(move || {
-            test_info_share.do_test(times_per_thread);
-        });
-        handles.push(handle);
-    }
-
-    // handles ctrlc
-    let test_info_share = test_info.clone();
-    ctrlc::set_handler(move || {
-        println!("Ctrl-c pressed. Terminating...");
-        test_info_share.ctrlc_signal.store(true, Ordering::Relaxed);
-    })
-    .unwrap();
-
-    if test_info.cli_args.progress {
-        let total_progress = test_info.cli_args.times;
-        let mut current_progress = test_info.get_progress();
-        let progress_bar = ProgressBar::new(total_progress as u64);
-        let progress_bar_template =
-            "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta})";
-        let eta_progress_fn = |state: &ProgressState, w: &mut dyn std::fmt::Write| {
-            write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
-        };
-        let progress_bar_style = ProgressStyle::with_template(progress_bar_template)
-            .unwrap()
-            .with_key("eta", eta_progress_fn)
-            .progress_chars("#>-");
-        progress_bar.set_style(progress_bar_style);
-        let update_duration = Duration::from_secs_f32(0.1);
-        while current_progress < total_progress {
-            if test_info.ctrlc_signaled() {
-                break;
-            }
-            current_progress = test_info.get_progress();
-            progress_bar.set_position(current_progress as u64);
-            sleep(update_duration);
+    };
+    match result {
+        Ok(outcome) => exit_code(outcome),
+        Err(err) => {
+            error!("#tester {}", err);
+            exit_code(err.outcome())
         }
-        progress_bar.finish();
-    }
-
-    for handle in handles {
-        handle.join().unwrap();
     }
-
-    test_info.print_summary();
 }
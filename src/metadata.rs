@@ -0,0 +1,168 @@
+//! Descriptive information about a session that has no effect on how it runs, carried into
+//! its reports so they can be told apart later.
+
+use crate::{objective::Objective, provenance::BinaryStamp, runs::Shard};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The prefix of the keys of the tags tester adds itself, refused in `--tag`.
+pub const BUILTIN_PREFIX: &str = "tester.";
+
+/// A `--tag key=value` pair.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    /// The part before the first `=`.
+    pub key: String,
+    /// The part after the first `=`.
+    pub value: String,
+}
+
+/// Parse a `--tag key=value` pair. The value may itself contain `=` but not line breaks, the
+/// key may not contain control characters nor start with [`BUILTIN_PREFIX`].
+pub fn parse_tag(s: &str) -> Result<Tag, String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid tag `{}`: expected key=value", s))?;
+    if key.is_empty() {
+        return Err(format!("invalid tag `{}`: the key is empty", s));
+    }
+    if key.chars().any(char::is_control) {
+        return Err(format!(
+            "invalid tag {:?}: the key contains control characters",
+            s
+        ));
+    }
+    if value.contains(['\n', '\r']) {
+        return Err(format!("invalid tag {:?}: the value spans lines", s));
+    }
+    if key.starts_with(BUILTIN_PREFIX) {
+        return Err(format!(
+            "invalid tag `{}`: keys starting with `{}` are reserved",
+            s, BUILTIN_PREFIX
+        ));
+    }
+    Ok(Tag {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// The tags tester adds to every session started at `start`: `tester.version`,
+/// `tester.start_time` in UTC and `tester.hostname` when it is known.
+pub fn builtin_tags(start: SystemTime) -> Vec<Tag> {
+    let tag = builtin_tag;
+    let mut tags = vec![
+        tag("version", env!("CARGO_PKG_VERSION").to_string()),
+        tag("start_time", format_utc(start)),
+    ];
+    if let Some(hostname) = hostname() {
+        tags.push(tag("hostname", hostname));
+    }
+    tags
+}
+
+/// The built-in tag `tester.<name>`.
+pub fn builtin_tag(name: &str, value: String) -> Tag {
+    Tag {
+        key: format!("{}{}", BUILTIN_PREFIX, name),
+        value,
+    }
+}
+
+/// `time` as an RFC 3339 UTC timestamp with whole seconds, e.g. `2024-05-01T12:00:00Z`.
+pub fn format_utc(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, second) = (seconds / 86400, seconds % 86400);
+    // Days since the epoch to a civil date, after Howard Hinnant's `civil_from_days`.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        second / 3600,
+        second / 60 % 60,
+        second % 60
+    )
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut name = [0u8; 256];
+    // SAFETY: the buffer is writable for its whole length.
+    if unsafe { libc::gethostname(name.as_mut_ptr().cast(), name.len()) } != 0 {
+        return None;
+    }
+    let length = name.iter().position(|&byte| byte == 0)?;
+    Some(String::from_utf8_lossy(&name[..length]).into_owned()).filter(|name| !name.is_empty())
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+/// Labels and tags describing a session.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    /// A short name for the session, from `--label`.
+    pub label: Option<String>,
+    /// `--tag` pairs in the order they were given, then the [built-in](builtin_tags) ones.
+    pub tags: Vec<Tag>,
+    /// Whether higher or lower scores are better.
+    #[serde(default)]
+    pub objective: Objective,
+    /// Which session this is of those run one after the other by `--watch`, from 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<u64>,
+    /// The seed the runs were shuffled with, with `--shuffle`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shuffle_seed: Option<u64>,
+    /// The seed the start of the runs was delayed with, with `--jitter`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jitter_seed: Option<u64>,
+    /// The executables of the steps when the session started, unless `--no-stamp`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binaries: Vec<BinaryStamp>,
+    /// Which share of the runs this session ran, with `--shard`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard: Option<Shard>,
+}
+
+impl SessionMetadata {
+    /// The label and generation in parentheses with a leading space, or nothing, for use in
+    /// message headers.
+    pub fn label_suffix(&self) -> String {
+        let generation = self
+            .generation
+            .map(|generation| format!("generation {}", generation));
+        let parts = self
+            .label
+            .iter()
+            .cloned()
+            .chain(generation)
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", parts.join(", "))
+        }
+    }
+}
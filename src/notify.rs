@@ -0,0 +1,73 @@
+//! Desktop notifications, see `--notify`, shown with the notifier every platform ships so no
+//! library is needed: `notify-send` on Linux and the BSDs, `osascript` on macOS and a
+//! PowerShell balloon on Windows.
+
+use std::process::{Command, Stdio};
+
+/// `text` as an AppleScript string literal.
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// `text` as a PowerShell string literal, in which nothing is expanded.
+#[cfg(windows)]
+fn powershell_string(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+#[cfg(target_os = "macos")]
+fn notifier(title: &str, body: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        applescript_string(body),
+        applescript_string(title)
+    ));
+    command
+}
+
+#[cfg(windows)]
+fn notifier(title: &str, body: &str) -> Command {
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command"])
+        .arg(format!(
+            "Add-Type -AssemblyName System.Windows.Forms; \
+         $icon = New-Object System.Windows.Forms.NotifyIcon; \
+         $icon.Icon = [System.Drawing.SystemIcons]::Information; \
+         $icon.Visible = $true; \
+         $icon.ShowBalloonTip(10000, {}, {}, 'Info'); \
+         Start-Sleep -Seconds 5; $icon.Dispose()",
+            powershell_string(title),
+            powershell_string(body)
+        ));
+    command
+}
+
+#[cfg(not(any(target_os = "macos", windows)))]
+fn notifier(title: &str, body: &str) -> Command {
+    let mut command = Command::new("notify-send");
+    command.args(["--app-name", "tester", "--", title, body]);
+    command
+}
+
+/// Show a notification with `title` and `body` on the desktop, waiting until the notifier
+/// took it. Fails if there is no notifier or it could not reach a notification service, e.g.
+/// in a session without a desktop.
+pub fn send(title: &str, body: &str) -> Result<(), String> {
+    let mut command = notifier(title, body);
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| format!("cannot start {}: {}", program, err))?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    match stderr.lines().map(str::trim).find(|line| !line.is_empty()) {
+        Some(reason) => Err(format!("{} failed: {}", program, reason)),
+        None => Err(format!("{} failed ({})", program, output.status)),
+    }
+}
@@ -0,0 +1,106 @@
+//! Whether higher or lower scores are better, and the best run of a session.
+
+use crate::{RunObserver, RunResult};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, fmt, sync::Mutex};
+
+/// Which way scores improve.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Objective {
+    /// Higher scores are better, e.g. throughput. The default.
+    #[default]
+    Maximize,
+    /// Lower scores are better, e.g. latency.
+    Minimize,
+}
+
+impl Objective {
+    /// How `a` compares with `b` as a score: `Greater` if it is better.
+    pub fn compare(&self, a: f64, b: f64) -> Ordering {
+        match self {
+            Objective::Maximize => a.total_cmp(&b),
+            Objective::Minimize => b.total_cmp(&a),
+        }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Objective::Maximize => "maximize",
+            Objective::Minimize => "minimize",
+        })
+    }
+}
+
+/// Parse an `--objective` value, `maximize` or `minimize`.
+pub fn parse_objective(s: &str) -> Result<Objective, String> {
+    match s {
+        "maximize" => Ok(Objective::Maximize),
+        "minimize" => Ok(Objective::Minimize),
+        _ => Err(format!(
+            "invalid objective `{}`: expected maximize or minimize",
+            s
+        )),
+    }
+}
+
+/// The run with the best score.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BestRun {
+    /// Run index.
+    pub index: u64,
+    /// Its score.
+    pub score: f64,
+    /// Whether the score was written with a time unit.
+    #[serde(default)]
+    pub time_score: bool,
+    /// Wall-clock seconds it took.
+    pub duration: f64,
+}
+
+/// An observer keeping the passed run with the best score, the first one of equally good
+/// runs in index order.
+pub struct BestRunTracker {
+    objective: Objective,
+    best: Mutex<Option<BestRun>>,
+}
+
+impl BestRunTracker {
+    /// Keep the best run as judged by `objective`.
+    pub fn new(objective: Objective) -> Self {
+        BestRunTracker {
+            objective,
+            best: Mutex::default(),
+        }
+    }
+
+    /// The best run so far, if any run was scored.
+    pub fn best(&self) -> Option<BestRun> {
+        self.best.lock().unwrap().clone()
+    }
+}
+
+impl RunObserver for BestRunTracker {
+    fn on_run(&self, result: &RunResult) {
+        let Some(score) = result.score.filter(|score| !score.is_nan()) else {
+            return;
+        };
+        let mut best = self.best.lock().unwrap();
+        let better = best.as_ref().is_none_or(|best| {
+            self.objective
+                .compare(score, best.score)
+                .then(best.index.cmp(&result.index))
+                .is_gt()
+        });
+        if better {
+            *best = Some(BestRun {
+                index: result.index,
+                score,
+                time_score: result.time_score,
+                duration: result.duration.as_secs_f64(),
+            });
+        }
+    }
+}
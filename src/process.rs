@@ -0,0 +1,840 @@
+//! Waiting for child processes, killing them when they run past their deadline.
+//!
+//! On Unix every child is the leader of its own process group, so killing it also kills
+//! whatever it started, and a ctrl-c in the terminal reaches it only through
+//! [`interrupt_running`]. On Windows every child gets a process group and a Job Object of its
+//! own instead: terminating the job takes down the processes the child started too, and
+//! [`interrupt_running`] sends the groups a ctrl-break.
+//!
+//! A command that ignores the interrupt is killed after [`INTERRUPT_GRACE`], or right away when
+//! interrupted a second time.
+
+use crate::score::{Counts, OutputCounts};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Output},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// How long the pipes of a child may stay open once it exited or was killed, held by what it
+/// left running in the background or by processes that escaped its process group. What was
+/// read by then is kept.
+const READ_GRACE: Duration = Duration::from_secs(1);
+
+/// Process ids, and so process group ids, of the children spawned with [`spawn`] that have not
+/// been waited for yet.
+static RUNNING: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// The children in [`RUNNING`] already interrupted by [`interrupt_running`], to kill if
+/// interrupted again.
+static INTERRUPTED: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// How long commands interrupted by [`interrupt_running`] have to exit before they are killed.
+pub(crate) const INTERRUPT_GRACE: Duration = Duration::from_secs(5);
+
+/// The Job Objects of the children in [`RUNNING`], by process id.
+#[cfg(windows)]
+static JOBS: Mutex<Vec<(u32, Job)>> = Mutex::new(Vec::new());
+
+/// The exit code of the processes of a job terminated by [`kill`], 1 like `Child::kill` uses.
+#[cfg(windows)]
+const KILLED_EXIT_CODE: u32 = 1;
+
+/// A Job Object whose processes are all killed once it is closed, so nothing a child started
+/// outlives it.
+#[cfg(windows)]
+struct Job(windows_sys::Win32::Foundation::HANDLE);
+
+#[cfg(windows)]
+impl Job {
+    fn new() -> io::Result<Self> {
+        use windows_sys::Win32::System::JobObjects::{
+            CreateJobObjectW, JobObjectExtendedLimitInformation, SetInformationJobObject,
+            JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        };
+        // SAFETY: both arguments may be null.
+        let handle = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if handle == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let job = Job(handle);
+        // SAFETY: the structure is plain data, for which all zeroes is valid.
+        let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        // SAFETY: `limits` outlives the call and its size is passed along.
+        let set = unsafe {
+            SetInformationJobObject(
+                job.0,
+                JobObjectExtendedLimitInformation,
+                &limits as *const _ as *const std::ffi::c_void,
+                std::mem::size_of_val(&limits) as u32,
+            )
+        };
+        if set == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(job)
+    }
+
+    /// Put `child` in the job. What it started before is left out, which is why this happens
+    /// right after spawning.
+    fn assign(&self, child: &Child) -> io::Result<()> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::System::JobObjects::AssignProcessToJobObject;
+        // SAFETY: both handles are open for the duration of the call.
+        if unsafe { AssignProcessToJobObject(self.0, child.as_raw_handle() as _) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn terminate(&self) {
+        // SAFETY: the handle is open until the job is dropped.
+        unsafe {
+            windows_sys::Win32::System::JobObjects::TerminateJobObject(self.0, KILLED_EXIT_CODE)
+        };
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        // SAFETY: the handle was opened by `Job::new` and is closed only here.
+        unsafe { windows_sys::Win32::Foundation::CloseHandle(self.0) };
+    }
+}
+
+/// Spawn `command` in a process group of its own.
+pub(crate) fn spawn(command: &mut Command) -> io::Result<Child> {
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(command, 0);
+    #[cfg(windows)]
+    std::os::windows::process::CommandExt::creation_flags(
+        command,
+        windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP,
+    );
+    let child = command.spawn()?;
+    #[cfg(windows)]
+    match Job::new().and_then(|job| job.assign(&child).map(|()| job)) {
+        Ok(job) => JOBS.lock().unwrap().push((child.id(), job)),
+        // Only the child itself is killed then, as by `Child::kill`.
+        Err(err) => debug!("#tester no Job Object for process {}: {}", child.id(), err),
+    }
+    RUNNING.lock().unwrap().push(child.id());
+    Ok(child)
+}
+
+/// Limit every process `command` starts to `seconds` of CPU time, like an online judge: the
+/// kernel sends SIGXCPU once they are used up, and SIGKILL a second later.
+#[cfg(unix)]
+pub(crate) fn limit_cpu(command: &mut Command, seconds: u64) {
+    let hook = move || {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: getrlimit and setrlimit are async-signal-safe and only access `limit`.
+        unsafe {
+            if libc::getrlimit(libc::RLIMIT_CPU, &mut limit) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            // Raising the hard limit is not allowed, lowering it is.
+            limit.rlim_cur = limit.rlim_max.min(seconds as libc::rlim_t);
+            limit.rlim_max = limit.rlim_max.min(seconds as libc::rlim_t + 1);
+            if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    };
+    // SAFETY: the hook allocates nothing and calls only async-signal-safe functions.
+    unsafe { std::os::unix::process::CommandExt::pre_exec(command, hook) };
+}
+
+/// Put every process `command` starts in the namespaces of `isolation`, in a user namespace
+/// of its own too unless we are root. With a private `/tmp`, `run_dir` is bound where it was
+/// if it was under `/tmp`, as it usually is.
+#[cfg(target_os = "linux")]
+pub(crate) fn isolate(
+    command: &mut Command,
+    isolation: crate::isolation::Isolation,
+    run_dir: Option<&std::path::Path>,
+) {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt, path::Path};
+
+    let c_path = |path: &Path| CString::new(path.as_os_str().as_bytes()).ok();
+    // SAFETY: geteuid and getegid cannot fail.
+    let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    let new_user = uid != 0;
+    let mut flags = 0;
+    if new_user {
+        flags |= libc::CLONE_NEWUSER;
+    }
+    if isolation.net {
+        flags |= libc::CLONE_NEWNET;
+    }
+    if isolation.tmp {
+        flags |= libc::CLONE_NEWNS;
+    }
+    // Everything the hook needs is made here, it must not allocate.
+    let uid_map = format!("{} {} 1", uid, uid).into_bytes();
+    let gid_map = format!("{} {} 1", gid, gid).into_bytes();
+    let rebound = run_dir
+        .filter(|dir| isolation.tmp && dir.starts_with("/tmp"))
+        .and_then(|dir| {
+            // The directories to create in the new /tmp, outermost first.
+            let mut parents = dir
+                .ancestors()
+                .take_while(|parent| parent.starts_with("/tmp") && *parent != Path::new("/tmp"))
+                .map(c_path)
+                .collect::<Option<Vec<_>>>()?;
+            parents.reverse();
+            Some((c_path(dir)?, parents))
+        });
+
+    let hook = move || {
+        let check = |result: libc::c_int| match result {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        };
+        // SAFETY: every call is async-signal-safe, and every pointer is to a live
+        // nul-terminated string or buffer of the length given.
+        unsafe {
+            let write_file = |path: &[u8], content: &[u8]| -> io::Result<()> {
+                let fd = libc::open(path.as_ptr().cast(), libc::O_WRONLY | libc::O_CLOEXEC);
+                check(fd)?;
+                let written = libc::write(fd, content.as_ptr().cast(), content.len());
+                libc::close(fd);
+                if written != content.len() as isize {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            };
+            check(libc::unshare(flags))?;
+            if new_user {
+                match write_file(b"/proc/self/setgroups\0", b"deny") {
+                    // Kernels before 3.19 have no such file, nor need it.
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    result => result?,
+                }
+                write_file(b"/proc/self/uid_map\0", &uid_map)?;
+                write_file(b"/proc/self/gid_map\0", &gid_map)?;
+            }
+            if isolation.tmp {
+                let null = std::ptr::null();
+                // Our mounts stay ours.
+                check(libc::mount(
+                    null,
+                    c"/".as_ptr(),
+                    null,
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    null.cast(),
+                ))?;
+            }
+            // Opened in the new namespace, where they can be bound, before /tmp is covered.
+            let dirs = match &rebound {
+                Some((dir, _)) => {
+                    let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC;
+                    let run_dir = libc::open(dir.as_ptr(), flags);
+                    check(run_dir)?;
+                    let cwd = libc::open(c".".as_ptr(), flags);
+                    check(cwd)?;
+                    Some((run_dir, cwd))
+                }
+                None => None,
+            };
+            if isolation.tmp {
+                check(libc::mount(
+                    c"tmpfs".as_ptr(),
+                    c"/tmp".as_ptr(),
+                    c"tmpfs".as_ptr(),
+                    libc::MS_NOSUID | libc::MS_NODEV,
+                    c"mode=1777".as_ptr().cast(),
+                ))?;
+            }
+            if let (Some((dir, parents)), Some((run_dir, cwd))) = (&rebound, dirs) {
+                for parent in parents {
+                    check(libc::mkdir(parent.as_ptr(), 0o700))?;
+                }
+                // Bound from the directory itself, the path of which is covered now.
+                check(libc::fchdir(run_dir))?;
+                check(libc::mount(
+                    c".".as_ptr(),
+                    dir.as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_BIND,
+                    std::ptr::null(),
+                ))?;
+                check(libc::fchdir(cwd))?;
+                libc::close(run_dir);
+                libc::close(cwd);
+            }
+        }
+        Ok(())
+    };
+    // SAFETY: the hook allocates nothing and calls only async-signal-safe functions.
+    unsafe { std::os::unix::process::CommandExt::pre_exec(command, hook) };
+}
+
+/// Make stderr a copy of stdout in every process `command` starts, after its stdio is set up,
+/// so both go to the same pipe.
+#[cfg(unix)]
+pub(crate) fn merge_stderr(command: &mut Command) {
+    let hook = || {
+        // SAFETY: dup2 is async-signal-safe.
+        if unsafe { libc::dup2(libc::STDOUT_FILENO, libc::STDERR_FILENO) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    };
+    // SAFETY: the hook allocates nothing and calls only async-signal-safe functions.
+    unsafe { std::os::unix::process::CommandExt::pre_exec(command, hook) };
+}
+
+/// Whether a process limited with [`limit_cpu`] was killed for using up its CPU time. Only a
+/// SIGXCPU says so: a SIGKILL may come from anything, such as the OOM killer, and a process
+/// ignoring the SIGXCPU to be killed at the hard limit is reported as killed.
+#[cfg(unix)]
+pub(crate) fn exceeded_cpu_limit(status: ExitStatus) -> bool {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal() == Some(libc::SIGXCPU)
+}
+
+/// Send `signal` to the process group led by `pgid`. Never signals our own group: every
+/// group signaled was created by [`spawn`], and 0 or 1 would mean something else to `kill`.
+#[cfg(unix)]
+fn signal_group(pgid: u32, signal: libc::c_int) {
+    if let Ok(pgid @ 2..) = libc::pid_t::try_from(pgid) {
+        // SAFETY: kill has no memory safety requirements.
+        unsafe { libc::kill(-pgid, signal) };
+    }
+}
+
+/// Kill `child` and everything in its process group. A child that already exited but was not
+/// reaped yet keeps its process id, and so its group, so the signal never reaches an
+/// unrelated process.
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        let running = RUNNING.lock().unwrap();
+        if running.contains(&child.id()) {
+            signal_group(child.id(), libc::SIGKILL);
+            return;
+        }
+    }
+    #[cfg(windows)]
+    {
+        let jobs = JOBS.lock().unwrap();
+        if let Some((_, job)) = jobs.iter().find(|(id, _)| *id == child.id()) {
+            job.terminate();
+            return;
+        }
+    }
+    // The child may exit between the caller's check and the kill, which is fine: the
+    // caller's `reap` reaps it either way.
+    let _ = child.kill();
+}
+
+/// Stop tracking the process group of `child`, which exited. On Windows this closes its job,
+/// killing whatever it left running.
+fn forget(child: &Child) {
+    RUNNING.lock().unwrap().retain(|&id| id != child.id());
+    INTERRUPTED.lock().unwrap().retain(|&id| id != child.id());
+    #[cfg(windows)]
+    JOBS.lock().unwrap().retain(|(id, _)| *id != child.id());
+}
+
+/// Wait for `child` to exit and forget its process group. After a kill this wait is short: a
+/// SIGKILL cannot be caught or ignored.
+fn reap(child: &mut Child) -> io::Result<ExitStatus> {
+    let status = child.wait();
+    forget(child);
+    status
+}
+
+/// Whether `child` exited, without reaping it so its process group can still be signaled.
+#[cfg(unix)]
+fn has_exited(child: &mut Child) -> io::Result<bool> {
+    // SAFETY: an all-zero siginfo_t is valid, and waitid only writes to it.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let options = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+    // SAFETY: `info` outlives the call.
+    if unsafe { libc::waitid(libc::P_PID, child.id(), &mut info, options) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: waitid filled in si_pid, leaving it 0 if the child is still running.
+    Ok(unsafe { info.si_pid() } != 0)
+}
+
+#[cfg(not(unix))]
+fn has_exited(child: &mut Child) -> io::Result<bool> {
+    Ok(child.try_wait()?.is_some())
+}
+
+/// Block until `child` exits, without reaping it, like [`has_exited`].
+#[cfg(unix)]
+fn wait_exited(child: &mut Child) -> io::Result<()> {
+    loop {
+        // SAFETY: as in `has_exited`.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let options = libc::WEXITED | libc::WNOWAIT;
+        // SAFETY: `info` outlives the call.
+        if unsafe { libc::waitid(libc::P_PID, child.id(), &mut info, options) } == 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn wait_exited(child: &mut Child) -> io::Result<()> {
+    child.wait().map(drop)
+}
+
+/// Kill and reap a child that will not be waited for otherwise.
+pub(crate) fn abandon(mut child: Child) {
+    kill(&mut child);
+    let _ = reap(&mut child);
+}
+
+/// Interrupt every command running right now, the way a ctrl-c in the terminal would if they
+/// were not in process groups of their own. Those interrupted before are killed instead, and
+/// the others once [`INTERRUPT_GRACE`] is over if they are still running then.
+pub(crate) fn interrupt_running() {
+    let running = RUNNING.lock().unwrap().clone();
+    let mut interrupted = INTERRUPTED.lock().unwrap();
+    let (again, first): (Vec<u32>, Vec<u32>) =
+        running.into_iter().partition(|id| interrupted.contains(id));
+    kill_groups(|id| again.contains(&id));
+    #[cfg(unix)]
+    for &pgid in &first {
+        signal_group(pgid, libc::SIGINT);
+    }
+    // Processes started in a group of their own ignore ctrl-c, but not ctrl-break.
+    #[cfg(windows)]
+    for &pgid in &first {
+        use windows_sys::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+        // SAFETY: GenerateConsoleCtrlEvent has no memory safety requirements.
+        unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pgid) };
+    }
+    interrupted.extend(&first);
+    drop(interrupted);
+    if !first.is_empty() {
+        // A group still in RUNNING was not reaped, so its id cannot have been reused.
+        thread::spawn(move || {
+            thread::sleep(INTERRUPT_GRACE);
+            kill_groups(|id| first.contains(&id));
+        });
+    }
+}
+
+/// Kill every child spawned with [`spawn`] that is still running, with whatever it started.
+pub(crate) fn kill_running() {
+    kill_groups(|_| true);
+}
+
+/// Kill the children spawned with [`spawn`] still running whose process id is `selected`, with
+/// whatever they started.
+fn kill_groups(selected: impl Fn(u32) -> bool) {
+    #[cfg(unix)]
+    for &pgid in RUNNING.lock().unwrap().iter() {
+        if selected(pgid) {
+            signal_group(pgid, libc::SIGKILL);
+        }
+    }
+    #[cfg(windows)]
+    for (id, job) in JOBS.lock().unwrap().iter() {
+        if selected(*id) {
+            job.terminate();
+        }
+    }
+}
+
+/// Why a process exited, for failure reasons. Windows exit codes with the high bit set are
+/// NTSTATUS values such as an access violation, shown in hex and named when well known
+/// rather than as a ten-digit number.
+pub(crate) fn describe_status(status: ExitStatus) -> String {
+    #[cfg(windows)]
+    if let Some(code) = status.code().map(|code| code as u32) {
+        if code >= 0x8000_0000 {
+            let name = match code {
+                0xC000_0005 => " (STATUS_ACCESS_VIOLATION)",
+                0xC000_0017 => " (STATUS_NO_MEMORY)",
+                0xC000_001D => " (STATUS_ILLEGAL_INSTRUCTION)",
+                0xC000_0094 => " (STATUS_INTEGER_DIVIDE_BY_ZERO)",
+                0xC000_00FD => " (STATUS_STACK_OVERFLOW)",
+                0xC000_013A => " (STATUS_CONTROL_C_EXIT)",
+                0xC000_0409 => " (STATUS_STACK_BUFFER_OVERRUN)",
+                _ => "",
+            };
+            return format!("exit code: {:#010X}{}", code, name);
+        }
+    }
+    status.to_string()
+}
+
+/// What is kept of the output read from a pipe: all of it up to `limit` bytes, otherwise the
+/// first and the last half of that, around a line saying how much was left out.
+struct Retained {
+    limit: Option<usize>,
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    total: usize,
+    /// Of everything read, kept or not.
+    counts: Counts,
+}
+
+impl Retained {
+    fn new(limit: Option<usize>) -> Self {
+        Retained {
+            limit,
+            head: vec![],
+            tail: VecDeque::new(),
+            total: 0,
+            counts: Counts::default(),
+        }
+    }
+
+    fn extend(&mut self, chunk: &[u8]) {
+        self.total += chunk.len();
+        self.counts.add(chunk);
+        let Some(limit) = self.limit else {
+            self.head.extend_from_slice(chunk);
+            return;
+        };
+        let head_room = (limit - limit / 2).saturating_sub(self.head.len());
+        let (head, tail) = chunk.split_at(head_room.min(chunk.len()));
+        self.head.extend_from_slice(head);
+        self.tail.extend(tail);
+        let excess = self.tail.len().saturating_sub(limit / 2);
+        self.tail.drain(..excess);
+    }
+
+    /// What was kept so far and the counts of everything read, which are then forgotten.
+    fn take(&mut self) -> (Vec<u8>, Counts) {
+        let mut bytes = std::mem::take(&mut self.head);
+        let kept = bytes.len() + self.tail.len();
+        if self.total > kept {
+            if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+                bytes.push(b'\n');
+            }
+            let marker = format!("#tester {} bytes of output left out\n", self.total - kept);
+            bytes.extend_from_slice(marker.as_bytes());
+        }
+        bytes.extend(self.tail.drain(..));
+        self.total = 0;
+        (bytes, std::mem::take(&mut self.counts))
+    }
+}
+
+/// Everything read from a pipe on a separate thread, so the child never blocks on a full pipe.
+/// Past the limit it goes on reading, keeping only the end.
+struct Drain {
+    read: Arc<Mutex<Retained>>,
+    reader: thread::JoinHandle<io::Result<()>>,
+}
+
+impl Drain {
+    fn start<R: Read + Send + 'static>(pipe: Option<R>, limit: Option<usize>) -> Self {
+        let read = Arc::new(Mutex::new(Retained::new(limit)));
+        let buf = read.clone();
+        let reader = thread::spawn(move || {
+            let Some(mut pipe) = pipe else {
+                return Ok(());
+            };
+            let mut chunk = vec![0; 64 * 1024];
+            loop {
+                match pipe.read(&mut chunk) {
+                    Ok(0) => return Ok(()),
+                    Ok(read) => buf.lock().unwrap().extend(&chunk[..read]),
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => return Err(err),
+                }
+            }
+        });
+        Drain { read, reader }
+    }
+
+    /// Whether every writer closed the pipe.
+    fn is_finished(&self) -> bool {
+        self.reader.is_finished()
+    }
+
+    /// What was read once every writer closed the pipe, or what was read by `give_up` if a
+    /// writer still holds it open then.
+    fn finish(self, give_up: Option<Instant>) -> io::Result<(Vec<u8>, Counts)> {
+        if let Some(give_up) = give_up {
+            while !self.reader.is_finished() && Instant::now() < give_up {
+                thread::sleep(POLL_INTERVAL);
+            }
+            if !self.reader.is_finished() {
+                debug!("#tester gave up reading a pipe held open by an escaped process");
+                return Ok(self.read.lock().unwrap().take());
+            }
+        }
+        self.reader.join().expect("pipe reader panicked")?;
+        Ok(self.read.lock().unwrap().take())
+    }
+}
+
+/// Write `input` to the child's stdin on a separate thread and close it, so a child that
+/// writes before reading cannot deadlock with us. A child that exits without reading
+/// everything is not an error.
+fn feed(child: &mut Child, input: Option<Arc<[u8]>>) {
+    let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) else {
+        return;
+    };
+    thread::spawn(move || {
+        if let Err(err) = stdin.write_all(&input) {
+            if err.kind() != io::ErrorKind::BrokenPipe {
+                debug!("#tester cannot write the input of a run: {}", err);
+            }
+        }
+    });
+}
+
+/// What went through a pipe between two children, as far as it was kept.
+#[derive(Debug, Default)]
+pub(crate) struct Captured {
+    /// The first bytes that went through.
+    pub bytes: Vec<u8>,
+    /// How many bytes went through in all.
+    pub total: u64,
+}
+
+/// Copy everything from `from` to `to` on a separate thread, keeping the first `limit` bytes,
+/// or all of them without a limit. Once `to` is closed the rest is still read, so the writer
+/// never blocks on a pipe nobody reads.
+fn tee(
+    mut from: ChildStdout,
+    to: Option<ChildStdin>,
+    limit: Option<usize>,
+) -> thread::JoinHandle<io::Result<Captured>> {
+    thread::spawn(move || {
+        let mut to = to;
+        let mut captured = Captured::default();
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let read = match from.read(&mut buf) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            let chunk = &buf[..read];
+            if let Some(stdin) = &mut to {
+                if let Err(err) = stdin.write_all(chunk) {
+                    if err.kind() != io::ErrorKind::BrokenPipe {
+                        debug!("#tester cannot write the input of a run: {}", err);
+                    }
+                    to = None;
+                }
+            }
+            let room = limit.map_or(read, |limit| limit.saturating_sub(captured.bytes.len()));
+            captured.bytes.extend_from_slice(&chunk[..room.min(read)]);
+            captured.total += read as u64;
+        }
+        Ok(captured)
+    })
+}
+
+/// Run `producer` with its stdout piped into the stdin of `consumer`, both until they exit or
+/// `deadline` passes, like [`wait_with_deadline`] for each on its own. The bytes that went
+/// through are captured up to `limit`, the outputs of the consumer and the stderr of the
+/// producer up to `max_output`.
+#[allow(clippy::type_complexity)]
+pub(crate) fn pipe_with_deadline(
+    mut producer: Child,
+    mut consumer: Child,
+    limit: Option<usize>,
+    max_output: Option<usize>,
+    deadline: Option<Instant>,
+) -> io::Result<((Output, bool), (Output, bool, OutputCounts), Captured)> {
+    let stdout = producer
+        .stdout
+        .take()
+        .expect("the producer's stdout is piped");
+    let tee = tee(stdout, consumer.stdin.take(), limit);
+    let producer = thread::spawn(move || wait_with_deadline(producer, None, max_output, deadline));
+    let consumer = wait_counted(consumer, None, max_output, deadline);
+    let producer = producer.join().expect("producer waiter panicked");
+    let captured = tee.join().expect("input tee panicked")?;
+    Ok((producer?, consumer?, captured))
+}
+
+/// Wait for `child` and collect its output like [`Child::wait_with_output`], killing it once
+/// `deadline` passes. `input`, if any, is written to its piped stdin. Of each output at most
+/// `max_output` bytes are kept, see [`Retained`]. Returns whether the child was killed.
+///
+/// The output is complete once every process holding the pipes closed them, which includes
+/// what the child left running in the background. Those get [`READ_GRACE`] once the child
+/// exited, then they are killed with its process group, keeping what they wrote by then.
+pub(crate) fn wait_with_deadline(
+    child: Child,
+    input: Option<Arc<[u8]>>,
+    max_output: Option<usize>,
+    deadline: Option<Instant>,
+) -> io::Result<(Output, bool)> {
+    let (output, timed_out, _) = wait_counted(child, input, max_output, deadline)?;
+    Ok((output, timed_out))
+}
+
+/// Wait for `child` like [`wait_with_deadline`], also counting all of its output, what was
+/// left out too.
+pub(crate) fn wait_counted(
+    mut child: Child,
+    input: Option<Arc<[u8]>>,
+    max_output: Option<usize>,
+    deadline: Option<Instant>,
+) -> io::Result<(Output, bool, OutputCounts)> {
+    feed(&mut child, input);
+    let stdout = Drain::start(child.stdout.take(), max_output);
+    let stderr = Drain::start(child.stderr.take(), max_output);
+    let mut timed_out = false;
+    match deadline {
+        Some(deadline) => {
+            while !has_exited(&mut child)? {
+                if Instant::now() >= deadline {
+                    kill(&mut child);
+                    timed_out = true;
+                    break;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+        None => wait_exited(&mut child)?,
+    }
+    // The child is not reaped yet, so its process group can still be killed.
+    let mut killed = timed_out;
+    let exited = Instant::now();
+    while !(killed || stdout.is_finished() && stderr.is_finished()) {
+        if exited.elapsed() >= READ_GRACE {
+            debug!("#tester killed what a command left running with its pipes open");
+            kill(&mut child);
+            killed = true;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let status = reap(&mut child)?;
+    let give_up = killed.then(|| Instant::now() + READ_GRACE);
+    let (stdout, stdout_counts) = stdout.finish(give_up)?;
+    let (stderr, stderr_counts) = stderr.finish(give_up)?;
+    let counts = OutputCounts {
+        stdout: stdout_counts,
+        stderr: stderr_counts,
+    };
+    Ok((
+        Output {
+            status,
+            stdout,
+            stderr,
+        },
+        timed_out,
+        counts,
+    ))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::{fs, process::Stdio};
+
+    fn sh(script: &str) -> Child {
+        spawn(
+            Command::new("sh")
+                .args(["-c", script])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped()),
+        )
+        .unwrap()
+    }
+
+    /// Whether the process `pid` is gone, or a zombie nobody reaped yet.
+    fn is_dead(pid: u32) -> bool {
+        match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            // The state follows the parenthesized command name.
+            Ok(stat) => stat[stat.rfind(')').unwrap() + 1..]
+                .trim_start()
+                .starts_with('Z'),
+            Err(_) => true,
+        }
+    }
+
+    fn open_fds() -> usize {
+        fs::read_dir("/proc/self/fd").unwrap().count()
+    }
+
+    #[test]
+    fn background_holding_pipes() {
+        let started = Instant::now();
+        let (output, timed_out) =
+            wait_with_deadline(sh("sleep 8 & echo $!"), None, None, None).unwrap();
+        assert!(
+            started.elapsed() < READ_GRACE * 3,
+            "{:?}",
+            started.elapsed()
+        );
+        assert!(output.status.success() && !timed_out);
+        let background = String::from_utf8(output.stdout).unwrap();
+        let background = background.trim().parse().unwrap();
+        let killed = Instant::now();
+        while !is_dead(background) && killed.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(is_dead(background), "sleep {} still running", background);
+    }
+
+    #[test]
+    fn cpu_limit_signals() {
+        for (signal, exceeded) in [("XCPU", true), ("KILL", false), ("TERM", false)] {
+            let mut command = Command::new("sh");
+            command.args(["-c", &format!("kill -{} $$", signal)]);
+            limit_cpu(&mut command, 10);
+            let status = command.status().unwrap();
+            assert_eq!(exceeded_cpu_limit(status), exceeded, "{}", status);
+        }
+    }
+
+    #[test]
+    fn no_leaks() {
+        let fds = open_fds();
+        let mut children = vec![];
+        for i in 0..40 {
+            let (script, deadline) = match i % 20 {
+                0 => ("sleep 5", Some(Instant::now() + Duration::from_millis(50))),
+                1 => ("sleep 5 & echo started", None),
+                _ => ("echo out; echo err >&2", None),
+            };
+            let child = sh(script);
+            children.push(child.id());
+            let (output, timed_out, _) = wait_counted(child, None, Some(16), deadline).unwrap();
+            assert_eq!(timed_out, deadline.is_some());
+            assert!(timed_out || !output.stdout.is_empty());
+        }
+        for &pid in &children {
+            // Reaped, so gone unless its id was reused already.
+            let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).unwrap_or_default();
+            assert!(!stat.contains(") Z"), "{} not reaped: {}", pid, stat);
+        }
+        assert!(!RUNNING
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|pid| children.contains(pid)));
+        // Other tests open files meanwhile, but 40 runs leaking would have left far more.
+        assert!(
+            open_fds() <= fds + 8,
+            "{} open files, {} before",
+            open_fds(),
+            fds
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! `tester report`: statistics of sessions saved with `--csv` or `--jsonl`, recomputed offline.
+
+use crate::{
+    export::{self, RunRecord},
+    objective::{self, Objective},
+    session::{Fatal, NumberFormat},
+    stats::Distribution,
+    units, RunStatus,
+};
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+/// The arguments of `tester report`.
+#[derive(Args, Debug)]
+pub struct ReportArgs {
+    /// A file saved with --csv or --jsonl; give a second one to compare it against the first
+    #[arg(required = true, num_args = 1..=2)]
+    files: Vec<PathBuf>,
+    /// Number of slowest runs to list
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    slowest: usize,
+    /// Whether higher scores are better (maximize) or lower ones (minimize), to judge the
+    /// change of the mean score
+    #[arg(long, value_name = "DIRECTION", default_value = "maximize", value_parser = objective::parse_objective)]
+    objective: Objective,
+    #[command(flatten)]
+    number_format: NumberFormat,
+}
+
+impl ReportArgs {
+    /// How the numbers are shown.
+    pub fn number_format(&self) -> &NumberFormat {
+        &self.number_format
+    }
+}
+
+/// `tester report`: print the statistics of every file, then compare them if given two.
+pub fn run(args: ReportArgs) -> Result<(), Fatal> {
+    let summaries = args
+        .files
+        .iter()
+        .map(|path| load(path, args.slowest).map_err(Fatal::Config))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (path, summary) in args.files.iter().zip(&summaries) {
+        print(path, summary);
+    }
+    if let [old, new] = &summaries[..] {
+        print_comparison(&args.files[0], old, &args.files[1], new, args.objective);
+    }
+    Ok(())
+}
+
+/// What a saved session adds up to.
+struct SessionSummary {
+    pub runs: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    pub cpu_limit_exceeded: usize,
+    /// Runs killed by `--timeout-factor`.
+    pub relative_timeout: usize,
+    /// Runs with invalid input, left out of everything else.
+    pub invalid: usize,
+    /// Runs whose setup failed, left out of everything else too.
+    pub setup_failed: usize,
+    /// Runs skipped by their exit code, left out of everything else as well.
+    pub skipped: usize,
+    pub scores: Option<Distribution>,
+    pub durations: Option<Distribution>,
+    /// The slowest runs, slowest first.
+    pub slowest: Vec<RunRecord>,
+    /// The hashes of the executables that ran, from the `tester.binary_sha256` tag of CSV
+    /// files.
+    pub binary_sha256: Option<String>,
+}
+
+impl SessionSummary {
+    pub fn new(records: &[RunRecord], slowest: usize) -> Self {
+        let count = |status| records.iter().filter(|r| r.status == status).count();
+        let valid = records
+            .iter()
+            .filter(|r| r.status.is_counted())
+            .cloned()
+            .collect::<Vec<_>>();
+        let scores = valid.iter().filter_map(|r| r.score).collect::<Vec<_>>();
+        let durations = valid.iter().map(|r| r.duration).collect::<Vec<_>>();
+        let mut by_duration = valid.clone();
+        by_duration.sort_by(|a, b| b.duration.total_cmp(&a.duration));
+        by_duration.truncate(slowest);
+        SessionSummary {
+            runs: valid.len(),
+            failed: count(RunStatus::Failed),
+            timed_out: count(RunStatus::TimedOut),
+            cpu_limit_exceeded: count(RunStatus::CpuLimitExceeded),
+            relative_timeout: count(RunStatus::RelativeTimeout),
+            invalid: count(RunStatus::Invalid),
+            setup_failed: count(RunStatus::SetupFailed),
+            skipped: count(RunStatus::Skipped),
+            scores: Distribution::of(&scores),
+            durations: Distribution::of(&durations),
+            slowest: by_duration,
+            binary_sha256: None,
+        }
+    }
+
+    /// Runs that did not pass, in percent, `None` without any counted run.
+    pub fn failure_rate(&self) -> Option<f64> {
+        let failed = self.failed + self.timed_out + self.cpu_limit_exceeded + self.relative_timeout;
+        (self.runs > 0).then(|| failed as f64 * 100.0 / self.runs as f64)
+    }
+}
+
+fn seconds(value: f64) -> String {
+    units::format_duration(std::time::Duration::from_secs_f64(value.max(0.0)))
+}
+
+fn distribution_line(distribution: &Distribution, show: impl Fn(f64) -> String) -> String {
+    format!(
+        "mean {} ± {}, min {}, p50 {}, p90 {}, p99 {}, max {}",
+        show(distribution.mean),
+        show(distribution.std_dev),
+        show(distribution.min),
+        show(distribution.p50),
+        show(distribution.p90),
+        show(distribution.p99),
+        show(distribution.max)
+    )
+}
+
+/// Read `path` and summarize it.
+fn load(path: &Path, slowest: usize) -> Result<SessionSummary, String> {
+    let (_, records) = export::read_records(path)?;
+    if records.is_empty() {
+        return Err(format!("{} has no runs", path.display()));
+    }
+    let mut summary = SessionSummary::new(&records, slowest);
+    summary.binary_sha256 = export::read_tags(path)?
+        .into_iter()
+        .find(|tag| tag.key == "tester.binary_sha256")
+        .map(|tag| tag.value);
+    Ok(summary)
+}
+
+fn print(path: &Path, summary: &SessionSummary) {
+    let passed = summary.runs
+        - summary.failed
+        - summary.timed_out
+        - summary.cpu_limit_exceeded
+        - summary.relative_timeout;
+    let count = |count: usize| units::format_integer(count as u64);
+    println!("report {}: {} runs", path.display(), count(summary.runs));
+    println!("  passed:    {}", count(passed));
+    println!("  failed:    {}", count(summary.failed));
+    println!("  timed out: {}", count(summary.timed_out));
+    if summary.cpu_limit_exceeded > 0 {
+        println!("  CPU limit: {}", summary.cpu_limit_exceeded);
+    }
+    if summary.relative_timeout > 0 {
+        println!("  relative timeout: {}", summary.relative_timeout);
+    }
+    if summary.invalid > 0 {
+        println!("  invalid:   {} (not counted)", summary.invalid);
+    }
+    if summary.setup_failed > 0 {
+        println!("  setup failed: {} (not counted)", summary.setup_failed);
+    }
+    if summary.skipped > 0 {
+        println!("  skipped:   {} (not counted)", summary.skipped);
+    }
+    if let Some(scores) = &summary.scores {
+        println!(
+            "  score:     {} ({} scored)",
+            distribution_line(scores, units::format_score),
+            scores.count
+        );
+    }
+    if let Some(durations) = &summary.durations {
+        println!("  time:      {}", distribution_line(durations, seconds));
+    }
+    if !summary.slowest.is_empty() {
+        println!("  slowest runs:");
+        for record in &summary.slowest {
+            println!(
+                "    run {} (thread {}): {}, {}",
+                record.index,
+                record.thread,
+                seconds(record.duration),
+                record.status
+            );
+        }
+    }
+}
+
+/// Describe a change from `old` to `new` where lower is better.
+fn judge(old: f64, new: f64) -> &'static str {
+    if new > old {
+        " (worse)"
+    } else if new < old {
+        " (better)"
+    } else {
+        ""
+    }
+}
+
+fn percent_change(old: f64, new: f64) -> String {
+    if old == 0.0 {
+        return String::new();
+    }
+    format!(", {:+.1}%", (new - old) * 100.0 / old)
+}
+
+fn print_comparison(
+    old_path: &Path,
+    old: &SessionSummary,
+    new_path: &Path,
+    new: &SessionSummary,
+    objective: Objective,
+) {
+    println!("compare {} -> {}:", old_path.display(), new_path.display());
+    if let (Some(old_hash), Some(new_hash)) = (&old.binary_sha256, &new.binary_sha256) {
+        if old_hash == new_hash {
+            println!(
+                "  WARNING: both ran the very same build (sha256 {}), probably comparing a build against itself",
+                old_hash
+            );
+        }
+    }
+    if let (Some(old_rate), Some(new_rate)) = (old.failure_rate(), new.failure_rate()) {
+        println!(
+            "  failure rate: {:.2}% -> {:.2}% ({:+.2} points){}",
+            old_rate,
+            new_rate,
+            new_rate - old_rate,
+            judge(old_rate, new_rate)
+        );
+    }
+    if let (Some(old_scores), Some(new_scores)) = (&old.scores, &new.scores) {
+        let judgement = match objective {
+            Objective::Maximize => judge(new_scores.mean, old_scores.mean),
+            Objective::Minimize => judge(old_scores.mean, new_scores.mean),
+        };
+        println!(
+            "  mean score:   {} -> {} ({}{}{}){}",
+            units::format_score(old_scores.mean),
+            units::format_score(new_scores.mean),
+            if new_scores.mean < old_scores.mean {
+                ""
+            } else {
+                "+"
+            },
+            units::format_score(new_scores.mean - old_scores.mean),
+            percent_change(old_scores.mean, new_scores.mean),
+            judgement
+        );
+    }
+    if let (Some(old_times), Some(new_times)) = (&old.durations, &new.durations) {
+        let delta = new_times.mean - old_times.mean;
+        println!(
+            "  mean time:    {} -> {} ({}{}{}){}",
+            seconds(old_times.mean),
+            seconds(new_times.mean),
+            if delta < 0.0 { "-" } else { "+" },
+            seconds(delta.abs()),
+            percent_change(old_times.mean, new_times.mean),
+            judge(old_times.mean, new_times.mean)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Running again only the runs that failed in an earlier session, e.g. after a fix, see
+//! `--rerun-failures`.
+
+use crate::{export, summary::SessionSummary, RunStatus};
+use std::{collections::HashMap, fs, path::Path, sync::Arc};
+
+/// The runs that failed in an earlier session.
+#[derive(Clone, Debug, Default)]
+pub struct PreviousFailures {
+    /// Their run indices, sorted.
+    pub runs: Vec<u64>,
+    /// The inputs saved for some of them.
+    pub inputs: HashMap<u64, Arc<[u8]>>,
+}
+
+/// Read the failed runs of an earlier session from `path`: a summary written with `--json`,
+/// records written with `--csv` or `--jsonl`, or a `--save-failures` directory, which has
+/// their inputs too.
+pub fn load(path: &Path) -> Result<PreviousFailures, String> {
+    if path.is_dir() {
+        return load_dir(path);
+    }
+    // A summary is a single JSON object, while JSON lines have one per line.
+    let runs = match SessionSummary::load(path) {
+        Ok(summary) => summary.report.failed_runs.iter().collect(),
+        Err(_) => {
+            let (_, records) = export::read_records(path)?;
+            let mut runs = records
+                .iter()
+                .filter(|record| record.status.is_counted() && record.status != RunStatus::Passed)
+                .map(|record| record.index)
+                .collect::<Vec<_>>();
+            runs.sort_unstable();
+            runs.dedup();
+            runs
+        }
+    };
+    Ok(PreviousFailures {
+        runs,
+        inputs: HashMap::new(),
+    })
+}
+
+/// The runs with a `failing_input_<index>.txt` in `dir`, and those inputs.
+fn load_dir(dir: &Path) -> Result<PreviousFailures, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|err| format!("cannot read {}: {}", dir.display(), err))?;
+    let mut failures = PreviousFailures::default();
+    for entry in entries {
+        let entry = entry.map_err(|err| format!("cannot read {}: {}", dir.display(), err))?;
+        let name = entry.file_name();
+        let Some(index) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("failing_input_")?.strip_suffix(".txt"))
+            .and_then(|index| index.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let input = fs::read(entry.path())
+            .map_err(|err| format!("cannot read {}: {}", entry.path().display(), err))?;
+        failures.runs.push(index);
+        failures.inputs.insert(index, Arc::from(input));
+    }
+    failures.runs.sort_unstable();
+    Ok(failures)
+}
@@ -0,0 +1,2101 @@
+//! Running the iterations of a session on worker threads and aggregating their results.
+
+use crate::{
+    artifacts,
+    checker::{CheckFiles, Checker},
+    command::Step,
+    compare::{self, DiffOptions, Tolerance},
+    environment::Environment,
+    error::Error,
+    generator::Generator,
+    isolation::Isolation,
+    process,
+    runs::{RunSet, Shard},
+    score::{OutputCounts, ScoreMissing, ScoreParser, Stream, WholeOutput},
+    shuffle::Jitter,
+    sink::Sink,
+    stats::{RollingMedian, Stats, StepStats},
+    tmpdir, units,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often a [paused](TestRunner::pause) worker checks whether to go on.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often the count of failure outputs left out is printed while they are.
+const SUPPRESSED_NOTE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What to run and how often.
+#[derive(Clone, Debug)]
+pub struct RunConfig {
+    /// Commands run in order by every iteration. The iteration fails at the first step that
+    /// fails.
+    pub steps: Vec<Step>,
+    /// Number of iterations, identified by the run indices `0..times`.
+    pub times: u64,
+    /// Number of worker threads running iterations concurrently.
+    pub threads: u8,
+    /// Kill an iteration that is still running after this long and count it as timed out.
+    pub timeout: Option<Duration>,
+    /// Kill an iteration running much longer than the passed ones before it, and count it as
+    /// [`RunStatus::RelativeTimeout`]. With `timeout` too, the smaller limit applies.
+    pub timeout_factor: Option<TimeoutFactor>,
+    /// Run these variants of `steps` instead, taking turns: run `i` runs variant
+    /// `i % variants.len()`. Every variant has as many steps as `steps`.
+    pub variants: Vec<Variant>,
+    /// Run the iterations in the order [shuffled](crate::shuffle::shuffled) with this seed
+    /// instead of in index order, before they are split over the workers.
+    pub shuffle: Option<u64>,
+    /// Wait this [jitter](Jitter::delay) before every iteration, outside of its time.
+    pub jitter: Option<Jitter>,
+    /// Run only the iterations of this shard of `0..times`. Run indices stay those of the
+    /// whole session; [`Snapshot::completed`] and [`TestRunner::pending`] hold positions in the
+    /// shard instead.
+    pub shard: Option<Shard>,
+    /// Run only the iterations with these run indices, sorted, e.g. those that failed before,
+    /// instead of those of the shard. [`Snapshot::completed`] and [`TestRunner::pending`] hold
+    /// positions in it, like in a shard.
+    pub only_runs: Option<Arc<[u64]>>,
+    /// Feed these iterations' steps this input instead of generating it, e.g. the input saved
+    /// from an earlier failure.
+    pub given_inputs: HashMap<u64, Arc<[u8]>>,
+    /// Limit every step to this many seconds of CPU time, counting an iteration whose step
+    /// used them up as [`RunStatus::CpuLimitExceeded`]. Only supported on Unix, ignored
+    /// elsewhere.
+    pub cpu_limit: Option<u64>,
+    /// Run every step in namespaces of its own. Only supported on Linux, ignored elsewhere.
+    pub isolation: Isolation,
+    /// Read a score from the output of this step (counting from 0) of every passed iteration.
+    pub score_step: Option<usize>,
+    /// How the score is read from the output of the scored step.
+    pub score_parser: Arc<dyn ScoreParser>,
+    /// What to do with an iteration whose score cannot be read.
+    pub score_missing: ScoreMissing,
+    /// Generate the stdin of every iteration's steps with this command.
+    pub generator: Option<Generator>,
+    /// Keep at most this many bytes of the generated input when nothing after the first step
+    /// needs all of it. The input is streamed to the first step as it is generated, unless a
+    /// validator must see it first.
+    pub capture_input_limit: Option<usize>,
+    /// Check every generated input with this command before the steps run; the iteration has
+    /// [`RunStatus::Invalid`] input if it fails.
+    pub validator: Option<Step>,
+    /// Stop with [`Error::TooManyInvalid`] once more iterations than this had invalid input.
+    pub max_invalid: Option<u64>,
+    /// A step exiting with one of these codes makes the iteration [`RunStatus::Skipped`]
+    /// instead of failed. Timeouts and signals never skip.
+    pub skip_exit_codes: Vec<i32>,
+    /// Stop with [`Error::TooManySkipped`] once more iterations than this were skipped.
+    pub max_skipped: Option<u64>,
+    /// Run this reference solution on the same input after the steps passed; the iteration
+    /// fails if the stdout of the last step differs from the reference's.
+    pub reference: Option<Step>,
+    /// Judge the output of the last step with this command instead of comparing it with the
+    /// reference's byte for byte.
+    pub checker: Option<Checker>,
+    /// How loosely the output is compared with the reference's.
+    pub tolerance: Tolerance,
+    /// How much of the difference between the output and the reference's is shown.
+    pub diff: DiffOptions,
+    /// Print what a failed iteration has to say, its diff, the output of the validator and
+    /// the files kept, for only the first this many failed iterations. The others print a
+    /// count now and then, see [`TestRunner::suppressed_failure_outputs`]; observers still
+    /// see them all.
+    pub max_failure_output: Option<u64>,
+    /// Count [`max_failure_output`](Self::max_failure_output) in kinds of failures instead,
+    /// see [`artifacts::signature`]: only the first iteration of each kind prints.
+    pub dedup_failure_output: bool,
+    /// Run this command at the start of every iteration; if it fails, the iteration has
+    /// [`RunStatus::SetupFailed`] and nothing else runs but the teardown.
+    pub setup: Option<Step>,
+    /// Run this command at the end of every iteration, however it went. Its failure is only
+    /// warned about.
+    pub teardown: Option<Step>,
+    /// The environment every command runs in.
+    pub env: Environment,
+    /// Give every iteration a fresh directory in this one, removed once it finished, see
+    /// [`tmpdir::command_in`].
+    pub tmpdir: Option<PathBuf>,
+    /// Leave the directories of iterations that did not pass behind.
+    pub keep_failed_tmpdirs: bool,
+    /// Cancel the runner after the first iteration that did not pass.
+    pub until_fail: bool,
+    /// Fail the first iteration with a step whose captured output matches one of these, cancel
+    /// the runner and kill every command running, see [`TestRunner::aborted`].
+    pub abort_on: Vec<regex::bytes::Regex>,
+    /// Give every step the same pipe as stdout and stderr, so its stdout holds both in the
+    /// order they were written and its stderr is empty. Only on Unix.
+    pub merge_output: bool,
+    /// Keep at most this many bytes of each output of a command, its start and its end. The
+    /// score and everything else reading an output sees what was kept, except that the stdout
+    /// of the last step and of the reference is kept whole when they are compared or checked.
+    /// The generated input is never cut.
+    pub max_output: Option<usize>,
+    /// Give the steps an empty stdin instead of ours when there is no generator, e.g. because
+    /// ours is being read.
+    pub null_stdin: bool,
+    /// Copy the child's stdout and stderr to ours once it finished.
+    pub show_output: bool,
+    /// Where the stdout of every command goes when it is shown. Only the steps inherit ours
+    /// with [`Sink::Inherit`].
+    pub stdout: Sink,
+    /// Where the stderr of every command goes when it is shown, likewise.
+    pub stderr: Sink,
+    /// Whether observers read [`RunResult::stdout`] and [`RunResult::stderr`]. Without them,
+    /// a stream of a step nothing else reads, to score, compare or show it, is not captured
+    /// at all.
+    pub observed_output: bool,
+}
+
+/// How a stream of a step is set up, see [`RunConfig::stdio`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StdioChoice {
+    /// The step writes to tester's own stream.
+    Inherit,
+    /// The stream is captured.
+    Piped,
+    /// Nothing reads the stream, so it goes to the null device.
+    Null,
+}
+
+impl RunConfig {
+    /// Run `steps` `times` times on a single thread, without timeout, scoring or output. When
+    /// scoring is enabled the whole stdout is the score and runs without one fail.
+    pub fn new(steps: Vec<Step>, times: u64) -> Self {
+        RunConfig {
+            steps,
+            times,
+            threads: 1,
+            timeout: None,
+            timeout_factor: None,
+            variants: vec![],
+            shuffle: None,
+            jitter: None,
+            shard: None,
+            only_runs: None,
+            given_inputs: HashMap::new(),
+            cpu_limit: None,
+            isolation: Isolation::default(),
+            score_step: None,
+            score_parser: Arc::new(WholeOutput::default()),
+            score_missing: ScoreMissing::default(),
+            generator: None,
+            capture_input_limit: None,
+            validator: None,
+            max_invalid: None,
+            skip_exit_codes: vec![],
+            max_skipped: None,
+            reference: None,
+            checker: None,
+            tolerance: Tolerance::default(),
+            diff: DiffOptions::default(),
+            max_failure_output: None,
+            dedup_failure_output: false,
+            setup: None,
+            teardown: None,
+            env: Environment::default(),
+            tmpdir: None,
+            keep_failed_tmpdirs: false,
+            until_fail: false,
+            abort_on: vec![],
+            merge_output: false,
+            max_output: None,
+            null_stdin: false,
+            show_output: false,
+            stdout: Sink::default(),
+            stderr: Sink::default(),
+            observed_output: true,
+        }
+    }
+
+    /// The variant run `index` runs, if there are variants.
+    pub fn variant_of(&self, index: u64) -> Option<&Variant> {
+        let variants = &self.variants;
+        (!variants.is_empty()).then(|| &variants[(index % variants.len() as u64) as usize])
+    }
+
+    /// Number of iterations this runner runs: those [selected](Self::only_runs), those of its
+    /// [shard](Self::shard), or all.
+    pub fn shard_len(&self) -> u64 {
+        if let Some(runs) = &self.only_runs {
+            return runs.len() as u64;
+        }
+        self.shard.map_or(self.times, |shard| shard.len(self.times))
+    }
+
+    /// The run index of the iteration at `position` of the [selected](Self::only_runs) runs or
+    /// the [shard](Self::shard).
+    pub fn run_index(&self, position: u64) -> u64 {
+        if let Some(runs) = &self.only_runs {
+            return runs[position as usize];
+        }
+        self.shard
+            .map_or(position, |shard| shard.run_index(position))
+    }
+
+    /// The steps run `index` runs.
+    pub fn steps_of(&self, index: u64) -> &[Step] {
+        self.variant_of(index)
+            .map_or(&self.steps, |variant| &variant.steps)
+    }
+
+    /// How `stream` of step `step_index` of `steps` is set up: inherited with
+    /// [`Sink::Inherit`], else piped if anything reads it and discarded if nothing does.
+    pub fn stdio(&self, step_index: usize, steps: usize, stream: Stream) -> StdioChoice {
+        let sink = match stream {
+            Stream::Stdout => &self.stdout,
+            Stream::Stderr => &self.stderr,
+        };
+        if !sink.captures() {
+            return StdioChoice::Inherit;
+        }
+        let shown = self.show_output && !matches!(sink, Sink::Null);
+        let scored = self.score_step == Some(step_index)
+            && self
+                .score_parser
+                .stream()
+                .is_none_or(|scored| scored == stream);
+        let compared = step_index + 1 == steps
+            && stream == Stream::Stdout
+            && (self.reference.is_some() || self.checker.is_some());
+        // What a step killed by the time limit printed until then is reported.
+        let limited = self.timeout.is_some() || self.timeout_factor.is_some();
+        if shown
+            || scored
+            || compared
+            || limited
+            || self.observed_output
+            || !self.abort_on.is_empty()
+        {
+            StdioChoice::Piped
+        } else {
+            StdioChoice::Null
+        }
+    }
+
+    /// How the `pending` iterations are split over the workers: contiguous chunks, shuffled
+    /// first with [`shuffle`](Self::shuffle), the first workers getting one extra iteration
+    /// each when they don't divide evenly. Workers with nothing to do are left out.
+    pub fn schedule(&self, pending: &RunSet) -> Vec<Schedule> {
+        let Some(seed) = self.shuffle else {
+            return pending
+                .split(self.threads)
+                .into_iter()
+                .map(Schedule::InOrder)
+                .collect();
+        };
+        let order = crate::shuffle::shuffled(pending, seed);
+        let threads = self.threads.max(1) as usize;
+        let (per_thread, extra) = (order.len() / threads, order.len() % threads);
+        let mut rest = &order[..];
+        (0..threads)
+            .map(|i| {
+                let (chunk, after) = rest.split_at(per_thread + usize::from(i < extra));
+                rest = after;
+                Schedule::Shuffled(chunk.to_vec())
+            })
+            .filter(|schedule| !schedule.is_empty())
+            .collect()
+    }
+}
+
+/// The iterations one worker runs, in the order it runs them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    /// In index order.
+    InOrder(RunSet),
+    /// In this order.
+    Shuffled(Vec<u64>),
+}
+
+impl Schedule {
+    /// The run indices in the order they run.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = u64> + '_> {
+        match self {
+            Schedule::InOrder(runs) => Box::new(runs.iter()),
+            Schedule::Shuffled(runs) => Box::new(runs.iter().copied()),
+        }
+    }
+
+    /// Number of iterations.
+    pub fn len(&self) -> u64 {
+        match self {
+            Schedule::InOrder(runs) => runs.len(),
+            Schedule::Shuffled(runs) => runs.len() as u64,
+        }
+    }
+
+    /// Whether there is nothing to run.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Schedule::InOrder(runs) => write!(f, "{}", runs),
+            Schedule::Shuffled(runs) => write!(f, "{} shuffled runs", runs.len()),
+        }
+    }
+}
+
+/// One variant of the steps, e.g. with a swept parameter substituted.
+#[derive(Clone, Debug)]
+pub struct Variant {
+    /// What sets the variant apart, e.g. `threads=4`.
+    pub label: String,
+    /// The steps it runs.
+    pub steps: Vec<Step>,
+}
+
+/// A time limit relative to how long iterations usually take, see
+/// [`RunConfig::timeout_factor`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeoutFactor {
+    /// The limit is this many times the median duration of the last passed iterations.
+    pub factor: f64,
+    /// There is no such limit before this many iterations passed.
+    pub min_runs: u64,
+}
+
+/// How many of the last passed iterations the median of a [`TimeoutFactor`] is taken over.
+const MEDIAN_WINDOW: usize = 1001;
+
+/// The time limit of an iteration.
+#[derive(Clone, Copy, Debug)]
+struct TimeLimit {
+    limit: Duration,
+    /// The median duration the limit is a [`TimeoutFactor`] of, when that limit is the one
+    /// applying.
+    median: Option<Duration>,
+}
+
+/// How an iteration ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// Every step exited successfully.
+    Passed,
+    /// A step exited unsuccessfully.
+    Failed,
+    /// The iteration was killed after exceeding [`RunConfig::timeout`].
+    TimedOut,
+    /// The iteration was killed after exceeding [`RunConfig::timeout_factor`] times the
+    /// median duration of the iterations before it.
+    RelativeTimeout,
+    /// A step was killed after using up [`RunConfig::cpu_limit`].
+    CpuLimitExceeded,
+    /// The validator rejected the generated input, so the steps did not run.
+    Invalid,
+    /// The setup command failed, so the steps did not run.
+    SetupFailed,
+    /// A step exited with one of [`RunConfig::skip_exit_codes`], e.g. for lacking a
+    /// prerequisite, so the later steps did not run.
+    Skipped,
+}
+
+impl RunStatus {
+    /// Whether iterations ending like this say something about the steps, and so count in
+    /// the statistics of a session.
+    pub fn is_counted(self) -> bool {
+        !matches!(
+            self,
+            RunStatus::Invalid | RunStatus::SetupFailed | RunStatus::Skipped
+        )
+    }
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            RunStatus::Passed => "passed",
+            RunStatus::Failed => "failed",
+            RunStatus::TimedOut => "timed out",
+            RunStatus::RelativeTimeout => "timed out relative to the median",
+            RunStatus::CpuLimitExceeded => "CPU time limit exceeded",
+            RunStatus::Invalid => "invalid input",
+            RunStatus::SetupFailed => "setup failed",
+            RunStatus::Skipped => "skipped",
+        })
+    }
+}
+
+/// Aggregated results of the iterations run so far.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionReport {
+    /// Iterations that finished, whatever their outcome, except those that are not
+    /// [counted](RunStatus::is_counted).
+    pub run_times: u64,
+    /// Iterations that did not pass, including the timed out ones and those over the CPU limit.
+    pub fail_times: u64,
+    /// Iterations killed after exceeding the timeout.
+    pub timeout_times: u64,
+    /// Iterations killed after using up the CPU limit.
+    #[serde(default)]
+    pub cpu_limit_times: u64,
+    /// Iterations killed after taking too many times the median duration.
+    #[serde(default)]
+    pub relative_timeout_times: u64,
+    /// Scores of the passed iterations.
+    pub scores: Stats,
+    /// How many of those were written with a time unit.
+    #[serde(default)]
+    pub time_scores: u64,
+    /// Wall-clock seconds taken by every iteration.
+    pub times: Stats,
+    /// Tallies of every step, in order.
+    pub steps: Vec<StepStats>,
+    /// Run indices of the iterations that did not pass.
+    #[serde(default)]
+    pub failed_runs: RunSet,
+    /// Iterations whose generated input the validator rejected. They count nowhere else.
+    #[serde(default)]
+    pub invalid_times: u64,
+    /// Iterations whose setup failed. They count nowhere else either.
+    #[serde(default)]
+    pub setup_failed_times: u64,
+    /// Iterations skipped by a step's exit code. Nor do they.
+    #[serde(default)]
+    pub skipped_times: u64,
+    /// Tallies of the setup command, over every iteration.
+    #[serde(default)]
+    pub setup: StepStats,
+    /// Tallies of the teardown command, over every iteration.
+    #[serde(default)]
+    pub teardown: StepStats,
+}
+
+impl SessionReport {
+    /// An empty report for iterations of `steps` steps.
+    pub fn new(steps: usize) -> Self {
+        SessionReport {
+            steps: vec![StepStats::default(); steps],
+            ..Default::default()
+        }
+    }
+
+    /// Iterations that passed.
+    pub fn pass_times(&self) -> u64 {
+        self.run_times - self.fail_times
+    }
+
+    /// Add the results of `other` to this report.
+    pub fn merge(&mut self, other: &SessionReport) {
+        self.run_times += other.run_times;
+        self.fail_times += other.fail_times;
+        self.timeout_times += other.timeout_times;
+        self.cpu_limit_times += other.cpu_limit_times;
+        self.relative_timeout_times += other.relative_timeout_times;
+        self.invalid_times += other.invalid_times;
+        self.setup_failed_times += other.setup_failed_times;
+        self.skipped_times += other.skipped_times;
+        self.setup.append(&other.setup);
+        self.teardown.append(&other.teardown);
+        self.scores.merge(&other.scores);
+        self.time_scores += other.time_scores;
+        self.times.merge(&other.times);
+        if self.steps.len() < other.steps.len() {
+            self.steps.resize(other.steps.len(), StepStats::default());
+        }
+        for (stats, other_stats) in self.steps.iter_mut().zip(&other.steps) {
+            stats.append(other_stats);
+        }
+        self.failed_runs.extend(&other.failed_runs);
+    }
+
+    /// Add one finished iteration to this report.
+    pub fn record(&mut self, result: &RunResult) {
+        if let Some(setup) = &result.setup {
+            self.setup.append(setup);
+        }
+        if let Some(teardown) = &result.teardown {
+            self.teardown.append(teardown);
+        }
+        match result.status {
+            RunStatus::Invalid => self.invalid_times += 1,
+            RunStatus::SetupFailed => self.setup_failed_times += 1,
+            RunStatus::Skipped => self.skipped_times += 1,
+            _ => {}
+        }
+        if !result.status.is_counted() {
+            return;
+        }
+        self.run_times += 1;
+        match result.status {
+            RunStatus::Passed
+            | RunStatus::Invalid
+            | RunStatus::SetupFailed
+            | RunStatus::Skipped => {}
+            RunStatus::Failed => self.fail_times += 1,
+            RunStatus::TimedOut => {
+                self.fail_times += 1;
+                self.timeout_times += 1;
+            }
+            RunStatus::CpuLimitExceeded => {
+                self.fail_times += 1;
+                self.cpu_limit_times += 1;
+            }
+            RunStatus::RelativeTimeout => {
+                self.fail_times += 1;
+                self.relative_timeout_times += 1;
+            }
+        }
+        if result.status != RunStatus::Passed {
+            self.failed_runs.insert(result.index);
+        }
+        if let Some(score) = result.score {
+            self.scores.add(score);
+            self.time_scores += u64::from(result.time_score);
+        }
+        self.times.add(result.duration.as_secs_f64());
+        for (stats, step_stats) in self.steps.iter_mut().zip(&result.steps) {
+            stats.append(step_stats);
+        }
+    }
+}
+
+/// Which iterations have finished and what they added up to, consistent with each other.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Run indices of the finished iterations, or their positions in the
+    /// [shard](RunConfig::shard).
+    pub completed: RunSet,
+    /// Aggregated results of exactly the iterations in `completed`.
+    pub report: SessionReport,
+}
+
+impl Snapshot {
+    /// Add the iterations of `other`, which must not be in this snapshot already.
+    pub fn merge(&mut self, other: &Snapshot) {
+        self.completed.extend(&other.completed);
+        self.report.merge(&other.report);
+    }
+}
+
+/// The iteration that stopped a runner because its output matched an
+/// [abort pattern](RunConfig::abort_on).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Abort {
+    /// Run index of the iteration.
+    pub index: u64,
+    /// The pattern that matched.
+    pub pattern: String,
+    /// The step whose output matched, counted from 1.
+    pub step: usize,
+    /// The stream it matched in.
+    pub stream: Stream,
+    /// That output from the start of the line with the match on.
+    pub output: Vec<u8>,
+}
+
+/// Stops a [`TestRunner`] from starting new iterations once cancelled. Iterations already
+/// running are finished. Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// A token that is not cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation. Safe to call from any thread, including signal handlers set up
+    /// with the `ctrlc` crate.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](Self::cancel) was called on this token or one of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// The outcome of one finished iteration, as passed to every [`RunObserver`].
+#[derive(Clone, Debug)]
+pub struct RunResult {
+    /// Run index of the iteration, in `0..times`.
+    pub index: u64,
+    /// Index of the worker thread that ran it.
+    pub thread: usize,
+    /// When it started, with its setup.
+    pub started: Instant,
+    /// How the iteration ended.
+    pub status: RunStatus,
+    /// Wall-clock time of the whole iteration, without its setup and teardown.
+    pub duration: Duration,
+    /// The score read from the scored step, only set for passed iterations when scoring.
+    pub score: Option<f64>,
+    /// Whether the score was written with a time unit, see [`Score::time`](crate::score::Score::time).
+    pub time_score: bool,
+    /// The seed passed to the generator.
+    pub seed: Option<u64>,
+    /// The label of the [variant](RunConfig::variants) it ran.
+    pub variant: Option<String>,
+    /// Why the iteration did not pass.
+    pub failure: Option<Failure>,
+    /// The input generated for the iteration's steps.
+    pub input: Option<Arc<[u8]>>,
+    /// Whether the generated input was longer than [`RunConfig::capture_input_limit`], so
+    /// `input` only has its start.
+    pub input_truncated: bool,
+    /// The outputs compared with [`RunConfig::reference`], if it ran.
+    pub comparison: Option<Comparison>,
+    /// Exit code of the last step that ran, unless it was killed by a signal.
+    pub exit_code: Option<i32>,
+    /// Standard output of every step that ran, in order.
+    pub stdout: Vec<u8>,
+    /// Standard error of every step that ran, in order.
+    pub stderr: Vec<u8>,
+    /// Tallies of this iteration's steps. Steps after the one that failed did not run.
+    pub steps: Vec<StepStats>,
+    /// Tallies of the setup command, if it ran.
+    pub setup: Option<StepStats>,
+    /// Tallies of the teardown command, if it ran.
+    pub teardown: Option<StepStats>,
+    /// Whether what it had to say on failure was not printed, see
+    /// [`RunConfig::max_failure_output`].
+    pub output_suppressed: bool,
+}
+
+/// Where and why an iteration did not pass.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// Where it failed, e.g. `at step 2 ./check` or `in generator ./gen 5`. Empty when the
+    /// only step failed.
+    pub place: String,
+    /// Why, e.g. `exit status: 1` or `timed out`.
+    pub reason: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.place.is_empty() {
+            f.write_str(&self.reason)
+        } else {
+            write!(f, "{} {}", self.reason, self.place)
+        }
+    }
+}
+
+/// The stdout of the last step and of the reference solution for the same input.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// What the last step wrote.
+    pub actual: Vec<u8>,
+    /// What the reference solution wrote.
+    pub expected: Vec<u8>,
+    /// How loosely they are compared.
+    pub tolerance: Tolerance,
+}
+
+impl Comparison {
+    /// Whether the outputs match.
+    pub fn matches(&self) -> bool {
+        self.tolerance.matches(&self.actual, &self.expected)
+    }
+}
+
+/// A hook called with the [`RunResult`] of every iteration as soon as it finished.
+///
+/// Observers are called on the worker thread that ran the iteration, so several of them may
+/// be called concurrently, and in whatever order the iterations happen to finish. The runner
+/// holds none of its locks while calling them: an observer may call back into the runner,
+/// e.g. [`TestRunner::snapshot`], which already includes the iteration. An observer that
+/// blocks only holds up the worker calling it, which starts its next iteration once every
+/// observer returned.
+///
+/// Any `Fn(&RunResult) + Send + Sync` closure is an observer.
+pub trait RunObserver: Send + Sync {
+    /// Called once for every finished iteration.
+    fn on_run(&self, result: &RunResult);
+}
+
+impl<F: Fn(&RunResult) + Send + Sync> RunObserver for F {
+    fn on_run(&self, result: &RunResult) {
+        self(result)
+    }
+}
+
+/// A value alone on its cache lines, so threads writing neighbouring values do not slow each
+/// other down.
+#[repr(align(128))]
+struct CachePadded<T>(T);
+
+/// Keeps the [`Snapshot`] of a runner up to date, the first observer of every runner.
+///
+/// Every worker thread records into a snapshot of its own, so with many threads and short
+/// iterations they never wait for each other; readers merge them. The first one also holds
+/// the iterations resumed from.
+struct SnapshotRecorder {
+    steps: usize,
+    /// The [`RunConfig::shard`] of the runs, whose positions are recorded as completed.
+    session_shard: Option<Shard>,
+    /// The [`RunConfig::only_runs`], likewise.
+    only_runs: Option<Arc<[u64]>>,
+    shards: Vec<CachePadded<Mutex<Snapshot>>>,
+}
+
+impl SnapshotRecorder {
+    fn new(steps: usize, threads: u8, config: &RunConfig) -> Self {
+        SnapshotRecorder {
+            steps,
+            session_shard: config.shard,
+            only_runs: config.only_runs.clone(),
+            shards: (0..threads.max(1))
+                .map(|_| {
+                    CachePadded(Mutex::new(Snapshot {
+                        completed: RunSet::default(),
+                        report: SessionReport::new(steps),
+                    }))
+                })
+                .collect(),
+        }
+    }
+
+    fn shards(&self) -> impl Iterator<Item = std::sync::MutexGuard<'_, Snapshot>> {
+        self.shards.iter().map(|shard| shard.0.lock().unwrap())
+    }
+
+    /// Replace what was recorded with `snapshot`.
+    fn reset(&self, snapshot: Snapshot) {
+        let mut shards = self.shards();
+        if let Some(mut first) = shards.next() {
+            *first = snapshot;
+        }
+        for mut shard in shards {
+            shard.completed = RunSet::default();
+            shard.report = SessionReport::new(self.steps);
+        }
+    }
+
+    /// Everything recorded so far. Every shard is consistent on its own, so their merge is
+    /// too, even though iterations may finish while they are locked one after the other.
+    fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot {
+            completed: RunSet::default(),
+            report: SessionReport::new(self.steps),
+        };
+        for shard in self.shards() {
+            snapshot.merge(&shard);
+        }
+        snapshot
+    }
+
+    fn completed(&self) -> u64 {
+        self.shards().map(|shard| shard.completed.len()).sum()
+    }
+
+    fn invalid_times(&self) -> u64 {
+        self.shards().map(|shard| shard.report.invalid_times).sum()
+    }
+
+    fn skipped_times(&self) -> u64 {
+        self.shards().map(|shard| shard.report.skipped_times).sum()
+    }
+}
+
+impl RunObserver for SnapshotRecorder {
+    fn on_run(&self, result: &RunResult) {
+        let mut snapshot = self.shards[result.thread % self.shards.len()]
+            .0
+            .lock()
+            .unwrap();
+        snapshot.report.record(result);
+        let position = match &self.only_runs {
+            Some(runs) => runs.binary_search(&result.index).unwrap_or_default() as u64,
+            None => self
+                .session_shard
+                .map_or(result.index, |shard| shard.position(result.index)),
+        };
+        snapshot.completed.insert(position);
+    }
+}
+
+/// Which failed iterations print what they have to say, see
+/// [`RunConfig::max_failure_output`].
+#[derive(Default)]
+struct FailureOutputs {
+    /// Failed iterations, or kinds of failures, that printed.
+    shown: u64,
+    /// The kinds of failures seen, when counting them.
+    signatures: HashSet<String>,
+    /// Failed iterations that did not print.
+    suppressed: u64,
+    /// When the count of suppressed ones was last printed.
+    noted: Option<Instant>,
+}
+
+impl FailureOutputs {
+    /// Whether failed iteration `result` prints, noting the count of those that do not now
+    /// and then.
+    fn admit(&mut self, result: &RunResult, config: &RunConfig) -> bool {
+        let Some(max) = config.max_failure_output else {
+            return true;
+        };
+        let new_kind =
+            !config.dedup_failure_output || self.signatures.insert(artifacts::signature(result));
+        if new_kind && self.shown < max {
+            self.shown += 1;
+            return true;
+        }
+        self.suppressed += 1;
+        match self.noted {
+            None => notice!(
+                "#tester {} failures printed, the rest only count (--max-failure-output)",
+                units::format_integer(self.shown)
+            ),
+            Some(noted) if noted.elapsed() >= SUPPRESSED_NOTE_INTERVAL => notice!(
+                "#tester suppressed {} further failure outputs",
+                units::format_integer(self.suppressed)
+            ),
+            Some(_) => return false,
+        }
+        self.noted = Some(Instant::now());
+        false
+    }
+}
+
+/// The iteration a worker thread is running, written by the worker when it starts and ends
+/// one and read by whoever watches, without locks.
+#[derive(Default)]
+struct InFlight {
+    index: AtomicU64,
+    /// Nanoseconds since the runner was created plus one, 0 while idle.
+    started: AtomicU64,
+}
+
+/// Runs the iterations described by a [`RunConfig`] on worker threads.
+///
+/// Run indices `0..times` are split into contiguous chunks, one per worker. The runner is
+/// `Sync`, so it can be shared with another thread that watches [`snapshot`](Self::snapshot)
+/// while [`run`](Self::run) blocks, and [`RunObserver`]s registered with
+/// [`observe`](Self::observe) are told about every iteration as it finishes.
+pub struct TestRunner {
+    config: RunConfig,
+    cancellation: CancellationToken,
+    /// Set by [`interrupt_running`](Self::interrupt_running).
+    interrupted: AtomicBool,
+    /// Set by [`pause`](Self::pause).
+    paused: AtomicBool,
+    /// The first iteration that matched an abort pattern.
+    abort: Mutex<Option<Abort>>,
+    progress: Arc<SnapshotRecorder>,
+    observers: Vec<Arc<dyn RunObserver>>,
+    failure_outputs: Mutex<FailureOutputs>,
+    /// When the runner was created, what [`InFlight::started`] counts from.
+    created: Instant,
+    in_flight: Box<[InFlight]>,
+    /// The durations of the last passed iterations, for [`RunConfig::timeout_factor`].
+    durations: Mutex<RollingMedian>,
+}
+
+impl TestRunner {
+    /// A runner that has not run anything yet.
+    pub fn new(config: RunConfig) -> Self {
+        let progress = Arc::new(SnapshotRecorder::new(
+            config.steps.len(),
+            config.threads,
+            &config,
+        ));
+        let in_flight = (0..config.threads.max(1))
+            .map(|_| InFlight::default())
+            .collect();
+        TestRunner {
+            config,
+            cancellation: CancellationToken::new(),
+            interrupted: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            abort: Mutex::new(None),
+            observers: vec![progress.clone()],
+            progress,
+            failure_outputs: Mutex::default(),
+            created: Instant::now(),
+            in_flight,
+            durations: Mutex::new(RollingMedian::new(MEDIAN_WINDOW)),
+        }
+    }
+
+    /// Continue from an earlier snapshot of the same configuration: only the iterations not in
+    /// `snapshot.completed` are run and the earlier results are included in the report.
+    pub fn resume_from(self, snapshot: Snapshot) -> Self {
+        self.progress.reset(snapshot);
+        self
+    }
+
+    /// Call `observer` for every iteration this runner finishes, after the observers
+    /// registered before it.
+    pub fn observe(mut self, observer: impl RunObserver + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// The configuration this runner was built from.
+    pub fn config(&self) -> &RunConfig {
+        &self.config
+    }
+
+    /// A token that cancels this runner.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Run indices that have not finished yet, or their positions in the
+    /// [shard](RunConfig::shard).
+    pub fn pending(&self) -> RunSet {
+        self.progress
+            .snapshot()
+            .completed
+            .complement(self.config.shard_len())
+    }
+
+    /// Number of iterations finished so far, including resumed ones and those with invalid
+    /// input.
+    pub fn run_times(&self) -> u64 {
+        self.progress.completed()
+    }
+
+    /// Number of failed iterations so far that did not print what they had to say, see
+    /// [`RunConfig::max_failure_output`].
+    pub fn suppressed_failure_outputs(&self) -> u64 {
+        self.failure_outputs.lock().unwrap().suppressed
+    }
+
+    /// The iterations running right now and for how long, with their setup, in no particular
+    /// order.
+    pub fn in_flight(&self) -> Vec<(u64, Duration)> {
+        let now = self.created.elapsed().as_nanos() as u64 + 1;
+        self.in_flight
+            .iter()
+            .filter_map(|slot| {
+                let started = slot.started.load(Ordering::Acquire);
+                let index = slot.index.load(Ordering::Acquire);
+                // Changed in between: the worker moved on to another iteration.
+                if started == 0 || slot.started.load(Ordering::Acquire) != started {
+                    return None;
+                }
+                Some((index, Duration::from_nanos(now.saturating_sub(started))))
+            })
+            .collect()
+    }
+
+    /// Interrupt the commands running right now, like a ctrl-c in the terminal would if
+    /// they were not in process groups of their own. Iterations of this runner that do not
+    /// pass from then on are dropped as cancelled rather than recorded as failed, and stay
+    /// [pending](Self::pending); cancel the runner too to stop it from starting more. Applies
+    /// to every runner of this process. Commands still running a few seconds later, or when
+    /// interrupted again, are killed.
+    pub fn interrupt_running(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+        process::interrupt_running();
+    }
+
+    /// The iteration whose output matched an [abort pattern](RunConfig::abort_on), if one
+    /// did. It counts as failed; the iterations it killed are dropped like interrupted ones.
+    pub fn aborted(&self) -> Option<Abort> {
+        self.abort.lock().unwrap().clone()
+    }
+
+    /// Stop starting new iterations until resumed with `pause(false)`; the running ones finish.
+    /// Cancelling the runner ends the pause.
+    pub fn pause(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Whether the runner is [paused](Self::pause).
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// The iterations finished so far and their aggregated results.
+    pub fn snapshot(&self) -> Snapshot {
+        self.progress.snapshot()
+    }
+
+    /// Run all pending iterations, blocking until they finished or the runner was cancelled,
+    /// and return the aggregated results.
+    ///
+    /// If a step cannot be run at all, the runner cancels itself, lets the other workers finish
+    /// their current iteration and returns the first such error. [`snapshot`](Self::snapshot)
+    /// still has the results of every iteration that finished.
+    pub fn run(&self) -> Result<SessionReport, Error> {
+        let pending = self.pending();
+        let runs_per_thread = self.config.schedule(&pending);
+        debug!(
+            "#tester scheduling {} runs on {} threads: [{}]",
+            pending.len(),
+            runs_per_thread.len(),
+            runs_per_thread
+                .iter()
+                .map(|runs| runs.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        );
+        let results = thread::scope(|scope| {
+            let workers = runs_per_thread
+                .into_iter()
+                .enumerate()
+                .map(|(thread_index, runs)| {
+                    scope.spawn(move || {
+                        let result = self.run_thread(thread_index, runs);
+                        if result.is_err() {
+                            self.cancellation.cancel();
+                        }
+                        result
+                    })
+                })
+                .collect::<Vec<_>>();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("worker thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        results.into_iter().collect::<Result<(), _>>()?;
+        Ok(self.snapshot().report)
+    }
+
+    /// Run the steps, and the reference if any, of run `index` on `input` instead of the
+    /// generated one, e.g. to check whether a reduced input still fails. Nothing is recorded,
+    /// printed or passed to observers, and cancellation is ignored.
+    pub fn rerun(&self, index: u64, input: Arc<[u8]>) -> Result<RunResult, Error> {
+        let mut programs = self.step_commands(None, self.config.steps_of(index));
+        let result = self.run_iteration(0, index, &mut programs, Some(input), true)?;
+        Ok(result.expect("quiet iterations are never cancelled"))
+    }
+
+    fn step_commands(&self, run_dir: Option<&Path>, steps: &[Step]) -> Vec<Command> {
+        steps
+            .iter()
+            .enumerate()
+            .map(|(step_index, step)| self.step_command(step, run_dir, step_index, steps.len()))
+            .collect()
+    }
+
+    /// The command running `step` as step `step_index` of `steps`.
+    fn step_command(
+        &self,
+        step: &Step,
+        run_dir: Option<&Path>,
+        step_index: usize,
+        steps: usize,
+    ) -> Command {
+        let mut program = tmpdir::command_in(step, run_dir, &self.config.env);
+        program.stderr(self.stdio(step_index, steps, Stream::Stderr));
+        program.stdout(self.stdio(step_index, steps, Stream::Stdout));
+        #[cfg(unix)]
+        if self.config.merge_output {
+            // Replaced by stdout in the child anyway.
+            program.stderr(Stdio::null());
+            process::merge_stderr(&mut program);
+        }
+        #[cfg(unix)]
+        if let Some(seconds) = self.config.cpu_limit {
+            process::limit_cpu(&mut program, seconds);
+        }
+        #[cfg(target_os = "linux")]
+        if !self.config.isolation.is_empty() {
+            process::isolate(&mut program, self.config.isolation, run_dir);
+        }
+        if self.config.generator.is_some() {
+            program.stdin(Stdio::piped());
+        } else if self.config.null_stdin {
+            program.stdin(Stdio::null());
+        }
+        program
+    }
+
+    /// Estimate how long spawning and reaping a command takes on this machine: the median
+    /// wall time of `samples` runs of a command doing nothing, set up like the first step.
+    pub fn calibrate_spawn(&self, samples: usize) -> Result<Duration, Error> {
+        let noop = if cfg!(windows) {
+            Step {
+                exec: "cmd".to_string(),
+                args: vec!["/C".to_string(), "rem".to_string()],
+            }
+        } else {
+            Step {
+                exec: "true".to_string(),
+                args: vec![],
+            }
+        };
+        let mut command = self.step_command(&noop, None, 0, self.config.steps.len());
+        // Close the piped stdin right away.
+        let input = self.config.generator.as_ref().map(|_| Arc::from(&[][..]));
+        let mut times = (0..samples.max(1))
+            .map(|_| {
+                let start = Instant::now();
+                Self::run_process(&noop, &mut command, input.clone(), None, None)?;
+                Ok(start.elapsed())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        times.sort();
+        Ok(times[times.len() / 2])
+    }
+
+    /// How `stream` of step `step_index` of `steps` is set up, see [`RunConfig::stdio`].
+    fn stdio(&self, step_index: usize, steps: usize, stream: Stream) -> Stdio {
+        match self.config.stdio(step_index, steps, stream) {
+            StdioChoice::Inherit => Stdio::inherit(),
+            StdioChoice::Piped => Stdio::piped(),
+            StdioChoice::Null => Stdio::null(),
+        }
+    }
+
+    fn run_thread(&self, thread_index: usize, runs: Schedule) -> Result<(), Error> {
+        // The commands of every variant, or of the steps without variants.
+        let mut programs = (0..self.config.variants.len().max(1) as u64)
+            .map(|index| self.step_commands(None, self.config.steps_of(index)))
+            .collect::<Vec<_>>();
+        debug!(
+            "#tester thread {}: runs {}: {}",
+            thread_index,
+            runs,
+            crate::command::pipeline_line(&self.config.steps)
+        );
+        for position in runs.iter() {
+            let run_index = self.config.run_index(position);
+            while self.is_paused() && !self.cancellation.is_cancelled() {
+                thread::sleep(PAUSE_POLL_INTERVAL);
+            }
+            if let Some(jitter) = &self.config.jitter {
+                let until = Instant::now() + jitter.delay(run_index);
+                // In short naps, so the runner stops right away when cancelled.
+                while !self.cancellation.is_cancelled() {
+                    let left = until.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        break;
+                    }
+                    thread::sleep(left.min(PAUSE_POLL_INTERVAL));
+                }
+            }
+            if self.cancellation.is_cancelled() {
+                break;
+            }
+            let variant = (run_index % programs.len() as u64) as usize;
+            let programs = &mut programs[variant];
+            let input = self.config.given_inputs.get(&run_index).cloned();
+            let slot = &self.in_flight[thread_index % self.in_flight.len()];
+            slot.started.store(0, Ordering::Release);
+            slot.index.store(run_index, Ordering::Release);
+            let started = self.created.elapsed().as_nanos() as u64 + 1;
+            slot.started.store(started, Ordering::Release);
+            let result = self.run_iteration(thread_index, run_index, programs, input, false);
+            slot.started.store(0, Ordering::Release);
+            let Some(result) = result? else {
+                break;
+            };
+            if self.config.timeout_factor.is_some() && result.status == RunStatus::Passed {
+                let mut durations = self.durations.lock().unwrap();
+                durations.add(result.duration.as_secs_f64());
+            }
+            for observer in &self.observers {
+                observer.on_run(&result);
+            }
+            match result.status {
+                RunStatus::Failed
+                | RunStatus::TimedOut
+                | RunStatus::CpuLimitExceeded
+                | RunStatus::RelativeTimeout
+                    if self.config.until_fail =>
+                {
+                    self.cancellation.cancel()
+                }
+                RunStatus::Invalid => {
+                    let invalid = self.progress.invalid_times();
+                    if let Some(max) = self.config.max_invalid.filter(|&max| invalid > max) {
+                        return Err(Error::TooManyInvalid { invalid, max });
+                    }
+                }
+                RunStatus::Skipped => {
+                    let skipped = self.progress.skipped_times();
+                    if let Some(max) = self.config.max_skipped.filter(|&max| skipped > max) {
+                        return Err(Error::TooManySkipped { skipped, max });
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the setup or teardown `step` of an iteration with its own timeout, returning its
+    /// tallies, why it failed, if it did, and its output.
+    fn run_hook(
+        &self,
+        step: &Step,
+        run_dir: Option<&Path>,
+    ) -> Result<(StepStats, Option<String>, Output), Error> {
+        let mut command = tmpdir::command_in(step, run_dir, &self.config.env);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        let start = Instant::now();
+        let deadline = self.config.timeout.map(|timeout| start + timeout);
+        let (output, timed_out) =
+            Self::run_process(step, &mut command, None, self.output_limit(None), deadline)?;
+        let reason = if timed_out {
+            Some("timed out".to_string())
+        } else {
+            (!output.status.success()).then(|| process::describe_status(output.status))
+        };
+        let stats = StepStats {
+            run_times: 1,
+            fail_times: u64::from(reason.is_some()),
+            total_time: start.elapsed(),
+        };
+        Ok((stats, reason, output))
+    }
+
+    /// Spawn the generator and the first step with the generator's stdout streamed to the
+    /// step's stdin, see [`process::pipe_with_deadline`].
+    #[allow(clippy::type_complexity)]
+    fn run_streamed(
+        &self,
+        generator: &Step,
+        command: &mut Command,
+        program: &mut Command,
+        deadline: Option<Instant>,
+    ) -> Result<
+        (
+            (Output, bool),
+            (Output, bool, OutputCounts),
+            process::Captured,
+        ),
+        Error,
+    > {
+        let producer = process::spawn(command).map_err(|source| Error::Spawn {
+            step: generator.command_line(),
+            source,
+        })?;
+        let consumer = match process::spawn(program) {
+            Ok(consumer) => consumer,
+            Err(source) => {
+                process::abandon(producer);
+                return Err(Error::Spawn {
+                    step: self.config.steps[0].command_line(),
+                    source,
+                });
+            }
+        };
+        // Later steps, the reference and the checker read the input again.
+        let needs_whole_input = self.config.steps.len() > 1
+            || self.config.reference.is_some()
+            || self.config.checker.is_some();
+        let limit = self
+            .config
+            .capture_input_limit
+            .filter(|_| !needs_whole_input);
+        let max_output = self.output_limit(Some(0));
+        process::pipe_with_deadline(producer, consumer, limit, max_output, deadline).map_err(
+            |source| Error::Wait {
+                step: generator.command_line(),
+                source,
+            },
+        )
+    }
+
+    /// How much of each output of step `step_index`, or of another command with `None`, is
+    /// kept: all of the last step's if it is compared or checked, see
+    /// [`RunConfig::max_output`].
+    fn output_limit(&self, step_index: Option<usize>) -> Option<usize> {
+        let compared = self.config.reference.is_some() || self.config.checker.is_some();
+        if compared && step_index == Some(self.config.steps.len() - 1) {
+            return None;
+        }
+        self.config.max_output
+    }
+
+    /// Spawn `command` running `step` and wait for it, keeping `max_output` bytes of each
+    /// output, see [`process::wait_with_deadline`].
+    fn run_process(
+        step: &Step,
+        command: &mut Command,
+        input: Option<Arc<[u8]>>,
+        max_output: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Result<(Output, bool), Error> {
+        let (output, timed_out, _) = Self::run_counted(step, command, input, max_output, deadline)?;
+        Ok((output, timed_out))
+    }
+
+    /// Run `command` like [`run_process`](Self::run_process), also counting all of its
+    /// output, see [`process::wait_counted`].
+    fn run_counted(
+        step: &Step,
+        command: &mut Command,
+        input: Option<Arc<[u8]>>,
+        max_output: Option<usize>,
+        deadline: Option<Instant>,
+    ) -> Result<(Output, bool, OutputCounts), Error> {
+        let child = process::spawn(command).map_err(|source| Error::Spawn {
+            step: step.command_line(),
+            source,
+        })?;
+        process::wait_counted(child, input, max_output, deadline).map_err(|source| Error::Wait {
+            step: step.command_line(),
+            source,
+        })
+    }
+
+    /// Copy the output of a command to where [`RunConfig::stdout`] and [`RunConfig::stderr`]
+    /// send it if [`RunConfig::show_output`], though only to files unless `to_ours`.
+    fn show_output(&self, stdout: &[u8], stderr: &[u8], to_ours: bool) -> Result<(), Error> {
+        if !self.config.show_output {
+            return Ok(());
+        }
+        for (sink, output, ours) in [
+            (&self.config.stdout, stdout, Stream::Stdout),
+            (&self.config.stderr, stderr, Stream::Stderr),
+        ] {
+            if !to_ours && sink.to_ours() {
+                continue;
+            }
+            match ours {
+                Stream::Stdout => sink.write(output, std::io::stdout()),
+                Stream::Stderr => sink.write(output, std::io::stderr()),
+            }
+            .map_err(Error::Output)?;
+        }
+        Ok(())
+    }
+
+    /// Show the output of a command of an iteration, or add it to `held`, if given, to show
+    /// once it is known whether the iteration prints, see [`RunConfig::max_failure_output`].
+    fn show_or_hold(
+        &self,
+        held: Option<&mut Vec<(Vec<u8>, Vec<u8>)>>,
+        stdout: &[u8],
+        stderr: &[u8],
+    ) -> Result<(), Error> {
+        match held {
+            Some(held) if self.config.show_output => {
+                held.push((stdout.to_vec(), stderr.to_vec()));
+                Ok(())
+            }
+            Some(_) => Ok(()),
+            None => self.show_output(stdout, stderr, true),
+        }
+    }
+
+    /// What a wrong answer prints: its input and how the output differs from the reference's.
+    fn describe_mismatch(
+        &self,
+        run_index: u64,
+        input: Option<&[u8]>,
+        comparison: &Comparison,
+        reason: &str,
+    ) -> String {
+        const SHOWN_LINES: usize = 20;
+        let mut message = format!("#tester run {}: {}\n", run_index, reason);
+        if let Some(input) = input {
+            message += "#tester input:\n";
+            message += &compare::excerpt(input, SHOWN_LINES);
+        }
+        let diff =
+            compare::unified_diff(&comparison.actual, &comparison.expected, self.config.diff);
+        if diff.is_empty() {
+            message += "#tester the output is the same as the reference's\n";
+        } else {
+            message += &diff;
+            message += &compare::tolerance_notes(
+                &comparison.actual,
+                &comparison.expected,
+                &comparison.tolerance,
+                SHOWN_LINES,
+            );
+        }
+        message
+    }
+
+    /// What a step killed by the time limit prints: the end of the output it wrote until
+    /// then, `elapsed` after the iteration started. It is all there was when it was killed,
+    /// as far as [`RunConfig::max_output`] kept it.
+    fn describe_partial(run_index: u64, elapsed: Duration, output: &Output) -> String {
+        const SHOWN_LINES: usize = 20;
+        let mut message = format!(
+            "#tester run {}: timed out after {}",
+            run_index,
+            units::format_duration(elapsed)
+        );
+        if output.stdout.is_empty() && output.stderr.is_empty() {
+            return message + ", it printed nothing until then\n";
+        }
+        message += ", what it printed until then (partial):\n";
+        for stream in [Stream::Stdout, Stream::Stderr] {
+            let bytes = stream.of(output);
+            if !bytes.is_empty() {
+                message += &format!("#tester {}:\n", stream);
+                message += &compare::tail_excerpt(bytes, SHOWN_LINES);
+            }
+        }
+        message
+    }
+
+    fn run_iteration(
+        &self,
+        thread_index: usize,
+        run_index: u64,
+        programs: &mut [Command],
+        given_input: Option<Arc<[u8]>>,
+        quiet: bool,
+    ) -> Result<Option<RunResult>, Error> {
+        let started = Instant::now();
+        let steps = self.config.steps_of(run_index);
+        let mut step_stats = vec![StepStats::default(); steps.len()];
+        let mut score = None;
+        // The output to show, held back while a failure might not print it.
+        let mut held = self.config.max_failure_output.map(|_| vec![]);
+        let mut stdout = vec![];
+        let mut stderr = vec![];
+        let mut last_stdout = vec![];
+        let mut exit_code = None;
+        // Where, why and how the iteration failed.
+        let mut failure: Option<(String, String, RunStatus)> = None;
+        let mut invalid = false;
+        let mut setup_failed = false;
+        let mut skipped = false;
+        // What the iteration prints if it fails, unless too many did.
+        let mut failure_output = vec![];
+
+        // Reruns get a directory of their own, so they never remove a directory kept for the
+        // original run.
+        let run_dir = self.config.tmpdir.as_deref().map(|root| {
+            let dir = tmpdir::run_dir(root, run_index);
+            if quiet {
+                dir.with_extension("rerun")
+            } else {
+                dir
+            }
+        });
+        let mut run_programs;
+        let programs = match &run_dir {
+            Some(dir) => {
+                fs::create_dir_all(dir).map_err(Error::TmpDir)?;
+                run_programs = self.step_commands(Some(dir), steps);
+                &mut run_programs[..]
+            }
+            None => programs,
+        };
+
+        let mut setup = None;
+        if let Some(step) = &self.config.setup {
+            let (stats, reason, output) = self.run_hook(step, run_dir.as_deref())?;
+            if !quiet {
+                self.show_or_hold(held.as_mut(), &output.stdout, &output.stderr)?;
+            }
+            if let Some(reason) = reason {
+                let place = format!(" in setup {}", step.command_line());
+                failure = Some((place, reason, RunStatus::Failed));
+                setup_failed = true;
+            }
+            setup = Some(stats);
+        }
+        let run_start = Instant::now();
+        let time_limit = self.time_limit();
+        let deadline = time_limit.map(|limit| run_start + limit.limit);
+
+        let generator = self.config.generator.as_ref();
+        let seed = generator.and_then(|generator| generator.seed(run_index));
+        let mut input = given_input.clone();
+        let mut input_truncated = false;
+        // The first step's outcome and duration, when it ran on the streamed generated input.
+        let mut first_step = None;
+        if let (Some(generator), None, None) = (generator, &given_input, &failure) {
+            let step = generator.step_for(run_index);
+            let mut command = tmpdir::command_in(&step, None, &self.config.env);
+            command
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            let (mut output, timed_out) = match (&self.config.validator, programs.first_mut()) {
+                (None, Some(program)) => {
+                    let step_start = Instant::now();
+                    let (generated, streamed, captured) =
+                        self.run_streamed(&step, &mut command, program, deadline)?;
+                    first_step = Some((streamed, step_start.elapsed()));
+                    input_truncated = captured.total > captured.bytes.len() as u64;
+                    input = Some(Arc::from(captured.bytes));
+                    generated
+                }
+                // Its stdout is the input.
+                _ => Self::run_process(&step, &mut command, None, None, deadline)?,
+            };
+            if !quiet {
+                self.show_or_hold(held.as_mut(), &[], &output.stderr)?;
+            }
+            if timed_out || !output.status.success() {
+                let reason = if timed_out {
+                    "timed out".to_string()
+                } else {
+                    process::describe_status(output.status)
+                };
+                failure = Some((
+                    format!(" in generator {}", step.command_line()),
+                    reason,
+                    timed_out_status(timed_out),
+                ));
+            }
+            if first_step.is_none() {
+                input = Some(Arc::from(std::mem::take(&mut output.stdout)));
+            }
+        }
+
+        if let (None, Some(validator), Some(input)) = (&failure, &self.config.validator, &input) {
+            let mut command = tmpdir::command_in(validator, None, &self.config.env);
+            command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            let (output, timed_out) = Self::run_process(
+                validator,
+                &mut command,
+                Some(input.clone()),
+                self.output_limit(None),
+                deadline,
+            )?;
+            if timed_out || !output.status.success() {
+                let reason = if timed_out {
+                    "timed out".to_string()
+                } else {
+                    process::describe_status(output.status)
+                };
+                if !quiet {
+                    let mut message = format!(
+                        "#tester run {}: invalid input ({}), validator stderr:\n",
+                        run_index, reason
+                    );
+                    message += &compare::excerpt(&output.stderr, 20);
+                    failure_output.push(message);
+                }
+                stderr.extend_from_slice(&output.stderr);
+                failure = Some((
+                    format!(" in validator {}", validator.command_line()),
+                    reason,
+                    RunStatus::Failed,
+                ));
+                invalid = true;
+            }
+        }
+
+        for (step_index, program) in programs.iter_mut().enumerate() {
+            if failure.is_some() {
+                break;
+            }
+            let step_start = Instant::now();
+            let ((p_ret, timed_out, counts), step_time) = match first_step.take() {
+                Some(streamed) => streamed,
+                None => {
+                    let ran = Self::run_counted(
+                        &steps[step_index],
+                        program,
+                        input.clone(),
+                        self.output_limit(Some(step_index)),
+                        deadline,
+                    )?;
+                    (ran, step_start.elapsed())
+                }
+            };
+            // Output the steps wrote to ours directly was seen already. What a step killed by
+            // the time limit printed is shown in its report only.
+            let captured = self.config.stdout.captures() || self.config.stderr.captures();
+            let partial = timed_out && captured;
+            if !quiet && !partial {
+                self.show_or_hold(held.as_mut(), &p_ret.stdout, &p_ret.stderr)?;
+            }
+            stdout.extend_from_slice(&p_ret.stdout);
+            stderr.extend_from_slice(&p_ret.stderr);
+            last_stdout = p_ret.stdout.clone();
+            exit_code = p_ret.status.code();
+
+            if partial && !quiet {
+                failure_output.push(Self::describe_partial(
+                    run_index,
+                    run_start.elapsed(),
+                    &p_ret,
+                ));
+            }
+            let mut failed_status = timed_out_status(timed_out);
+            let mut reason = if timed_out {
+                Some("timed out".to_string())
+            } else if p_ret
+                .status
+                .code()
+                .is_some_and(|code| self.config.skip_exit_codes.contains(&code))
+            {
+                skipped = true;
+                Some(process::describe_status(p_ret.status))
+            } else if !p_ret.status.success() {
+                #[cfg(unix)]
+                if self.config.cpu_limit.is_some() && process::exceeded_cpu_limit(p_ret.status) {
+                    failed_status = RunStatus::CpuLimitExceeded;
+                }
+                Some(match failed_status {
+                    RunStatus::CpuLimitExceeded => failed_status.to_string(),
+                    _ => process::describe_status(p_ret.status),
+                })
+            } else {
+                None
+            };
+            if let (false, Some(abort)) = (quiet, self.match_abort(run_index, step_index, &p_ret)) {
+                // The output says more than the exit status of, say, a sanitizer.
+                reason = Some(format!("output matched abort pattern `{}`", abort.pattern));
+                failed_status = RunStatus::Failed;
+                self.abort_with(abort);
+            }
+            if reason.is_none() && self.config.score_step == Some(step_index) {
+                match self.config.score_parser.parse_counted(&p_ret, &counts) {
+                    Ok(parsed) => score = parsed,
+                    Err(err) => match self.config.score_missing {
+                        ScoreMissing::Fail => reason = Some(format!("no score: {}", err)),
+                        ScoreMissing::Ignore if quiet => {}
+                        ScoreMissing::Ignore => {
+                            warn!("#tester run {}: no score: {}", run_index, err)
+                        }
+                    },
+                }
+            }
+            step_stats[step_index] = StepStats {
+                run_times: 1,
+                fail_times: u64::from(reason.is_some()),
+                total_time: step_time,
+            };
+
+            if let Some(reason) = reason {
+                let place = if steps.len() > 1 {
+                    format!(
+                        " at step {} {}",
+                        step_index + 1,
+                        steps[step_index].command_line()
+                    )
+                } else {
+                    String::new()
+                };
+                failure = Some((place, reason, failed_status));
+            }
+        }
+
+        let mut comparison = None;
+        if let (None, Some(reference)) = (&failure, &self.config.reference) {
+            let mut command = tmpdir::command_in(reference, run_dir.as_deref(), &self.config.env);
+            command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            let (output, timed_out) =
+                Self::run_process(reference, &mut command, input.clone(), None, deadline)?;
+            let place = format!(" in reference {}", reference.command_line());
+            if timed_out {
+                failure = Some((place, "timed out".to_string(), RunStatus::TimedOut));
+            } else if !output.status.success() {
+                failure = Some((
+                    place,
+                    process::describe_status(output.status),
+                    RunStatus::Failed,
+                ));
+            } else {
+                let compared = Comparison {
+                    actual: last_stdout.clone(),
+                    expected: output.stdout,
+                    tolerance: self.config.tolerance,
+                };
+                let mismatch = compare::first_mismatch(
+                    &compared.actual,
+                    &compared.expected,
+                    &compared.tolerance,
+                );
+                if let (None, Some(mismatch)) = (&self.config.checker, mismatch) {
+                    let reason = format!("wrong answer, {}", mismatch);
+                    if !quiet {
+                        failure_output.push(self.describe_mismatch(
+                            run_index,
+                            input.as_deref(),
+                            &compared,
+                            &reason,
+                        ));
+                    }
+                    failure = Some((String::new(), reason, RunStatus::Failed));
+                }
+                comparison = Some(compared);
+            }
+        }
+
+        if let (None, Some(checker)) = (&failure, &self.config.checker) {
+            let expected = comparison.as_ref().map(|c: &Comparison| &c.expected[..]);
+            let files = CheckFiles::write(
+                run_index,
+                input.as_deref().unwrap_or_default(),
+                &last_stdout,
+                expected,
+            )
+            .map_err(Error::CheckFiles)?;
+            let step = checker.step_for(&files);
+            let mut command = tmpdir::command_in(&step, run_dir.as_deref(), &self.config.env);
+            command
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+            let (output, timed_out) =
+                Self::run_process(&step, &mut command, None, self.output_limit(None), deadline)?;
+            if timed_out {
+                let place = format!(" in checker {}", step.command_line());
+                failure = Some((place, "timed out".to_string(), RunStatus::TimedOut));
+            } else if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let verdict = match stderr.lines().find(|line| !line.trim().is_empty()) {
+                    Some(line) => line.trim().to_string(),
+                    None => process::describe_status(output.status),
+                };
+                let reason = format!("wrong answer according to the checker: {}", verdict);
+                if let (false, Some(compared)) = (quiet, &comparison) {
+                    failure_output.push(self.describe_mismatch(
+                        run_index,
+                        input.as_deref(),
+                        compared,
+                        &reason,
+                    ));
+                }
+                failure = Some((String::new(), reason, RunStatus::Failed));
+            }
+            if failure.is_some() && checker.keep_failed {
+                if !quiet {
+                    failure_output.push(format!(
+                        "#tester run {}: checker files kept in {}",
+                        run_index,
+                        files.dir.display()
+                    ));
+                }
+            } else if let Err(err) = files.remove() {
+                warn!(
+                    "#tester run {}: cannot remove checker files: {}",
+                    run_index, err
+                );
+            }
+        }
+
+        let duration = run_start.elapsed();
+        let mut teardown = None;
+        if let Some(step) = &self.config.teardown {
+            let (stats, reason, output) = self.run_hook(step, run_dir.as_deref())?;
+            if !quiet {
+                self.show_or_hold(held.as_mut(), &output.stdout, &output.stderr)?;
+            }
+            if let (Some(reason), false) = (reason, quiet) {
+                warn!(
+                    "#tester run {}: teardown {} failed ({})",
+                    run_index,
+                    step.command_line(),
+                    reason
+                );
+            }
+            teardown = Some(stats);
+        }
+
+        if let Some(dir) = &run_dir {
+            if failure.is_some() && self.config.keep_failed_tmpdirs && !quiet {
+                failure_output.push(format!(
+                    "#tester run {}: temporary directory kept at {}",
+                    run_index,
+                    dir.display()
+                ));
+            } else if let Err(err) = fs::remove_dir_all(dir) {
+                warn!(
+                    "#tester run {}: cannot remove {}: {}",
+                    run_index,
+                    dir.display(),
+                    err
+                );
+            }
+        }
+
+        if let (
+            Some(TimeLimit {
+                limit,
+                median: Some(median),
+            }),
+            Some(relative),
+            Some((_, reason, status @ RunStatus::TimedOut)),
+        ) = (time_limit, self.config.timeout_factor, &mut failure)
+        {
+            *status = RunStatus::RelativeTimeout;
+            *reason = format!(
+                "timed out after {}, {} times the median duration {}",
+                units::format_duration(limit),
+                units::format_number(relative.factor),
+                units::format_duration(median)
+            );
+        }
+        let status = match &failure {
+            _ if setup_failed => RunStatus::SetupFailed,
+            _ if invalid => RunStatus::Invalid,
+            _ if skipped => RunStatus::Skipped,
+            None => RunStatus::Passed,
+            Some((_, _, status)) => *status,
+        };
+        if failure.is_some() {
+            score = None;
+        }
+        let mut result = RunResult {
+            index: run_index,
+            thread: thread_index,
+            started,
+            status,
+            duration,
+            score: score.map(|score| score.value),
+            time_score: score.is_some_and(|score| score.time),
+            seed,
+            variant: self
+                .config
+                .variant_of(run_index)
+                .map(|variant| variant.label.clone()),
+            failure: failure.map(|(place, reason, _)| Failure {
+                place: place.trim_start().to_string(),
+                reason,
+            }),
+            input,
+            input_truncated,
+            comparison,
+            exit_code,
+            stdout,
+            stderr,
+            steps: step_stats,
+            setup,
+            teardown,
+            output_suppressed: false,
+        };
+        let aborting = self
+            .abort
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|abort| abort.index == run_index);
+        if !quiet
+            && result.status != RunStatus::Passed
+            && self.interrupted.load(Ordering::Relaxed)
+            && !aborting
+        {
+            // Most likely it failed because it was interrupted, which says nothing about the
+            // command, so it is not reported either.
+            debug!("#tester run {} cancelled by the interrupt", run_index);
+            return Ok(None);
+        }
+        let held = held.unwrap_or_default();
+        // Only failures that print something count for --max-failure-output.
+        let prints = !failure_output.is_empty()
+            || result.seed.is_some()
+            || held.iter().any(|(stdout, stderr)| {
+                self.config.stdout.to_ours() && !stdout.is_empty()
+                    || self.config.stderr.to_ours() && !stderr.is_empty()
+            });
+        if !quiet && prints && result.failure.is_some() && result.status != RunStatus::Skipped {
+            result.output_suppressed = !self
+                .failure_outputs
+                .lock()
+                .unwrap()
+                .admit(&result, &self.config);
+        }
+        for (stdout, stderr) in &held {
+            self.show_output(stdout, stderr, !result.output_suppressed)?;
+        }
+        if !result.output_suppressed {
+            for message in failure_output {
+                notice!("{}", message.trim_end());
+            }
+        }
+        if !quiet {
+            self.log_result(&result);
+        }
+        Ok(Some(result))
+    }
+
+    /// The time limit of an iteration starting now: the smaller of [`RunConfig::timeout`] and
+    /// the one of [`RunConfig::timeout_factor`], once enough iterations passed for it.
+    fn time_limit(&self) -> Option<TimeLimit> {
+        let absolute = self.config.timeout.map(|limit| TimeLimit {
+            limit,
+            median: None,
+        });
+        let relative = self.config.timeout_factor.and_then(|relative| {
+            let durations = self.durations.lock().unwrap();
+            if durations.count() < relative.min_runs {
+                return None;
+            }
+            let median = Duration::from_secs_f64(durations.median()?);
+            let limit = Duration::try_from_secs_f64(median.as_secs_f64() * relative.factor)
+                .unwrap_or(Duration::MAX);
+            Some(TimeLimit {
+                limit,
+                median: Some(median),
+            })
+        });
+        match (absolute, relative) {
+            (Some(absolute), Some(relative)) if absolute.limit <= relative.limit => Some(absolute),
+            (absolute, relative) => relative.or(absolute),
+        }
+    }
+
+    /// Where the `output` of step `step_index` of iteration `run_index` matches an abort
+    /// pattern first, trying the patterns in order.
+    fn match_abort(&self, run_index: u64, step_index: usize, output: &Output) -> Option<Abort> {
+        self.config.abort_on.iter().find_map(|pattern| {
+            [Stream::Stdout, Stream::Stderr]
+                .into_iter()
+                .find_map(|stream| {
+                    let bytes = stream.of(output);
+                    let found = pattern.find(bytes)?;
+                    let line_start = bytes[..found.start()]
+                        .iter()
+                        .rposition(|&byte| byte == b'\n')
+                        .map_or(0, |newline| newline + 1);
+                    Some(Abort {
+                        index: run_index,
+                        pattern: pattern.as_str().to_string(),
+                        step: step_index + 1,
+                        stream,
+                        output: bytes[line_start..].to_vec(),
+                    })
+                })
+        })
+    }
+
+    /// Stop the runner for `abort` unless another iteration did already: no new iterations
+    /// start and the running commands are killed rather than waited for.
+    fn abort_with(&self, abort: Abort) {
+        let mut first = self.abort.lock().unwrap();
+        if first.is_some() {
+            return;
+        }
+        *first = Some(abort);
+        drop(first);
+        self.cancellation.cancel();
+        self.interrupted.store(true, Ordering::Relaxed);
+        process::kill_running();
+    }
+
+    fn log_result(&self, result: &RunResult) {
+        let seed_note = result
+            .seed
+            .map(|seed| format!(", seed {}", seed))
+            .unwrap_or_default();
+        if let (false, Some(failure)) = (result.status.is_counted(), &result.failure) {
+            info!(
+                "#tester run {}: {} ({} {}){}",
+                result.index, result.status, failure.reason, failure.place, seed_note
+            );
+        } else if let Some(failure) = &result.failure {
+            let place = if failure.place.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", failure.place)
+            };
+            info!(
+                "#tester run {}: failed{} ({}){}",
+                result.index, place, failure.reason, seed_note
+            );
+            if let (Some(generator), Some(seed), false) = (
+                &self.config.generator,
+                result.seed,
+                result.output_suppressed,
+            ) {
+                notice!(
+                    "#tester run {} failed with seed {}, replay its input with: {}",
+                    result.index,
+                    seed,
+                    generator.step_for(result.index).command_line()
+                );
+            }
+        } else if let Some(score) = result.score {
+            info!(
+                "#tester run {}: passed, score {}{}",
+                result.index, score, seed_note
+            );
+        } else {
+            info!("#tester run {}: passed{}", result.index, seed_note);
+        }
+    }
+}
+
+/// How an iteration failed, given whether it was because it timed out.
+fn timed_out_status(timed_out: bool) -> RunStatus {
+    if timed_out {
+        RunStatus::TimedOut
+    } else {
+        RunStatus::Failed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(index: u64, status: RunStatus, score: Option<f64>) -> RunResult {
+        RunResult {
+            index,
+            thread: 0,
+            started: Instant::now(),
+            status,
+            duration: Duration::from_millis(1),
+            score,
+            time_score: false,
+            seed: None,
+            variant: None,
+            failure: None,
+            input: None,
+            input_truncated: false,
+            comparison: None,
+            exit_code: None,
+            stdout: vec![],
+            stderr: vec![],
+            steps: vec![StepStats {
+                run_times: 1,
+                fail_times: u64::from(status != RunStatus::Passed),
+                total_time: Duration::from_millis(1),
+            }],
+            setup: None,
+            teardown: None,
+            output_suppressed: false,
+        }
+    }
+
+    #[test]
+    fn counts_beyond_u32() {
+        let max = u64::from(u32::MAX);
+        let mut report = SessionReport::new(1);
+        report.run_times = max;
+        report.fail_times = max - 1;
+        report.timeout_times = max - 1;
+        report.steps[0].run_times = max;
+        report.steps[0].fail_times = max - 1;
+        report.record(&result(max, RunStatus::Passed, Some(2.0)));
+        report.record(&result(max + 1, RunStatus::TimedOut, None));
+        report.record(&result(max + 2, RunStatus::Skipped, None));
+        assert_eq!(report.run_times, max + 2);
+        assert_eq!(report.fail_times, max);
+        assert_eq!(report.pass_times(), 2);
+        assert_eq!(report.skipped_times, 1);
+
+        let mut merged = report.clone();
+        merged.merge(&report);
+        assert_eq!(merged.run_times, 2 * max + 4);
+        assert_eq!(merged.fail_times, 2 * max);
+        assert_eq!(merged.timeout_times, 2 * max);
+        assert_eq!(merged.pass_times(), 4);
+        assert_eq!(merged.steps[0].run_times, 2 * max + 4);
+        assert_eq!(merged.scores.count, 2);
+        assert_eq!(merged.scores.mean(), Some(2.0));
+        // Indices past u32::MAX are kept as they are.
+        assert_eq!(merged.failed_runs.iter().collect::<Vec<_>>(), [max + 1]);
+    }
+}
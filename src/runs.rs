@@ -0,0 +1,211 @@
+//! Sets of run indices, kept as sorted, disjoint ranges so millions of runs stay cheap.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::Range};
+
+/// A set of run indices. Serialized as a list of `[start, end)` pairs.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<[u64; 2]>", into = "Vec<[u64; 2]>")]
+pub struct RunSet {
+    ranges: Vec<Range<u64>>,
+}
+
+impl RunSet {
+    /// Number of indices in the set.
+    pub fn len(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+
+    /// Whether the set has no indices.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// The indices in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.ranges.iter().flat_map(|range| range.clone())
+    }
+
+    /// Add `index`, merging it with adjacent ranges.
+    pub fn insert(&mut self, index: u64) {
+        let pos = self.ranges.partition_point(|range| range.end < index);
+        match self.ranges.get_mut(pos) {
+            Some(range) if range.contains(&index) => {}
+            Some(range) if range.end == index => {
+                range.end += 1;
+                let merges_next = self
+                    .ranges
+                    .get(pos + 1)
+                    .is_some_and(|next| next.start == index + 1);
+                if merges_next {
+                    self.ranges[pos].end = self.ranges.remove(pos + 1).end;
+                }
+            }
+            Some(range) if range.start == index + 1 => range.start = index,
+            _ => self.ranges.insert(pos, index..index + 1),
+        }
+    }
+
+    /// Add every index of `other`.
+    pub fn extend(&mut self, other: &RunSet) {
+        if other.is_empty() {
+            return;
+        }
+        let pairs = self
+            .ranges
+            .iter()
+            .chain(&other.ranges)
+            .map(|range| [range.start, range.end])
+            .collect::<Vec<_>>();
+        *self = RunSet::from(pairs);
+    }
+
+    /// Indices of `0..times` that are not in this set.
+    pub fn complement(&self, times: u64) -> RunSet {
+        let mut ranges = vec![];
+        let mut start = 0;
+        for range in &self.ranges {
+            if range.start > start {
+                ranges.push(start..range.start.min(times));
+            }
+            start = start.max(range.end);
+        }
+        if start < times {
+            ranges.push(start..times);
+        }
+        ranges.retain(|range| !range.is_empty());
+        RunSet { ranges }
+    }
+
+    /// Split into contiguous chunks over `threads` workers, giving the first workers one extra
+    /// run each when it doesn't divide evenly. Workers with nothing to do are left out.
+    pub fn split(&self, threads: u8) -> Vec<RunSet> {
+        let threads = std::cmp::max(threads, 1) as u64;
+        let times_per_thread = self.len() / threads;
+        let times_extra = self.len() % threads;
+        let mut remaining = self.ranges.iter().cloned();
+        let mut current = remaining.next();
+        (0..threads)
+            .map(|i| {
+                let mut times_this_thread = times_per_thread + u64::from(i < times_extra);
+                let mut chunk = RunSet::default();
+                while times_this_thread > 0 {
+                    let Some(range) = current.as_mut() else {
+                        break;
+                    };
+                    let taken = times_this_thread.min(range.end - range.start);
+                    chunk.ranges.push(range.start..range.start + taken);
+                    range.start += taken;
+                    times_this_thread -= taken;
+                    if range.start == range.end {
+                        current = remaining.next();
+                    }
+                }
+                chunk
+            })
+            .filter(|chunk| !chunk.is_empty())
+            .collect()
+    }
+}
+
+impl From<Vec<[u64; 2]>> for RunSet {
+    fn from(pairs: Vec<[u64; 2]>) -> Self {
+        let mut sorted = pairs
+            .into_iter()
+            .map(|[start, end]| start..end)
+            .filter(|range| !range.is_empty())
+            .collect::<Vec<_>>();
+        sorted.sort_by_key(|range| range.start);
+        let mut ranges: Vec<Range<u64>> = vec![];
+        for range in sorted {
+            match ranges.last_mut() {
+                Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+                _ => ranges.push(range),
+            }
+        }
+        RunSet { ranges }
+    }
+}
+
+impl From<RunSet> for Vec<[u64; 2]> {
+    fn from(set: RunSet) -> Self {
+        set.ranges
+            .into_iter()
+            .map(|range| [range.start, range.end])
+            .collect()
+    }
+}
+
+impl fmt::Display for RunSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|range| format!("{}..{}", range.start, range.end))
+            .collect::<Vec<_>>();
+        write!(f, "{}", ranges.join(", "))
+    }
+}
+
+/// The share of a session's runs one machine runs, see `--shard`: the run indices congruent
+/// to `index` modulo `count`. They are numbered by their position among those, from 0, so the
+/// shard's sets of runs stay as compact as a whole session's.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shard {
+    /// Which shard, in `0..count`.
+    pub index: u64,
+    /// Number of shards.
+    pub count: u64,
+}
+
+impl Shard {
+    /// Number of run indices of this shard in `0..times`.
+    pub fn len(&self, times: u64) -> u64 {
+        if times > self.index {
+            (times - self.index - 1) / self.count + 1
+        } else {
+            0
+        }
+    }
+
+    /// The run index at `position` in this shard.
+    pub fn run_index(&self, position: u64) -> u64 {
+        position * self.count + self.index
+    }
+
+    /// The position of `run_index`, one of this shard's, in it.
+    pub fn position(&self, run_index: u64) -> u64 {
+        run_index / self.count
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+/// Parse a shard such as `2/4`, the third of four.
+pub fn parse_shard(s: &str) -> Result<Shard, String> {
+    let expected = || format!("invalid shard `{}`: expected I/M like 2/4", s);
+    let (index, count) = s.split_once('/').ok_or_else(expected)?;
+    let index = index.trim().parse::<u64>().map_err(|_| expected())?;
+    let count = count.trim().parse::<u64>().map_err(|_| expected())?;
+    if count == 0 {
+        return Err(format!(
+            "invalid shard `{}`: there must be at least 1 shard",
+            s
+        ));
+    }
+    if index >= count {
+        return Err(format!(
+            "invalid shard `{}`: shards are numbered from 0 to {}",
+            s,
+            count - 1
+        ));
+    }
+    Ok(Shard { index, count })
+}
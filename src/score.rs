@@ -0,0 +1,578 @@
+//! Reading the score of a run from the output of its scored step.
+
+use regex::Regex;
+use std::{borrow::Cow, fmt, process::Output};
+
+/// Which output stream of the scored step a score is read from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Stream {
+    /// The step's standard output.
+    #[default]
+    Stdout,
+    /// The step's standard error.
+    Stderr,
+}
+
+impl Stream {
+    /// The bytes `output` captured for this stream.
+    pub fn of<'a>(&self, output: &'a Output) -> &'a [u8] {
+        match self {
+            Stream::Stdout => &output.stdout,
+            Stream::Stderr => &output.stderr,
+        }
+    }
+
+    /// The stream as text, with invalid UTF-8 replaced, so a score line can follow binary
+    /// output.
+    fn text<'a>(&self, output: &'a Output) -> Cow<'a, str> {
+        String::from_utf8_lossy(self.of(output))
+    }
+}
+
+impl fmt::Display for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        })
+    }
+}
+
+/// Parse a `--score-stream` value, `stdout` or `stderr`.
+pub fn parse_stream(s: &str) -> Result<Stream, String> {
+    match s {
+        "stdout" => Ok(Stream::Stdout),
+        "stderr" => Ok(Stream::Stderr),
+        _ => Err(format!("invalid stream `{}`: expected stdout or stderr", s)),
+    }
+}
+
+/// Time units a score may be followed by, in seconds.
+const TIME_UNITS: [(&str, f64); 5] = [
+    ("ns", 1e-9),
+    ("us", 1e-6),
+    ("µs", 1e-6),
+    ("ms", 1e-3),
+    ("s", 1.0),
+];
+/// Magnitudes a score may be followed by.
+const MAGNITUDES: [(&str, f64); 3] = [("k", 1e3), ("M", 1e6), ("G", 1e9)];
+
+/// A score read from the output of a run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Score {
+    /// Its value, in the unit of `--score-unit` if it is a time.
+    pub value: f64,
+    /// Whether it was written with a time unit, rather than as a plain number or with a
+    /// magnitude.
+    pub time: bool,
+}
+
+impl Score {
+    /// A score that is no time.
+    pub fn number(value: f64) -> Self {
+        Score { value, time: false }
+    }
+}
+
+/// Which unit suffixes a score may have, see `--score-unit`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ScoreUnit {
+    /// A plain number, the default.
+    #[default]
+    None,
+    /// A number, optionally followed by a time unit, converted to this one, or by a
+    /// magnitude, multiplied out.
+    Time {
+        /// The unit scores are converted to, as written after them, e.g. `ms`.
+        name: &'static str,
+        /// Its length in seconds.
+        seconds: f64,
+    },
+}
+
+impl ScoreUnit {
+    /// The unit scores are converted to, if any.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            ScoreUnit::None => None,
+            ScoreUnit::Time { name, .. } => Some(name),
+        }
+    }
+
+    /// Parse `text`, a number followed by a unit if enabled, with whitespace around.
+    fn parse(&self, text: &str) -> Result<Score, ScoreError> {
+        let text = text.trim();
+        let not_a_number = || ScoreError(format!("expected a number, found `{}`", text));
+        if let Ok(number) = text.parse() {
+            return Ok(Score::number(number));
+        }
+        let ScoreUnit::Time { seconds, .. } = self else {
+            return Err(not_a_number());
+        };
+        let unit_start = text
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphabetic())
+            .last()
+            .map_or(text.len(), |(start, _)| start);
+        let (number, unit) = text.split_at(unit_start);
+        let number = number
+            .trim_end()
+            .parse::<f64>()
+            .map_err(|_| not_a_number())?;
+        if let Some((_, unit_seconds)) = TIME_UNITS.iter().find(|(name, _)| *name == unit) {
+            return Ok(Score {
+                value: number * unit_seconds / seconds,
+                time: true,
+            });
+        }
+        match MAGNITUDES.iter().find(|(name, _)| *name == unit) {
+            Some((_, magnitude)) => Ok(Score::number(number * magnitude)),
+            None => Err(ScoreError(format!("unknown unit `{}` in `{}`", unit, text))),
+        }
+    }
+}
+
+/// Parse a `--score-unit` value: `none`, `auto` (the same as `seconds`), or the time unit
+/// scores are converted to.
+pub fn parse_unit(s: &str) -> Result<ScoreUnit, String> {
+    let name = match s {
+        "none" => return Ok(ScoreUnit::None),
+        "auto" | "s" | "seconds" => "s",
+        "ms" | "milliseconds" => "ms",
+        "us" | "µs" | "microseconds" => "us",
+        "ns" | "nanoseconds" => "ns",
+        _ => {
+            return Err(format!(
+                "invalid unit `{}`: expected auto, none, seconds, ms, us or ns",
+                s
+            ))
+        }
+    };
+    let (name, seconds) = *TIME_UNITS
+        .iter()
+        .find(|(unit, _)| *unit == name)
+        .expect("a known unit");
+    Ok(ScoreUnit::Time { name, seconds })
+}
+
+/// Why no score could be read from a run's output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScoreError(pub String);
+
+impl fmt::Display for ScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ScoreError {}
+
+/// What to do with a passed run whose score cannot be read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScoreMissing {
+    /// Count the run as failed.
+    #[default]
+    Fail,
+    /// Keep the run as passed, leaving it out of the average score.
+    Ignore,
+}
+
+/// Parse a `--score-missing` value, `fail` or `ignore`.
+pub fn parse_score_missing(s: &str) -> Result<ScoreMissing, String> {
+    match s {
+        "fail" => Ok(ScoreMissing::Fail),
+        "ignore" => Ok(ScoreMissing::Ignore),
+        _ => Err(format!("invalid policy `{}`: expected fail or ignore", s)),
+    }
+}
+
+/// How much went through one output stream of a command, counted as it was read, so including
+/// what [`RunConfig::max_output`](crate::RunConfig::max_output) left out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    bytes: u64,
+    newlines: u64,
+    /// Whether the last byte read was not a newline.
+    unterminated: bool,
+}
+
+impl Counts {
+    /// The counts of the whole of `bytes`.
+    pub fn of(bytes: &[u8]) -> Self {
+        let mut counts = Counts::default();
+        counts.add(bytes);
+        counts
+    }
+
+    /// Count `chunk`, read after what was counted so far.
+    pub fn add(&mut self, chunk: &[u8]) {
+        let Some(&last) = chunk.last() else {
+            return;
+        };
+        self.bytes += chunk.len() as u64;
+        self.newlines += chunk.iter().filter(|&&byte| byte == b'\n').count() as u64;
+        self.unterminated = last != b'\n';
+    }
+
+    /// Number of bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Number of lines, the last one counted whether or not it ends with a newline.
+    pub fn lines(&self) -> u64 {
+        self.newlines + u64::from(self.unterminated)
+    }
+}
+
+/// The [`Counts`] of both output streams of a command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutputCounts {
+    /// Of its standard output.
+    pub stdout: Counts,
+    /// Of its standard error.
+    pub stderr: Counts,
+}
+
+impl OutputCounts {
+    /// The counts of the whole of `output`.
+    pub fn of(output: &Output) -> Self {
+        OutputCounts {
+            stdout: Counts::of(&output.stdout),
+            stderr: Counts::of(&output.stderr),
+        }
+    }
+
+    /// The counts of `stream`.
+    pub fn stream(&self, stream: Stream) -> &Counts {
+        match stream {
+            Stream::Stdout => &self.stdout,
+            Stream::Stderr => &self.stderr,
+        }
+    }
+}
+
+/// Extracts the score of a run from the output of its scored step.
+///
+/// Parsers are shared by all worker threads. Returning `Ok(None)` means the run has no score,
+/// without that being an error.
+pub trait ScoreParser: Send + Sync {
+    /// Read the score from the output of the scored step of a passed run.
+    fn parse(&self, output: &Output) -> Result<Option<Score>, ScoreError>;
+
+    /// Read the score like [`parse`](Self::parse), knowing how much the step wrote in all,
+    /// for parsers that count it; `output` may only have the start and end of it.
+    fn parse_counted(
+        &self,
+        output: &Output,
+        counts: &OutputCounts,
+    ) -> Result<Option<Score>, ScoreError> {
+        let _ = counts;
+        self.parse(output)
+    }
+
+    /// The only stream [`parse`](Self::parse) reads, if it reads a single one; the other
+    /// one is then not captured.
+    fn stream(&self) -> Option<Stream> {
+        None
+    }
+}
+
+impl fmt::Debug for dyn ScoreParser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ScoreParser")
+    }
+}
+
+/// The whole stream, surrounding whitespace aside, is the score. The default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WholeOutput {
+    /// The stream to read.
+    pub stream: Stream,
+    /// The units the score may have.
+    pub unit: ScoreUnit,
+}
+
+impl ScoreParser for WholeOutput {
+    fn parse(&self, output: &Output) -> Result<Option<Score>, ScoreError> {
+        self.unit.parse(&self.stream.text(output)).map(Some)
+    }
+
+    fn stream(&self) -> Option<Stream> {
+        Some(self.stream)
+    }
+}
+
+/// The last non-blank line of the stream is the score, for commands that log before it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LastLine {
+    /// The stream to read.
+    pub stream: Stream,
+    /// The units the score may have.
+    pub unit: ScoreUnit,
+}
+
+impl ScoreParser for LastLine {
+    fn parse(&self, output: &Output) -> Result<Option<Score>, ScoreError> {
+        let text = self.stream.text(output);
+        let line = text
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .ok_or_else(|| ScoreError(format!("{} is empty", self.stream)))?;
+        self.unit.parse(line).map(Some)
+    }
+
+    fn stream(&self) -> Option<Stream> {
+        Some(self.stream)
+    }
+}
+
+/// The first match of a regex in the stream is the score: its first capture group if it has
+/// one, the whole match otherwise.
+#[derive(Clone, Debug)]
+pub struct RegexCapture {
+    /// The stream to search.
+    pub stream: Stream,
+    /// The pattern to search for.
+    pub regex: Regex,
+    /// The units the score may have.
+    pub unit: ScoreUnit,
+}
+
+impl ScoreParser for RegexCapture {
+    fn parse(&self, output: &Output) -> Result<Option<Score>, ScoreError> {
+        let text = self.stream.text(output);
+        let captures = self.regex.captures(&text).ok_or_else(|| {
+            ScoreError(format!("`{}` does not match {}", self.regex, self.stream))
+        })?;
+        let score = captures.get(1).or_else(|| captures.get(0)).unwrap();
+        self.unit.parse(score.as_str()).map(Some)
+    }
+
+    fn stream(&self) -> Option<Stream> {
+        Some(self.stream)
+    }
+}
+
+/// The number at a JSON pointer, as in RFC 6901, in the JSON the stream holds, or else in
+/// its last line that looks like JSON, for commands that log before it.
+#[derive(Clone, Debug)]
+pub struct JsonPointer {
+    /// The stream to read.
+    pub stream: Stream,
+    /// The pointer to the score, e.g. `/stats/throughput`.
+    pub pointer: String,
+    /// The units a score given as a string may have; JSON numbers are taken as they are.
+    pub unit: ScoreUnit,
+}
+
+impl ScoreParser for JsonPointer {
+    fn parse(&self, output: &Output) -> Result<Option<Score>, ScoreError> {
+        let text = self.stream.text(output);
+        let document = serde_json::from_str::<serde_json::Value>(&text).or_else(|err| {
+            let line = text
+                .lines()
+                .rev()
+                .map(str::trim)
+                .find(|line| line.starts_with('{') || line.starts_with('['))
+                .ok_or_else(|| ScoreError(format!("{} is not JSON: {}", self.stream, err)))?;
+            serde_json::from_str(line)
+                .map_err(|err| ScoreError(format!("invalid JSON `{}`: {}", excerpt(line), err)))
+        })?;
+        match document.pointer(&self.pointer) {
+            Some(serde_json::Value::Number(number)) => Ok(number.as_f64().map(Score::number)),
+            Some(serde_json::Value::String(string)) if self.unit != ScoreUnit::None => {
+                self.unit.parse(string).map(Some)
+            }
+            Some(value) => Err(ScoreError(format!(
+                "expected a number at {}, found `{}`",
+                self.pointer,
+                excerpt(&value.to_string())
+            ))),
+            None => Err(ScoreError(format!("nothing at {}", self.pointer))),
+        }
+    }
+
+    fn stream(&self) -> Option<Stream> {
+        Some(self.stream)
+    }
+}
+
+/// How much the step wrote is the score, nothing is parsed, see `--score-builtin`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Builtin {
+    /// Number of lines on stdout.
+    Lines,
+    /// Number of bytes on stdout.
+    Bytes,
+    /// Number of lines on stderr.
+    StderrLines,
+}
+
+impl Builtin {
+    fn count(&self, counts: &OutputCounts) -> u64 {
+        match self {
+            Builtin::Lines => counts.stdout.lines(),
+            Builtin::Bytes => counts.stdout.bytes(),
+            Builtin::StderrLines => counts.stderr.lines(),
+        }
+    }
+}
+
+impl fmt::Display for Builtin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Builtin::Lines => "lines",
+            Builtin::Bytes => "bytes",
+            Builtin::StderrLines => "stderr-lines",
+        })
+    }
+}
+
+impl ScoreParser for Builtin {
+    fn parse(&self, output: &Output) -> Result<Option<Score>, ScoreError> {
+        self.parse_counted(output, &OutputCounts::of(output))
+    }
+
+    fn parse_counted(
+        &self,
+        _: &Output,
+        counts: &OutputCounts,
+    ) -> Result<Option<Score>, ScoreError> {
+        Ok(Some(Score::number(self.count(counts) as f64)))
+    }
+
+    fn stream(&self) -> Option<Stream> {
+        Some(match self {
+            Builtin::Lines | Builtin::Bytes => Stream::Stdout,
+            Builtin::StderrLines => Stream::Stderr,
+        })
+    }
+}
+
+/// Parse a `--score-builtin` value: `lines`, `bytes` or `stderr-lines`.
+pub fn parse_builtin(s: &str) -> Result<Builtin, String> {
+    match s {
+        "lines" => Ok(Builtin::Lines),
+        "bytes" => Ok(Builtin::Bytes),
+        "stderr-lines" => Ok(Builtin::StderrLines),
+        _ => Err(format!(
+            "invalid count `{}`: expected lines, bytes or stderr-lines",
+            s
+        )),
+    }
+}
+
+/// At most the first 80 characters of `text`, to quote it in a message.
+fn excerpt(text: &str) -> Cow<'_, str> {
+    const SHOWN: usize = 80;
+    match text.char_indices().nth(SHOWN) {
+        Some((end, _)) => Cow::Owned(format!("{}...", &text[..end])),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Parse a `--score-json-path` value, a JSON pointer: empty or starting with `/`.
+pub fn parse_json_pointer(s: &str) -> Result<String, String> {
+    if !s.is_empty() && !s.starts_with('/') {
+        return Err(format!(
+            "invalid JSON pointer `{}`: expected it to start with /, e.g. /stats/throughput",
+            s
+        ));
+    }
+    Ok(s.to_string())
+}
+
+/// Parse a `--score-regex` value.
+pub fn parse_regex(s: &str) -> Result<Regex, String> {
+    Regex::new(s).map_err(|err| format!("invalid regex `{}`: {}", s, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::ExitStatus;
+
+    fn stdout(bytes: &[u8]) -> Output {
+        Output {
+            status: ExitStatus::default(),
+            stdout: bytes.to_vec(),
+            stderr: vec![],
+        }
+    }
+
+    fn score(parser: &dyn ScoreParser, bytes: &[u8]) -> Result<Option<Score>, ScoreError> {
+        parser.parse(&stdout(bytes))
+    }
+
+    fn regex(pattern: &str) -> RegexCapture {
+        RegexCapture {
+            stream: Stream::Stdout,
+            regex: parse_regex(pattern).unwrap(),
+            unit: ScoreUnit::None,
+        }
+    }
+
+    #[test]
+    fn last_line() {
+        let parser = LastLine::default();
+        assert_eq!(score(&parser, b"3"), Ok(Some(Score::number(3.0))));
+        assert_eq!(
+            score(&parser, b"compiling\nrunning\n12.5\n"),
+            Ok(Some(Score::number(12.5)))
+        );
+        // Trailing blank lines and whitespace around the number are skipped.
+        assert_eq!(
+            score(&parser, b"log\n  -4 \t\n\n  \n"),
+            Ok(Some(Score::number(-4.0)))
+        );
+        assert!(score(&parser, b"1\ndone\n").is_err());
+        assert!(score(&parser, b"\n \n").is_err());
+    }
+
+    #[test]
+    fn regex_capture() {
+        // The first capture group, if any.
+        let grouped = regex(r"score: (\S+)");
+        assert_eq!(
+            score(&grouped, b"time: 3\nscore: 0.25 \nscore: 7\n"),
+            Ok(Some(Score::number(0.25)))
+        );
+        assert!(score(&grouped, b"score: high\n").is_err());
+        assert!(score(&grouped, b"no score\n").is_err());
+        // The whole match otherwise.
+        let whole = regex(r"[0-9]+\.[0-9]+");
+        assert_eq!(
+            score(&whole, b"took 1.5 of 2.5\n"),
+            Ok(Some(Score::number(1.5)))
+        );
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        // Scores may follow binary output, which is replaced rather than rejected.
+        let output = b"\xff\xfe\n0.5\n";
+        assert_eq!(
+            score(&LastLine::default(), output),
+            Ok(Some(Score::number(0.5)))
+        );
+        assert_eq!(
+            score(&regex(r"(?m)^[0-9.]+$"), output),
+            Ok(Some(Score::number(0.5)))
+        );
+        assert_eq!(
+            score(&regex(r"\n(\S+)"), output),
+            Ok(Some(Score::number(0.5)))
+        );
+    }
+
+    #[test]
+    fn whole_output() {
+        let parser = WholeOutput::default();
+        assert_eq!(score(&parser, b" 42\n\n"), Ok(Some(Score::number(42.0))));
+        assert!(score(&parser, b"42\n43\n").is_err());
+    }
+}
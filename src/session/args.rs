@@ -0,0 +1,1287 @@
+//! The arguments of `tester run`, and what the session makes of them.
+
+use super::Fatal;
+use crate::{
+    checker::Checker,
+    command::{self, Step},
+    compare::{DiffOptions, Tolerance},
+    dashboard, determinism,
+    environment::{self, Environment},
+    generator::Generator,
+    isolation::{self, Isolation},
+    metadata::{self, SessionMetadata, Tag},
+    objective::{self, Objective},
+    provenance::{self, BinaryStamp},
+    rerun::PreviousFailures,
+    runs::{self, Shard},
+    score::{
+        self, Builtin, JsonPointer, LastLine, RegexCapture, ScoreMissing, ScoreParser, ScoreUnit,
+        Stream, WholeOutput,
+    },
+    shuffle::Jitter,
+    sink::{self, Sink, SinkTarget},
+    stats::FailureRate,
+    sweep::{self, Sweep},
+    units, RunConfig, SessionReport, TimeoutFactor,
+};
+use clap::{ArgAction, ArgGroup, Args};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    env, fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// How `--from-stdin` turns the lines it reads into commands.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum StdinLines {
+    /// Every line is appended to the arguments of every step.
+    Args,
+    /// Every line is a command, split into words like an --exec value.
+    Commands,
+}
+
+/// How the runs are shown as they finish, see `--status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum StatusDisplay {
+    /// The progress bar of --progress.
+    Bar,
+    /// One character per run.
+    Dots,
+}
+
+fn parse_status_display(s: &str) -> Result<StatusDisplay, String> {
+    match s {
+        "bar" => Ok(StatusDisplay::Bar),
+        "dots" => Ok(StatusDisplay::Dots),
+        _ => Err("expected bar or dots".to_string()),
+    }
+}
+
+fn parse_stdin_lines(s: &str) -> Result<StdinLines, String> {
+    match s {
+        "args" => Ok(StdinLines::Args),
+        "commands" => Ok(StdinLines::Commands),
+        _ => Err(format!("invalid mode `{}`: expected args or commands", s)),
+    }
+}
+
+/// How often `--interim-every` prints an interim summary.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) enum Cadence {
+    /// Whenever this long passed since the last one.
+    Time(Duration),
+    /// Whenever another this many runs finished.
+    Runs(u64),
+}
+
+impl fmt::Display for Cadence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cadence::Time(every) => write!(f, "{}", units::format_duration(*every)),
+            Cadence::Runs(every) => write!(f, "{} runs", units::format_integer(*every)),
+        }
+    }
+}
+
+/// Parse an `--interim-every`: a duration like `10m`, or a number of runs like `500runs`.
+fn parse_cadence(s: &str) -> Result<Cadence, String> {
+    let trimmed = s.trim();
+    if let Some(count) = trimmed
+        .strip_suffix("runs")
+        .or_else(|| trimmed.strip_suffix("run"))
+    {
+        let count = count.trim().parse::<u64>().map_err(|_| {
+            format!(
+                "invalid count `{}`: expected a number of runs like 500runs",
+                s
+            )
+        })?;
+        if count == 0 {
+            return Err("the number of runs must be positive".to_string());
+        }
+        return Ok(Cadence::Runs(count));
+    }
+    let every = units::parse_duration(s)?;
+    if every.is_zero() {
+        return Err("the duration must be positive".to_string());
+    }
+    Ok(Cadence::Time(every))
+}
+
+/// Parse a `--compare-float-epsilon`, finite and not negative.
+fn parse_epsilon(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(epsilon) if epsilon.is_finite() && epsilon >= 0.0 => Ok(epsilon),
+        Ok(_) => Err("the epsilon must be a finite number at least 0".to_string()),
+        Err(err) => Err(format!("invalid number `{}`: {}", s, err)),
+    }
+}
+
+fn parse_factor(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(factor) if factor.is_finite() && factor > 0.0 => Ok(factor),
+        Ok(_) => Err("the factor must be a finite number above 0".to_string()),
+        Err(err) => Err(format!("invalid number `{}`: {}", s, err)),
+    }
+}
+
+fn parse_abort_pattern(s: &str) -> Result<regex::bytes::Regex, String> {
+    regex::bytes::Regex::new(s).map_err(|err| format!("invalid regex `{}`: {}", s, err))
+}
+
+/// Parse a `--score-label`, which is saved as a tag value so must fit on a line.
+fn parse_score_label(s: &str) -> Result<String, String> {
+    if s.trim().is_empty() {
+        return Err("empty label".to_string());
+    }
+    if s.chars().any(char::is_control) {
+        return Err("the label must not have control characters".to_string());
+    }
+    Ok(s.to_string())
+}
+
+/// The arguments of `tester run`.
+#[derive(Args, Debug)]
+#[command(group(ArgGroup::new("saved_outputs").args(["generator", "check_deterministic"]).multiple(true)))]
+pub struct RunArgs {
+    /// Disable the execution's stdout and stderr
+    #[arg(long, default_value_t = false)]
+    pub(super) silent: bool,
+    /// Append the stdout of the commands to FILE instead of printing it, drop it with `null`,
+    /// or let the steps write to ours directly with `inherit`, which leaves nothing to score
+    /// or compare
+    #[arg(long, value_name = "FILE|null|inherit", value_parser = sink::parse_target, conflicts_with = "silent")]
+    pub(super) stdout_to: Option<SinkTarget>,
+    /// Likewise for the stderr of the commands
+    #[arg(long, value_name = "FILE|null|inherit", value_parser = sink::parse_target, conflicts_with = "silent")]
+    pub(super) stderr_to: Option<SinkTarget>,
+    /// Send the stderr of the steps into the same pipe as their stdout, so their output keeps
+    /// the order the two were written in; it is all stdout from then on, shown, saved, scored
+    /// and compared together, and piped to the next step. Unix only
+    #[arg(long, default_value_t = false, conflicts_with_all = ["stderr_to", "deterministic_stderr"])]
+    pub(super) merge_output: bool,
+    /// Keep at most SIZE bytes of each output of a command, half from its start and half from
+    /// its end, 0 for no limit; scores and --check-deterministic see only what was kept, the
+    /// output compared by --compare-with or --check is kept in full
+    #[arg(long, value_name = "SIZE", default_value = "16MiB", value_parser = units::parse_size)]
+    pub(super) max_output_bytes: usize,
+    /// Calculate the average score of every run
+    #[arg(short, long, default_value_t = false)]
+    pub(super) score: bool,
+    /// Take the score from this step (counting from 1) instead of the last one
+    #[arg(long, value_name = "STEP", requires = "score")]
+    pub(super) score_step: Option<usize>,
+    /// Read the score from this stream of the scored step: stdout or stderr
+    #[arg(long, value_name = "STREAM", default_value = "stdout", value_parser = score::parse_stream, requires = "score")]
+    pub(super) score_stream: Stream,
+    /// Take the score from the last non-blank line instead of the whole output
+    #[arg(long, default_value_t = false, requires = "score")]
+    pub(super) score_last_line: bool,
+    /// Take the score from the first match of REGEX (its first group if it has one)
+    #[arg(long, value_name = "REGEX", value_parser = score::parse_regex, requires = "score", conflicts_with = "score_last_line")]
+    pub(super) score_regex: Option<Regex>,
+    /// Take the score from the number at POINTER (RFC 6901, e.g. /stats/throughput) in the JSON
+    /// output, or in its last line that looks like JSON
+    #[arg(
+        long,
+        value_name = "POINTER",
+        value_parser = score::parse_json_pointer,
+        requires = "score",
+        conflicts_with_all = ["score_last_line", "score_regex"]
+    )]
+    pub(super) score_json_path: Option<String>,
+    /// Accept a time unit (ns, us, µs, ms, s) or a magnitude (k, M, G) after the score, taking
+    /// times in UNIT: auto for seconds, ms, us or ns; none for plain numbers only
+    #[arg(long, value_name = "UNIT", default_value = "none", value_parser = score::parse_unit, requires = "score")]
+    pub(super) score_unit: ScoreUnit,
+    /// What to do with a passed run whose score cannot be read: fail or ignore
+    #[arg(long, value_name = "POLICY", default_value = "fail", value_parser = score::parse_score_missing, requires = "score")]
+    pub(super) score_missing: ScoreMissing,
+    /// Take as the score how much the scored step wrote, without parsing: the lines or bytes
+    /// of its stdout or the lines of its stderr (lines, bytes, stderr-lines), counting what
+    /// --max-output-bytes leaves out too
+    #[arg(
+        long,
+        value_name = "COUNT",
+        value_parser = score::parse_builtin,
+        requires = "score",
+        conflicts_with_all = ["score_stream", "score_last_line", "score_regex", "score_json_path", "score_unit"]
+    )]
+    pub(super) score_builtin: Option<Builtin>,
+    /// Report how long runs (and every step of them) took on average
+    #[arg(long, default_value_t = false)]
+    pub(super) time: bool,
+    /// Before the runs, time a few hundred runs of a command doing nothing, set up like the
+    /// steps, to estimate how much of every run is spent spawning commands
+    #[arg(long, default_value_t = false, conflicts_with = "from_stdin")]
+    pub(super) calibrate_spawn: bool,
+    /// Also show the average time of the runs without the estimated spawn overhead
+    #[arg(long, default_value_t = false, requires = "calibrate_spawn")]
+    pub(super) subtract_spawn: bool,
+    /// Show progress bar
+    #[arg(long, default_value_t = false)]
+    pub(super) progress: bool,
+    /// Show the runs as they finish: `bar` is --progress, `dots` prints one character per run
+    /// like pytest, `.` passed, `F` failed, `T` timed out, `s` skipped, each line ending with
+    /// the count so far. With dots the output of the steps is not shown, unless --stdout-to
+    /// or --stderr-to send it somewhere
+    #[arg(
+        long,
+        value_name = "bar|dots",
+        value_parser = parse_status_display,
+        conflicts_with_all = ["progress", "tui", "from_stdin"]
+    )]
+    pub(super) status: Option<StatusDisplay>,
+    /// Print a one-line interim summary every DURATION, e.g. 10m, or every COUNT runs, e.g.
+    /// 500runs, for sessions whose output goes to a log
+    #[arg(
+        long,
+        value_name = "DURATION|COUNTruns",
+        value_parser = parse_cadence,
+        conflicts_with = "from_stdin"
+    )]
+    pub(super) interim_every: Option<Cadence>,
+    #[command(flatten)]
+    pub(super) number_format: NumberFormat,
+    /// Number of times to run the commands
+    #[arg(short = 'n', value_parser = units::parse_count)]
+    pub(super) times: u64,
+    /// Number of threads
+    #[arg(short = 'p', default_value_t = 1)]
+    pub(super) threads: u8,
+    /// Kill a run that is still going after DURATION and count it as failed
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub(super) timeout: Option<Duration>,
+    /// Kill a run still going after X times the median duration of the last passed runs, and
+    /// count it as timed out relative to the median; with --timeout too, the smaller limit
+    /// applies
+    #[arg(long, value_name = "X", value_parser = parse_factor)]
+    pub(super) timeout_factor: Option<f64>,
+    /// Runs that must have passed before --timeout-factor applies; until then only --timeout
+    /// does, if given
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 20,
+        requires = "timeout_factor"
+    )]
+    pub(super) timeout_factor_min_runs: u64,
+    /// Point out a run still going after DURATION, which may be hung, once in a note and in
+    /// the progress bar as long as it runs; unlike --timeout it is not killed
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub(super) warn_after: Option<Duration>,
+    /// Kill a step once it used DURATION of CPU time, rounded up to whole seconds, and count
+    /// the run as over the CPU limit (Unix only)
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub(super) cpu_limit: Option<Duration>,
+    /// Run every step in namespaces of its own, with no network (net) or with an empty /tmp
+    /// of its own (tmp), e.g. `net,tmp`; needs unprivileged user namespaces unless root (Linux
+    /// only)
+    #[arg(long, value_name = "WHAT", value_parser = isolation::parse_isolation)]
+    pub(super) isolate: Option<Isolation>,
+    /// Print what would be executed for the first RUNS runs without spawning anything
+    #[arg(long, value_name = "RUNS", num_args = 0..=1, require_equals = true, default_missing_value = "5")]
+    pub(super) dry_run: Option<u64>,
+    /// Print more of tester's own messages (-v: per-run results and warnings, -vv: commands and scheduling)
+    #[arg(short, long, action = ArgAction::Count)]
+    pub(super) verbose: u8,
+    /// Print less of tester's own messages (-q: errors only)
+    #[arg(short, long, action = ArgAction::Count, conflicts_with = "verbose")]
+    pub(super) quiet: u8,
+    /// Print tester's own messages without colors or charts, also implied by $NO_COLOR
+    #[arg(long, default_value_t = false)]
+    pub(super) no_color: bool,
+    /// Save the result of every run to FILE as CSV, appending when resuming
+    #[arg(long, value_name = "FILE")]
+    pub(super) csv: Option<PathBuf>,
+    /// Save the result of every run to FILE as JSON lines as soon as it finished, appending
+    /// when resuming, and end with the summary of the session, with a `type` of `summary`.
+    /// With `-` or no FILE they go to stdout, where the output of the commands then no longer
+    /// goes; write `--jsonl -` before the command
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "-")]
+    pub(super) jsonl: Option<PathBuf>,
+    /// Save the summary of the session to FILE as JSON
+    #[arg(long, value_name = "FILE")]
+    pub(super) json: Option<PathBuf>,
+    /// Add the session and the result of every run to the SQLite database FILE, in tables
+    /// `sessions` and `runs`, creating it if needed; needs the sqlite3 command
+    #[cfg(feature = "sqlite")]
+    #[arg(long, value_name = "FILE")]
+    pub(super) sqlite: Option<PathBuf>,
+    /// Save a self-contained HTML report of the session to FILE
+    #[arg(long, value_name = "FILE")]
+    pub(super) html: Option<PathBuf>,
+    /// Say in the summary whether the failures are consistent with a true failure rate of
+    /// RATE or less, e.g. 0.5%
+    #[arg(long, value_name = "RATE", value_parser = units::parse_rate)]
+    pub(super) target_failure_rate: Option<f64>,
+    /// Exit successfully when the failures are consistent with --target-failure-rate instead
+    /// of only without failures
+    #[arg(long, default_value_t = false, requires = "target_failure_rate")]
+    pub(super) judge_by_target: bool,
+    /// Exit successfully unless more than RATE of the runs failed, e.g. 2%, instead of only
+    /// without failures
+    #[arg(
+        long,
+        value_name = "RATE",
+        value_parser = units::parse_rate,
+        conflicts_with_all = ["judge_by_target", "allowed_failures"]
+    )]
+    pub(super) allowed_failure_rate: Option<f64>,
+    /// Exit successfully unless more than N runs failed, instead of only without failures
+    #[arg(long, value_name = "N", conflicts_with = "judge_by_target")]
+    pub(super) allowed_failures: Option<u64>,
+    /// Fail the session for --allowed-failure-rate or --allowed-failures only when the
+    /// failure rate is above the allowed one with 95% confidence, not just observed above it
+    #[arg(long, default_value_t = false)]
+    pub(super) require_confidence: bool,
+    /// Compare the session with the summary of an earlier one saved with --json, exiting with
+    /// 5 if it regressed
+    #[arg(long, value_name = "FILE")]
+    pub(super) baseline: Option<PathBuf>,
+    /// How much worse than --baseline is a regression: relatively for the mean score and
+    /// duration, in percentage points for the failure rate
+    #[arg(long, value_name = "RATE", default_value = "5%", value_parser = units::parse_rate, requires = "baseline")]
+    pub(super) regression_threshold: f64,
+    /// Whether higher scores are better (maximize) or lower ones (minimize), for the best run,
+    /// --baseline and --sweep
+    #[arg(long, value_name = "DIRECTION", default_value = "maximize", value_parser = objective::parse_objective)]
+    pub(super) objective: Objective,
+    /// The same as --objective minimize
+    #[arg(long, default_value_t = false, conflicts_with = "objective")]
+    pub(super) lower_score_is_better: bool,
+    /// Number of slowest runs to list with --time
+    #[arg(long, value_name = "K", default_value_t = 10, requires = "time")]
+    pub(super) slowest: usize,
+    /// Chart when every thread was busy after the summary, and save the interval of every
+    /// run in the --json summary, to tune -p
+    #[arg(long, default_value_t = false, conflicts_with = "from_stdin")]
+    pub(super) timeline: bool,
+    /// Periodically checkpoint completed runs to PATH so the session can be resumed
+    #[arg(long, value_name = "PATH")]
+    pub(super) state_file: Option<PathBuf>,
+    /// Resume the session checkpointed in --state-file, running only the remaining runs
+    #[arg(long, default_value_t = false, requires = "state_file")]
+    pub(super) resume: bool,
+    /// Time between two checkpoints of --state-file
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "10s",
+        value_parser = units::parse_duration,
+        requires = "state_file"
+    )]
+    pub(super) checkpoint_interval: Duration,
+    /// Skip the checks run before spawning anything (executable exists, paths are writable)
+    #[arg(long, default_value_t = false)]
+    pub(super) no_preflight: bool,
+    /// Do not record the path, size, modification time and SHA-256 hash of the executables in
+    /// saved reports, e.g. to skip hashing a huge binary
+    #[arg(long, default_value_t = false)]
+    pub(super) no_stamp: bool,
+    /// Name this session in the summary, the progress bar and saved reports
+    #[arg(long)]
+    pub(super) label: Option<String>,
+    /// Attach a key=value pair to saved reports, as a column of --csv; can be repeated. Keys
+    /// starting with `tester.` are reserved for the version, start time and hostname tester adds
+    #[arg(long, value_name = "KEY=VALUE", value_parser = metadata::parse_tag)]
+    pub(super) tag: Vec<Tag>,
+    /// The tags tester adds, set when the session starts.
+    #[arg(skip)]
+    pub(super) builtin_tags: Vec<Tag>,
+    /// The executables of the steps, set when the session starts.
+    #[arg(skip)]
+    pub(super) binaries: Vec<BinaryStamp>,
+    /// Run COMMAND at the start of every iteration and feed its stdout to the stdin of every step
+    #[arg(long = "gen", value_name = "COMMAND", value_parser = command::parse_step)]
+    pub(super) generator: Option<Step>,
+    /// Pass the generator of run i the seed N + i, replacing {seed} in its arguments or appended
+    /// after them
+    #[arg(long, value_name = "N", requires = "generator")]
+    pub(super) gen_seed_start: Option<u64>,
+    /// Keep at most SIZE bytes of every generated input for --save-failures; the input is
+    /// streamed to the first step as it is generated, and kept in full when later steps,
+    /// --compare-with or --check need it
+    #[arg(
+        long,
+        value_name = "SIZE",
+        default_value = "64MiB",
+        value_parser = units::parse_size,
+        requires = "generator"
+    )]
+    pub(super) capture_input_limit: usize,
+    /// Also run the reference solution COMMAND on every generated input and fail runs whose
+    /// last step's stdout differs from it; implies --until-fail
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step, requires = "generator")]
+    pub(super) compare_with: Option<Step>,
+    /// Compare with --compare-with ignoring whitespace at the start and end of lines, how much
+    /// of it separates words, and blank lines at the end
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "compare_with",
+        conflicts_with = "check"
+    )]
+    pub(super) compare_ignore_ws: bool,
+    /// Compare with --compare-with regardless of case
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "compare_with",
+        conflicts_with = "check"
+    )]
+    pub(super) compare_ignore_case: bool,
+    /// Compare with --compare-with letting numbers differ by at most E, absolutely or relative
+    /// to the larger one, e.g. 1e-6
+    #[arg(
+        long,
+        value_name = "E",
+        value_parser = parse_epsilon,
+        requires = "compare_with",
+        conflicts_with = "check"
+    )]
+    pub(super) compare_float_epsilon: Option<f64>,
+    /// Judge the output of every run with the checker COMMAND, which fails to reject it; {input},
+    /// {output} and {answer} in its arguments are replaced by the paths of files holding the
+    /// input, the last step's stdout and the --compare-with stdout, or those paths are appended;
+    /// implies --until-fail
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step, requires = "generator")]
+    pub(super) check: Option<Step>,
+    /// Unchanged lines shown around every change in the diff of a wrong answer
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub(super) diff_context: usize,
+    /// Show at most N hunks of the diff of a wrong answer; saved diffs have them all
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    pub(super) diff_hunks: usize,
+    /// Print the diff, validator output and kept files of only the first N failed runs, or
+    /// with --dedup-failures of the first N kinds of failures; the rest print a count now and
+    /// then, and are still saved and written to --csv and --jsonl
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub(super) max_failure_output: u64,
+    /// Run COMMAND on every generated input before the steps; if it fails, the run has invalid
+    /// input and counts neither as passed nor failed
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step, requires = "generator")]
+    pub(super) validate: Option<Step>,
+    /// Stop the session once more than N runs had invalid input
+    #[arg(long, value_name = "N", requires = "validate")]
+    pub(super) max_invalid: Option<u64>,
+    /// Count runs with a step exiting with one of these codes as skipped, neither passed nor
+    /// failed, e.g. 77 for a missing prerequisite as in automake; timeouts and signals are
+    /// never skips
+    #[arg(long, value_name = "CODES", value_delimiter = ',')]
+    pub(super) skip_exit_codes: Vec<i32>,
+    /// Stop the session once more than N runs were skipped
+    #[arg(long, value_name = "N", requires = "skip_exit_codes")]
+    pub(super) max_skipped: Option<u64>,
+    /// Also save the inputs rejected by --validate, as invalid_input_<index>.txt
+    #[arg(long, default_value_t = false, requires = "validate")]
+    pub(super) save_invalid: bool,
+    /// Hash the stdout of every passed run and report how many distinct outputs there were
+    #[arg(long, default_value_t = false)]
+    pub(super) check_deterministic: bool,
+    /// Hash stderr too with --check-deterministic
+    #[arg(long, default_value_t = false, requires = "check_deterministic")]
+    pub(super) deterministic_stderr: bool,
+    /// Remove the matches of REGEX from the output before hashing it, e.g. timestamps
+    #[arg(long, value_name = "REGEX", value_parser = determinism::parse_ignore, requires = "check_deterministic")]
+    pub(super) deterministic_ignore: Option<regex::bytes::Regex>,
+    /// Stop starting runs after the first failed one
+    #[arg(long, alias = "fail-fast", default_value_t = false)]
+    pub(super) until_fail: bool,
+    /// Fail a run whose steps print a match of REGEX and stop the session right away, killing
+    /// the runs still going, e.g. on `ERROR: AddressSanitizer`; repeat for several patterns.
+    /// Only captured output is matched, not what goes to --stdout-to inherit
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = parse_abort_pattern,
+        conflicts_with = "from_stdin"
+    )]
+    pub(super) abort_on: Vec<regex::bytes::Regex>,
+    /// Run only the share I of M of the runs, counted from 0: those whose index modulo M is I,
+    /// e.g. 2/4 on the third of four machines. Run indices, seeds and placeholders stay those
+    /// of the whole session, so the shards together run exactly what one session would
+    #[arg(long, value_name = "I/M", value_parser = runs::parse_shard, conflicts_with = "from_stdin")]
+    pub(super) shard: Option<Shard>,
+    /// Run again only the runs that failed in an earlier session, e.g. after a fix: those of
+    /// a --json summary, a --csv or --jsonl file, or a --save-failures directory. Generated
+    /// inputs come from the seeds of --gen-seed-start, else from the inputs saved in the
+    /// directory; give the same -n, --gen and --sweep as before
+    #[arg(long, value_name = "FILE|DIR", conflicts_with_all = ["shard", "resume", "from_stdin"])]
+    pub(super) rerun_failures: Option<PathBuf>,
+    /// The runs of --rerun-failures, read when the session starts.
+    #[arg(skip)]
+    pub(super) previous_failures: Option<PreviousFailures>,
+    /// Run the runs in a random order, reproducible with the same SEED; without one, a new
+    /// seed is picked and shown in the summary
+    #[arg(long, value_name = "SEED", num_args = 0..=1, require_equals = true)]
+    pub(super) shuffle: Option<Option<u64>>,
+    /// Wait a random time of up to DURATION before every run, to vary how parallel runs
+    /// interleave and shake out races; not part of the times of the runs
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub(super) jitter: Option<Duration>,
+    /// Draw the waits of --jitter from SEED, to wait the same again; without it, a new seed is
+    /// picked and shown in the summary
+    #[arg(long, value_name = "SEED", requires = "jitter")]
+    pub(super) jitter_seed: Option<u64>,
+    /// Keep running after failures with --compare-with or --check
+    #[arg(long, default_value_t = false, conflicts_with = "until_fail")]
+    pub(super) keep_going: bool,
+    /// After the session, minimize the input of the first failed run by trying reductions that
+    /// still fail, saving the result as failing_input_<index>.min.txt
+    #[arg(long, default_value_t = false, requires = "generator")]
+    pub(super) shrink: bool,
+    /// Time --shrink may take at most
+    #[arg(
+        long,
+        value_name = "DURATION",
+        default_value = "1m",
+        value_parser = units::parse_duration,
+        requires = "shrink"
+    )]
+    pub(super) shrink_budget: Duration,
+    /// Save the generated input of failed runs to DIR/failing_input_<index>.txt instead of the
+    /// current directory, what timed out runs printed until killed to
+    /// DIR/timeout_output_<index>.txt, and with --check-deterministic a sample of every
+    /// distinct output
+    #[arg(long, value_name = "DIR", requires = "saved_outputs")]
+    pub(super) save_failures: Option<PathBuf>,
+    /// Save the input of only the first failed run of every kind of failure
+    #[arg(long, default_value_t = false, requires = "generator")]
+    pub(super) dedup_failures: bool,
+    /// Run COMMAND once before the first run, e.g. to build the solution or start a server;
+    /// if it fails, nothing is run
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step)]
+    pub(super) before_all: Option<Step>,
+    /// Run COMMAND once after the last run, even when the session was interrupted or stopped
+    /// early
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step)]
+    pub(super) after_all: Option<Step>,
+    /// Run COMMAND once the session ended, however it ended, with the JSON summary on its
+    /// stdin and TESTER_STATUS, TESTER_RUNS, TESTER_FAILURES and TESTER_AVG_SCORE set, e.g. to
+    /// send a notification; a failure is only warned about
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step)]
+    pub(super) notify_cmd: Option<Step>,
+    /// Show a desktop notification once the session ended, ringing the terminal bell instead
+    /// without a desktop
+    #[arg(long, default_value_t = false)]
+    pub(super) notify: bool,
+    /// With --notify, also notify as soon as the first run fails
+    #[arg(long, default_value_t = false, requires = "notify")]
+    pub(super) notify_on_failure: bool,
+    /// Once the session ended, watch the executables of the steps and the --watch-path files
+    /// and run a new session whenever they change; ctrl-c stops the session, a second one
+    /// stops watching
+    #[arg(long, default_value_t = false, conflicts_with_all = ["from_stdin", "resume", "state_file", "dry_run"])]
+    pub(super) watch: bool,
+    /// With --watch, also watch PATH, e.g. a file the steps read
+    #[arg(long, value_name = "PATH", requires = "watch")]
+    pub(super) watch_path: Vec<PathBuf>,
+    /// Read single keys from the terminal during the session: `s` prints the runs so far, the
+    /// failure rate, the mean score and the time left, `q` stops like ctrl-c. The steps get an
+    /// empty stdin instead of the terminal
+    #[arg(long, default_value_t = false, conflicts_with = "from_stdin")]
+    pub(super) keys: bool,
+    /// Show a full-screen dashboard instead of the output of the steps: the progress, the
+    /// runs, times and scores so far, how busy every thread was, and the last failures and
+    /// messages, which are printed again afterwards. With a terminal on stdin, `p` pauses or
+    /// resumes, `q` stops like ctrl-c and `w` writes --state-file. Falls back to --progress
+    /// when stderr is not a terminal of at least 60x16
+    #[arg(long, default_value_t = false, conflicts_with_all = ["from_stdin", "keys"])]
+    pub(super) tui: bool,
+    /// Which session of --watch this is, from 1.
+    #[arg(skip)]
+    pub(super) generation: Option<u64>,
+    /// Run COMMAND at the start of every run; if it fails, the run counts as setup failed,
+    /// neither passed nor failed, and only --teardown runs
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step)]
+    pub(super) setup: Option<Step>,
+    /// Run COMMAND at the end of every run, whether it passed or not; a failure is only warned
+    /// about
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step)]
+    pub(super) teardown: Option<Step>,
+    /// Leave the temporary directory of failed runs behind and print its path; every run gets
+    /// a fresh one in $TESTER_TMPDIR, also substituted for {tmpdir} in the arguments
+    #[arg(long, default_value_t = false)]
+    pub(super) keep_failed_tmpdirs: bool,
+    /// Set the variable NAME to VALUE in the environment of every command; can be repeated
+    #[arg(long = "env", value_name = "NAME=VALUE", value_parser = environment::parse_var)]
+    pub(super) env_vars: Vec<(String, String)>,
+    /// Expand $NAME and ${NAME} in the commands and their arguments with the value of the
+    /// variable in the environment they run in, $$ standing for a $; an undefined variable is
+    /// an error
+    #[arg(long, default_value_t = false)]
+    pub(super) expand_env: bool,
+    /// With --expand-env, expand undefined variables to nothing
+    #[arg(long, default_value_t = false, requires = "expand_env")]
+    pub(super) allow_undefined_env: bool,
+    /// Run every command with only PATH, the TESTER_* variables, --passthrough-env and --env
+    /// in its environment instead of inheriting tester's
+    #[arg(long, default_value_t = false)]
+    pub(super) clean_env: bool,
+    /// The PATH of --clean-env, instead of tester's own
+    #[arg(long, value_name = "PATH", requires = "clean_env")]
+    pub(super) clean_env_path: Option<String>,
+    /// Keep the inherited variables whose name matches PATTERN with --clean-env, where * and ?
+    /// are wildcards, e.g. 'LC_*'; can be repeated
+    #[arg(long, value_name = "PATTERN", requires = "clean_env")]
+    pub(super) passthrough_env: Vec<String>,
+    /// Run every step under COMMAND, which gets the step's program and arguments appended and
+    /// whose exit status decides whether the step passed, e.g. "valgrind --error-exitcode=1";
+    /// repeat to nest wrappers, the first one outermost
+    #[arg(long, value_name = "COMMAND", value_parser = command::parse_step)]
+    pub(super) wrapper: Vec<Step>,
+    /// Run COMMAND as a step of every iteration; repeat to run several commands in order,
+    /// the iteration fails at the first one that fails
+    #[arg(
+        long = "exec",
+        value_name = "COMMAND",
+        value_parser = command::parse_step,
+        conflicts_with = "exec"
+    )]
+    pub(super) exec_steps: Vec<Step>,
+    /// Read lines from stdin as they come and run the command -n times for each, with the line
+    /// appended to the arguments of every step; with MODE `commands` every line is the command
+    /// to run instead. The lines are spread over the -p workers
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "args",
+        value_parser = parse_stdin_lines,
+        conflicts_with_all = [
+            "resume", "state_file", "dry_run", "generator", "check_deterministic", "csv", "jsonl",
+            "json", "html", "baseline", "progress"
+        ]
+    )]
+    pub(super) from_stdin: Option<StdinLines>,
+    /// Run the command -n times for every VALUE, with {NAME} in the commands and their
+    /// arguments replaced by it, and compare the values; repeat to run every combination of
+    /// the values of several sweeps. The runs of all values share the -p workers
+    #[arg(
+        long,
+        value_name = "NAME=VALUE,...",
+        value_parser = sweep::parse,
+        conflicts_with_all = ["from_stdin", "resume", "state_file", "check_deterministic"]
+    )]
+    pub(super) sweep: Vec<Sweep>,
+    /// Run a --sweep of more than 256 combinations
+    #[arg(long, default_value_t = false)]
+    pub(super) yes: bool,
+    #[arg(required_unless_present_any = ["exec_steps", "from_stdin"])]
+    pub(super) exec: Option<String>,
+    pub(super) exec_args: Vec<String>,
+}
+
+impl RunArgs {
+    /// How much is logged: positive with `--verbose`, negative with `--quiet`.
+    pub fn verbosity(&self) -> i8 {
+        self.verbose as i8 - self.quiet as i8
+    }
+
+    /// How the numbers are shown.
+    pub fn number_format(&self) -> &NumberFormat {
+        &self.number_format
+    }
+
+    pub(super) fn metadata(&self) -> SessionMetadata {
+        SessionMetadata {
+            label: self.label.clone(),
+            tags: self.tag.iter().chain(&self.builtin_tags).cloned().collect(),
+            objective: self.objective(),
+            generation: self.generation,
+            shuffle_seed: self.shuffle.flatten(),
+            jitter_seed: self.jitter.and(self.jitter_seed),
+            binaries: self.binaries.clone(),
+            shard: self.shard,
+        }
+    }
+
+    /// Set what is recorded of a session starting now: the built-in tags and, when a report
+    /// is saved or compared, the stamps of the executables.
+    pub(super) fn start_session(&mut self) {
+        let recorded = !self.output_files().is_empty() || self.baseline.is_some();
+        self.binaries = if recorded && !self.no_stamp && self.dry_run.is_none() {
+            self.executables()
+                .iter()
+                .filter_map(|path| {
+                    provenance::stamp(path)
+                        .map_err(|err| {
+                            notice!("#tester cannot stamp {}: {}", path.display(), err);
+                        })
+                        .ok()
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+        let mut tags = metadata::builtin_tags(SystemTime::now());
+        if let Some(label) = &self.number_format.score_label {
+            tags.push(metadata::builtin_tag("score_label", label.clone()));
+        }
+        if !self.binaries.is_empty() {
+            let hashes = self.binaries.iter().map(|binary| binary.sha256.as_str());
+            tags.push(metadata::builtin_tag(
+                "binary_sha256",
+                hashes.collect::<Vec<_>>().join(" "),
+            ));
+        }
+        self.builtin_tags = tags;
+    }
+
+    /// The executables of the steps that can be found, each once.
+    pub(super) fn executables(&self) -> Vec<PathBuf> {
+        let mut paths = vec![];
+        let executables = self.unwrapped_steps().into_iter().filter_map(|step| {
+            command::resolve_executable(&step.exec).or_else(|| {
+                (step.exec.contains(std::path::MAIN_SEPARATOR) || step.exec.contains('/'))
+                    .then(|| PathBuf::from(&step.exec))
+            })
+        });
+        for path in executables {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// What --watch looks at: the executables of the steps and the --watch-path files.
+    pub(super) fn watched_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.executables();
+        for path in &self.watch_path {
+            if !paths.contains(path) {
+                paths.push(path.clone());
+            }
+        }
+        paths
+    }
+
+    pub(super) fn objective(&self) -> Objective {
+        if self.lower_score_is_better {
+            Objective::Minimize
+        } else {
+            self.objective
+        }
+    }
+
+    pub(super) fn generator(&self) -> Option<Generator> {
+        Some(Generator {
+            step: self.generator.clone()?,
+            seed_start: self.gen_seed_start,
+        })
+    }
+
+    pub(super) fn checker(&self) -> Option<Checker> {
+        Some(Checker {
+            step: self.check.clone()?,
+            keep_failed: self.save_failures.is_some(),
+        })
+    }
+
+    pub(super) fn tolerance(&self) -> Tolerance {
+        Tolerance {
+            ignore_whitespace: self.compare_ignore_ws,
+            ignore_case: self.compare_ignore_case,
+            float_epsilon: self.compare_float_epsilon,
+        }
+    }
+
+    /// Where failing inputs are saved, if anywhere.
+    pub(super) fn failures_dir(&self) -> Option<&Path> {
+        self.generator.as_ref()?;
+        Some(self.save_failures.as_deref().unwrap_or(Path::new(".")))
+    }
+
+    pub(super) fn environment(&self) -> Environment {
+        if self.clean_env {
+            Environment::clean(
+                self.clean_env_path.clone(),
+                &self.passthrough_env,
+                &self.env_vars,
+            )
+        } else {
+            Environment {
+                clear: false,
+                vars: self.env_vars.clone(),
+            }
+        }
+    }
+
+    /// Expand the variables in every command with --expand-env, see [`environment::expand`].
+    pub(super) fn expand_env(&mut self) -> Result<(), String> {
+        if !self.expand_env {
+            return Ok(());
+        }
+        let env = self.environment();
+        let expand = |s: &mut String| -> Result<(), String> {
+            *s = environment::expand(s, &env, self.allow_undefined_env)?;
+            Ok(())
+        };
+        let expand_step = |step: &mut Step| -> Result<(), String> {
+            expand(&mut step.exec)?;
+            step.args.iter_mut().try_for_each(expand)
+        };
+        if let Some(exec) = &mut self.exec {
+            expand(exec)?;
+        }
+        self.exec_args.iter_mut().try_for_each(expand)?;
+        self.exec_steps
+            .iter_mut()
+            .chain(&mut self.wrapper)
+            .chain(&mut self.generator)
+            .chain(&mut self.compare_with)
+            .chain(&mut self.check)
+            .chain(&mut self.validate)
+            .chain(&mut self.before_all)
+            .chain(&mut self.after_all)
+            .chain(&mut self.notify_cmd)
+            .chain(&mut self.setup)
+            .chain(&mut self.teardown)
+            .try_for_each(expand_step)
+    }
+
+    /// The steps as given, without --wrapper.
+    pub(super) fn unwrapped_steps(&self) -> Vec<Step> {
+        match &self.exec {
+            Some(exec) => vec![Step {
+                exec: exec.clone(),
+                args: self.exec_args.clone(),
+            }],
+            None => self.exec_steps.clone(),
+        }
+    }
+
+    /// Commands spawned by every run, for --calibrate-spawn.
+    pub(super) fn commands_per_run(&self) -> u64 {
+        let others = [
+            &self.generator,
+            &self.validate,
+            &self.compare_with,
+            &self.check,
+            &self.setup,
+            &self.teardown,
+        ];
+        (self.steps().len() + others.iter().filter(|step| step.is_some()).count()) as u64
+    }
+
+    /// Runs of the whole session: -n for every combination of values of --sweep.
+    pub(super) fn total_runs(&self) -> u64 {
+        let combinations = sweep::combination_count(&self.sweep).unwrap_or(u64::MAX);
+        self.times.saturating_mul(combinations)
+    }
+
+    /// Whether run `index` of --rerun-failures can be run as it was: its input is generated
+    /// from its seed or was saved, or it has no input to generate.
+    pub(super) fn reproducible(&self, index: u64) -> bool {
+        let saved = |failures: &PreviousFailures| failures.inputs.contains_key(&index);
+        match (&self.generator, &self.previous_failures) {
+            (Some(_), Some(failures)) => self.gen_seed_start.is_some() || saved(failures),
+            _ => true,
+        }
+    }
+
+    /// The runs of --rerun-failures that can be run again, `None` without it.
+    pub(super) fn rerun_runs(&self) -> Option<Vec<u64>> {
+        let failures = self.previous_failures.as_ref()?;
+        Some(
+            failures
+                .runs
+                .iter()
+                .copied()
+                .filter(|&index| self.reproducible(index))
+                .collect(),
+        )
+    }
+
+    /// Number of runs this session runs: those of its --shard of the total, or those of
+    /// --rerun-failures.
+    pub(super) fn shard_runs(&self) -> u64 {
+        if let Some(failures) = &self.previous_failures {
+            let runs = failures.runs.iter();
+            return runs.filter(|&&index| self.reproducible(index)).count() as u64;
+        }
+        let total = self.total_runs();
+        self.shard.map_or(total, |shard| shard.len(total))
+    }
+
+    /// The steps as run, inside every --wrapper, the first one outermost.
+    pub(super) fn steps(&self) -> Vec<Step> {
+        self.unwrapped_steps()
+            .into_iter()
+            .map(|step| self.wrapped(step))
+            .collect()
+    }
+
+    /// `step` inside every --wrapper.
+    pub(super) fn wrapped(&self, step: Step) -> Step {
+        self.wrapper
+            .iter()
+            .rev()
+            .fold(step, |step, wrapper| step.wrapped_in(wrapper))
+    }
+
+    /// The steps run for `line` read with --from-stdin.
+    pub(super) fn steps_for_line(&self, mode: StdinLines, line: &str) -> Result<Vec<Step>, String> {
+        let steps = match mode {
+            StdinLines::Args => self
+                .unwrapped_steps()
+                .into_iter()
+                .map(|mut step| {
+                    step.args.push(line.to_string());
+                    step
+                })
+                .collect(),
+            StdinLines::Commands => vec![command::parse_step(line)?],
+        };
+        Ok(steps.into_iter().map(|step| self.wrapped(step)).collect())
+    }
+
+    /// Index of the step scores are read from.
+    pub(super) fn score_step(&self) -> Result<usize, String> {
+        let steps = match self.from_stdin {
+            Some(StdinLines::Commands) => 1,
+            _ => self.steps().len(),
+        };
+        match self.score_step {
+            None => Ok(steps - 1),
+            Some(step) if (1..=steps).contains(&step) => Ok(step - 1),
+            Some(step) => Err(format!(
+                "--score-step {} is out of range, there are {} steps",
+                step, steps
+            )),
+        }
+    }
+
+    /// Whether tester's own messages may use colors and charts.
+    pub(super) fn colors(&self) -> bool {
+        !self.no_color && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+    }
+
+    /// Whether the records of --jsonl go to stdout.
+    pub(super) fn jsonl_to_stdout(&self) -> bool {
+        self.jsonl.as_deref() == Some(Path::new("-"))
+    }
+
+    /// The file --jsonl writes to, unless it is stdout.
+    pub(super) fn jsonl_file(&self) -> Option<&Path> {
+        self.jsonl.as_deref().filter(|_| !self.jsonl_to_stdout())
+    }
+
+    /// Every file the session writes, with what it is for.
+    pub(super) fn output_files(&self) -> Vec<(&'static str, &Path)> {
+        [
+            ("state file", self.state_file.as_deref()),
+            ("CSV file", self.csv.as_deref()),
+            ("JSONL file", self.jsonl_file()),
+            ("JSON summary", self.json.as_deref()),
+            ("HTML report", self.html.as_deref()),
+            #[cfg(feature = "sqlite")]
+            ("SQLite database", self.sqlite.as_deref()),
+        ]
+        .into_iter()
+        .filter_map(|(what, path)| Some((what, path?)))
+        .collect()
+    }
+
+    /// The flag making the score read from stderr, when it is.
+    pub(super) fn stderr_score_flag(&self) -> &'static str {
+        match self.score_builtin {
+            Some(_) => "--score-builtin stderr-lines",
+            None => "--score-stream stderr",
+        }
+    }
+
+    /// The stream the score is read from.
+    pub(super) fn scored_stream(&self) -> Stream {
+        self.score_parser().stream().unwrap_or(self.score_stream)
+    }
+
+    /// The score parser selected by the --score-* flags.
+    pub(super) fn score_parser(&self) -> Arc<dyn ScoreParser> {
+        if let Some(builtin) = self.score_builtin {
+            return Arc::new(builtin);
+        }
+        let (stream, unit) = (self.score_stream, self.score_unit);
+        match (&self.score_regex, &self.score_json_path) {
+            (Some(regex), _) => Arc::new(RegexCapture {
+                stream,
+                regex: regex.clone(),
+                unit,
+            }),
+            (None, Some(pointer)) => Arc::new(JsonPointer {
+                stream,
+                pointer: pointer.clone(),
+                unit,
+            }),
+            (None, None) if self.score_last_line => Arc::new(LastLine { stream, unit }),
+            (None, None) => Arc::new(WholeOutput { stream, unit }),
+        }
+    }
+
+    /// The whole seconds of CPU time a step may use, the resolution of the limit.
+    pub(super) fn cpu_limit_seconds(&self) -> Option<u64> {
+        self.cpu_limit
+            .map(|limit| limit.as_secs() + u64::from(limit.subsec_nanos() > 0))
+    }
+
+    /// Whether the session failed: any run failed, or with --judge-by-target more than the
+    /// target failure rate allows, or more than --allowed-failure-rate or --allowed-failures.
+    pub(super) fn failed(&self, report: &SessionReport) -> bool {
+        if let Some(allowed) = self.allowed_rate(report.run_times) {
+            let rate = FailureRate::of(report.fail_times, report.run_times);
+            return match (self.require_confidence, self.allowed_failures) {
+                (true, _) => rate.is_some_and(|rate| !rate.allows(allowed)),
+                (false, Some(count)) => report.fail_times > count,
+                (false, None) => rate.is_some_and(|rate| rate.observed > allowed),
+            };
+        }
+        match self.target_failure_rate {
+            Some(target) if self.judge_by_target => {
+                FailureRate::of(report.fail_times, report.run_times)
+                    .is_some_and(|rate| !rate.allows(target))
+            }
+            _ => report.fail_times > 0,
+        }
+    }
+
+    /// The failure rate --allowed-failure-rate or --allowed-failures allows over `run_times`
+    /// runs, if given.
+    pub(super) fn allowed_rate(&self, run_times: u64) -> Option<f64> {
+        self.allowed_failure_rate.or_else(|| {
+            let count = self.allowed_failures?;
+            Some((count as f64 / run_times.max(1) as f64).min(1.0))
+        })
+    }
+
+    /// Why `--stdout-to inherit` or `--stderr-to inherit` cannot work with the other options,
+    /// if it cannot: everything reading the stream needs it captured.
+    pub(super) fn uncaptured_conflict(&self) -> Option<String> {
+        let inherited = |target: &Option<SinkTarget>| target == &Some(SinkTarget::Inherit);
+        let mut readers = vec![];
+        if inherited(&self.stdout_to) {
+            if self.score && self.scored_stream() == Stream::Stdout {
+                readers.push(("--stdout-to", "--score"));
+            }
+            if self.compare_with.is_some() {
+                readers.push(("--stdout-to", "--compare-with"));
+            }
+            if self.check.is_some() {
+                readers.push(("--stdout-to", "--check"));
+            }
+            if self.check_deterministic {
+                readers.push(("--stdout-to", "--check-deterministic"));
+            }
+        }
+        if inherited(&self.stderr_to) {
+            if self.score && self.scored_stream() == Stream::Stderr {
+                readers.push(("--stderr-to", self.stderr_score_flag()));
+            }
+            if self.deterministic_stderr {
+                readers.push(("--stderr-to", "--deterministic-stderr"));
+            }
+        }
+        readers.first().map(|(flag, reader)| {
+            format!(
+                "{} inherit leaves the output uncaptured, which {} needs",
+                flag, reader
+            )
+        })
+    }
+
+    /// Whether --tui is given and stderr is a terminal large enough for the dashboard.
+    pub(super) fn tui_active(&self) -> bool {
+        let term = console::Term::stderr();
+        let (rows, columns) = term.size();
+        let (min_columns, min_rows) = dashboard::MIN_SIZE;
+        self.tui && term.is_term() && columns as usize >= min_columns && rows as usize >= min_rows
+    }
+
+    /// Open the files given with `--stdout-to` and `--stderr-to`, once if they are the same.
+    pub(super) fn sinks(&self) -> Result<(Sink, Sink), Fatal> {
+        let open = |target: &Option<SinkTarget>| match target {
+            Some(target) => Sink::open(target).map_err(Fatal::Config),
+            // It would be drawn over, or tear the lines of dots.
+            None if self.tui_active() || self.status == Some(StatusDisplay::Dots) => Ok(Sink::Null),
+            None => Ok(Sink::default()),
+        };
+        let stdout = match &self.stdout_to {
+            // The records are read from there.
+            None if self.jsonl_to_stdout() => Sink::Null,
+            target => open(target)?,
+        };
+        let stderr = match (&self.stdout_to, &self.stderr_to) {
+            (Some(SinkTarget::File(out)), Some(SinkTarget::File(err))) if out == err => {
+                stdout.clone()
+            }
+            _ => open(&self.stderr_to)?,
+        };
+        Ok((stdout, stderr))
+    }
+
+    pub(super) fn run_config(
+        &self,
+        score_step: usize,
+        tmpdir: Option<&Path>,
+        (stdout, stderr): (Sink, Sink),
+    ) -> RunConfig {
+        RunConfig {
+            steps: self.steps(),
+            times: self.total_runs(),
+            threads: self.threads,
+            timeout: self.timeout,
+            timeout_factor: self.timeout_factor.map(|factor| TimeoutFactor {
+                factor,
+                min_runs: self.timeout_factor_min_runs,
+            }),
+            variants: match &self.sweep[..] {
+                [] => vec![],
+                sweeps => sweep::variants(sweeps, &self.steps()),
+            },
+            shuffle: self.shuffle.flatten(),
+            jitter: self
+                .jitter
+                .zip(self.jitter_seed)
+                .map(|(max, seed)| Jitter { max, seed }),
+            shard: self.shard,
+            only_runs: self.rerun_runs().map(Arc::from),
+            // Seeds give the whole input, saved inputs may be cut.
+            given_inputs: match (&self.previous_failures, self.gen_seed_start) {
+                (Some(failures), None) => failures.inputs.clone(),
+                _ => HashMap::new(),
+            },
+            cpu_limit: self.cpu_limit_seconds(),
+            isolation: self.isolate.unwrap_or_default(),
+            score_step: self.score.then_some(score_step),
+            score_parser: self.score_parser(),
+            score_missing: self.score_missing,
+            generator: self.generator(),
+            capture_input_limit: Some(self.capture_input_limit),
+            validator: self.validate.clone(),
+            max_invalid: self.max_invalid,
+            skip_exit_codes: self.skip_exit_codes.clone(),
+            max_skipped: self.max_skipped,
+            reference: self.compare_with.clone(),
+            checker: self.checker(),
+            tolerance: self.tolerance(),
+            setup: self.setup.clone(),
+            teardown: self.teardown.clone(),
+            env: self.environment(),
+            tmpdir: tmpdir.map(Path::to_path_buf),
+            keep_failed_tmpdirs: self.keep_failed_tmpdirs,
+            diff: DiffOptions {
+                context: self.diff_context,
+                max_hunks: Some(self.diff_hunks),
+            },
+            max_failure_output: Some(self.max_failure_output),
+            dedup_failure_output: self.dedup_failures,
+            until_fail: self.until_fail
+                || ((self.compare_with.is_some() || self.check.is_some()) && !self.keep_going),
+            abort_on: self.abort_on.clone(),
+            merge_output: self.merge_output,
+            max_output: Some(self.max_output_bytes).filter(|&limit| limit > 0),
+            // Our stdin is the lines, or the keys.
+            null_stdin: self.from_stdin.is_some() || self.keys || self.tui,
+            show_output: !self.silent,
+            stdout,
+            stderr,
+            // The observers that read the outputs of the runs.
+            observed_output: self.check_deterministic
+                || self.failures_dir().is_some()
+                || self.html.is_some()
+                || self.jsonl.is_some(),
+        }
+    }
+}
+
+/// How the numbers shown to people look; saved reports always keep full precision.
+#[derive(Args, Debug)]
+pub struct NumberFormat {
+    /// Show scores and times with N decimal places instead of as many as needed
+    #[arg(long, value_name = "N")]
+    pub(super) precision: Option<usize>,
+    /// Group the digits of scores, times and counts by thousands, e.g. 12,345
+    #[arg(long, default_value_t = false)]
+    pub(super) thousands_separators: bool,
+    /// What scores are measured in, e.g. "MB/s", shown after every score and saved as the
+    /// tester.score_label tag
+    #[arg(long, value_name = "STRING", value_parser = parse_score_label)]
+    pub(super) score_label: Option<String>,
+}
+
+impl NumberFormat {
+    /// Show the numbers this way from now on.
+    pub fn apply(&self) {
+        units::set_precision(self.precision);
+        units::set_thousands_separators(self.thousands_separators);
+        units::set_score_label(self.score_label.as_deref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StdioChoice::{self, Inherit, Null, Piped};
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: RunArgs,
+    }
+
+    /// How the stdout and stderr of the only step are set up with `flags`.
+    fn stdio(flags: &[&str]) -> (StdioChoice, StdioChoice) {
+        let args = ["tester", "-n", "1"]
+            .iter()
+            .chain(flags)
+            .chain(&["--", "true"]);
+        let args = Cli::parse_from(args).args;
+        let config = args.run_config(0, None, args.sinks().unwrap());
+        (
+            config.stdio(0, 1, Stream::Stdout),
+            config.stdio(0, 1, Stream::Stderr),
+        )
+    }
+
+    #[test]
+    fn captured_streams() {
+        let cases: [(&[&str], (StdioChoice, StdioChoice)); 12] = [
+            (&[], (Piped, Piped)),
+            (&["--quiet"], (Piped, Piped)),
+            (&["--silent"], (Null, Null)),
+            (&["--silent", "--quiet"], (Null, Null)),
+            (&["--silent", "--max-output-bytes", "0"], (Null, Null)),
+            (&["--max-output-bytes", "0"], (Piped, Piped)),
+            // Failing inputs are saved with the outputs of the run.
+            (
+                &[
+                    "--silent",
+                    "--gen",
+                    "echo 1",
+                    "--capture-input-limit",
+                    "1KiB",
+                ],
+                (Piped, Piped),
+            ),
+            (&["--silent", "--score"], (Piped, Null)),
+            (
+                &["--silent", "--score", "--score-stream", "stderr"],
+                (Null, Piped),
+            ),
+            (&["--silent", "--timeout", "1s"], (Piped, Piped)),
+            (&["--stdout-to", "inherit"], (Inherit, Piped)),
+            (
+                &["--stdout-to", "null", "--stderr-to", "inherit"],
+                (Null, Inherit),
+            ),
+        ];
+        for (flags, streams) in cases {
+            assert_eq!(stdio(flags), streams, "{:?}", flags);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! `--dry-run`: what a session would run, without running it.
+
+use super::{Fatal, RunArgs};
+use crate::{runs::RunSet, tmpdir, units, RunConfig};
+use std::env;
+
+pub(super) fn dry_run(
+    cli: &RunArgs,
+    config: &RunConfig,
+    pending: &RunSet,
+    shown_runs: u64,
+    preflight_passed: bool,
+) -> Result<(), Fatal> {
+    let cwd = env::current_dir()
+        .map(|dir| dir.display().to_string())
+        .unwrap_or_else(|_| "(unknown)".to_string());
+    let runs_per_thread = config.schedule(pending);
+    println!(
+        "#tester dry run{}: {} runs on {} threads",
+        cli.metadata().label_suffix(),
+        units::format_count(pending.len()),
+        runs_per_thread.len()
+    );
+    if let Some(seed) = config.shuffle {
+        println!("#tester dry run: runs shuffled with seed {}", seed);
+    }
+    if let Some(jitter) = config.jitter {
+        println!(
+            "#tester dry run: runs delayed by up to {} with seed {}",
+            units::format_duration(jitter.max),
+            jitter.seed
+        );
+    }
+    if let Some(shard) = config.shard {
+        println!(
+            "#tester dry run: shard {}, the runs whose index modulo {} is {}, of {}",
+            shard,
+            shard.count,
+            shard.index,
+            units::format_count(config.times)
+        );
+    }
+    if !config.variants.is_empty() {
+        println!(
+            "#tester dry run: {} runs of each of {} combinations of --sweep:",
+            units::format_count(cli.times),
+            units::format_count(config.variants.len() as u64)
+        );
+        for variant in &config.variants {
+            println!("  {}", variant.label);
+        }
+    }
+    if let Some(state_file) = &cli.state_file {
+        println!(
+            "#tester dry run: checkpoint to {} every {}",
+            state_file.display(),
+            units::format_duration(cli.checkpoint_interval)
+        );
+    }
+    if let Some(cadence) = cli.interim_every {
+        println!("#tester dry run: interim summary every {}", cadence);
+    }
+    let hooks = [("before", &cli.before_all), ("after", &cli.after_all)];
+    for (when, step) in hooks {
+        if let Some(step) = step {
+            println!(
+                "#tester dry run: {} all runs: {}",
+                when,
+                step.command_line()
+            );
+        }
+    }
+    if let Some(step) = &cli.notify_cmd {
+        println!("#tester dry run: notify with: {}", step.command_line());
+    }
+    if let Some(timeout) = cli.timeout {
+        println!(
+            "#tester dry run: runs are killed after {}",
+            units::format_duration(timeout)
+        );
+    }
+    if let Some(factor) = cli.timeout_factor {
+        println!(
+            "#tester dry run: runs are killed after {} times the median duration, once {} passed",
+            units::format_number(factor),
+            units::format_integer(cli.timeout_factor_min_runs)
+        );
+    }
+    if let Some(seconds) = cli.cpu_limit_seconds() {
+        println!(
+            "#tester dry run: steps are killed after {}s of CPU time",
+            seconds
+        );
+    }
+
+    let shown = runs_per_thread
+        .iter()
+        .enumerate()
+        .flat_map(|(thread_index, runs)| {
+            runs.iter()
+                .map(move |position| (config.run_index(position), thread_index))
+        })
+        .take(shown_runs as usize);
+    let generator = cli.generator();
+    for (index, thread_index) in shown {
+        println!("run {} (thread {})", index, thread_index);
+        if let Some(variant) = config.variant_of(index) {
+            println!("  sweep:   {}", variant.label);
+        }
+        let steps = config.steps_of(index);
+        if let Some(generator) = &generator {
+            println!("  gen:     {}", generator.step_for(index).command_line());
+        }
+        if steps.len() == 1 {
+            println!("  command: {}", steps[0].command_line());
+        } else {
+            for (step_index, step) in steps.iter().enumerate() {
+                println!("  step {}:  {}", step_index + 1, step.command_line());
+            }
+        }
+        if let Some(setup) = &cli.setup {
+            println!("  setup:   {}", setup.command_line());
+        }
+        if let Some(teardown) = &cli.teardown {
+            println!("  teardown: {}", teardown.command_line());
+        }
+        println!("  cwd:     {}", cwd);
+        let env = cli.environment();
+        println!(
+            "  env:     {}",
+            if env.clear {
+                "cleared, then"
+            } else {
+                "inherited, plus"
+            }
+        );
+        for (name, value) in &env.vars {
+            println!("    {}={}", name, value);
+        }
+        println!("    {}=(a fresh directory)", tmpdir::TMPDIR_ENV);
+        if let Some(reference) = &cli.compare_with {
+            match cli.tolerance() {
+                tolerance if tolerance.is_exact() => {
+                    println!("  compare: {}", reference.command_line())
+                }
+                tolerance => println!("  compare: {} ({})", reference.command_line(), tolerance),
+            }
+        }
+        if let Some(checker) = &cli.check {
+            println!("  check:   {}", checker.command_line());
+        }
+        if generator.is_some() {
+            println!("  stdin:   the generator's stdout");
+        } else if cli.keys {
+            println!("  stdin:   none, the terminal is read by --keys");
+        } else {
+            println!("  stdin:   inherited");
+        }
+        if cli.merge_output {
+            println!("  stderr:  merged into stdout");
+        }
+        if let Some(isolation) = cli.isolate {
+            println!("  isolated: {}", isolation);
+        }
+    }
+    if pending.len() > shown_runs {
+        println!(
+            "#tester dry run: {} more runs not shown",
+            units::format_count(pending.len() - shown_runs)
+        );
+    }
+
+    if !preflight_passed {
+        return Err(Fatal::Config("preflight failed".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,569 @@
+//! One session: `--before-all`, the runs and `--after-all`, then what it found and the
+//! notifications of how it ended.
+
+use super::{
+    checkpoint,
+    dry_run::dry_run,
+    lines::run_lines,
+    load_resumed_state, monitor,
+    observers::{FirstFailure, Observers},
+    pipeline_description,
+    print::{
+        print_abort, print_baseline_comparison, print_reruns, print_sparkline, print_summary,
+        print_suppressed, print_sweep, print_timeline, print_variants,
+    },
+    Fatal, Interruption, Outcome, RunArgs,
+};
+use crate::{
+    baseline::BaselineComparison,
+    command::Step,
+    dashboard,
+    environment::Environment,
+    isolation::Isolation,
+    notify, process,
+    runs::RunSet,
+    shrink,
+    summary::{SessionSummary, SpawnOverhead},
+    tmpdir::SessionDir,
+    units, RunConfig, RunStatus, SessionReport, TestRunner,
+};
+use std::{
+    io::Write,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Runs of the command doing nothing timed by --calibrate-spawn.
+const SPAWN_SAMPLES: usize = 300;
+
+/// Tell whether the runs failed only because of --isolate, by rerunning the `first_failure`
+/// without it if there was one with a complete generated input.
+fn print_isolation_check(
+    runner: &TestRunner,
+    isolation: Isolation,
+    first_failure: Option<&FirstFailure>,
+) -> Result<(), Fatal> {
+    let Some((index, status, input)) = first_failure else {
+        notice!(
+            "#tester the failed runs ran under --isolate {}, rerun them without it to tell whether they fail only isolated",
+            isolation
+        );
+        return Ok(());
+    };
+    let unisolated = TestRunner::new(RunConfig {
+        isolation: Isolation::default(),
+        ..runner.config().clone()
+    });
+    let result = unisolated
+        .rerun(*index, input.clone())
+        .map_err(|err| Fatal::Runtime(err.to_string()))?;
+    if result.status == RunStatus::Passed {
+        notice!(
+            "#tester run {} passed when rerun without --isolate {}: it fails only isolated",
+            index,
+            isolation
+        );
+    } else {
+        notice!(
+            "#tester run {} {} too when rerun without --isolate {}, the isolation is not to blame",
+            index,
+            if result.status == *status {
+                "fails".to_string()
+            } else {
+                format!("ends {}", result.status)
+            },
+            isolation
+        );
+    }
+    Ok(())
+}
+
+/// Minimize the failing `input` of run `index` and save the result next to the original.
+fn shrink(
+    cli: &RunArgs,
+    runner: &TestRunner,
+    index: u64,
+    status: RunStatus,
+    input: &[u8],
+    interrupted: &AtomicBool,
+) -> Result<(), Fatal> {
+    notice!(
+        "#tester shrinking the input of run {} ({} bytes) for at most {}",
+        index,
+        input.len(),
+        units::format_duration(cli.shrink_budget)
+    );
+    let deadline = Instant::now() + cli.shrink_budget;
+    let shrunk = shrink::shrink(input, deadline, |candidate| {
+        if interrupted.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        let result = runner.rerun(index, Arc::from(candidate))?;
+        Ok::<_, crate::Error>(result.status == status)
+    })
+    .map_err(|err| Fatal::Runtime(err.to_string()))?;
+
+    let dir = cli.failures_dir().expect("--shrink requires --gen");
+    let path = dir.join(format!("failing_input_{}.min.txt", index));
+    if let Err(err) = std::fs::write(&path, &shrunk.input) {
+        warn!("#tester cannot save {}: {}", path.display(), err);
+        return Ok(());
+    }
+    let stopped = if interrupted.load(Ordering::Relaxed) {
+        ", interrupted"
+    } else if shrunk.out_of_time {
+        ", stopped by --shrink-budget"
+    } else {
+        ""
+    };
+    notice!(
+        "#tester shrank the input of run {} from {} to {} bytes in {} reduction steps ({} tried{}), saved to {}",
+        index,
+        input.len(),
+        shrunk.input.len(),
+        shrunk.steps,
+        shrunk.trials,
+        stopped,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Run one session, or show what it would run with --dry-run.
+pub(super) fn run_generation(
+    cli_args: &RunArgs,
+    score_step: usize,
+    baseline: Option<&SessionSummary>,
+    preflight_passed: bool,
+) -> Result<Outcome, Fatal> {
+    // Removed when dropped, however the session ends.
+    let session_dir = match cli_args.dry_run {
+        Some(_) => None,
+        None => Some(
+            SessionDir::create(cli_args.keep_failed_tmpdirs).map_err(|err| {
+                Fatal::Config(format!("cannot create a temporary directory: {}", err))
+            })?,
+        ),
+    };
+    // A dry run creates no files.
+    let sinks = match cli_args.dry_run {
+        Some(_) => Default::default(),
+        None => cli_args.sinks()?,
+    };
+    let mut runner = TestRunner::new(cli_args.run_config(
+        score_step,
+        session_dir.as_ref().map(SessionDir::path),
+        sinks,
+    ));
+    if cli_args.resume {
+        runner = runner.resume_from(load_resumed_state(cli_args)?.snapshot);
+    }
+
+    let pending = runner.pending();
+    if let Some(shown_runs) = cli_args.dry_run {
+        dry_run(
+            cli_args,
+            runner.config(),
+            &pending,
+            shown_runs,
+            preflight_passed,
+        )?;
+        return Ok(Outcome::Passed);
+    }
+
+    let (result, summary) = run_with_hooks(cli_args, runner, &pending, baseline);
+    if let Some(step) = &cli_args.notify_cmd {
+        run_notify_cmd(cli_args, step, &result, summary.as_ref());
+    }
+    if cli_args.notify {
+        let title = match session_status(&result) {
+            "passed" | "failed" => "tester finished",
+            "interrupted" => "tester interrupted",
+            "regression" => "tester found a regression",
+            _ => "tester stopped",
+        };
+        let body = match (&result, &summary) {
+            (_, Some(summary)) => {
+                let report = &summary.report;
+                let mut body = format!(
+                    "{} failures in {} runs",
+                    units::format_integer(report.fail_times),
+                    units::format_integer(report.run_times)
+                );
+                if let (true, Some(mean)) = (cli_args.score, report.scores.mean()) {
+                    body += &format!(", avg {}", units::format_score(mean));
+                }
+                body
+            }
+            (Err(fatal), None) => fatal.to_string(),
+            (Ok(_), None) => String::new(),
+        };
+        desktop_notify(title, &body);
+    }
+    result
+}
+
+/// Show a `--notify` notification, falling back to the terminal bell.
+pub(super) fn desktop_notify(title: &str, body: &str) {
+    if let Err(err) = notify::send(title, body) {
+        eprint!("\x07");
+        notice!(
+            "#tester cannot show a desktop notification, rang the bell instead: {}",
+            err
+        );
+    }
+}
+
+/// Run the `--before-all` or `--after-all` command `step` to completion, showing its output
+/// unless `quiet`. It runs in a process group of its own like the runs, interrupted by what
+/// ctrl-c does.
+fn run_once(flag: &str, step: &Step, quiet: bool, env: &Environment) -> Result<(), String> {
+    let mut command = step.command();
+    env.apply(&mut command);
+    if quiet {
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+    }
+    debug!("#tester {}: {}", flag, step.command_line());
+    let (output, _) = process::spawn(&mut command)
+        .and_then(|child| process::wait_with_deadline(child, None, None, None))
+        .map_err(|err| format!("cannot start {} `{}`: {}", flag, step.command_line(), err))?;
+    let status = output.status;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} `{}` failed ({})",
+            flag,
+            step.command_line(),
+            status
+        ))
+    }
+}
+
+/// Run the session between `--before-all` and `--after-all`, returning its summary too if it
+/// ran to the end or was interrupted.
+fn run_with_hooks(
+    cli_args: &RunArgs,
+    runner: TestRunner,
+    pending: &RunSet,
+    baseline: Option<&SessionSummary>,
+) -> (Result<Outcome, Fatal>, Option<SessionSummary>) {
+    let quiet = cli_args.quiet > 0;
+    // Until the runs handle it, a ctrl-c interrupts --before-all and skips the runs, but still
+    // leaves --after-all to clean up.
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let cancelled_share = cancelled.clone();
+    if let Err(fatal) = monitor::on_ctrl_c(move || {
+        warn!("#tester ctrl-c pressed. Terminating...");
+        cancelled_share.store(true, Ordering::Relaxed);
+        process::interrupt_running();
+    }) {
+        return (Err(fatal), None);
+    }
+    if let Some(step) = &cli_args.before_all {
+        let ran = run_once("--before-all", step, quiet, &cli_args.environment());
+        if let (Err(err), false) = (ran, cancelled.load(Ordering::Relaxed)) {
+            return (
+                Err(Fatal::Config(format!("{}, nothing was run", err))),
+                None,
+            );
+        }
+    }
+    let (result, summary) = if cancelled.load(Ordering::Relaxed) {
+        notice!("#tester interrupted by SIGINT during --before-all, nothing was run.");
+        (Ok(Outcome::Interrupted), None)
+    } else {
+        let result = match cli_args.from_stdin {
+            Some(mode) => run_lines(cli_args, mode, runner.config()),
+            None => run_session(cli_args, runner, pending, baseline),
+        };
+        match result {
+            Ok((code, summary)) => (Ok(code), Some(summary)),
+            Err(fatal) => (Err(fatal), None),
+        }
+    };
+    let Some(step) = &cli_args.after_all else {
+        return (result, summary);
+    };
+    let result = match (
+        run_once("--after-all", step, quiet, &cli_args.environment()),
+        result,
+    ) {
+        (Ok(()), result) => result,
+        (Err(err), Ok(_)) => {
+            error!("#tester {}", err);
+            Ok(Outcome::AfterAllFailed)
+        }
+        (Err(err), Err(fatal)) => {
+            error!("#tester {}", err);
+            Err(fatal)
+        }
+    };
+    (result, summary)
+}
+
+/// How a session that ended with `result` is described to `--notify-cmd`.
+fn session_status(result: &Result<Outcome, Fatal>) -> &'static str {
+    result.as_ref().map_or("error", |outcome| outcome.name())
+}
+
+/// Run the `--notify-cmd` command `step` once the session ended with `result`, passing it the
+/// `summary` if there is one. Its output is shown even with --quiet, and a failure is only
+/// reported.
+fn run_notify_cmd(
+    cli: &RunArgs,
+    step: &Step,
+    result: &Result<Outcome, Fatal>,
+    summary: Option<&SessionSummary>,
+) {
+    let mut command = step.command();
+    cli.environment().apply(&mut command);
+    command
+        .env("TESTER_STATUS", session_status(result))
+        .stdin(Stdio::piped());
+    if let Some(summary) = summary {
+        command
+            .env("TESTER_RUNS", summary.report.run_times.to_string())
+            .env("TESTER_FAILURES", summary.report.fail_times.to_string());
+        if let Some(mean) = summary.report.scores.mean() {
+            command.env("TESTER_AVG_SCORE", mean.to_string());
+        }
+    }
+    debug!("#tester --notify-cmd: {}", step.command_line());
+    let json = summary
+        .map(|summary| serde_json::to_string_pretty(summary).expect("summaries serialize") + "\n")
+        .unwrap_or_default();
+    let status = command.spawn().and_then(|mut child| {
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that does not read its stdin closes it early, which is fine.
+            let _ = stdin.write_all(json.as_bytes());
+        }
+        child.wait()
+    });
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => notice!(
+            "#tester --notify-cmd `{}` failed ({})",
+            step.command_line(),
+            status
+        ),
+        Err(err) => notice!(
+            "#tester cannot start --notify-cmd `{}`: {}",
+            step.command_line(),
+            err
+        ),
+    }
+}
+
+/// Time spawning a command doing nothing for --calibrate-spawn, and say how long it takes.
+fn calibrate_spawn(cli_args: &RunArgs, runner: &TestRunner) -> Result<Duration, Fatal> {
+    let overhead = runner
+        .calibrate_spawn(SPAWN_SAMPLES)
+        .map_err(|err| Fatal::Runtime(format!("cannot calibrate the spawn overhead: {}", err)))?;
+    let commands = cli_args.commands_per_run();
+    let mut message = format!(
+        "#tester spawn overhead: {} per command, the median of {} runs of a command doing nothing",
+        units::format_duration(overhead),
+        SPAWN_SAMPLES
+    );
+    if commands > 1 {
+        message += &format!(
+            "; {} per run of {} commands",
+            units::format_duration(overhead * commands as u32),
+            commands
+        );
+    }
+    notice!("{}.", message);
+    Ok(overhead)
+}
+
+/// Run the pending runs of `runner`, between `--before-all` and `--after-all`.
+fn run_session(
+    cli_args: &RunArgs,
+    runner: TestRunner,
+    pending: &RunSet,
+    baseline: Option<&SessionSummary>,
+) -> Result<(Outcome, SessionSummary), Fatal> {
+    if cli_args.resume {
+        notice!(
+            "#tester resuming: {} of {} runs already done, {} remaining",
+            runner.run_times(),
+            cli_args.shard_runs(),
+            pending.len()
+        );
+    }
+
+    let spawn_overhead = if cli_args.calibrate_spawn {
+        Some(calibrate_spawn(cli_args, &runner)?)
+    } else {
+        None
+    };
+
+    let tui = cli_args.tui_active();
+    if cli_args.tui && !tui {
+        let (columns, rows) = dashboard::MIN_SIZE;
+        notice!(
+            "#tester --tui needs stderr to be a terminal of at least {}x{}, showing the progress bar instead.",
+            columns,
+            rows
+        );
+    }
+    let started = Instant::now();
+    let (runner, observers) = Observers::attach(cli_args, runner, tui, started)?;
+    let runner = Arc::new(runner);
+    let (report, interrupted) = monitor::follow(cli_args, &runner, &observers, tui, started)?;
+    if let Some(state_file) = &cli_args.state_file {
+        checkpoint(cli_args, state_file, runner.snapshot());
+    }
+    let report = report.map_err(|err| Fatal::Runtime(err.to_string()))?;
+    finish_session(
+        cli_args,
+        &runner,
+        &observers,
+        report,
+        &interrupted,
+        baseline,
+        spawn_overhead,
+    )
+}
+
+/// Sum up the session of `runner` that ended with `report`, interrupted by ctrl-c if
+/// `interrupted` is set: print what it and its `observers` found, then save its summary.
+fn finish_session(
+    cli_args: &RunArgs,
+    runner: &TestRunner,
+    observers: &Observers,
+    report: SessionReport,
+    interrupted: &AtomicBool,
+    baseline: Option<&SessionSummary>,
+    spawn_overhead: Option<Duration>,
+) -> Result<(Outcome, SessionSummary), Fatal> {
+    let first_failure = observers.first_failure.lock().unwrap().take();
+    if let (true, Some((index, status, input))) = (cli_args.shrink, &first_failure) {
+        shrink(cli_args, runner, *index, *status, input, interrupted)?;
+    }
+
+    let slowest = observers.slowest.runs();
+    let best_run = observers
+        .best_run
+        .as_ref()
+        .and_then(|best_run| best_run.best());
+    let completed = runner.run_times();
+    let abort = runner.aborted();
+    if let Some(abort) = &abort {
+        print_abort(
+            &runner.config().steps_of(abort.index)[abort.step - 1],
+            abort,
+        );
+    }
+    let interruption = ((interrupted.load(Ordering::Relaxed) && completed < cli_args.shard_runs())
+        || abort.is_some())
+    .then_some(Interruption { completed, abort });
+    print_summary(
+        cli_args,
+        cli_args.shard_runs(),
+        &pipeline_description(cli_args),
+        &report,
+        &slowest,
+        best_run.as_ref(),
+        interruption.as_ref(),
+    );
+    if let (Some(isolation), true) = (cli_args.isolate, report.fail_times > 0) {
+        print_isolation_check(runner, isolation, first_failure.as_ref())?;
+    }
+    let suppressed = runner.suppressed_failure_outputs();
+    if suppressed > 0 {
+        print_suppressed(cli_args, suppressed);
+    }
+    if let (Some(failures), Some(reruns)) = (&cli_args.previous_failures, &observers.reruns) {
+        print_reruns(cli_args, failures, &reruns.lock().unwrap());
+    }
+    let spawn_overhead = spawn_overhead.map(|overhead| {
+        SpawnOverhead::new(
+            overhead.as_secs_f64(),
+            SPAWN_SAMPLES as u64,
+            cli_args.commands_per_run(),
+            report.times.mean(),
+        )
+    });
+    if let (true, Some(adjusted)) = (
+        cli_args.subtract_spawn,
+        spawn_overhead
+            .as_ref()
+            .and_then(|overhead| overhead.adjusted_mean_duration),
+    ) {
+        notice!(
+            "#tester average time adjusted for the spawn overhead: {} per run.",
+            units::format_duration(Duration::from_secs_f64(adjusted))
+        );
+    }
+    let variants = observers
+        .variant_reports
+        .as_ref()
+        .map(|variant_reports| variant_reports.reports())
+        .unwrap_or_default();
+    if !variants.is_empty() {
+        print_sweep(cli_args, &variants);
+    }
+    for (name, series) in &observers.charts {
+        print_sparkline(name, series);
+    }
+    let timeline = observers
+        .timeline
+        .as_ref()
+        .filter(|_| cli_args.timeline)
+        .map(|timeline| timeline.intervals())
+        .unwrap_or_default();
+    if cli_args.timeline {
+        print_timeline(&timeline, cli_args.threads.into());
+    }
+    if let Some(determinism) = &observers.determinism {
+        print_variants(&determinism.variants());
+    }
+    let failed = cli_args.failed(&report);
+    let comparison = baseline.map(|baseline| {
+        BaselineComparison::new(
+            &baseline.report,
+            &report,
+            cli_args.regression_threshold,
+            cli_args.objective(),
+        )
+    });
+    if let (Some(path), Some(comparison)) = (&cli_args.baseline, &comparison) {
+        print_baseline_comparison(path, comparison);
+    }
+    // A run matching --abort-on failed, however many passed.
+    let aborted = interruption
+        .as_ref()
+        .is_some_and(|interruption| interruption.abort.is_some());
+    let outcome = if interruption.is_some() && !aborted {
+        Outcome::Interrupted
+    } else if comparison
+        .as_ref()
+        .is_some_and(BaselineComparison::regressed)
+    {
+        Outcome::Regression
+    } else if failed || aborted {
+        Outcome::Failed
+    } else {
+        Outcome::Passed
+    };
+    let summary = SessionSummary {
+        metadata: cli_args.metadata(),
+        command: pipeline_description(cli_args),
+        threads: Some(cli_args.threads),
+        report,
+        best_run,
+        slowest,
+        baseline: comparison,
+        variants,
+        spawn_overhead,
+        timeline,
+    };
+    observers.export(cli_args, &summary);
+    Ok((outcome, summary))
+}
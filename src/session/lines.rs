@@ -0,0 +1,204 @@
+//! `--from-stdin`: a session of the commands made of the lines read from stdin.
+
+use super::{
+    args::StdinLines, monitor::on_ctrl_c, pipeline_description, print::print_summary, Fatal,
+    Interruption, Outcome, RunArgs,
+};
+use crate::{summary::SessionSummary, units, RunConfig, SessionReport, TestRunner};
+use std::{
+    io::{BufRead, BufReader},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+/// How the runs of one line read with --from-stdin went.
+struct LineResult {
+    /// Which non-blank line it was, counting from 1.
+    number: u64,
+    line: String,
+    /// The report of its runs and how many finished, or why they could not run.
+    result: Result<(SessionReport, u64), String>,
+}
+
+/// Run the session of --from-stdin: every line read from stdin gets a runner of its own with
+/// the steps of `template` replaced, on one of the worker threads.
+pub(super) fn run_lines(
+    cli_args: &RunArgs,
+    mode: StdinLines,
+    template: &RunConfig,
+) -> Result<(Outcome, SessionSummary), Fatal> {
+    let input = Mutex::new((0, BufReader::new(std::io::stdin()).lines()));
+    let results = Mutex::new(vec![]);
+    let running = Arc::new(Mutex::new(Vec::<Arc<TestRunner>>::new()));
+    let stop = Arc::new(AtomicBool::new(false));
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let (running, stop, interrupted) = (running.clone(), stop.clone(), interrupted.clone());
+        on_ctrl_c(move || {
+            warn!("#tester ctrl-c pressed. Terminating...");
+            interrupted.store(true, Ordering::Relaxed);
+            stop.store(true, Ordering::Relaxed);
+            let running = running.lock().unwrap();
+            for runner in running.iter() {
+                runner.cancellation_token().cancel();
+            }
+            if let Some(runner) = running.first() {
+                runner.interrupt_running();
+            }
+        })?;
+    }
+
+    // The next non-blank line and its number in stdin, blank ones counted, read only once a
+    // worker is free for it.
+    let next_line = || -> Result<Option<(u64, String)>, Fatal> {
+        let mut input = input.lock().unwrap();
+        loop {
+            let line = match input.1.next() {
+                None => return Ok(None),
+                Some(Err(err)) => {
+                    return Err(Fatal::Runtime(format!("cannot read stdin: {}", err)))
+                }
+                Some(Ok(line)) => line,
+            };
+            input.0 += 1;
+            if !line.trim().is_empty() {
+                return Ok(Some((input.0, line)));
+            }
+        }
+    };
+    let run_line = |number: u64, line: &str| -> Result<(SessionReport, u64), String> {
+        let mut config = template.clone();
+        config.steps = cli_args.steps_for_line(mode, line)?;
+        config.threads = 1;
+        // Every line numbers its runs from 0.
+        config.tmpdir = template
+            .tmpdir
+            .as_ref()
+            .map(|dir| dir.join(format!("line_{}", number)));
+        let runner = Arc::new(TestRunner::new(config));
+        running.lock().unwrap().push(runner.clone());
+        if stop.load(Ordering::Relaxed) {
+            runner.cancellation_token().cancel();
+        }
+        let report = runner.run();
+        running
+            .lock()
+            .unwrap()
+            .retain(|other| !Arc::ptr_eq(other, &runner));
+        let report = report.map_err(|err| err.to_string())?;
+        Ok((report, runner.run_times()))
+    };
+    thread::scope(|scope| {
+        let workers = (0..std::cmp::max(cli_args.threads, 1))
+            .map(|_| {
+                scope.spawn(|| -> Result<(), Fatal> {
+                    while !stop.load(Ordering::Relaxed) {
+                        let Some((number, line)) = next_line()? else {
+                            break;
+                        };
+                        let result = run_line(number, &line);
+                        match &result {
+                            Ok((report, _)) if report.fail_times > 0 && template.until_fail => {
+                                stop.store(true, Ordering::Relaxed)
+                            }
+                            Err(err) => error!("#tester line {}: {}", number, err),
+                            _ => {}
+                        }
+                        results.lock().unwrap().push(LineResult {
+                            number,
+                            line,
+                            result,
+                        });
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("worker thread panicked"))
+            .collect::<Vec<_>>()
+    })
+    .into_iter()
+    .collect::<Result<(), _>>()?;
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|result| result.number);
+    let mut total = SessionReport::new(0);
+    let mut completed = 0;
+    let mut broken = false;
+    for LineResult {
+        number,
+        line,
+        result,
+    } in &results
+    {
+        let (report, finished) = match result {
+            Ok(result) => result,
+            Err(_) => {
+                notice!("#tester line {} `{}`: could not run", number, line);
+                broken = true;
+                continue;
+            }
+        };
+        let mut outcome = if report.fail_times > 0 {
+            format!("failed {} / {}", report.fail_times, report.run_times)
+        } else {
+            format!("no failure in {} runs", report.run_times)
+        };
+        if let (true, Some(mean)) = (cli_args.score, report.scores.mean()) {
+            outcome += &format!(", average score {}", units::format_score(mean));
+        }
+        notice!("#tester line {} `{}`: {}", number, line, outcome);
+        total.merge(report);
+        completed += finished;
+    }
+
+    let requested = results.len() as u64 * cli_args.times;
+    let pipeline = match mode {
+        StdinLines::Args => format!(
+            "{} with each line of stdin appended",
+            pipeline_description(cli_args)
+        ),
+        StdinLines::Commands => "the commands read from stdin".to_string(),
+    };
+    let interruption =
+        (interrupted.load(Ordering::Relaxed) && completed < requested).then_some(Interruption {
+            completed,
+            abort: None,
+        });
+    print_summary(
+        cli_args,
+        requested,
+        &pipeline,
+        &total,
+        &[],
+        None,
+        interruption.as_ref(),
+    );
+    let exit_code = if interrupted.load(Ordering::Relaxed) {
+        Outcome::Interrupted
+    } else if broken {
+        Outcome::Broken
+    } else if cli_args.failed(&total) {
+        Outcome::Failed
+    } else {
+        Outcome::Passed
+    };
+    let summary = SessionSummary {
+        metadata: cli_args.metadata(),
+        command: pipeline,
+        threads: Some(cli_args.threads),
+        report: total,
+        best_run: None,
+        slowest: vec![],
+        baseline: None,
+        variants: vec![],
+        spawn_overhead: None,
+        timeline: vec![],
+    };
+    Ok((exit_code, summary))
+}
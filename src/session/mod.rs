@@ -0,0 +1,547 @@
+//! `tester run`: checking the arguments, then running the session they describe with its
+//! observers, displays and exports, once or again on every change with `--watch`.
+
+mod args;
+mod dry_run;
+mod generation;
+mod lines;
+mod monitor;
+mod observers;
+mod print;
+mod state;
+
+pub use args::{NumberFormat, RunArgs};
+
+use crate::{
+    checker,
+    command::{self, Step},
+    isolation,
+    provenance::BinaryStamp,
+    rerun,
+    score::Stream,
+    shuffle,
+    sink::SinkTarget,
+    summary::SessionSummary,
+    sweep, units,
+    watch::Watcher,
+    Abort, Snapshot,
+};
+use args::StdinLines;
+use generation::run_generation;
+use monitor::on_ctrl_c;
+use state::{SessionConfig, SessionState};
+use std::{
+    fmt,
+    io::IsTerminal,
+    path::Path,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// How a session ended; every way has an exit status of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every run passed, or there was nothing to run.
+    Passed,
+    /// Some runs failed, or a run matched `--abort-on`.
+    Failed,
+    /// Nothing was run: the command line or environment is unusable.
+    Unusable,
+    /// The session broke off, or some lines of `--from-stdin` could not be run.
+    Broken,
+    /// The session finished but `--after-all` failed.
+    AfterAllFailed,
+    /// The session regressed from its `--baseline`.
+    Regression,
+    /// Ctrl-c stopped the session before every run finished.
+    Interrupted,
+}
+
+impl Outcome {
+    /// How the outcome is described to `--notify-cmd`, in `TESTER_STATUS`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Outcome::Passed => "passed",
+            Outcome::Failed => "failed",
+            Outcome::Unusable | Outcome::Broken => "error",
+            Outcome::AfterAllFailed => "after_all_failed",
+            Outcome::Regression => "regression",
+            Outcome::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// Why tester gave up on a session.
+#[derive(Debug)]
+pub enum Fatal {
+    /// Nothing was run: the command line or environment is unusable.
+    Config(String),
+    /// The session could not be run to the end.
+    Runtime(String),
+}
+
+impl Fatal {
+    /// How the session ended by giving up.
+    pub fn outcome(&self) -> Outcome {
+        match self {
+            Fatal::Config(_) => Outcome::Unusable,
+            Fatal::Runtime(_) => Outcome::Broken,
+        }
+    }
+}
+
+impl fmt::Display for Fatal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fatal::Config(message) | Fatal::Runtime(message) => f.write_str(message),
+        }
+    }
+}
+
+/// Sweeps making more combinations than this need --yes.
+const MAX_COMBINATIONS: u64 = 256;
+
+/// What every run executes, e.g. `./gen | ./solution compared with ./brute`.
+fn pipeline_description(cli: &RunArgs) -> String {
+    let mut pipeline = command::pipeline_line(&cli.steps());
+    if let Some(generator) = &cli.generator {
+        pipeline = format!("{} | {}", generator.command_line(), pipeline);
+    }
+    if let Some(reference) = &cli.compare_with {
+        pipeline = format!("{} compared with {}", pipeline, reference.command_line());
+    }
+    if let Some(checker) = &cli.check {
+        pipeline = format!("{} checked by {}", pipeline, checker.command_line());
+    }
+    pipeline
+}
+
+/// What happened to the requested runs of a session stopped by ctrl-c or --abort-on.
+struct Interruption {
+    /// Runs finished, including those with invalid input or failed setup and resumed ones.
+    completed: u64,
+    /// The run that stopped the session by matching --abort-on, if it was not ctrl-c.
+    abort: Option<Abort>,
+}
+
+/// Load the summary `path` to compare the session with, warning about the differences in
+/// configuration that make the comparison doubtful.
+fn load_baseline(cli: &RunArgs, path: &Path) -> Result<SessionSummary, Fatal> {
+    let baseline = SessionSummary::load(path).map_err(Fatal::Config)?;
+    let command = pipeline_description(cli);
+    let mut differences = vec![];
+    if baseline.command != command {
+        differences.push(format!("ran {}, not {}", baseline.command, command));
+    }
+    if let Some(threads) = baseline.threads.filter(|&threads| threads != cli.threads) {
+        differences.push(format!("ran on {} threads, not {}", threads, cli.threads));
+    }
+    if (baseline.report.scores.count > 0) != cli.score {
+        differences.push(if cli.score {
+            "has no scores".to_string()
+        } else {
+            "has scores, but --score is not given".to_string()
+        });
+    }
+    for difference in differences {
+        notice!(
+            "#tester the baseline {} {}, the comparison may not mean much",
+            path.display(),
+            difference
+        );
+    }
+    let hashes = |binaries: &[BinaryStamp]| {
+        binaries
+            .iter()
+            .map(|binary| binary.sha256.clone())
+            .collect::<Vec<_>>()
+    };
+    if !cli.binaries.is_empty() && hashes(&baseline.metadata.binaries) == hashes(&cli.binaries) {
+        error!(
+            "#tester WARNING: the baseline {} ran the very same build ({}), probably comparing a build against itself",
+            path.display(),
+            cli.binaries
+                .iter()
+                .map(|binary| format!("{} sha256 {}", binary.path, binary.sha256))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(baseline)
+}
+
+fn session_config(cli: &RunArgs) -> SessionConfig {
+    SessionConfig {
+        steps: cli.steps().iter().map(Step::argv).collect(),
+        times: cli.times,
+        timeout: cli.timeout,
+        timeout_factor: cli.timeout_factor,
+        score: cli.score,
+        score_step: cli.score_step().unwrap_or_default(),
+        generator: cli.generator.as_ref().map(Step::argv),
+        gen_seed_start: cli.gen_seed_start,
+        compare_with: cli.compare_with.as_ref().map(Step::argv),
+        validate: cli.validate.as_ref().map(Step::argv),
+        check: cli.check.as_ref().map(Step::argv),
+        setup: cli.setup.as_ref().map(Step::argv),
+        teardown: cli.teardown.as_ref().map(Step::argv),
+        cpu_limit: cli.cpu_limit_seconds(),
+        shard: cli.shard,
+    }
+}
+
+/// Load the checkpoint to resume from, refusing one written for a different configuration.
+fn load_resumed_state(cli: &RunArgs) -> Result<SessionState, Fatal> {
+    let path = cli
+        .state_file
+        .as_deref()
+        .expect("--resume requires --state-file");
+    let state =
+        SessionState::load(path).map_err(|err| Fatal::Config(format!("cannot resume: {}", err)))?;
+    if let Some(mismatch) = state.mismatch(&session_config(cli)) {
+        return Err(Fatal::Config(format!(
+            "cannot resume from {}: configuration changed ({})",
+            path.display(),
+            mismatch
+        )));
+    }
+    Ok(state)
+}
+
+/// Check that files can be created in `dir` by creating and removing a probe file.
+fn check_writable_dir(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".tester-preflight-{}", process::id()));
+    std::fs::File::create(&probe)?;
+    std::fs::remove_file(&probe)
+}
+
+/// Check everything that can be verified before spawning, returning one message per problem.
+fn preflight(cli: &RunArgs) -> Vec<String> {
+    let mut problems = vec![];
+    let extra_steps = cli
+        .generator
+        .iter()
+        .chain(&cli.validate)
+        .chain(&cli.compare_with)
+        .chain(&cli.check)
+        .chain(&cli.setup)
+        .chain(&cli.teardown)
+        .chain(&cli.before_all)
+        .chain(&cli.after_all)
+        .chain(&cli.notify_cmd)
+        .cloned();
+    let steps = cli.unwrapped_steps().into_iter().chain(cli.wrapper.clone());
+    for step in extra_steps.chain(steps) {
+        if command::resolve_executable(&step.exec).is_none() {
+            problems.push(format!(
+                "executable `{}` not found or not executable",
+                step.exec
+            ));
+        }
+    }
+    for (what, path) in cli.output_files() {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if let Err(err) = check_writable_dir(dir) {
+            problems.push(format!("cannot write {} {}: {}", what, path.display(), err));
+        }
+    }
+    if let Some(dir) = cli.failures_dir() {
+        // The directory is created when the session starts, so check the part that exists.
+        let existing = dir
+            .ancestors()
+            .find(|dir| dir.exists())
+            .unwrap_or(Path::new("."));
+        let existing = if existing.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            existing
+        };
+        if let Err(err) = check_writable_dir(existing) {
+            problems.push(format!(
+                "cannot save failing inputs to {}: {}",
+                dir.display(),
+                err
+            ));
+        }
+    }
+    problems
+}
+
+/// Run the preflight checks unless `--no-preflight` is given, printing every problem found.
+/// Returns whether all checks passed.
+fn run_preflight(cli: &RunArgs) -> bool {
+    if cli.no_preflight {
+        return true;
+    }
+    let problems = preflight(cli);
+    for problem in &problems {
+        error!("#tester preflight: {}", problem);
+    }
+    problems.is_empty()
+}
+
+fn checkpoint(cli: &RunArgs, path: &Path, snapshot: Snapshot) {
+    let state = SessionState::new(session_config(cli), cli.metadata(), snapshot);
+    if let Err(err) = state.save(path) {
+        warn!(
+            "#tester failed to write state file {}: {}",
+            path.display(),
+            err
+        );
+    }
+}
+
+/// How long the watched files must stay the same before --watch starts a new session.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Check that the sweeps of `cli_args` are used, distinct and not too many, and leave room
+/// for the seeds of their runs.
+fn check_sweeps(cli_args: &RunArgs) -> Result<(), Fatal> {
+    for (index, sweep) in cli_args.sweep.iter().enumerate() {
+        if cli_args.sweep[..index]
+            .iter()
+            .any(|other| other.name == sweep.name)
+        {
+            return Err(Fatal::Config(format!(
+                "--sweep {} is given twice",
+                sweep.name
+            )));
+        }
+        if !sweep.is_used(&cli_args.steps()) {
+            return Err(Fatal::Config(format!(
+                "--sweep {}: {} appears in no command",
+                sweep.name,
+                sweep.placeholder()
+            )));
+        }
+    }
+    if !cli_args.sweep.is_empty() {
+        let combinations = sweep::combination_count(&cli_args.sweep)
+            .filter(|&count| cli_args.times.checked_mul(count).is_some())
+            .ok_or_else(|| Fatal::Config("--sweep makes too many runs to count".to_string()))?;
+        if combinations > MAX_COMBINATIONS && !cli_args.yes && cli_args.dry_run.is_none() {
+            return Err(Fatal::Config(format!(
+                "--sweep makes {} combinations, {} runs; check them with --dry-run, run them with --yes",
+                units::format_count(combinations),
+                units::format_count(cli_args.total_runs())
+            )));
+        }
+    }
+    if let Some(seed_start) = cli_args.gen_seed_start {
+        if seed_start.checked_add(cli_args.total_runs()).is_none() {
+            return Err(Fatal::Config(format!(
+                "--gen-seed-start {} leaves no room for {} seeds",
+                seed_start,
+                cli_args.total_runs()
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check the options of `cli_args` that need others, or a terminal or platform.
+fn check_options(cli_args: &RunArgs) -> Result<(), Fatal> {
+    if cli_args.keys {
+        if cfg!(not(unix)) {
+            return Err(Fatal::Config(
+                "--keys is only supported on Unix".to_string(),
+            ));
+        }
+        if !std::io::stdin().is_terminal() {
+            return Err(Fatal::Config(
+                "--keys needs a terminal on stdin".to_string(),
+            ));
+        }
+    }
+    if cli_args.require_confidence && cli_args.allowed_rate(cli_args.total_runs()).is_none() {
+        return Err(Fatal::Config(
+            "--require-confidence needs --allowed-failure-rate or --allowed-failures".to_string(),
+        ));
+    }
+    if let Some(isolation) = cli_args.isolate {
+        isolation::check(isolation).map_err(|err| {
+            Fatal::Config(format!(
+                "--isolate {}: cannot isolate the steps: {}; unprivileged user namespaces may be disabled here, e.g. by kernel.unprivileged_userns_clone or an AppArmor restriction",
+                isolation, err
+            ))
+        })?;
+    }
+    if let Some(limit) = cli_args.cpu_limit {
+        if cfg!(not(unix)) {
+            return Err(Fatal::Config(
+                "--cpu-limit is only supported on Unix".to_string(),
+            ));
+        }
+        if limit.is_zero() {
+            return Err(Fatal::Config("--cpu-limit must be positive".to_string()));
+        }
+        if limit.subsec_nanos() > 0 {
+            notice!(
+                "#tester --cpu-limit {} rounded up to {}s, CPU time is limited in whole seconds",
+                units::format_duration(limit),
+                cli_args.cpu_limit_seconds().unwrap()
+            );
+        }
+    }
+    if let (Some(checker), None) = (cli_args.checker(), &cli_args.compare_with) {
+        if checker.uses_answer() {
+            return Err(Fatal::Config(format!(
+                "--check uses {}, which needs --compare-with",
+                checker::ANSWER_PLACEHOLDER
+            )));
+        }
+    }
+    if let Some(conflict) = cli_args.uncaptured_conflict() {
+        return Err(Fatal::Config(conflict));
+    }
+    if cli_args.jsonl_to_stdout() && cli_args.stdout_to == Some(SinkTarget::Inherit) {
+        return Err(Fatal::Config(
+            "--stdout-to inherit would mix the output of the steps into the records of --jsonl \
+             on stdout"
+                .to_string(),
+        ));
+    }
+    if cli_args.merge_output {
+        if cfg!(not(unix)) {
+            return Err(Fatal::Config(
+                "--merge-output is only supported on Unix".to_string(),
+            ));
+        }
+        if cli_args.score && cli_args.scored_stream() == Stream::Stderr {
+            return Err(Fatal::Config(format!(
+                "--merge-output leaves no stderr for {} to read",
+                cli_args.stderr_score_flag()
+            )));
+        }
+    }
+    match (cli_args.from_stdin, cli_args.unwrapped_steps().is_empty()) {
+        (Some(StdinLines::Args), true) => {
+            return Err(Fatal::Config(
+                "--from-stdin needs a command to append the lines to".to_string(),
+            ))
+        }
+        (Some(StdinLines::Commands), false) => return Err(Fatal::Config(
+            "--from-stdin=commands reads the commands from stdin, give none on the command line"
+                .to_string(),
+        )),
+        _ => {}
+    }
+    #[cfg(feature = "sqlite")]
+    if cli_args.sqlite.is_some() {
+        crate::sqlite::check_shell().map_err(|err| Fatal::Config(format!("--sqlite: {}", err)))?;
+    }
+    Ok(())
+}
+
+/// `tester run`: check `cli_args`, then run the session, or a dry run of it, and with
+/// `--watch` run it again whenever the watched files change.
+pub fn run(mut cli_args: RunArgs) -> Result<Outcome, Fatal> {
+    if let Some(seed @ None) = &mut cli_args.shuffle {
+        *seed = Some(shuffle::new_seed());
+    }
+    if let (Some(_), None) = (cli_args.jitter, cli_args.jitter_seed) {
+        cli_args.jitter_seed = Some(shuffle::new_seed());
+    }
+    cli_args.expand_env().map_err(Fatal::Config)?;
+    cli_args.start_session();
+    let score_step = cli_args.score_step().map_err(Fatal::Config)?;
+    check_sweeps(&cli_args)?;
+    if let Some(path) = &cli_args.rerun_failures {
+        let failures =
+            rerun::load(path).map_err(|err| Fatal::Config(format!("--rerun-failures: {}", err)))?;
+        if let Some(&index) = failures
+            .runs
+            .iter()
+            .find(|&&index| index >= cli_args.total_runs())
+        {
+            return Err(Fatal::Config(format!(
+                "--rerun-failures: run {} is not among the {} runs, give the same -n, --gen and --sweep as before",
+                index,
+                cli_args.total_runs()
+            )));
+        }
+        if failures.runs.is_empty() {
+            notice!(
+                "#tester no run failed in {}, nothing to rerun",
+                path.display()
+            );
+            return Ok(Outcome::Passed);
+        }
+        cli_args.previous_failures = Some(failures);
+        if cli_args.shard_runs() == 0 {
+            return Err(Fatal::Config(
+                "--rerun-failures: no saved inputs to rerun the runs with, add --gen-seed-start if the session had it".to_string(),
+            ));
+        }
+    }
+    check_options(&cli_args)?;
+    let baseline = match &cli_args.baseline {
+        Some(path) => Some(load_baseline(&cli_args, path)?),
+        None => None,
+    };
+    let preflight_passed = run_preflight(&cli_args);
+    if !preflight_passed && cli_args.dry_run.is_none() {
+        return Err(Fatal::Config(
+            "preflight failed, nothing was run (skip it with --no-preflight)".to_string(),
+        ));
+    }
+    if !cli_args.watch {
+        return run_generation(&cli_args, score_step, baseline.as_ref(), preflight_passed);
+    }
+
+    let mut watcher = Watcher::new(cli_args.watched_paths());
+    if watcher.paths().is_empty() {
+        return Err(Fatal::Config(
+            "--watch has nothing to watch, add --watch-path".to_string(),
+        ));
+    }
+    let mut generation = 1;
+    loop {
+        cli_args.generation = Some(generation);
+        if generation > 1 {
+            cli_args.start_session();
+        }
+        let outcome =
+            match run_generation(&cli_args, score_step, baseline.as_ref(), preflight_passed) {
+                Ok(outcome) => outcome,
+                Err(err) => {
+                    error!("#tester {}", err);
+                    err.outcome()
+                }
+            };
+        notice!(
+            "#tester watching {} for changes, ctrl-c to stop",
+            watcher
+                .paths()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let stop = Arc::new(AtomicBool::new(false));
+        {
+            let stop = stop.clone();
+            on_ctrl_c(move || stop.store(true, Ordering::Relaxed))?;
+        }
+        let Some(changed) = watcher.wait(WATCH_DEBOUNCE, &stop) else {
+            return Ok(outcome);
+        };
+        generation += 1;
+        notice!(
+            "#tester ======== generation {}: {} changed ========",
+            generation,
+            changed
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! Following the runs while they go on: ctrl-c and the signals of [`signals`], the progress
+//! bar, `--keys`, the dashboard of `--tui` and what is due every so often.
+
+use super::{
+    args::Cadence, checkpoint, observers::Observers, pipeline_description, print::format_seconds,
+    Fatal, RunArgs,
+};
+use crate::{
+    dashboard::{self, FailureLog, Screen, View},
+    dots,
+    keys::KeyReader,
+    logging, metadata, signals,
+    sparkline::{self, Series},
+    timeline::{self, Timeline},
+    units, Error, SessionReport, TestRunner,
+};
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use std::{
+    collections::HashSet,
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread::{self, sleep, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How the progress bar looks, with a spinner while the runs go on.
+pub(super) fn progress_style(cli: &RunArgs, spinner: bool) -> ProgressStyle {
+    let mut template = if cli.colors() {
+        "[{elapsed_precise}] [{wide_bar:.cyan/blue}] {pos}/{len} ({eta}) {msg}"
+    } else {
+        "[{elapsed_precise}] [{wide_bar}] {pos}/{len} ({eta}) {msg}"
+    }
+    .to_string();
+    if !spinner {
+        template = template.replace(" ({eta})", "");
+    }
+    if cli.number_format.thousands_separators {
+        template = template.replace("{pos}/{len}", "{human_pos}/{human_len}");
+    }
+    if spinner {
+        template.insert_str(
+            0,
+            if cli.colors() {
+                "{spinner:.green} "
+            } else {
+                "{spinner} "
+            },
+        );
+    }
+    if cli.label.is_some() {
+        template.insert_str(
+            0,
+            if cli.colors() {
+                "{prefix:.bold} "
+            } else {
+                "{prefix} "
+            },
+        );
+    }
+    let eta_progress_fn = |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+        let decimals = units::precision().unwrap_or(1);
+        write!(w, "{:.*}s", decimals, state.eta().as_secs_f64()).unwrap()
+    };
+    ProgressStyle::with_template(&template)
+        .unwrap()
+        .with_key("eta", eta_progress_fn)
+        .progress_chars("#>-")
+}
+
+/// End the progress bar of a session that `completed` runs of with `report`: finished with
+/// the final counts if every run was done, abandoned where it stopped otherwise. The bar is
+/// drawn one last time without its spinner, and cleared when stderr is not a terminal so
+/// nothing half drawn is left in a log.
+fn finish_progress_bar(
+    cli: &RunArgs,
+    progress_bar: &ProgressBar,
+    completed: u64,
+    report: &Result<SessionReport, crate::Error>,
+) {
+    if !console::Term::stderr().is_term() {
+        progress_bar.finish_and_clear();
+        return;
+    }
+    progress_bar.set_style(progress_style(cli, false));
+    let total = cli.shard_runs();
+    match report {
+        Ok(report) if completed >= total => {
+            progress_bar.finish_with_message(format!(
+                "done, {} failed",
+                units::format_integer(report.fail_times)
+            ));
+        }
+        Ok(_) => progress_bar.abandon_with_message(format!(
+            "aborted at {}/{}",
+            units::format_integer(completed),
+            units::format_integer(total)
+        )),
+        Err(_) => progress_bar.abandon_with_message(format!(
+            "stopped by an error at {}/{}",
+            units::format_integer(completed),
+            units::format_integer(total)
+        )),
+    }
+    // The bar leaves the cursor at the end of its line.
+    eprintln!();
+}
+
+/// Lines of messages kept while --tui is drawn, and printed once it is gone.
+const TUI_MESSAGES: usize = 1000;
+
+/// How long the key reader of --keys waits for a key before checking if the session ended.
+const KEY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The first line of the status of [`print_status`]: `completed` of `total` runs finished, of
+/// which `already_done` before this session started `elapsed` ago.
+fn status_line(
+    report: &SessionReport,
+    completed: u64,
+    already_done: u64,
+    total: u64,
+    elapsed: Duration,
+) -> String {
+    let mut line = format!(
+        "{}/{} runs, {} failed",
+        units::format_integer(completed),
+        units::format_integer(total),
+        units::format_integer(report.fail_times)
+    );
+    if report.run_times > 0 {
+        line += &format!(
+            " ({}%)",
+            units::format_number(report.fail_times as f64 * 100.0 / report.run_times as f64)
+        );
+    }
+    if let Some(left) = time_left(completed, already_done, total, elapsed) {
+        line += &format!(", about {} left", units::format_duration(left));
+    }
+    line
+}
+
+/// About how long the rest of `total` runs takes at the pace of the session started `elapsed`
+/// ago, `completed` runs being done, `already_done` of them before it started.
+fn time_left(completed: u64, already_done: u64, total: u64, elapsed: Duration) -> Option<Duration> {
+    let done_now = completed.saturating_sub(already_done);
+    (done_now > 0 && completed < total).then(|| {
+        Duration::from_secs_f64(
+            elapsed.as_secs_f64() * (total - completed) as f64 / done_now as f64,
+        )
+    })
+}
+
+/// Print the summary of --interim-every: `completed` of `total` runs finished, and how many
+/// per second since the last summary, which was `since` ago with `runs_then` runs done.
+fn print_interim(
+    report: &SessionReport,
+    completed: u64,
+    total: u64,
+    since: Duration,
+    runs_then: u64,
+) {
+    let mut line = format!(
+        "#tester interim {}: {}/{} runs, {} failed",
+        metadata::format_utc(SystemTime::now()),
+        units::format_integer(completed),
+        units::format_integer(total),
+        units::format_integer(report.fail_times)
+    );
+    if let Some(mean) = report.scores.mean() {
+        line += &format!(", mean score {}", units::format_score(mean));
+    }
+    if !since.is_zero() {
+        line += &format!(
+            ", {:.1} runs/s lately",
+            completed.saturating_sub(runs_then) as f64 / since.as_secs_f64()
+        );
+    }
+    notice!("{}", line);
+}
+
+/// Point out run `index`, still going after `elapsed`, past `warn_after`.
+fn warn_hung(index: u64, elapsed: Duration, warn_after: Duration) {
+    notice!(
+        "#tester run {} is still running after {}, longer than --warn-after {}; it may be hung",
+        index,
+        units::format_duration(elapsed),
+        units::format_duration(warn_after)
+    );
+}
+
+/// The message of the progress bar: whether the runs are paused, and which of the `in_flight`
+/// runs has been running the longest, in red once past --warn-after.
+fn progress_message(cli: &RunArgs, runner: &TestRunner, in_flight: &[(u64, Duration)]) -> String {
+    let mut parts = vec![];
+    if runner.is_paused() {
+        parts.push("paused".to_string());
+    }
+    if let Some(&(index, elapsed)) = in_flight.iter().max_by_key(|(_, elapsed)| *elapsed) {
+        let mut longest = format!("longest: run {} {}", index, units::format_duration(elapsed));
+        if cli
+            .warn_after
+            .is_some_and(|warn_after| elapsed >= warn_after)
+        {
+            longest += " (!)";
+            if cli.colors() {
+                longest = console::style(longest).red().to_string();
+            }
+        }
+        parts.push(longest);
+    }
+    parts.join(", ")
+}
+
+/// Print how the session started at `started` is going, for the `s` of --keys and SIGUSR1.
+fn print_status(runner: &TestRunner, already_done: u64, total: u64, started: Instant) {
+    let report = runner.snapshot().report;
+    notice!(
+        "#tester status{}: {}",
+        if runner.is_paused() { " (paused)" } else { "" },
+        status_line(
+            &report,
+            runner.run_times(),
+            already_done,
+            total,
+            started.elapsed()
+        )
+    );
+    for (name, stats, format) in [
+        ("times", &report.times, format_seconds as fn(f64) -> String),
+        ("scores", &report.scores, units::format_score),
+    ] {
+        if let Some(mean) = stats.mean() {
+            notice!(
+                "#tester   {}: min {}, mean {}, max {}",
+                name,
+                format(stats.min),
+                format(mean),
+                format(stats.max)
+            );
+        }
+    }
+}
+
+/// The dashboard of --tui for the terminal as it is now, of the session of `runner` started at
+/// `started`, `already_done` runs being done before.
+fn dashboard_lines(
+    cli: &RunArgs,
+    runner: &TestRunner,
+    already_done: u64,
+    started: Instant,
+    durations: &Series,
+    timeline: &Timeline,
+    failure_log: &FailureLog,
+) -> Vec<String> {
+    let (rows, columns) = console::Term::stderr().size();
+    let (rows, columns) = (rows as usize, columns as usize);
+    let threads = usize::from(cli.threads);
+    let label_width = (threads - 1).to_string().len();
+    // The label, the bars around the chart and the share busy after it.
+    let chart_width = columns.saturating_sub(label_width + 17).max(1);
+    let threads = timeline::render(
+        &timeline.intervals(),
+        threads,
+        started.elapsed().as_secs_f64(),
+        chart_width,
+    )
+    .into_iter()
+    .enumerate()
+    .map(|(thread, row)| format!("thread {:>w$} {}", thread, row, w = label_width))
+    .collect::<Vec<_>>();
+    let report = runner.snapshot().report;
+    let completed = runner.run_times();
+    let total = cli.shard_runs();
+    let durations = sparkline::render(&durations.values(columns.saturating_sub(10)));
+    let view = View {
+        title: &pipeline_description(cli),
+        report: &report,
+        completed,
+        total,
+        elapsed: started.elapsed(),
+        left: time_left(completed, already_done, total, started.elapsed()),
+        paused: runner.is_paused(),
+        durations: &durations,
+        threads: &threads,
+        failures: &failure_log.lines(),
+        messages: &logging::captured(rows),
+        keys: "p pause/resume   q stop   w write --state-file",
+    };
+    dashboard::render(&view, columns, rows)
+}
+
+/// What ctrl-c does right now, see [`on_ctrl_c`].
+type CtrlCHandler = Box<dyn Fn() + Send>;
+static CTRL_C: Mutex<Option<CtrlCHandler>> = Mutex::new(None);
+
+/// Make ctrl-c call `handler` from now on instead of what it did before. The process can only
+/// have one handler, which calls the latest one given here.
+pub(super) fn on_ctrl_c(handler: impl Fn() + Send + 'static) -> Result<(), Fatal> {
+    static INSTALLED: OnceLock<Result<(), String>> = OnceLock::new();
+    INSTALLED
+        .get_or_init(|| ctrlc::set_handler(press_ctrl_c).map_err(|err| err.to_string()))
+        .clone()
+        .map_err(|err| Fatal::Runtime(format!("cannot handle ctrl-c: {}", err)))?;
+    *CTRL_C.lock().unwrap() = Some(Box::new(handler));
+    Ok(())
+}
+
+/// Do what ctrl-c does right now, for the `q` of --keys.
+pub(super) fn press_ctrl_c() {
+    if let Some(handler) = CTRL_C.lock().unwrap().as_ref() {
+        handler();
+    }
+}
+
+/// How often the runs are checked on while they go on.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Handle ctrl-c, and the signals of [`signals`], for the session of `runner`; returns what
+/// tells whether ctrl-c was pressed. This must come before the first child is spawned:
+/// children spawned earlier would be left running by a ctrl-c, and SIGUSR1 would kill tester.
+fn handle_interrupts(runner: &Arc<TestRunner>) -> Result<Arc<AtomicBool>, Fatal> {
+    let runner = runner.clone();
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let interrupted_share = interrupted.clone();
+    on_ctrl_c(move || {
+        warn!("#tester ctrl-c pressed. Terminating...");
+        interrupted_share.store(true, Ordering::Relaxed);
+        runner.cancellation_token().cancel();
+        runner.interrupt_running();
+    })?;
+    signals::install()
+        .map_err(|err| Fatal::Runtime(format!("cannot handle SIGUSR1 and SIGUSR2: {}", err)))?;
+    Ok(interrupted)
+}
+
+/// Run the runs of `runner` on a thread of their own and follow them until they end, with the
+/// displays of `observers`, the dashboard of --tui if `tui`, --keys, the signals of
+/// [`signals`], --interim-every, --warn-after and the checkpoints of --state-file, for a
+/// session started at `started`. Returns the report of the runs and what tells whether
+/// ctrl-c interrupted them.
+pub(super) fn follow(
+    cli_args: &RunArgs,
+    runner: &Arc<TestRunner>,
+    observers: &Observers,
+    tui: bool,
+    started: Instant,
+) -> Result<(Result<SessionReport, Error>, Arc<AtomicBool>), Fatal> {
+    let key_reader = (cli_args.keys || (tui && std::io::stdin().is_terminal()))
+        .then(KeyReader::new)
+        .transpose()
+        .map_err(|err| Fatal::Runtime(format!("cannot read keys from the terminal: {}", err)))?;
+    let already_done = runner.run_times();
+    let interrupted = handle_interrupts(runner)?;
+    let runner_share = runner.clone();
+    let handle = thread::spawn(move || runner_share.run());
+
+    let mut follower = Follower {
+        cli_args,
+        runner,
+        observers,
+        started,
+        already_done,
+        save_requested: Arc::new(AtomicBool::new(false)),
+        last_checkpoint: Instant::now(),
+        last_interim: (started, already_done),
+        warned: HashSet::new(),
+    };
+    let stop_keys = Arc::new(AtomicBool::new(false));
+    let keys = key_reader.map(|reader| follower.read_keys(reader, tui, stop_keys.clone()));
+    let screen = if tui {
+        logging::start_capture(TUI_MESSAGES);
+        let screen = Screen::enter();
+        if screen.is_err() {
+            logging::stop_capture();
+        }
+        Some(
+            screen
+                .map_err(|err| Fatal::Runtime(format!("cannot take over the terminal: {}", err)))?,
+        )
+    } else {
+        None
+    };
+    while !handle.is_finished() {
+        follower.check(screen.as_ref());
+        sleep(UPDATE_INTERVAL);
+    }
+
+    let report = handle.join().expect("runner thread panicked");
+    stop_keys.store(true, Ordering::Relaxed);
+    if let Some(keys) = keys {
+        // Restores the terminal.
+        keys.join().expect("key reader thread panicked");
+    }
+    if screen.is_some() {
+        drop(screen);
+        for line in logging::stop_capture() {
+            eprintln!("{}", line);
+        }
+    }
+    if let Some(progress_bar) = &observers.progress_bar {
+        finish_progress_bar(cli_args, progress_bar, runner.run_times(), &report);
+    }
+    // Everything from here on prints below the finished bar.
+    logging::attach_progress_bar(None);
+    if let Some(dots) = &observers.dots {
+        dots.finish();
+        logging::attach_dots(None);
+        notice!("#tester {}", dots::legend());
+    }
+    Ok((report, interrupted))
+}
+
+/// What [`follow`] keeps track of while the runs go on.
+struct Follower<'a> {
+    cli_args: &'a RunArgs,
+    runner: &'a Arc<TestRunner>,
+    observers: &'a Observers,
+    started: Instant,
+    /// Runs done before the session started.
+    already_done: u64,
+    /// Set by the `w` of --tui, for [`check`](Follower::check) to write the state file.
+    save_requested: Arc<AtomicBool>,
+    last_checkpoint: Instant,
+    /// When the last interim summary was printed and how many runs were done then.
+    last_interim: (Instant, u64),
+    /// The runs pointed out for --warn-after.
+    warned: HashSet<u64>,
+}
+
+impl Follower<'_> {
+    /// Handle the keys of --keys, or of the dashboard if `tui`, on a thread of their own until
+    /// `stop` is set.
+    fn read_keys(&self, reader: KeyReader, tui: bool, stop: Arc<AtomicBool>) -> JoinHandle<()> {
+        if !tui {
+            notice!("#tester press s for the status, q to stop.");
+        }
+        let runner = self.runner.clone();
+        let save = self.save_requested.clone();
+        let (already_done, total, started) =
+            (self.already_done, self.cli_args.shard_runs(), self.started);
+        thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                match reader.read_key(KEY_POLL_INTERVAL) {
+                    Ok(Some(b's')) if !tui => print_status(&runner, already_done, total, started),
+                    Ok(Some(b'p')) if tui => {
+                        let paused = !runner.is_paused();
+                        runner.pause(paused);
+                        if paused {
+                            notice!("#tester paused, the running runs finish; p again resumes.");
+                        } else {
+                            notice!("#tester resumed.");
+                        }
+                    }
+                    Ok(Some(b'w')) if tui => save.store(true, Ordering::Relaxed),
+                    Ok(Some(b'q')) => press_ctrl_c(),
+                    Ok(_) => {}
+                    Err(err) => {
+                        debug!("#tester no more keys: {}", err);
+                        break;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Do what is due while the runs go on, drawing the dashboard on `screen` if there is one.
+    fn check(&mut self, screen: Option<&Screen>) {
+        let cli_args = self.cli_args;
+        let runner = self.runner;
+        if let Some(cadence) = cli_args.interim_every {
+            let completed = runner.run_times();
+            let due = match cadence {
+                Cadence::Time(every) => self.last_interim.0.elapsed() >= every,
+                Cadence::Runs(every) => completed / every > self.last_interim.1 / every,
+            };
+            if due {
+                print_interim(
+                    &runner.snapshot().report,
+                    completed,
+                    cli_args.shard_runs(),
+                    self.last_interim.0.elapsed(),
+                    self.last_interim.1,
+                );
+                self.last_interim = (Instant::now(), completed);
+            }
+        }
+        if signals::status_requested() {
+            print_status(
+                runner,
+                self.already_done,
+                cli_args.shard_runs(),
+                self.started,
+            );
+        }
+        if signals::pause_toggled() {
+            let paused = !runner.is_paused();
+            runner.pause(paused);
+            if paused {
+                notice!(
+                    "#tester paused by SIGUSR2, the running runs finish; SIGUSR2 again resumes."
+                );
+            } else {
+                notice!("#tester resumed by SIGUSR2.");
+            }
+        }
+        let in_flight = runner.in_flight();
+        if let Some(warn_after) = cli_args.warn_after {
+            for &(index, elapsed) in &in_flight {
+                if elapsed >= warn_after && self.warned.insert(index) {
+                    warn_hung(index, elapsed, warn_after);
+                }
+            }
+        }
+        if let Some(progress_bar) = &self.observers.progress_bar {
+            progress_bar.set_message(progress_message(cli_args, runner, &in_flight));
+        }
+        if let Some(state_file) = &cli_args.state_file {
+            if self.last_checkpoint.elapsed() >= cli_args.checkpoint_interval {
+                checkpoint(cli_args, state_file, runner.snapshot());
+                self.last_checkpoint = Instant::now();
+            }
+        }
+        if self.save_requested.swap(false, Ordering::Relaxed) {
+            match &cli_args.state_file {
+                Some(state_file) => {
+                    checkpoint(cli_args, state_file, runner.snapshot());
+                    self.last_checkpoint = Instant::now();
+                    notice!("#tester state written to {}.", state_file.display());
+                }
+                None => notice!("#tester there is no --state-file to write."),
+            }
+        }
+        let observers = self.observers;
+        if let (Some(screen), Some(durations), Some(timeline), Some(failure_log)) = (
+            screen,
+            &observers.tui_durations,
+            &observers.timeline,
+            &observers.failure_log,
+        ) {
+            let lines = dashboard_lines(
+                cli_args,
+                runner,
+                self.already_done,
+                self.started,
+                durations,
+                timeline,
+                failure_log,
+            );
+            let (_, columns) = console::Term::stderr().size();
+            if let Err(err) = screen.draw(&lines, columns as usize) {
+                debug!("#tester cannot draw the dashboard: {}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! The observers of a session: the displays, the files of the runs and what the summary
+//! needs beyond the report.
+
+use super::{
+    args::StatusDisplay, generation::desktop_notify, monitor::progress_style, Fatal, RunArgs,
+};
+#[cfg(feature = "sqlite")]
+use crate::sqlite::SqliteWriter;
+use crate::{
+    artifacts::FailureSaver,
+    dashboard::FailureLog,
+    determinism::DeterminismCheck,
+    dots::Dots,
+    export::{Format, RecordWriter},
+    html::{self, ReportData},
+    logging,
+    objective::BestRunTracker,
+    slowest::SlowestRuns,
+    sparkline::{Measure, Series},
+    summary::SessionSummary,
+    sweep::VariantReports,
+    timeline::Timeline,
+    RunObserver, RunResult, RunStatus, TestRunner,
+};
+use indicatif::ProgressBar;
+use std::{
+    collections::HashMap,
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+/// Failures shown by --tui.
+const TUI_FAILURES: usize = 50;
+/// At most this many intervals are kept for --timeline, merging runs beyond.
+const MAX_TIMELINE_INTERVALS: usize = 100_000;
+
+/// The first failure with a complete generated input: its run, status and input.
+pub(super) type FirstFailure = (u64, RunStatus, Arc<[u8]>);
+
+/// The status and failure of every run rerun by --rerun-failures, by index.
+pub(super) type Reruns = HashMap<u64, (RunStatus, Option<String>)>;
+
+/// The observers `cli_args` asks for, attached by [`attach`](Observers::attach) and kept to
+/// show and save what they saw once the runs end.
+pub(super) struct Observers {
+    pub(super) progress_bar: Option<ProgressBar>,
+    pub(super) dots: Option<Arc<Dots>>,
+    /// The --jsonl records, ended with the summary.
+    jsonl: Option<Arc<RecordWriter>>,
+    #[cfg(feature = "sqlite")]
+    sqlite: Option<Arc<SqliteWriter>>,
+    pub(super) determinism: Option<Arc<DeterminismCheck>>,
+    report_data: Option<Arc<ReportData>>,
+    /// The sparklines of --score and --time, by what they chart.
+    pub(super) charts: Vec<(&'static str, Arc<Series>)>,
+    /// The durations, the failures and the intervals the dashboard of --tui shows.
+    pub(super) tui_durations: Option<Arc<Series>>,
+    pub(super) failure_log: Option<Arc<FailureLog>>,
+    pub(super) timeline: Option<Arc<Timeline>>,
+    pub(super) slowest: Arc<SlowestRuns>,
+    pub(super) reruns: Option<Arc<Mutex<Reruns>>>,
+    pub(super) variant_reports: Option<Arc<VariantReports>>,
+    pub(super) best_run: Option<Arc<BestRunTracker>>,
+    /// For --shrink and to rerun it without --isolate.
+    pub(super) first_failure: Arc<Mutex<Option<FirstFailure>>>,
+}
+
+impl Observers {
+    /// Attach to `runner` the observers `cli_args` asks for, the dashboard's too if `tui`,
+    /// for the runs of a session started at `started`. Fails if a file cannot be created.
+    pub(super) fn attach(
+        cli_args: &RunArgs,
+        mut runner: TestRunner,
+        tui: bool,
+        started: Instant,
+    ) -> Result<(TestRunner, Observers), Fatal> {
+        let progress_bar = (cli_args.progress
+            || cli_args.status == Some(StatusDisplay::Bar)
+            || (cli_args.tui && !tui))
+            .then(|| {
+                let progress_bar = ProgressBar::new(cli_args.shard_runs());
+                if let Some(label) = &cli_args.label {
+                    progress_bar.set_prefix(label.clone());
+                }
+                progress_bar.set_style(progress_style(cli_args, true));
+                progress_bar
+            });
+        let dots = (cli_args.status == Some(StatusDisplay::Dots)).then(|| {
+            let (_, columns) = console::Term::stderr().size();
+            Arc::new(Dots::new(
+                cli_args.shard_runs(),
+                columns.into(),
+                cli_args.colors(),
+            ))
+        });
+        if let Some(dots) = &dots {
+            let dots = dots.clone();
+            runner = runner.observe(move |result: &RunResult| dots.on_run(result));
+        }
+        logging::attach_dots(dots.clone());
+        // The --jsonl records end with the summary.
+        let mut jsonl = None;
+        for (path, format) in [
+            (&cli_args.csv, Format::Csv),
+            (&cli_args.jsonl, Format::Jsonl),
+        ] {
+            let Some(path) = path else { continue };
+            let tags = &cli_args.metadata().tags;
+            let writer = if format == Format::Jsonl && cli_args.jsonl_to_stdout() {
+                RecordWriter::stdout(format, tags)
+            } else if cli_args.resume {
+                RecordWriter::append(path, format, tags)
+            } else {
+                RecordWriter::create(path, format, tags)
+            };
+            let writer = Arc::new(writer.map_err(|err| {
+                Fatal::Config(format!("cannot write {}: {}", path.display(), err))
+            })?);
+            if format == Format::Jsonl {
+                jsonl = Some(writer.clone());
+            }
+            runner = runner.observe(move |result: &RunResult| writer.on_run(result));
+        }
+        #[cfg(feature = "sqlite")]
+        let sqlite = match &cli_args.sqlite {
+            Some(path) => {
+                let flags = std::env::args().skip(1).collect::<Vec<_>>();
+                let writer = SqliteWriter::open(
+                    path,
+                    &super::pipeline_description(cli_args),
+                    &flags,
+                    cli_args.label.as_deref(),
+                )
+                .map_err(|err| {
+                    Fatal::Config(format!("cannot write {}: {}", path.display(), err))
+                })?;
+                let writer = Arc::new(writer);
+                let observed = writer.clone();
+                runner = runner.observe(move |result: &RunResult| observed.on_run(result));
+                Some(writer)
+            }
+            None => None,
+        };
+        let mut determinism = None;
+        if cli_args.check_deterministic {
+            let mut check = DeterminismCheck::new(
+                cli_args.deterministic_stderr,
+                cli_args.deterministic_ignore.clone(),
+            );
+            if let Some(dir) = &cli_args.save_failures {
+                std::fs::create_dir_all(dir).map_err(|err| {
+                    Fatal::Config(format!("cannot create {}: {}", dir.display(), err))
+                })?;
+                check = check.save_samples(dir);
+            }
+            let check = Arc::new(check);
+            determinism = Some(check.clone());
+            runner = runner.observe(move |result: &RunResult| check.on_run(result));
+        }
+        if let Some(dir) = cli_args.failures_dir() {
+            let saver = FailureSaver::new(dir, cli_args.dedup_failures)
+                .map_err(|err| Fatal::Config(format!("cannot create {}: {}", dir.display(), err)))?
+                .save_invalid(cli_args.save_invalid)
+                .diff_context(cli_args.diff_context);
+            runner = runner.observe(saver);
+        }
+        // The charts are drawn over the runs left to run.
+        let runs_left = cli_args.shard_runs().saturating_sub(runner.run_times());
+        let report_data = cli_args
+            .html
+            .is_some()
+            .then(|| Arc::new(ReportData::new(runs_left)));
+        if let Some(report_data) = &report_data {
+            let report_data = report_data.clone();
+            runner = runner.observe(move |result: &RunResult| report_data.on_run(result));
+        }
+        // Charts only make sense on a terminal.
+        let show_charts = cli_args.colors() && std::io::stderr().is_terminal();
+        let charts = [
+            ("scores", cli_args.score, Measure::Score),
+            ("times", cli_args.time, Measure::Duration),
+        ]
+        .into_iter()
+        .filter(|&(_, enabled, _)| enabled && show_charts)
+        .map(|(name, _, measure)| (name, Arc::new(Series::new(measure, runs_left))))
+        .collect::<Vec<_>>();
+        for (_, series) in &charts {
+            let series = series.clone();
+            runner = runner.observe(move |result: &RunResult| series.on_run(result));
+        }
+        // The dashboard charts the times of --time when there is one.
+        let tui_durations = match charts.iter().find(|(name, _)| *name == "times") {
+            Some((_, series)) if tui => Some(series.clone()),
+            _ if tui => {
+                let series = Arc::new(Series::new(Measure::Duration, runs_left));
+                let observed = series.clone();
+                runner = runner.observe(move |result: &RunResult| observed.on_run(result));
+                Some(series)
+            }
+            _ => None,
+        };
+        let failure_log = tui.then(|| Arc::new(FailureLog::new(TUI_FAILURES)));
+        if let Some(failure_log) = &failure_log {
+            let failure_log = failure_log.clone();
+            runner = runner.observe(move |result: &RunResult| failure_log.on_run(result));
+        }
+        let slowest = Arc::new(SlowestRuns::new(
+            if cli_args.time { cli_args.slowest } else { 0 },
+            cli_args.threads.into(),
+        ));
+        {
+            let slowest = slowest.clone();
+            runner = runner.observe(move |result: &RunResult| slowest.on_run(result));
+        }
+        if cli_args.notify_on_failure {
+            let notified = AtomicBool::new(false);
+            runner = runner.observe(move |result: &RunResult| {
+                let failed = result.status.is_counted() && result.status != RunStatus::Passed;
+                if failed && !notified.swap(true, Ordering::Relaxed) {
+                    let body = match &result.failure {
+                        Some(failure) => format!("run {} failed: {}", result.index, failure),
+                        None => format!("run {} {}", result.index, result.status),
+                    };
+                    // Not on the worker thread, which has more runs to do.
+                    thread::spawn(move || desktop_notify("tester: first failure", &body));
+                }
+            });
+        }
+        let reruns = cli_args
+            .previous_failures
+            .is_some()
+            .then(|| Arc::new(Mutex::new(HashMap::new())));
+        if let Some(reruns) = &reruns {
+            let reruns = reruns.clone();
+            runner = runner.observe(move |result: &RunResult| {
+                let rerun = (
+                    result.status,
+                    result.failure.as_ref().map(ToString::to_string),
+                );
+                reruns.lock().unwrap().insert(result.index, rerun);
+            });
+        }
+        let variant_reports = (!runner.config().variants.is_empty()).then(|| {
+            Arc::new(VariantReports::new(
+                &runner.config().variants,
+                runner.config().steps.len(),
+            ))
+        });
+        if let Some(variant_reports) = &variant_reports {
+            let variant_reports = variant_reports.clone();
+            runner = runner.observe(move |result: &RunResult| variant_reports.on_run(result));
+        }
+        let best_run = cli_args
+            .score
+            .then(|| Arc::new(BestRunTracker::new(cli_args.objective())));
+        if let Some(best_run) = &best_run {
+            let best_run = best_run.clone();
+            runner = runner.observe(move |result: &RunResult| best_run.on_run(result));
+        }
+        if let Some(progress_bar) = &progress_bar {
+            progress_bar.set_position(runner.run_times());
+            let progress_bar = progress_bar.clone();
+            runner = runner.observe(move |_: &RunResult| progress_bar.inc(1));
+        }
+        logging::attach_progress_bar(progress_bar.clone());
+
+        let first_failure = Arc::new(Mutex::new(None));
+        if cli_args.shrink || cli_args.isolate.is_some() {
+            let first_failure = first_failure.clone();
+            runner = runner.observe(move |result: &RunResult| {
+                let generated = !matches!(result.status, RunStatus::Invalid | RunStatus::Skipped)
+                    && result
+                        .failure
+                        .as_ref()
+                        .is_some_and(|failure| !failure.place.starts_with("in generator"));
+                if let (true, false, Some(input)) =
+                    (generated, result.input_truncated, &result.input)
+                {
+                    let mut first_failure = first_failure.lock().unwrap();
+                    first_failure.get_or_insert((result.index, result.status, input.clone()));
+                }
+            });
+        }
+
+        let timeline = (cli_args.timeline || tui).then(|| {
+            Arc::new(Timeline::new(
+                started,
+                cli_args.threads.into(),
+                MAX_TIMELINE_INTERVALS,
+            ))
+        });
+        if let Some(timeline) = &timeline {
+            let timeline = timeline.clone();
+            runner = runner.observe(move |result: &RunResult| timeline.on_run(result));
+        }
+        Ok((
+            runner,
+            Observers {
+                progress_bar,
+                dots,
+                jsonl,
+                #[cfg(feature = "sqlite")]
+                sqlite,
+                determinism,
+                report_data,
+                charts,
+                tui_durations,
+                failure_log,
+                timeline,
+                slowest,
+                reruns,
+                variant_reports,
+                best_run,
+                first_failure,
+            },
+        ))
+    }
+
+    /// Save the `summary` of the session to the files of --json, --jsonl, --sqlite and
+    /// --html, only reporting the files that cannot be written.
+    pub(super) fn export(&self, cli_args: &RunArgs, summary: &SessionSummary) {
+        if let Some(path) = &cli_args.json {
+            if let Err(err) = summary.save(path) {
+                error!("#tester cannot write {}: {}", path.display(), err);
+            }
+        }
+        if let Some(jsonl) = &self.jsonl {
+            jsonl.finish(summary);
+        }
+        #[cfg(feature = "sqlite")]
+        if let (Some(sqlite), Some(path)) = (&self.sqlite, &cli_args.sqlite) {
+            if let Err(err) = sqlite.finish(summary) {
+                error!("#tester cannot write {}: {}", path.display(), err);
+            }
+        }
+        if let (Some(path), Some(report_data)) = (&cli_args.html, &self.report_data) {
+            if let Err(err) = std::fs::write(path, html::render(summary, report_data)) {
+                error!("#tester cannot write {}: {}", path.display(), err);
+            }
+        }
+    }
+}
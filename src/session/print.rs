@@ -0,0 +1,919 @@
+//! What a session prints once its runs end: the summary, the verdict, and the tables and
+//! charts of the options asking for them.
+
+use super::{observers::Reruns, Interruption, RunArgs};
+use crate::{
+    baseline::{BaselineComparison, Metric},
+    command::Step,
+    compare,
+    determinism::OutputVariant,
+    objective::{BestRun, Objective},
+    rerun::PreviousFailures,
+    slowest::SlowRun,
+    sparkline::{self, Series},
+    stats::FailureRate,
+    sweep::{Sweep, VariantReport},
+    timeline::{self, Interval},
+    units, Abort, RunStatus, SessionReport,
+};
+use std::{path::Path, time::Duration};
+
+/// Print how many failed runs printed nothing because of --max-failure-output, and where they
+/// are instead.
+pub(super) fn print_suppressed(cli_args: &RunArgs, suppressed: u64) {
+    let places = [
+        cli_args.failures_dir(),
+        cli_args.csv.as_deref(),
+        cli_args.jsonl_file(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|path| path.display().to_string())
+    .collect::<Vec<_>>();
+    let found = if places.is_empty() {
+        String::new()
+    } else {
+        format!(", find them in {}", places.join(" and "))
+    };
+    notice!(
+        "#tester suppressed the output of {} failed runs (--max-failure-output {}){}",
+        units::format_integer(suppressed),
+        cli_args.max_failure_output,
+        found
+    );
+}
+
+/// Print how each run of --rerun-failures went this time, from the status and failure of
+/// each run rerun.
+pub(super) fn print_reruns(cli_args: &RunArgs, failures: &PreviousFailures, reruns: &Reruns) {
+    let mut fixed = 0;
+    let mut not_reproducible = Vec::new();
+    let mut not_rerun = Vec::new();
+    for &index in &failures.runs {
+        match reruns.get(&index) {
+            Some((RunStatus::Passed, _)) => {
+                fixed += 1;
+                notice!("#tester rerun of run {}: fixed", index);
+            }
+            Some((status, failure)) => notice!(
+                "#tester rerun of run {}: still fails ({})",
+                index,
+                failure.clone().unwrap_or_else(|| status.to_string())
+            ),
+            None if !cli_args.reproducible(index) => not_reproducible.push(index),
+            None => not_rerun.push(index),
+        }
+    }
+    let rerun = failures.runs.len() - not_reproducible.len() - not_rerun.len();
+    notice!(
+        "#tester fixed {} of {} previously failing runs.",
+        units::format_integer(fixed),
+        units::format_integer(rerun as u64)
+    );
+    let list = |runs: &[u64]| {
+        runs.iter()
+            .map(|index| index.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    if !not_reproducible.is_empty() {
+        notice!(
+            "#tester not rerun, no seed (--gen-seed-start) or saved input to generate their inputs again: runs {}",
+            list(&not_reproducible)
+        );
+    }
+    if !not_rerun.is_empty() {
+        notice!(
+            "#tester not rerun before the session stopped: runs {}",
+            list(&not_rerun)
+        );
+    }
+}
+
+/// Sum up a session of `requested` runs of `pipeline`.
+pub(super) fn print_summary(
+    cli: &RunArgs,
+    requested: u64,
+    pipeline: &str,
+    report: &SessionReport,
+    slowest: &[SlowRun],
+    best: Option<&BestRun>,
+    interruption: Option<&Interruption>,
+) {
+    let steps = cli.steps();
+    let fail_times = report.fail_times;
+    let run_times = report.run_times;
+    notice!(
+        "#tester summary{}: {} runs of {} on {} threads",
+        cli.metadata().label_suffix(),
+        units::format_count(requested),
+        pipeline,
+        std::cmp::max(cli.threads, 1)
+    );
+    if let Some(seed) = cli.shuffle.flatten() {
+        notice!(
+            "#tester runs shuffled with seed {}, repeat the order with --shuffle={}",
+            seed,
+            seed
+        );
+    }
+    if let (Some(max), Some(seed)) = (cli.jitter, cli.jitter_seed) {
+        notice!(
+            "#tester runs delayed by up to {} with seed {}, repeat the delays with --jitter-seed {}",
+            units::format_duration(max),
+            seed,
+            seed
+        );
+    }
+    if let Some(shard) = cli.shard {
+        notice!(
+            "#tester shard {}: the runs whose index modulo {} is {}, of {} in all",
+            shard,
+            shard.count,
+            shard.index,
+            units::format_count(cli.total_runs())
+        );
+    }
+    for line in summary_lines(cli, requested, report, interruption) {
+        notice!("{}", line);
+    }
+    if let (true, Some(best)) = (cli.score, best) {
+        let duration = if cli.time {
+            format!(
+                " in {}",
+                units::format_duration(Duration::from_secs_f64(best.duration))
+            )
+        } else {
+            String::new()
+        };
+        notice!(
+            "#tester objective: {}; best run {}: score {}{}{}.",
+            cli.objective(),
+            best.index,
+            units::format_score(best.score),
+            match cli.score_unit.name() {
+                Some(unit) if best.time_score => format!(" (time in {})", unit),
+                _ => String::new(),
+            },
+            duration
+        );
+    }
+    if fail_times > 0 || cli.target_failure_rate.is_some() {
+        if let Some(rate) = FailureRate::of(fail_times, run_times) {
+            print_failure_rate(&rate, fail_times, run_times, cli.target_failure_rate);
+        }
+    }
+    if let Some(allowed) = cli.allowed_rate(run_times) {
+        print_verdict(cli, report, allowed);
+    }
+    if let Some(generator) = cli.generator().filter(|g| g.seed_start.is_some()) {
+        if !report.failed_runs.is_empty() {
+            const SHOWN_SEEDS: usize = 10;
+            let mut seeds = report
+                .failed_runs
+                .iter()
+                .take(SHOWN_SEEDS)
+                .map(|index| generator.seed(index).unwrap().to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if report.failed_runs.len() as usize > SHOWN_SEEDS {
+                seeds += &format!(
+                    " and {} more",
+                    report.failed_runs.len() as usize - SHOWN_SEEDS
+                );
+            }
+            notice!("#tester failing seeds: {}", seeds);
+        }
+    }
+    if let (true, Some(avg_time)) = (cli.time, report.times.mean()) {
+        notice!(
+            "#tester average time: {} per run.",
+            units::format_duration(Duration::from_secs_f64(avg_time))
+        );
+    }
+    if !slowest.is_empty() {
+        print_slowest(slowest);
+    }
+    if steps.len() > 1 && (fail_times > 0 || cli.time) {
+        for (index, (step, stats)) in steps.iter().zip(&report.steps).enumerate() {
+            let mut line = format!(
+                "#tester step {} {}: failed {} / {}",
+                index + 1,
+                step.command_line(),
+                stats.fail_times,
+                stats.run_times
+            );
+            if cli.time {
+                line += &format!(", average {}", units::format_duration(stats.average_time()));
+            }
+            notice!("{}", line);
+        }
+    }
+    let hooks = [
+        ("setup", &cli.setup, &report.setup),
+        ("teardown", &cli.teardown, &report.teardown),
+    ];
+    for (what, step, stats) in hooks {
+        let Some(step) = step else { continue };
+        if stats.fail_times > 0 || cli.time {
+            let mut line = format!(
+                "#tester {} {}: failed {} / {}",
+                what,
+                step.command_line(),
+                stats.fail_times,
+                stats.run_times
+            );
+            if cli.time {
+                line += &format!(", average {}", units::format_duration(stats.average_time()));
+            }
+            notice!("{}", line);
+        }
+    }
+}
+
+/// What [`print_summary`] says of how the `requested` runs went: whether they were cut short,
+/// how many failed and why, the average score, and the runs that were not counted.
+fn summary_lines(
+    cli: &RunArgs,
+    requested: u64,
+    report: &SessionReport,
+    interruption: Option<&Interruption>,
+) -> Vec<String> {
+    let fail_times = report.fail_times;
+    let run_times = report.run_times;
+    let mut lines = vec![];
+    if let Some(interruption) = interruption {
+        match &interruption.abort {
+            Some(abort) => lines.push(format!(
+                "#tester aborted by run {}: the {} of step {} matched --abort-on `{}`.",
+                abort.index, abort.stream, abort.step, abort.pattern
+            )),
+            None => lines.push("#tester interrupted by SIGINT.".to_string()),
+        }
+        lines.push(format!(
+            "#tester requested {}, completed {}, failed {}, cancelled {}.",
+            units::format_integer(requested),
+            units::format_integer(interruption.completed),
+            units::format_integer(fail_times),
+            units::format_integer(requested - interruption.completed)
+        ));
+    }
+    // Only scores written with a time unit were converted to --score-unit.
+    let unit = match cli.score_unit.name() {
+        Some(unit) if report.time_scores == report.scores.count => {
+            format!(" (times in {})", unit)
+        }
+        Some(unit) if report.time_scores > 0 => format!(
+            " ({} of {} scores times in {})",
+            units::format_integer(report.time_scores),
+            units::format_integer(report.scores.count),
+            unit
+        ),
+        _ => String::new(),
+    };
+    let score_line = |label: &str| match report.scores.mean() {
+        Some(avg_score) => format!(
+            "#tester {}: {}{}.",
+            label,
+            units::format_score(avg_score),
+            unit
+        ),
+        None => "#tester no successful runs to average the score of.".to_string(),
+    };
+    if run_times == 0 {
+        if interruption.is_some() {
+            lines.push(
+                "#tester 0 runs executed (interrupted before the first one finished).".to_string(),
+            );
+        } else {
+            lines.push("#tester 0 runs executed.".to_string());
+        }
+    } else if fail_times > 0 {
+        let mut kinds = vec![];
+        if report.timeout_times > 0 {
+            kinds.push(format!("{} timed out", report.timeout_times));
+        }
+        if report.cpu_limit_times > 0 {
+            kinds.push(format!("{} over the CPU limit", report.cpu_limit_times));
+        }
+        if report.relative_timeout_times > 0 {
+            kinds.push(format!(
+                "{} over --timeout-factor",
+                report.relative_timeout_times
+            ));
+        }
+        if kinds.is_empty() {
+            lines.push(format!(
+                "#tester finished. Failed {} / {}",
+                units::format_integer(fail_times),
+                units::format_integer(run_times)
+            ));
+        } else {
+            lines.push(format!(
+                "#tester finished. Failed {} / {} ({})",
+                units::format_integer(fail_times),
+                units::format_integer(run_times),
+                kinds.join(", ")
+            ));
+        }
+        if cli.score {
+            lines.push(score_line("average score(Ignore failed runs)"));
+        }
+    } else {
+        lines.push(format!(
+            "#tester finished. No failure in {} runs.",
+            units::format_integer(run_times)
+        ));
+        if cli.score {
+            lines.push(score_line("average score"));
+        }
+    }
+    if report.invalid_times > 0 {
+        lines.push(format!(
+            "#tester {} runs had invalid input and were not counted.",
+            report.invalid_times
+        ));
+    }
+    if report.setup_failed_times > 0 {
+        lines.push(format!(
+            "#tester {} runs failed in setup and were not counted.",
+            report.setup_failed_times
+        ));
+    }
+    if report.skipped_times > 0 {
+        lines.push(format!(
+            "#tester {} runs were skipped by their exit code and were not counted.",
+            report.skipped_times
+        ));
+    }
+    lines
+}
+
+pub(super) fn print_variants(variants: &[OutputVariant]) {
+    match variants {
+        [] => notice!("#tester no passed run to check the determinism of."),
+        [variant] => notice!(
+            "#tester deterministic: all {} passed runs wrote the same output.",
+            variant.count
+        ),
+        _ => {
+            error!(
+                "#tester NONDETERMINISTIC: passed runs wrote {} distinct outputs.",
+                variants.len()
+            );
+            for variant in variants {
+                notice!(
+                    "#tester output {:016x}: {} runs, first run {}",
+                    variant.hash,
+                    variant.count,
+                    variant.first_run
+                );
+            }
+        }
+    }
+}
+
+/// The best of the variants of --sweep without failures: the best mean score with --score,
+/// else the lowest mean time.
+fn best_variant(cli: &RunArgs, variants: &[VariantReport]) -> Option<usize> {
+    let mean_score = |variant: &VariantReport| {
+        let mean = variant.report.scores.mean().filter(|_| cli.score)?;
+        Some(match cli.objective() {
+            Objective::Maximize => mean,
+            Objective::Minimize => -mean,
+        })
+    };
+    let best = |key: &dyn Fn(&VariantReport) -> Option<f64>| {
+        variants
+            .iter()
+            .enumerate()
+            .filter(|(_, variant)| variant.report.run_times > 0 && variant.report.fail_times == 0)
+            .filter_map(|(index, variant)| Some((index, key(variant)?)))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    };
+    best(&mean_score).or_else(|| best(&|variant| Some(-variant.report.times.mean()?)))
+}
+
+/// `rows` as lines of aligned columns, the first one left-aligned and the others
+/// right-aligned.
+fn table_lines(rows: &[Vec<String>]) -> Vec<String> {
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    let widths = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| Some(row.get(column)?.chars().count()))
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    rows.iter()
+        .map(|row| {
+            let cells = row
+                .iter()
+                .zip(&widths)
+                .enumerate()
+                .map(|(column, (cell, &width))| {
+                    if column == 0 {
+                        format!("{:<width$}", cell)
+                    } else {
+                        format!("{:>width$}", cell)
+                    }
+                });
+            cells.collect::<Vec<_>>().join("  ")
+        })
+        .collect()
+}
+
+fn failure_rate(report: &SessionReport) -> String {
+    match report.run_times {
+        0 => "-".to_string(),
+        runs => units::format_rate(report.fail_times as f64 / runs as f64),
+    }
+}
+
+/// The totals of every variant of --sweep, the best one highlighted: as a matrix of the
+/// values of two sweeps, else as a table with a row per variant.
+pub(super) fn print_sweep(cli: &RunArgs, variants: &[VariantReport]) {
+    let best = best_variant(cli, variants);
+    if let [rows, columns] = &cli.sweep[..] {
+        return print_matrix(cli, (rows, columns), variants, best);
+    }
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let rows = variants.iter().map(|variant| {
+        let report = &variant.report;
+        vec![
+            variant.label.clone(),
+            report.run_times.to_string(),
+            report.fail_times.to_string(),
+            failure_rate(report),
+            or_dash(report.scores.mean().map(units::format_score)),
+            or_dash(
+                report
+                    .times
+                    .mean()
+                    .map(|mean| units::format_duration(Duration::from_secs_f64(mean))),
+            ),
+        ]
+    });
+    let header = [
+        "value",
+        "runs",
+        "failed",
+        "failure rate",
+        "mean score",
+        "mean time",
+    ];
+    let table = std::iter::once(header.map(String::from).to_vec())
+        .chain(rows)
+        .collect::<Vec<_>>();
+    notice!(
+        "#tester per {}:",
+        if cli.sweep.len() == 1 {
+            "value"
+        } else {
+            "combination"
+        }
+    );
+    for (index, mut line) in table_lines(&table).into_iter().enumerate() {
+        // The header is row 0.
+        if best.is_some_and(|best| best + 1 == index) {
+            line += "  <- best";
+            if cli.colors() {
+                line = console::style(line).bold().to_string();
+            }
+        }
+        notice!("#tester   {}", line.trim_end());
+    }
+}
+
+/// The variants of two sweeps as a matrix, a row per value of the first one and a column per
+/// value of the second, showing the mean score, or time without --score, and failure rate of
+/// each combination.
+fn print_matrix(
+    cli: &RunArgs,
+    (rows, columns): (&Sweep, &Sweep),
+    variants: &[VariantReport],
+    best: Option<usize>,
+) {
+    let cell = |index: usize| {
+        let report = &variants[index].report;
+        let mean = if cli.score {
+            report.scores.mean().map(units::format_score)
+        } else {
+            report
+                .times
+                .mean()
+                .map(|mean| units::format_duration(Duration::from_secs_f64(mean)))
+        };
+        let mut cell = format!(
+            "{} ({})",
+            mean.unwrap_or_else(|| "-".to_string()),
+            failure_rate(report)
+        );
+        // Keep the other cells aligned with the best one.
+        match best {
+            Some(best) if best == index => cell += " *",
+            Some(_) => cell += "  ",
+            None => {}
+        }
+        cell
+    };
+    let header = std::iter::once(format!("{} \\ {}", rows.name, columns.name))
+        .chain(columns.values.iter().map(|value| match best {
+            Some(_) => format!("{}  ", value),
+            None => value.clone(),
+        }))
+        .collect();
+    let table = std::iter::once(header)
+        .chain(rows.values.iter().enumerate().map(|(row, value)| {
+            std::iter::once(value.clone())
+                .chain(
+                    (0..columns.values.len())
+                        .map(|column| cell(row * columns.values.len() + column)),
+                )
+                .collect()
+        }))
+        .collect::<Vec<_>>();
+    notice!(
+        "#tester mean {} (failure rate) per combination:",
+        if cli.score { "score" } else { "time" }
+    );
+    for line in table_lines(&table) {
+        notice!("#tester   {}", line.trim_end());
+    }
+    if let Some(best) = best {
+        let line = format!("#tester   * best: {}", variants[best].label);
+        if cli.colors() {
+            notice!("{}", console::style(line).bold());
+        } else {
+            notice!("{}", line);
+        }
+    }
+}
+
+fn print_failure_rate(rate: &FailureRate, fail_times: u64, run_times: u64, target: Option<f64>) {
+    let interval = if fail_times == 0 {
+        format!(
+            "at most {} with 95% confidence",
+            units::format_rate(rate.high)
+        )
+    } else if fail_times == run_times {
+        format!(
+            "at least {} with 95% confidence",
+            units::format_rate(rate.low)
+        )
+    } else {
+        format!(
+            "95% confidence interval {} to {}",
+            units::format_rate(rate.low),
+            units::format_rate(rate.high)
+        )
+    };
+    notice!(
+        "#tester failure rate: {} ({})",
+        units::format_rate(rate.observed),
+        interval
+    );
+    if let Some(target) = target {
+        if rate.allows(target) {
+            notice!(
+                "#tester consistent with the target failure rate of {} or less.",
+                units::format_rate(target)
+            );
+        } else {
+            error!(
+                "#tester the failure rate exceeds the target of {} with 95% confidence.",
+                units::format_rate(target)
+            );
+        }
+    }
+}
+
+/// Print whether the session passes --allowed-failure-rate or --allowed-failures, which allow
+/// a failure rate of `allowed`, and why.
+fn print_verdict(cli: &RunArgs, report: &SessionReport, allowed: f64) {
+    let failed = cli.failed(report);
+    let threshold = match cli.allowed_failures {
+        Some(count) => format!(
+            "{} failure{} ({})",
+            units::format_integer(count),
+            if count == 1 { "" } else { "s" },
+            units::format_rate(allowed)
+        ),
+        None => units::format_rate(allowed),
+    };
+    let observed = FailureRate::of(report.fail_times, report.run_times).map_or_else(
+        || "no runs".to_string(),
+        |rate| units::format_rate(rate.observed),
+    );
+    let how = match (cli.require_confidence, failed) {
+        (true, true) => "above the allowed failure rate with 95% confidence",
+        (true, false) => "not above the allowed failure rate with 95% confidence",
+        (false, true) => "more failures than allowed",
+        (false, false) => "no more failures than allowed",
+    };
+    let message = format!(
+        "#tester verdict: {}, {} of {} runs failed ({}), {}; up to {} allowed.",
+        if failed { "failed" } else { "passed" },
+        units::format_integer(report.fail_times),
+        units::format_integer(report.run_times),
+        observed,
+        how,
+        threshold
+    );
+    if failed {
+        error!("{}", message);
+    } else {
+        notice!("{}", message);
+    }
+}
+
+pub(super) fn print_baseline_comparison(path: &Path, comparison: &BaselineComparison) {
+    notice!("#tester compared with the baseline {}:", path.display());
+    for delta in &comparison.deltas {
+        let (baseline, current, change) = match delta.metric {
+            Metric::FailureRate => (
+                units::format_rate(delta.baseline),
+                units::format_rate(delta.current),
+                delta
+                    .change
+                    .map(|change| format!("{:+.2} points", change * 100.0)),
+            ),
+            Metric::MeanScore => (
+                units::format_score(delta.baseline),
+                units::format_score(delta.current),
+                delta
+                    .change
+                    .map(|change| format!("{:+.1}%", change * 100.0)),
+            ),
+            Metric::MeanDuration => (
+                units::format_duration(Duration::from_secs_f64(delta.baseline)),
+                units::format_duration(Duration::from_secs_f64(delta.current)),
+                delta
+                    .change
+                    .map(|change| format!("{:+.1}%", change * 100.0)),
+            ),
+        };
+        let mut line = format!(
+            "#tester   {}: {} -> {}",
+            delta.metric.name(),
+            baseline,
+            current
+        );
+        if let Some(change) = change {
+            line += &format!(" ({})", change);
+        }
+        if delta.regression {
+            error!("{} REGRESSION", line);
+        } else {
+            notice!("{}", line);
+        }
+    }
+}
+
+pub(super) fn print_sparkline(name: &str, series: &Series) {
+    let label = |value: f64| match name {
+        "times" => units::format_duration(Duration::from_secs_f64(value)),
+        _ => units::format_number(value),
+    };
+    let range = |values: &[Option<f64>]| {
+        let known = values.iter().flatten();
+        Some((
+            label(known.clone().copied().reduce(f64::min)?),
+            label(known.copied().reduce(f64::max)?),
+        ))
+    };
+    // The labels of the finest chart are about as long as those of the one drawn.
+    let Some((min, max)) = range(&series.values(usize::MAX)) else {
+        return;
+    };
+    let (_, columns) = console::Term::stderr().size();
+    let labels = format!("#tester {}: {}  {}", name, min, max)
+        .chars()
+        .count();
+    let values = series.values((columns as usize).saturating_sub(labels).max(10));
+    let Some((min, max)) = range(&values) else {
+        return;
+    };
+    notice!(
+        "#tester {}: {} {} {}",
+        name,
+        min,
+        sparkline::render(&values),
+        max
+    );
+}
+
+pub(super) fn print_timeline(intervals: &[Interval], threads: usize) {
+    if intervals.is_empty() {
+        return;
+    }
+    let threads = threads.max(1);
+    // Until the last run finished.
+    let duration = intervals
+        .iter()
+        .map(|interval| interval.end)
+        .fold(0.0, f64::max);
+    let label = |thread: usize| {
+        format!(
+            "#tester   thread {:>w$} ",
+            thread,
+            w = (threads - 1).to_string().len()
+        )
+    };
+    let (_, columns) = console::Term::stderr().size();
+    // The label, the bars around the chart and the share busy after it.
+    let width = (columns as usize)
+        .saturating_sub(label(0).chars().count() + 7)
+        .max(10);
+    notice!(
+        "#tester timeline over {}, `#` busy, `.` briefly busy, blank idle:",
+        units::format_duration(Duration::from_secs_f64(duration))
+    );
+    let rows = timeline::render(intervals, threads, duration, width);
+    for (thread, row) in rows.iter().enumerate() {
+        notice!("{}{}", label(thread), row);
+    }
+}
+
+fn print_slowest(slowest: &[SlowRun]) {
+    let rows = slowest
+        .iter()
+        .map(|run| {
+            let status = match &run.reason {
+                Some(reason) => format!("{} ({})", run.status, reason),
+                None => run.status.to_string(),
+            };
+            let score = run.score.map(units::format_score).unwrap_or_default();
+            [
+                run.index.to_string(),
+                units::format_duration(Duration::from_secs_f64(run.duration)),
+                status,
+                score,
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = ["run", "duration", "status", "score"];
+    let width = |column: usize| {
+        rows.iter()
+            .map(|row| row[column].chars().count())
+            .chain([header[column].len()])
+            .max()
+            .unwrap_or_default()
+    };
+    let widths = [width(0), width(1), width(2)];
+    notice!("#tester slowest runs:");
+    for row in [header.map(String::from)].iter().chain(&rows) {
+        let line = format!(
+            "#tester   {:>w0$}  {:>w1$}  {:<w2$}  {}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2]
+        );
+        notice!("{}", line.trim_end());
+    }
+}
+
+/// `seconds` as a duration.
+pub(super) fn format_seconds(seconds: f64) -> String {
+    units::format_duration(Duration::from_secs_f64(seconds.max(0.0)))
+}
+
+/// Lines of the output of the run that matched --abort-on shown, from the match on.
+const ABORT_EXCERPT_LINES: usize = 40;
+
+/// Print how `abort` stopped the session, its `step` printing what matched --abort-on.
+pub(super) fn print_abort(step: &Step, abort: &Abort) {
+    error!(
+        "#tester run {} aborted the session, the {} of {} matched `{}`:",
+        abort.index,
+        abort.stream,
+        step.command_line(),
+        abort.pattern
+    );
+    error!(
+        "{}",
+        compare::excerpt(&abort.output, ABORT_EXCERPT_LINES).trim_end()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        args: RunArgs,
+    }
+
+    fn args(flags: &[&str]) -> RunArgs {
+        let args = ["tester", "-n", "10"]
+            .iter()
+            .chain(flags)
+            .chain(&["--", "true"]);
+        Cli::parse_from(args).args
+    }
+
+    fn report(run_times: u64, fail_times: u64) -> SessionReport {
+        SessionReport {
+            run_times,
+            fail_times,
+            ..SessionReport::new(1)
+        }
+    }
+
+    #[test]
+    fn passed() {
+        let mut report = report(10, 0);
+        report.scores.add(0.5);
+        report.scores.add(1.5);
+        assert_eq!(
+            summary_lines(&args(&["--score"]), 10, &report, None),
+            [
+                "#tester finished. No failure in 10 runs.",
+                "#tester average score: 1.",
+            ]
+        );
+    }
+
+    #[test]
+    fn failed() {
+        assert_eq!(
+            summary_lines(&args(&["--score"]), 10, &report(10, 10), None),
+            [
+                "#tester finished. Failed 10 / 10",
+                "#tester no successful runs to average the score of.",
+            ]
+        );
+    }
+
+    #[test]
+    fn timed_out() {
+        let mut report = report(10, 3);
+        report.timeout_times = 2;
+        assert_eq!(
+            summary_lines(&args(&[]), 10, &report, None),
+            ["#tester finished. Failed 3 / 10 (2 timed out)"]
+        );
+    }
+
+    #[test]
+    fn skipped() {
+        let mut report = report(8, 0);
+        report.skipped_times = 2;
+        assert_eq!(
+            summary_lines(&args(&[]), 10, &report, None),
+            [
+                "#tester finished. No failure in 8 runs.",
+                "#tester 2 runs were skipped by their exit code and were not counted.",
+            ]
+        );
+    }
+
+    #[test]
+    fn beyond_u32() {
+        let max = u64::from(u32::MAX);
+        let mut report = report(2 * max, max + 1);
+        report.timeout_times = max;
+        assert_eq!(
+            summary_lines(&args(&[]), 2 * max, &report, None),
+            ["#tester finished. Failed 4294967296 / 8589934590 (4294967295 timed out)"]
+        );
+    }
+
+    #[test]
+    fn cancelled() {
+        let interruption = |completed| Interruption {
+            completed,
+            abort: None,
+        };
+        assert_eq!(
+            summary_lines(&args(&[]), 10, &report(4, 1), Some(&interruption(4))),
+            [
+                "#tester interrupted by SIGINT.",
+                "#tester requested 10, completed 4, failed 1, cancelled 6.",
+                "#tester finished. Failed 1 / 4",
+            ]
+        );
+        assert_eq!(
+            summary_lines(
+                &args(&["--score"]),
+                10,
+                &report(0, 0),
+                Some(&interruption(0))
+            ),
+            [
+                "#tester interrupted by SIGINT.",
+                "#tester requested 10, completed 0, failed 0, cancelled 10.",
+                "#tester 0 runs executed (interrupted before the first one finished).",
+            ]
+        );
+    }
+}
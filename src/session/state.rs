@@ -0,0 +1,274 @@
+//! Checkpoints of a session so an interrupted run can be resumed with `--resume`.
+
+use crate::{metadata::SessionMetadata, runs::Shard, Snapshot};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, io::Write, path::Path, time::Duration};
+
+const STATE_VERSION: u32 = 3;
+
+/// The parts of the command line that must match for a checkpoint to be resumable.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// The argv of every step of an iteration.
+    pub steps: Vec<Vec<String>>,
+    pub times: u64,
+    pub timeout: Option<Duration>,
+    pub score: bool,
+    pub score_step: usize,
+    #[serde(default)]
+    pub generator: Option<Vec<String>>,
+    #[serde(default)]
+    pub gen_seed_start: Option<u64>,
+    #[serde(default)]
+    pub compare_with: Option<Vec<String>>,
+    #[serde(default)]
+    pub validate: Option<Vec<String>>,
+    #[serde(default)]
+    pub check: Option<Vec<String>>,
+    #[serde(default)]
+    pub setup: Option<Vec<String>>,
+    #[serde(default)]
+    pub teardown: Option<Vec<String>>,
+    #[serde(default)]
+    pub cpu_limit: Option<u64>,
+    #[serde(default)]
+    pub shard: Option<Shard>,
+    #[serde(default)]
+    pub timeout_factor: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionState {
+    pub version: u32,
+    pub config: SessionConfig,
+    #[serde(default)]
+    pub metadata: SessionMetadata,
+    #[serde(flatten)]
+    pub snapshot: Snapshot,
+}
+
+impl SessionState {
+    pub fn new(config: SessionConfig, metadata: SessionMetadata, snapshot: Snapshot) -> Self {
+        SessionState {
+            version: STATE_VERSION,
+            config,
+            metadata,
+            snapshot,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read state file {}: {}", path.display(), err))?;
+        let state: SessionState = serde_json::from_str(&content)
+            .map_err(|err| format!("invalid state file {}: {}", path.display(), err))?;
+        if state.version != STATE_VERSION {
+            return Err(format!(
+                "state file {} has version {}, expected {}",
+                path.display(),
+                state.version,
+                STATE_VERSION
+            ));
+        }
+        Ok(state)
+    }
+
+    /// Explain how `config` differs from the configuration this state was written for.
+    pub fn mismatch(&self, config: &SessionConfig) -> Option<String> {
+        let saved = &self.config;
+        let describe = |what: &str, saved: String, current: String| {
+            Some(format!("{} was {}, now {}", what, saved, current))
+        };
+        if saved.steps != config.steps {
+            describe(
+                "commands",
+                format!("{:?}", saved.steps),
+                format!("{:?}", config.steps),
+            )
+        } else if saved.times != config.times {
+            describe("-n", saved.times.to_string(), config.times.to_string())
+        } else if saved.timeout != config.timeout {
+            describe(
+                "--timeout",
+                format!("{:?}", saved.timeout),
+                format!("{:?}", config.timeout),
+            )
+        } else if saved.score != config.score {
+            describe("--score", saved.score.to_string(), config.score.to_string())
+        } else if saved.score_step != config.score_step {
+            describe(
+                "--score-step",
+                (saved.score_step + 1).to_string(),
+                (config.score_step + 1).to_string(),
+            )
+        } else if saved.generator != config.generator {
+            describe(
+                "--gen",
+                format!("{:?}", saved.generator),
+                format!("{:?}", config.generator),
+            )
+        } else if saved.gen_seed_start != config.gen_seed_start {
+            describe(
+                "--gen-seed-start",
+                format!("{:?}", saved.gen_seed_start),
+                format!("{:?}", config.gen_seed_start),
+            )
+        } else if saved.compare_with != config.compare_with {
+            describe(
+                "--compare-with",
+                format!("{:?}", saved.compare_with),
+                format!("{:?}", config.compare_with),
+            )
+        } else if saved.validate != config.validate {
+            describe(
+                "--validate",
+                format!("{:?}", saved.validate),
+                format!("{:?}", config.validate),
+            )
+        } else if saved.check != config.check {
+            describe(
+                "--check",
+                format!("{:?}", saved.check),
+                format!("{:?}", config.check),
+            )
+        } else if saved.setup != config.setup {
+            describe(
+                "--setup",
+                format!("{:?}", saved.setup),
+                format!("{:?}", config.setup),
+            )
+        } else if saved.teardown != config.teardown {
+            describe(
+                "--teardown",
+                format!("{:?}", saved.teardown),
+                format!("{:?}", config.teardown),
+            )
+        } else if saved.cpu_limit != config.cpu_limit {
+            describe(
+                "--cpu-limit",
+                format!("{:?}", saved.cpu_limit),
+                format!("{:?}", config.cpu_limit),
+            )
+        } else if saved.shard != config.shard {
+            let describe_shard = |shard: Option<Shard>| {
+                shard.map_or_else(|| "none".to_string(), |shard| shard.to_string())
+            };
+            describe(
+                "--shard",
+                describe_shard(saved.shard),
+                describe_shard(config.shard),
+            )
+        } else if saved.timeout_factor != config.timeout_factor {
+            describe(
+                "--timeout-factor",
+                format!("{:?}", saved.timeout_factor),
+                format!("{:?}", config.timeout_factor),
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Write the state next to `path` first and rename it over, so a crash mid-write never
+    /// leaves a truncated state file behind.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        let mut file = fs::File::create(&tmp_path)?;
+        serde_json::to_writer(&mut file, self)?;
+        file.write_all(b"\n")?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SessionConfig {
+        SessionConfig {
+            steps: vec![vec!["./test".to_string(), "--fast".to_string()]],
+            times: 40,
+            timeout: Some(Duration::from_secs(5)),
+            score: false,
+            score_step: 0,
+            generator: None,
+            gen_seed_start: None,
+            compare_with: None,
+            validate: None,
+            check: None,
+            setup: None,
+            teardown: None,
+            cpu_limit: None,
+            shard: None,
+            timeout_factor: None,
+        }
+    }
+
+    fn state() -> SessionState {
+        let mut snapshot = Snapshot::default();
+        for index in [0, 1, 2, 7] {
+            snapshot.completed.insert(index);
+        }
+        snapshot.report.run_times = 4;
+        snapshot.report.fail_times = 1;
+        SessionState::new(config(), SessionMetadata::default(), snapshot)
+    }
+
+    fn path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tester-state-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = path("round-trip");
+        let state = state();
+        state.save(&path).unwrap();
+        let loaded = SessionState::load(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.config, state.config);
+        assert_eq!(loaded.snapshot, state.snapshot);
+        assert_eq!(loaded.mismatch(&config()), None);
+    }
+
+    #[test]
+    fn refuse_other_sessions() {
+        let state = state();
+        let mut other = config();
+        other.times = 50;
+        assert_eq!(state.mismatch(&other).as_deref(), Some("-n was 40, now 50"));
+        let mut other = config();
+        other.score_step = 1;
+        assert_eq!(
+            state.mismatch(&other).as_deref(),
+            Some("--score-step was 1, now 2")
+        );
+        let mut other = config();
+        other.timeout_factor = Some(3.0);
+        assert_eq!(
+            state.mismatch(&other).as_deref(),
+            Some("--timeout-factor was None, now Some(3.0)")
+        );
+    }
+
+    #[test]
+    fn refuse_other_versions() {
+        let path = path("version");
+        let mut json = serde_json::to_value(state()).unwrap();
+        json["version"] = (STATE_VERSION + 1).into();
+        fs::write(&path, json.to_string()).unwrap();
+        let loaded = SessionState::load(&path);
+        fs::write(&path, "{\"version\":").unwrap();
+        let truncated = SessionState::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.unwrap_err().contains(&format!(
+            "has version {}, expected {}",
+            STATE_VERSION + 1,
+            STATE_VERSION
+        )));
+        assert!(truncated.unwrap_err().starts_with("invalid state file"));
+    }
+}
@@ -0,0 +1,304 @@
+//! Minimizing a failing input by trying simple reductions and keeping those that still fail.
+
+use std::time::Instant;
+
+/// What shrinking an input came to.
+#[derive(Clone, Debug)]
+pub struct Shrunk {
+    /// The smallest failing input found.
+    pub input: Vec<u8>,
+    /// Reductions that were kept.
+    pub steps: usize,
+    /// Candidates tried.
+    pub trials: usize,
+    /// Whether the deadline stopped shrinking before a fixed point was reached.
+    pub out_of_time: bool,
+}
+
+struct Shrinker<'a, F> {
+    still_fails: &'a mut F,
+    deadline: Instant,
+    steps: usize,
+    trials: usize,
+}
+
+impl<F, E> Shrinker<'_, F>
+where
+    F: FnMut(&[u8]) -> Result<bool, E>,
+{
+    fn out_of_time(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+
+    /// Whether `candidate` still fails, counting it as a kept reduction if it does.
+    fn try_candidate(&mut self, candidate: &[u8]) -> Result<bool, E> {
+        if self.out_of_time() {
+            return Ok(false);
+        }
+        self.trials += 1;
+        let fails = (self.still_fails)(candidate)?;
+        self.steps += usize::from(fails);
+        Ok(fails)
+    }
+
+    /// Keep the first half of the lines as long as that still fails.
+    fn halve_prefix(&mut self, lines: &mut Vec<Vec<u8>>) -> Result<bool, E> {
+        let mut reduced = false;
+        while lines.len() > 1 {
+            let half = &lines[..lines.len() / 2];
+            if !self.try_candidate(&half.concat())? {
+                break;
+            }
+            lines.truncate(lines.len() / 2);
+            reduced = true;
+        }
+        Ok(reduced)
+    }
+
+    /// Drop chunks of lines, from halves down to single lines.
+    fn drop_lines(&mut self, lines: &mut Vec<Vec<u8>>) -> Result<bool, E> {
+        let mut reduced = false;
+        let mut chunk = lines.len() / 2;
+        while chunk >= 1 && !self.out_of_time() {
+            let mut start = 0;
+            while start < lines.len() && !self.out_of_time() {
+                let end = (start + chunk).min(lines.len());
+                let candidate = [&lines[..start], &lines[end..]].concat().concat();
+                if self.try_candidate(&candidate)? {
+                    lines.drain(start..end);
+                    reduced = true;
+                } else {
+                    start = end;
+                }
+            }
+            chunk /= 2;
+        }
+        Ok(reduced)
+    }
+
+    /// Halve every integer token, towards zero, one at a time.
+    fn halve_numbers(&mut self, lines: &mut [Vec<u8>]) -> Result<bool, E> {
+        let mut reduced = false;
+        for line_index in 0..lines.len() {
+            let mut start = 0;
+            while let Some((token_start, token_end)) = next_number(&lines[line_index], start) {
+                if self.out_of_time() {
+                    return Ok(reduced);
+                }
+                let line = &lines[line_index];
+                let token = std::str::from_utf8(&line[token_start..token_end])
+                    .expect("next_number only matches ASCII");
+                let halved = token.parse::<i64>().ok().filter(|&n| n != 0).map(|n| n / 2);
+                let Some(halved) = halved else {
+                    start = token_end;
+                    continue;
+                };
+                let new_line = [
+                    &line[..token_start],
+                    halved.to_string().as_bytes(),
+                    &line[token_end..],
+                ]
+                .concat();
+                let mut candidate = lines.to_vec();
+                candidate[line_index] = new_line.clone();
+                if self.try_candidate(&candidate.concat())? {
+                    lines[line_index] = new_line;
+                    reduced = true;
+                    // Try halving the same token again.
+                    start = token_start;
+                } else {
+                    start = token_end;
+                }
+            }
+        }
+        Ok(reduced)
+    }
+}
+
+/// The byte range of the first integer token (ASCII digits, maybe after a `-`) at or after
+/// `from`, not part of a longer word.
+fn next_number(line: &[u8], from: usize) -> Option<(usize, usize)> {
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_' || b == b'.';
+    let mut i = from;
+    while i < line.len() {
+        let start = i;
+        let digits_start = if line[i] == b'-' { i + 1 } else { i };
+        let boundary = start == 0 || !is_word(line[start - 1]);
+        let digits_end = line[digits_start.min(line.len())..]
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .map_or(line.len(), |n| digits_start + n);
+        if boundary
+            && digits_end > digits_start
+            && (digits_end == line.len() || !is_word(line[digits_end]))
+        {
+            return Some((start, digits_end));
+        }
+        i = digits_end.max(i + 1);
+    }
+    None
+}
+
+/// Shrink the failing `input` until no reduction applies or `deadline` passes.
+///
+/// `still_fails` runs a candidate and tells whether it fails the same way the original did.
+/// The reductions, applied in rounds until none keeps a candidate: keeping halves of the
+/// lines from the start, dropping chunks of lines, and halving integer tokens.
+pub fn shrink<F, E>(input: &[u8], deadline: Instant, mut still_fails: F) -> Result<Shrunk, E>
+where
+    F: FnMut(&[u8]) -> Result<bool, E>,
+{
+    let mut lines = input
+        .split_inclusive(|&b| b == b'\n')
+        .map(<[u8]>::to_vec)
+        .collect::<Vec<_>>();
+    let mut shrinker = Shrinker {
+        still_fails: &mut still_fails,
+        deadline,
+        steps: 0,
+        trials: 0,
+    };
+    loop {
+        let mut reduced = shrinker.halve_prefix(&mut lines)?;
+        reduced |= shrinker.drop_lines(&mut lines)?;
+        reduced |= shrinker.halve_numbers(&mut lines)?;
+        if !reduced || shrinker.out_of_time() {
+            break;
+        }
+    }
+    Ok(Shrunk {
+        input: lines.concat(),
+        steps: shrinker.steps,
+        trials: shrinker.trials,
+        out_of_time: shrinker.out_of_time(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{convert::Infallible, thread, time::Duration};
+
+    fn contains_42(candidate: &[u8]) -> Result<bool, Infallible> {
+        Ok(candidate.windows(2).any(|pair| pair == b"42"))
+    }
+
+    fn lines(text: &str) -> Vec<Vec<u8>> {
+        text.split_inclusive('\n')
+            .map(|line| line.as_bytes().to_vec())
+            .collect()
+    }
+
+    fn never() -> Instant {
+        Instant::now() + Duration::from_secs(3600)
+    }
+
+    #[test]
+    fn halve_prefix() {
+        let mut predicate = contains_42;
+        let mut shrinker = Shrinker {
+            still_fails: &mut predicate,
+            deadline: never(),
+            steps: 0,
+            trials: 0,
+        };
+        let mut input = lines("42\n1\n2\n3\n4\n5\n6\n7\n");
+        assert_eq!(shrinker.halve_prefix(&mut input), Ok(true));
+        assert_eq!(input, lines("42\n"));
+        assert_eq!((shrinker.steps, shrinker.trials), (3, 3));
+        // The first half no longer fails.
+        let mut input = lines("1\n2\n3\n42\n");
+        assert_eq!(shrinker.halve_prefix(&mut input), Ok(false));
+        assert_eq!(input, lines("1\n2\n3\n42\n"));
+    }
+
+    #[test]
+    fn drop_lines() {
+        let mut predicate = contains_42;
+        let mut shrinker = Shrinker {
+            still_fails: &mut predicate,
+            deadline: never(),
+            steps: 0,
+            trials: 0,
+        };
+        let mut input = lines("1\n2\n3\n42\n5\n6\n7\n");
+        assert_eq!(shrinker.drop_lines(&mut input), Ok(true));
+        assert_eq!(input, lines("42\n"));
+        let mut input = lines("42\n");
+        assert_eq!(shrinker.drop_lines(&mut input), Ok(false));
+    }
+
+    #[test]
+    fn halve_numbers() {
+        let mut predicate = contains_42;
+        let mut shrinker = Shrinker {
+            still_fails: &mut predicate,
+            deadline: never(),
+            steps: 0,
+            trials: 0,
+        };
+        // Numbers inside words are left alone, the others halved down to what still fails.
+        let mut input = lines("a 84 b\nx1000 -168 7 1.5\n");
+        assert_eq!(shrinker.halve_numbers(&mut input), Ok(true));
+        assert_eq!(input, lines("a 42 b\nx1000 0 0 1.5\n"));
+    }
+
+    #[test]
+    fn minimal() {
+        let input = (0..200)
+            .map(|n| format!("{} {}\n", n, n * 3))
+            .collect::<String>();
+        let shrunk = shrink(input.as_bytes(), never(), contains_42).unwrap();
+        // The line `14 42` comes before `42 126`, and its 14 halves down to 0.
+        assert_eq!(shrunk.input, b"0 42\n");
+        assert!(!shrunk.out_of_time);
+        assert!(shrunk.steps > 0 && shrunk.trials >= shrunk.steps);
+        // Dropping any line or halving any number of it no longer fails.
+        assert_eq!(
+            shrink(&shrunk.input, never(), contains_42).unwrap().steps,
+            0
+        );
+    }
+
+    #[test]
+    fn deadline() {
+        let input = (0..1000).map(|n| format!("{}\n", n)).collect::<String>();
+        let shrunk = shrink(input.as_bytes(), Instant::now(), contains_42).unwrap();
+        assert_eq!((shrunk.trials, shrunk.input.len()), (0, input.len()));
+        assert!(shrunk.out_of_time);
+
+        let started = Instant::now();
+        let slow = |candidate: &[u8]| {
+            thread::sleep(Duration::from_millis(10));
+            contains_42(candidate)
+        };
+        let shrunk = shrink(input.as_bytes(), started + Duration::from_millis(100), slow).unwrap();
+        assert!(shrunk.out_of_time);
+        // No trial starts after the deadline.
+        assert!(
+            started.elapsed() < Duration::from_millis(500),
+            "{:?}",
+            started.elapsed()
+        );
+        assert!(shrunk.trials <= 10, "{}", shrunk.trials);
+        assert!(contains_42(&shrunk.input).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let mut trials = 0;
+        let failing = |_: &[u8]| {
+            trials += 1;
+            if trials == 3 {
+                Err("spawn failed")
+            } else {
+                Ok(true)
+            }
+        };
+        assert_eq!(
+            shrink(b"1\n2\n3\n4\n", never(), failing).unwrap_err(),
+            "spawn failed"
+        );
+        assert_eq!(trials, 3);
+    }
+}
@@ -0,0 +1,64 @@
+//! Running the iterations in a random but reproducible order, see `--shuffle`, or after a
+//! random but reproducible delay, see `--jitter`.
+
+use crate::runs::RunSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The SplitMix64 generator: tiny, fast and good enough to shuffle with, and the same
+/// sequence for the same seed on every platform and version.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..bound`, `bound` being positive.
+    fn below(&mut self, bound: u64) -> u64 {
+        ((self.next() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+/// A seed for when none was given, different every time.
+pub fn new_seed() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64);
+    SplitMix64(now ^ (u64::from(std::process::id()) << 32)).next()
+}
+
+/// The indices of `runs` in the order given by `seed`, the same for the same runs and seed.
+pub fn shuffled(runs: &RunSet, seed: u64) -> Vec<u64> {
+    let mut order = runs.iter().collect::<Vec<_>>();
+    let mut rng = SplitMix64(seed);
+    // Fisher-Yates.
+    for i in (1..order.len()).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        order.swap(i, j);
+    }
+    order
+}
+
+/// A random delay before every iteration starts, to vary how parallel iterations interleave.
+/// Each iteration gets its own, given by the seed and its run index alone, so the same seed
+/// delays the same iterations the same whichever thread runs them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Jitter {
+    /// The longest delay.
+    pub max: Duration,
+    /// What the delays are drawn from.
+    pub seed: u64,
+}
+
+impl Jitter {
+    /// The delay of run `index`, uniform in `0..=max`.
+    pub fn delay(&self, index: u64) -> Duration {
+        let mut rng = SplitMix64(self.seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        let nanos = self.max.as_nanos().min(u64::MAX as u128 - 1) as u64;
+        Duration::from_nanos(rng.below(nanos + 1))
+    }
+}
@@ -0,0 +1,61 @@
+//! Controlling a session with signals on Unix, e.g. one running under nohup: SIGUSR1 asks for
+//! the status, SIGUSR2 pauses or resumes the runs.
+//!
+//! The handlers only set flags, the only thing safe in a signal handler; whoever runs the
+//! session polls them with [`status_requested`] and [`pause_toggled`].
+
+use std::{
+    io,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+static STATUS: AtomicBool = AtomicBool::new(false);
+/// SIGUSR2 arrived this many times since last polled.
+static PAUSE: AtomicU32 = AtomicU32::new(0);
+
+#[cfg(unix)]
+extern "C" fn on_usr1(_: libc::c_int) {
+    STATUS.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+extern "C" fn on_usr2(_: libc::c_int) {
+    PAUSE.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+fn handle(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) -> io::Result<()> {
+    // SAFETY: sigaction is plain data, and the handlers only touch atomics.
+    unsafe {
+        let mut action = std::mem::zeroed::<libc::sigaction>();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Handle SIGUSR1 and SIGUSR2 from now on instead of being killed by them. Does nothing
+/// where there are no such signals.
+pub fn install() -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        handle(libc::SIGUSR1, on_usr1)?;
+        handle(libc::SIGUSR2, on_usr2)?;
+    }
+    Ok(())
+}
+
+/// Whether SIGUSR1 arrived since the last call.
+pub fn status_requested() -> bool {
+    STATUS.swap(false, Ordering::Relaxed)
+}
+
+/// Whether SIGUSR2 arrived an odd number of times since the last call, i.e. the pause is to be
+/// toggled.
+pub fn pause_toggled() -> bool {
+    PAUSE.swap(0, Ordering::Relaxed) % 2 == 1
+}
@@ -0,0 +1,90 @@
+//! Where the stdout and stderr of the commands end up, see `--stdout-to` and `--stderr-to`.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// A destination named on the command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SinkTarget {
+    /// `inherit`: the commands write to tester's own stream as they go.
+    Inherit,
+    /// `null`: the output is dropped.
+    Null,
+    /// Any other value: the output is appended to this file.
+    File(PathBuf),
+}
+
+/// Parse `inherit`, `null` or a path.
+pub fn parse_target(s: &str) -> Result<SinkTarget, String> {
+    match s {
+        "" => Err("expected inherit, null or a path".to_string()),
+        "inherit" => Ok(SinkTarget::Inherit),
+        "null" => Ok(SinkTarget::Null),
+        path => Ok(SinkTarget::File(PathBuf::from(path))),
+    }
+}
+
+/// Where one output stream of the steps goes.
+#[derive(Clone, Debug, Default)]
+pub enum Sink {
+    /// Captured, then copied to the same stream of tester once the step finished.
+    #[default]
+    Copy,
+    /// Not captured: the steps write to tester's stream directly. Nothing can be read from
+    /// the stream, e.g. no score.
+    Inherit,
+    /// Captured, then dropped.
+    Null,
+    /// Captured, then appended to the file a whole step at a time, so the outputs of
+    /// concurrent runs never interleave.
+    File(Arc<Mutex<File>>),
+}
+
+impl Sink {
+    /// Open `target`, creating its file if needed. Give every target naming the same file
+    /// the same sink so their writes are serialized too.
+    pub fn open(target: &SinkTarget) -> Result<Sink, String> {
+        Ok(match target {
+            SinkTarget::Inherit => Sink::Inherit,
+            SinkTarget::Null => Sink::Null,
+            SinkTarget::File(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| format!("cannot open {}: {}", path.display(), err))?;
+                Sink::File(Arc::new(Mutex::new(file)))
+            }
+        })
+    }
+
+    /// Whether the steps' stream is captured, so it can be scored, compared or saved.
+    pub fn captures(&self) -> bool {
+        !matches!(self, Sink::Inherit)
+    }
+
+    /// Whether [`write`](Self::write) sends output to tester's own stream.
+    pub(crate) fn to_ours(&self) -> bool {
+        matches!(self, Sink::Copy | Sink::Inherit)
+    }
+
+    /// Send `output` captured from a step to its destination, `ours` being what [`Copy`] and
+    /// [`Inherit`] mean.
+    ///
+    /// [`Copy`]: Sink::Copy
+    /// [`Inherit`]: Sink::Inherit
+    pub(crate) fn write(&self, output: &[u8], mut ours: impl Write) -> io::Result<()> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Sink::Copy | Sink::Inherit => ours.write_all(output),
+            Sink::Null => Ok(()),
+            Sink::File(file) => file.lock().unwrap().write_all(output),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Keeping track of the slowest runs of a session, to find the outliers behind a bad average.
+
+use crate::{RunObserver, RunResult, RunStatus};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, cmp::Reverse, collections::BinaryHeap, sync::Mutex};
+
+/// One of the slowest runs.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SlowRun {
+    /// Run index.
+    pub index: u64,
+    /// Index of the worker thread that ran it.
+    pub thread: usize,
+    /// How the run ended.
+    pub status: RunStatus,
+    /// Why the run did not pass, e.g. `exit status: 1`.
+    pub reason: Option<String>,
+    /// Wall-clock seconds the run took.
+    pub duration: f64,
+    /// The run's score, if it was scored.
+    pub score: Option<f64>,
+}
+
+/// Orders runs by duration, then by index so equally slow runs keep a stable order.
+struct ByDuration(SlowRun);
+
+impl PartialEq for ByDuration {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByDuration {}
+
+impl PartialOrd for ByDuration {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ByDuration {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .duration
+            .total_cmp(&other.0.duration)
+            .then(other.0.index.cmp(&self.0.index))
+    }
+}
+
+/// An observer keeping the `limit` slowest runs, with one heap per worker thread so threads
+/// never wait for each other.
+pub struct SlowestRuns {
+    limit: usize,
+    heaps: Vec<Mutex<BinaryHeap<Reverse<ByDuration>>>>,
+}
+
+impl SlowestRuns {
+    /// Keep the `limit` slowest of the runs of `threads` worker threads.
+    pub fn new(limit: usize, threads: usize) -> Self {
+        SlowestRuns {
+            limit,
+            heaps: (0..threads.max(1)).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    /// The slowest runs so far, slowest first.
+    pub fn runs(&self) -> Vec<SlowRun> {
+        let mut runs = self
+            .heaps
+            .iter()
+            .flat_map(|heap| {
+                let heap = heap.lock().unwrap();
+                heap.iter()
+                    .map(|Reverse(run)| ByDuration(run.0.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        runs.sort_by(|a, b| b.cmp(a));
+        runs.truncate(self.limit);
+        runs.into_iter().map(|run| run.0).collect()
+    }
+}
+
+impl RunObserver for SlowestRuns {
+    fn on_run(&self, result: &RunResult) {
+        if self.limit == 0 || !result.status.is_counted() {
+            return;
+        }
+        let run = ByDuration(SlowRun {
+            index: result.index,
+            thread: result.thread,
+            status: result.status,
+            reason: result.failure.as_ref().map(|failure| failure.to_string()),
+            duration: result.duration.as_secs_f64(),
+            score: result.score,
+        });
+        let mut heap = self.heaps[result.thread % self.heaps.len()].lock().unwrap();
+        if heap.len() < self.limit {
+            heap.push(Reverse(run));
+        } else if heap.peek().is_some_and(|Reverse(fastest)| run > *fastest) {
+            heap.pop();
+            heap.push(Reverse(run));
+        }
+    }
+}
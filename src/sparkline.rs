@@ -0,0 +1,184 @@
+//! A one-line chart of a series of values in the order the runs finished, to spot drift and
+//! periodic slowdowns.
+
+use crate::{RunObserver, RunResult};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+/// The blocks drawn for the lowest to the highest values.
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// At most this many buckets are kept however many runs there are.
+const MAX_BUCKETS: usize = 1024;
+
+/// Which value of a run is charted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Measure {
+    /// The score of passed runs.
+    Score,
+    /// How long every counted run took, in seconds.
+    Duration,
+}
+
+/// An observer averaging one value of the runs over buckets of runs that finished one after
+/// the other, so even very long sessions take constant memory. Runs are bucketed by when they
+/// finished rather than by index, which parallel runs finish out of.
+pub struct Series {
+    measure: Measure,
+    times: u64,
+    /// Runs finished so far, whether or not they have a value.
+    finished: AtomicU64,
+    /// Sum and count of the values of every bucket.
+    buckets: Mutex<Vec<(f64, u64)>>,
+}
+
+impl Series {
+    /// Chart `measure` over the `times` runs of a session.
+    pub fn new(measure: Measure, times: u64) -> Self {
+        let buckets = times.clamp(1, MAX_BUCKETS as u64) as usize;
+        Series {
+            measure,
+            times,
+            finished: AtomicU64::new(0),
+            buckets: Mutex::new(vec![(0.0, 0); buckets]),
+        }
+    }
+
+    /// The values averaged over at most `width` buckets in finishing order, `None` for buckets
+    /// without a value.
+    pub fn values(&self, width: usize) -> Vec<Option<f64>> {
+        let buckets = self.buckets.lock().unwrap();
+        let width = width.clamp(1, buckets.len());
+        let mut merged = vec![(0.0, 0); width];
+        for (index, (sum, count)) in buckets.iter().enumerate() {
+            let merged = &mut merged[index * width / buckets.len()];
+            merged.0 += sum;
+            merged.1 += count;
+        }
+        merged
+            .into_iter()
+            .map(|(sum, count)| (count > 0).then(|| sum / count as f64))
+            .collect()
+    }
+}
+
+impl RunObserver for Series {
+    fn on_run(&self, result: &RunResult) {
+        let position = self.finished.fetch_add(1, Ordering::Relaxed);
+        if !result.status.is_counted() {
+            return;
+        }
+        let value = match self.measure {
+            Measure::Score => result.score,
+            Measure::Duration => Some(result.duration.as_secs_f64()),
+        };
+        let Some(value) = value.filter(|value| value.is_finite()) else {
+            return;
+        };
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = u128::from(position) * buckets.len() as u128 / u128::from(self.times.max(1));
+        if let Some((sum, count)) = buckets.get_mut(bucket as usize) {
+            *sum += value;
+            *count += 1;
+        }
+    }
+}
+
+/// Draw `values` with one block each, scaled between their minimum and maximum, a space for
+/// `None` or a value that is not finite. A constant series is drawn at mid height.
+pub fn render(values: &[Option<f64>]) -> String {
+    let values = values
+        .iter()
+        .map(|value| value.filter(|value| value.is_finite()))
+        .collect::<Vec<_>>();
+    let known = values.iter().flatten();
+    let min = known.clone().copied().fold(f64::INFINITY, f64::min);
+    let max = known.copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| match value {
+            None => ' ',
+            Some(_) if max <= min => BLOCKS[BLOCKS.len() / 2 - 1],
+            Some(value) => {
+                let level = (value - min) / (max - min) * (BLOCKS.len() - 1) as f64;
+                BLOCKS[(level.round() as usize).min(BLOCKS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RunStatus;
+    use std::time::{Duration, Instant};
+
+    fn scored(index: u64, score: f64) -> RunResult {
+        RunResult {
+            index,
+            thread: 0,
+            started: Instant::now(),
+            status: RunStatus::Passed,
+            duration: Duration::from_millis(1),
+            score: Some(score),
+            time_score: false,
+            seed: None,
+            variant: None,
+            failure: None,
+            input: None,
+            input_truncated: false,
+            comparison: None,
+            exit_code: None,
+            stdout: vec![],
+            stderr: vec![],
+            steps: vec![],
+            setup: None,
+            teardown: None,
+            output_suppressed: false,
+        }
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(render(&[]), "");
+        assert_eq!(render(&[None, None]), "  ");
+        let series = Series::new(Measure::Score, 0);
+        assert_eq!(series.values(40), [None]);
+        assert_eq!(render(&series.values(40)), " ");
+    }
+
+    #[test]
+    fn constant() {
+        assert_eq!(render(&[Some(2.5); 4]), "▄▄▄▄");
+        assert_eq!(render(&[Some(0.0), None, Some(0.0)]), "▄ ▄");
+    }
+
+    #[test]
+    fn ramp() {
+        let values = (0..8).map(|n| Some(f64::from(n))).collect::<Vec<_>>();
+        assert_eq!(render(&values), "▁▂▃▄▅▆▇█");
+        assert_eq!(render(&[Some(-1.0), Some(1.0), Some(0.0)]), "▁█▅");
+    }
+
+    #[test]
+    fn not_finite() {
+        let values = [
+            Some(1.0),
+            Some(f64::NAN),
+            Some(f64::INFINITY),
+            Some(f64::NEG_INFINITY),
+            Some(3.0),
+        ];
+        assert_eq!(render(&values), "▁   █");
+        assert_eq!(render(&[Some(f64::NAN), Some(f64::INFINITY)]), "  ");
+
+        // The series leaves them out of the averages.
+        let series = Series::new(Measure::Score, 4);
+        for (index, score) in [1.0, f64::NAN, f64::INFINITY, 3.0].into_iter().enumerate() {
+            series.on_run(&scored(index as u64, score));
+        }
+        assert_eq!(series.values(4), [Some(1.0), None, None, Some(3.0)]);
+        assert_eq!(series.values(2), [Some(1.0), Some(3.0)]);
+    }
+}
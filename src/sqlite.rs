@@ -0,0 +1,345 @@
+//! Appending every session and its runs to an SQLite database, see `--sqlite`.
+//!
+//! The database is written by the `sqlite3` command-line shell, fed SQL on its stdin, so
+//! tester links no SQLite library. The runs are inserted in transactions of up to
+//! [`BATCH_RUNS`] runs, committed at least every [`COMMIT_INTERVAL`] even while no run
+//! finishes, so a session that is interrupted or killed leaves all but its last runs in the
+//! database.
+
+use crate::{command::shell_quote, export, summary::SessionSummary, RunObserver, RunResult};
+use std::{
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// The shell run to write the database.
+const SQLITE3: &str = "sqlite3";
+/// At most this many runs are inserted by one transaction.
+pub const BATCH_RUNS: u64 = 1000;
+/// The runs inserted are committed at least this often.
+pub const COMMIT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long to wait for another tester writing the same database.
+const BUSY_TIMEOUT_MS: u32 = 10_000;
+
+/// The statements bringing a database from schema version `i` to `i + 1`, applied in order
+/// to databases of an older version. Version 0 is a new database.
+const MIGRATIONS: [&str; 1] = ["\
+CREATE TABLE sessions (
+    id INTEGER PRIMARY KEY,
+    timestamp TEXT NOT NULL,
+    command TEXT NOT NULL,
+    flags TEXT NOT NULL,
+    label TEXT,
+    run_times INTEGER,
+    fail_times INTEGER,
+    timeout_times INTEGER,
+    mean_duration_ms REAL,
+    mean_score REAL
+);
+CREATE TABLE runs (
+    session_id INTEGER NOT NULL REFERENCES sessions (id),
+    \"index\" INTEGER NOT NULL,
+    thread INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    duration_ms REAL NOT NULL,
+    score REAL,
+    PRIMARY KEY (session_id, \"index\")
+);
+"];
+
+/// `text` as an SQL string literal.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+/// `value` as an SQL literal, `NULL` for none and for values SQLite has no literal for.
+fn number(value: Option<f64>) -> String {
+    match value.filter(|value| value.is_finite()) {
+        Some(value) => format!("{:?}", value),
+        None => "NULL".to_string(),
+    }
+}
+
+/// `err` from starting the shell, saying what is missing if it is not installed.
+fn start_error(err: io::Error) -> io::Error {
+    match err.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            err.kind(),
+            format!("cannot run {}, is SQLite installed?", SQLITE3),
+        ),
+        _ => err,
+    }
+}
+
+/// Fail unless the shell can be run, so a session whose database cannot be written stops
+/// before its first run.
+pub fn check_shell() -> io::Result<()> {
+    let status = Command::new(SQLITE3)
+        .arg("-version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(start_error)?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "{} -version failed ({})",
+            SQLITE3, status
+        )));
+    }
+    Ok(())
+}
+
+/// The shell on `path`, reading SQL from a pipe, in a process group of its own so a ctrl-c
+/// in the terminal leaves it to finish writing.
+fn shell(path: &Path, stdout: Stdio) -> io::Result<Child> {
+    let mut command = Command::new(SQLITE3);
+    command
+        .arg("-batch")
+        .arg("-bail")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        .stderr(Stdio::piped());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    #[cfg(windows)]
+    std::os::windows::process::CommandExt::creation_flags(
+        &mut command,
+        windows_sys::Win32::System::Threading::CREATE_NEW_PROCESS_GROUP,
+    );
+    command.spawn().map_err(start_error)
+}
+
+/// Wait for `child`, failing with what it printed on stderr unless it succeeded.
+fn wait(mut child: Child) -> io::Result<()> {
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        pipe.read_to_string(&mut stderr)?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        let stderr = stderr.trim();
+        return Err(io::Error::other(if stderr.is_empty() {
+            format!("{} failed ({})", SQLITE3, status)
+        } else {
+            format!("{} failed: {}", SQLITE3, stderr)
+        }));
+    }
+    Ok(())
+}
+
+/// The schema version of the database at `path`, creating it and its version table if needed.
+fn schema_version(path: &Path) -> io::Result<usize> {
+    let mut child = shell(path, Stdio::piped())?;
+    let script = format!(
+        ".timeout {}\n\
+         CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL);\n\
+         SELECT coalesce(max(version), 0) FROM schema_version;\n",
+        BUSY_TIMEOUT_MS
+    );
+    child.stdin.take().unwrap().write_all(script.as_bytes())?;
+    let mut stdout = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut stdout)?;
+    wait(child)?;
+    stdout.trim().parse().map_err(|_| {
+        io::Error::other(format!(
+            "unexpected schema version `{}` from {}",
+            stdout.trim(),
+            SQLITE3
+        ))
+    })
+}
+
+/// The shell writing the database, with the transaction of the runs not committed yet open.
+struct Shell {
+    child: Child,
+    input: BufWriter<ChildStdin>,
+    /// Runs inserted since the last commit.
+    runs: u64,
+}
+
+impl Shell {
+    /// Insert the run of `result`, committing those inserted so far if there are enough of
+    /// them.
+    fn insert(&mut self, result: &RunResult) -> io::Result<()> {
+        writeln!(
+            self.input,
+            "INSERT INTO runs VALUES ((SELECT id FROM temp.session), {}, {}, {}, {}, {});",
+            result.index,
+            result.thread,
+            quote(export::status_name(result.status)),
+            number(Some(result.duration.as_secs_f64() * 1000.0)),
+            number(result.score)
+        )?;
+        self.runs += 1;
+        if self.runs >= BATCH_RUNS {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Commit the runs inserted since the last commit, if any.
+    fn commit(&mut self) -> io::Result<()> {
+        if self.runs > 0 {
+            self.input.write_all(b"COMMIT;\nBEGIN;\n")?;
+            self.input.flush()?;
+            self.runs = 0;
+        }
+        Ok(())
+    }
+}
+
+/// The shell of `shell` after writing to it failed with `err`, ended so what it said is
+/// reported instead of the broken pipe, which it usually explains.
+fn close(shell: &mut Option<Shell>, err: io::Error) -> io::Error {
+    match shell.take() {
+        Some(Shell { child, input, .. }) => {
+            drop(input);
+            wait(child).err().unwrap_or(err)
+        }
+        None => err,
+    }
+}
+
+/// An observer inserting every run into the `runs` table of a database as it finished, after
+/// the session was added to its `sessions` table. The totals of the session are filled in by
+/// [`finish`](Self::finish).
+pub struct SqliteWriter {
+    /// The shell until finished, or until writing to it failed, which is warned about once.
+    shell: Arc<Mutex<Option<Shell>>>,
+    /// Ends the thread committing every [`COMMIT_INTERVAL`] once dropped.
+    committer: Mutex<Option<(Sender<()>, JoinHandle<()>)>>,
+}
+
+impl SqliteWriter {
+    /// Open the database at `path`, creating it and migrating its schema as needed, and add a
+    /// session started now running `command`; `flags` are the arguments tester was given.
+    pub fn open(
+        path: &Path,
+        command: &str,
+        flags: &[String],
+        label: Option<&str>,
+    ) -> io::Result<Self> {
+        let version = schema_version(path)?;
+        if version > MIGRATIONS.len() {
+            return Err(io::Error::other(format!(
+                "schema version {} is newer than this tester knows, {}",
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+        let mut child = shell(path, Stdio::null())?;
+        let mut input = BufWriter::new(child.stdin.take().unwrap());
+        write!(input, ".timeout {}\nBEGIN IMMEDIATE;\n", BUSY_TIMEOUT_MS)?;
+        for (version, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            writeln!(input, "{}", migration)?;
+            writeln!(
+                input,
+                "DELETE FROM schema_version; INSERT INTO schema_version VALUES ({});",
+                version + 1
+            )?;
+        }
+        let flags = flags
+            .iter()
+            .map(|flag| shell_quote(flag))
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(
+            input,
+            "INSERT INTO sessions (timestamp, command, flags, label) \
+             VALUES (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'), {}, {}, {});\n\
+             CREATE TEMP TABLE session AS SELECT last_insert_rowid() AS id;\n\
+             COMMIT;\nBEGIN;",
+            quote(command),
+            quote(&flags),
+            label.map_or("NULL".to_string(), quote)
+        )?;
+        input.flush()?;
+        let shell = Arc::new(Mutex::new(Some(Shell {
+            child,
+            input,
+            runs: 0,
+        })));
+        let (stop, stopped) = mpsc::channel();
+        let committed = shell.clone();
+        let committer = thread::spawn(move || {
+            while stopped.recv_timeout(COMMIT_INTERVAL) == Err(RecvTimeoutError::Timeout) {
+                let mut shell = committed.lock().unwrap();
+                let Some(open) = shell.as_mut() else {
+                    return;
+                };
+                if let Err(err) = open.commit() {
+                    let err = close(&mut shell, err);
+                    warn!(
+                        "#tester failed to save runs to the database, no more runs are: {}",
+                        err
+                    );
+                    return;
+                }
+            }
+        });
+        Ok(SqliteWriter {
+            shell,
+            committer: Mutex::new(Some((stop, committer))),
+        })
+    }
+
+    /// Commit the last runs, fill in the totals of the session from its `summary` and wait
+    /// for the database to be written.
+    pub fn finish(&self, summary: &SessionSummary) -> io::Result<()> {
+        if let Some((stop, committer)) = self.committer.lock().unwrap().take() {
+            drop(stop);
+            let _ = committer.join();
+        }
+        let Some(mut shell) = self.shell.lock().unwrap().take() else {
+            return Ok(());
+        };
+        let report = &summary.report;
+        let written = shell
+            .commit()
+            .and_then(|()| {
+                writeln!(
+                    shell.input,
+                    "UPDATE sessions SET run_times = {}, fail_times = {}, timeout_times = {}, \
+                     mean_duration_ms = {}, mean_score = {} \
+                     WHERE id = (SELECT id FROM temp.session);\n\
+                     COMMIT;",
+                    report.run_times,
+                    report.fail_times,
+                    report.timeout_times + report.relative_timeout_times,
+                    number(report.times.mean().map(|mean| mean * 1000.0)),
+                    number(report.scores.mean())
+                )
+            })
+            .and_then(|()| shell.input.flush());
+        // Closing its stdin ends the shell.
+        let Shell { child, input, .. } = shell;
+        drop(input);
+        let waited = wait(child);
+        written.and(waited)
+    }
+}
+
+impl RunObserver for SqliteWriter {
+    fn on_run(&self, result: &RunResult) {
+        let mut shell = self.shell.lock().unwrap();
+        let Some(open) = shell.as_mut() else {
+            return;
+        };
+        if let Err(err) = open.insert(result) {
+            let err = close(&mut shell, err);
+            warn!(
+                "#tester failed to save run {} to the database, no more runs are: {}",
+                result.index, err
+            );
+        }
+    }
+}
@@ -0,0 +1,390 @@
+//! Tallies accumulated over the runs of a session.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, time::Duration};
+
+/// Count, sum and extremes of a series of values, e.g. the scores of every passed run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    /// Number of values added.
+    pub count: u64,
+    /// Sum of all values added.
+    pub sum: f64,
+    /// Smallest value added, `0.0` while empty.
+    pub min: f64,
+    /// Largest value added, `0.0` while empty.
+    pub max: f64,
+    /// The low-order bits lost from `sum` so far, so billions of values still add up exactly.
+    #[serde(skip)]
+    compensation: f64,
+}
+
+impl Stats {
+    /// Add one value to the series.
+    pub fn add(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        self.add_to_sum(value);
+    }
+
+    /// Neumaier's compensated summation: keep what `sum + value` rounds off.
+    fn add_to_sum(&mut self, value: f64) {
+        let sum = self.sum + value;
+        if self.sum.abs() >= value.abs() {
+            self.compensation += (self.sum - sum) + value;
+        } else {
+            self.compensation += (value - sum) + self.sum;
+        }
+        self.sum = sum;
+    }
+
+    /// Sum of all values added, with the compensation for rounding applied.
+    pub fn total(&self) -> f64 {
+        self.sum + self.compensation
+    }
+
+    /// Add all values of `other` to the series.
+    pub fn merge(&mut self, other: &Stats) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        self.count += other.count;
+        self.add_to_sum(other.sum);
+        self.compensation += other.compensation;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// The average value, if any value was added.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.total() / self.count as f64)
+    }
+}
+
+/// How often one step of the iteration ran and failed, and how long it took in total.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StepStats {
+    /// Number of times the step was started.
+    pub run_times: u64,
+    /// Number of times the step failed, which also ended its iteration.
+    pub fail_times: u64,
+    /// Wall-clock time spent in the step over all its runs, written as seconds.
+    #[serde(with = "seconds")]
+    pub total_time: Duration,
+}
+
+impl StepStats {
+    /// Add the runs tallied in `other`.
+    pub fn append(&mut self, other: &StepStats) {
+        self.run_times += other.run_times;
+        self.fail_times += other.fail_times;
+        self.total_time += other.total_time;
+    }
+
+    /// Average wall-clock time of one run of the step, zero if it never ran.
+    pub fn average_time(&self) -> Duration {
+        if self.run_times == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = self.total_time.as_nanos() / u128::from(self.run_times);
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+/// (De)serializing a [`Duration`] as seconds, like every other time in the JSON tester writes.
+/// Files of earlier versions, which wrote `{"secs": .., "nanos": ..}`, can still be read.
+mod seconds {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Written {
+            Seconds(f64),
+            Legacy(Duration),
+        }
+        match Written::deserialize(deserializer)? {
+            Written::Seconds(seconds) => {
+                Duration::try_from_secs_f64(seconds).map_err(de::Error::custom)
+            }
+            Written::Legacy(duration) => Ok(duration),
+        }
+    }
+}
+
+/// The normal quantile of a two-sided 95% interval.
+const Z_TWO_SIDED_95: f64 = 1.959964;
+/// The normal quantile of a one-sided 95% bound.
+const Z_ONE_SIDED_95: f64 = 1.644854;
+
+/// A failure rate observed over a number of runs, with its 95% confidence interval.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FailureRate {
+    /// Failures divided by runs.
+    pub observed: f64,
+    /// Lower bound of the interval.
+    pub low: f64,
+    /// Upper bound of the interval.
+    pub high: f64,
+}
+
+impl FailureRate {
+    /// The Wilson score interval of `failures` out of `runs`, `None` without runs. With no
+    /// failure, or only failures, the interval is one-sided: only one bound can be estimated.
+    pub fn of(failures: u64, runs: u64) -> Option<Self> {
+        if runs == 0 {
+            return None;
+        }
+        let n = runs as f64;
+        let observed = failures as f64 / n;
+        let z = if failures == 0 || failures == runs {
+            Z_ONE_SIDED_95
+        } else {
+            Z_TWO_SIDED_95
+        };
+        let z2 = z * z;
+        let center = (observed + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let half_width =
+            z / (1.0 + z2 / n) * (observed * (1.0 - observed) / n + z2 / (4.0 * n * n)).sqrt();
+        let (low, high) = match failures {
+            0 => (0.0, center + half_width),
+            _ if failures == runs => (center - half_width, 1.0),
+            _ => (center - half_width, center + half_width),
+        };
+        Some(FailureRate {
+            observed,
+            low: low.clamp(0.0, 1.0),
+            high: high.clamp(0.0, 1.0),
+        })
+    }
+
+    /// Whether the true rate may be `target` or lower, i.e. `target` is not below the interval.
+    pub fn allows(&self, target: f64) -> bool {
+        self.low <= target
+    }
+}
+
+/// Summary statistics of a series of values whose individual values were all kept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Distribution {
+    /// Number of values.
+    pub count: usize,
+    /// Average value.
+    pub mean: f64,
+    /// Population standard deviation.
+    pub std_dev: f64,
+    /// Smallest value.
+    pub min: f64,
+    /// Median.
+    pub p50: f64,
+    /// 90th percentile.
+    pub p90: f64,
+    /// 99th percentile.
+    pub p99: f64,
+    /// Largest value.
+    pub max: f64,
+}
+
+impl Distribution {
+    /// The distribution of `values`, `None` if there are none. NaNs sort last.
+    pub fn of(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let count = sorted.len();
+        let mean = sorted.iter().sum::<f64>() / count as f64;
+        let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+        Some(Distribution {
+            count,
+            mean,
+            std_dev: variance.sqrt(),
+            min: sorted[0],
+            p50: percentile(&sorted, 50.0),
+            p90: percentile(&sorted, 90.0),
+            p99: percentile(&sorted, 99.0),
+            max: sorted[count - 1],
+        })
+    }
+}
+
+/// The median of the last values of a series, e.g. the durations of the last passed runs. The
+/// values are also kept sorted, so reading the median costs nothing and adding a value a
+/// shift of the window.
+#[derive(Clone, Debug)]
+pub struct RollingMedian {
+    window: usize,
+    /// Number of values added, also those out of the window by now.
+    count: u64,
+    /// The values in the window, oldest first.
+    order: VecDeque<f64>,
+    /// The values in the window, ascending.
+    sorted: Vec<f64>,
+}
+
+impl RollingMedian {
+    /// The median of the last `window` values added, at least one.
+    pub fn new(window: usize) -> Self {
+        RollingMedian {
+            window: window.max(1),
+            count: 0,
+            order: VecDeque::new(),
+            sorted: vec![],
+        }
+    }
+
+    /// Add `value`, dropping the oldest value once the window is full.
+    pub fn add(&mut self, value: f64) {
+        if self.order.len() == self.window {
+            let oldest = self.order.pop_front().expect("the window is full");
+            let at = self.sorted.partition_point(|&sorted| sorted < oldest);
+            self.sorted.remove(at);
+        }
+        let at = self.sorted.partition_point(|&sorted| sorted < value);
+        self.sorted.insert(at, value);
+        self.order.push_back(value);
+        self.count += 1;
+    }
+
+    /// Number of values added so far.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The median of the values in the window, the lower middle one of an even number of
+    /// them, `None` while empty.
+    pub fn median(&self) -> Option<f64> {
+        let middle = self.sorted.len().checked_sub(1)? / 2;
+        Some(self.sorted[middle])
+    }
+}
+
+/// The `p`th percentile of the non-empty, ascending `sorted`, by the nearest-rank method.
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 1e-4
+    }
+
+    #[test]
+    fn wilson_interval() {
+        let rate = FailureRate::of(10, 100).unwrap();
+        assert_eq!(rate.observed, 0.1);
+        assert!(close(rate.low, 0.05523), "{:?}", rate);
+        assert!(close(rate.high, 0.17437), "{:?}", rate);
+        assert!(rate.allows(0.06));
+        assert!(!rate.allows(0.05));
+        assert_eq!(FailureRate::of(0, 0), None);
+    }
+
+    #[test]
+    fn wilson_interval_one_sided() {
+        let none_failed = FailureRate::of(0, 20).unwrap();
+        assert_eq!(none_failed.low, 0.0);
+        assert!(close(none_failed.high, 0.11916), "{:?}", none_failed);
+        let all_failed = FailureRate::of(20, 20).unwrap();
+        assert!(close(all_failed.low, 0.88084), "{:?}", all_failed);
+        assert_eq!(all_failed.high, 1.0);
+    }
+
+    #[test]
+    fn wilson_interval_narrows() {
+        let few = FailureRate::of(1, 10).unwrap();
+        let many = FailureRate::of(1_000, 10_000).unwrap();
+        assert!(many.high - many.low < few.high - few.low);
+        assert!(many.low < 0.1 && 0.1 < many.high);
+    }
+
+    #[test]
+    fn compensated_sum() {
+        let mut stats = Stats::default();
+        stats.add(1e16);
+        for _ in 0..10 {
+            stats.add(1.0);
+        }
+        assert_eq!(stats.total(), 1e16 + 10.0);
+        assert_eq!((stats.count, stats.min, stats.max), (11, 1.0, 1e16));
+
+        let mut merged = Stats::default();
+        merged.merge(&Stats::default());
+        merged.merge(&stats);
+        merged.merge(&stats);
+        assert_eq!(merged.total(), 2e16 + 20.0);
+        assert_eq!(merged.count, 22);
+        assert_eq!(Stats::default().mean(), None);
+    }
+
+    #[test]
+    fn step_times_as_seconds() {
+        let stats = StepStats {
+            run_times: 2,
+            fail_times: 1,
+            total_time: Duration::from_millis(1500),
+        };
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(json, r#"{"run_times":2,"fail_times":1,"total_time":1.5}"#);
+        assert_eq!(serde_json::from_str::<StepStats>(&json).unwrap(), stats);
+        let legacy = r#"{"run_times":2,"fail_times":1,"total_time":{"secs":1,"nanos":500000000}}"#;
+        assert_eq!(serde_json::from_str::<StepStats>(legacy).unwrap(), stats);
+        assert!(serde_json::from_str::<StepStats>(
+            r#"{"run_times":2,"fail_times":1,"total_time":-1}"#
+        )
+        .is_err());
+        assert_eq!(stats.average_time(), Duration::from_millis(750));
+    }
+
+    #[test]
+    fn distribution() {
+        let values = (1..=100).rev().map(f64::from).collect::<Vec<_>>();
+        let distribution = Distribution::of(&values).unwrap();
+        assert_eq!(distribution.count, 100);
+        assert_eq!(distribution.mean, 50.5);
+        assert_eq!((distribution.min, distribution.max), (1.0, 100.0));
+        assert_eq!(
+            (distribution.p50, distribution.p90, distribution.p99),
+            (50.0, 90.0, 99.0)
+        );
+        assert_eq!(Distribution::of(&[]), None);
+        assert_eq!(percentile(&[3.0], 99.0), 3.0);
+    }
+
+    #[test]
+    fn rolling_median() {
+        let mut median = RollingMedian::new(3);
+        assert_eq!(median.median(), None);
+        for (value, expected) in [
+            (5.0, 5.0),
+            (1.0, 1.0),
+            (3.0, 3.0),
+            (10.0, 3.0),
+            (20.0, 10.0),
+        ] {
+            median.add(value);
+            assert_eq!(median.median(), Some(expected));
+        }
+        assert_eq!(median.count(), 5);
+    }
+}
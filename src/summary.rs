@@ -0,0 +1,95 @@
+//! The summary of a whole session written with `--json`.
+
+use crate::{
+    baseline::BaselineComparison, metadata::SessionMetadata, objective::BestRun, slowest::SlowRun,
+    sweep::VariantReport, timeline::Interval, SessionReport,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, io, path::Path};
+
+/// How long spawning commands takes, measured with `--calibrate-spawn`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpawnOverhead {
+    /// Seconds to spawn and reap a command doing nothing, the median of `samples` runs.
+    pub per_command: f64,
+    /// Runs of the command doing nothing.
+    pub samples: u64,
+    /// Commands every run spawns: its steps, generator, validator, reference, checker,
+    /// setup and teardown.
+    pub commands_per_run: u64,
+    /// The mean wall-clock seconds of the runs as measured.
+    pub mean_duration: Option<f64>,
+    /// The mean wall-clock seconds of the runs without the spawn overhead of their commands.
+    pub adjusted_mean_duration: Option<f64>,
+}
+
+impl SpawnOverhead {
+    /// The overhead of `commands_per_run` commands of `per_command` each, taken off
+    /// `mean_duration`.
+    pub fn new(
+        per_command: f64,
+        samples: u64,
+        commands_per_run: u64,
+        mean_duration: Option<f64>,
+    ) -> Self {
+        SpawnOverhead {
+            per_command,
+            samples,
+            commands_per_run,
+            mean_duration,
+            adjusted_mean_duration: mean_duration
+                .map(|mean| (mean - per_command * commands_per_run as f64).max(0.0)),
+        }
+    }
+}
+
+/// What a session added up to, with what it ran.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SessionSummary {
+    /// The session's label and tags.
+    #[serde(flatten)]
+    pub metadata: SessionMetadata,
+    /// The pipeline every run executed, as shown in the human summary.
+    pub command: String,
+    /// Number of worker threads the runs were spread over.
+    #[serde(default)]
+    pub threads: Option<u8>,
+    /// The totals of the session.
+    #[serde(flatten)]
+    pub report: SessionReport,
+    /// The passed run with the best score.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub best_run: Option<BestRun>,
+    /// The slowest runs, slowest first, with `--time`.
+    #[serde(default)]
+    pub slowest: Vec<SlowRun>,
+    /// How the session compares with the baseline given with `--baseline`.
+    #[serde(default)]
+    pub baseline: Option<BaselineComparison>,
+    /// The totals of every swept value, with `--sweep`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<VariantReport>,
+    /// The spawn overhead, with `--calibrate-spawn`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn_overhead: Option<SpawnOverhead>,
+    /// When every worker thread was busy, with `--timeline`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timeline: Vec<Interval>,
+}
+
+impl SessionSummary {
+    /// Write the summary to `path` as pretty-printed JSON.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut content = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        content.push('\n');
+        fs::write(path, content)
+    }
+
+    /// Read a summary written by [`save`](Self::save).
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read {}: {}", path.display(), err))?;
+        serde_json::from_str(&content)
+            .map_err(|err| format!("invalid summary {}: {}", path.display(), err))
+    }
+}
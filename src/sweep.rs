@@ -0,0 +1,280 @@
+//! Running the steps for every value of some parameters, or every combination of values of
+//! several, see `--sweep`.
+
+use crate::{command::Step, RunObserver, RunResult, SessionReport, Variant};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// A parameter and the values it takes, given as `NAME=v1,v2,v3`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sweep {
+    /// What `{NAME}` in the commands stands for.
+    pub name: String,
+    /// The values in the order given.
+    pub values: Vec<String>,
+}
+
+/// Parse `NAME=v1,v2,v3`. Names are made of letters, digits, `_` and `-`.
+pub fn parse(s: &str) -> Result<Sweep, String> {
+    let (name, values) = s
+        .split_once('=')
+        .ok_or_else(|| "expected NAME=VALUE,VALUE,...".to_string())?;
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!(
+            "invalid name `{}`: use letters, digits, `_` and `-`",
+            name
+        ));
+    }
+    let values = values.split(',').map(str::to_string).collect::<Vec<_>>();
+    if values.iter().any(String::is_empty) {
+        return Err("empty value".to_string());
+    }
+    if let Some(value) = values
+        .iter()
+        .enumerate()
+        .find_map(|(i, value)| values[..i].contains(value).then_some(value))
+    {
+        return Err(format!("`{}` is given twice", value));
+    }
+    Ok(Sweep {
+        name: name.to_string(),
+        values,
+    })
+}
+
+impl Sweep {
+    /// What stands for the value in the commands.
+    pub fn placeholder(&self) -> String {
+        format!("{{{}}}", self.name)
+    }
+
+    /// Whether the placeholder appears in any program or argument of `steps`.
+    pub fn is_used(&self, steps: &[Step]) -> bool {
+        let placeholder = self.placeholder();
+        steps
+            .iter()
+            .flat_map(|step| std::iter::once(&step.exec).chain(&step.args))
+            .any(|arg| arg.contains(&placeholder))
+    }
+}
+
+/// How many combinations of values `sweeps` make, `None` if too many to count.
+pub fn combination_count(sweeps: &[Sweep]) -> Option<u64> {
+    sweeps.iter().try_fold(1u64, |count, sweep| {
+        count.checked_mul(sweep.values.len() as u64)
+    })
+}
+
+/// Every combination of the values of `sweeps`, one value per sweep in their order, the
+/// values of the last sweep changing fastest. Mind [`combination_count`] first.
+pub fn combinations(sweeps: &[Sweep]) -> Vec<Vec<&str>> {
+    sweeps.iter().fold(vec![vec![]], |combinations, sweep| {
+        combinations
+            .iter()
+            .flat_map(|combination| {
+                sweep.values.iter().map(move |value| {
+                    let mut combination = combination.clone();
+                    combination.push(value.as_str());
+                    combination
+                })
+            })
+            .collect()
+    })
+}
+
+/// The label of a combination of the values of `sweeps`, e.g. `threads=4 algo=a`.
+pub fn label(sweeps: &[Sweep], combination: &[&str]) -> String {
+    sweeps
+        .iter()
+        .zip(combination)
+        .map(|(sweep, value)| format!("{}={}", sweep.name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `arg` with the placeholder of every sweep replaced by its value in `combination`, in one
+/// pass so values are never substituted into.
+fn substitute(arg: &str, sweeps: &[Sweep], combination: &[&str]) -> String {
+    let mut substituted = String::new();
+    let mut rest = arg;
+    while let Some(start) = rest.find('{') {
+        substituted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let sweep = sweeps.iter().position(|sweep| sweep.name == rest[1..end])?;
+            Some((end, combination[sweep]))
+        });
+        match value {
+            Some((end, value)) => {
+                substituted.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                substituted.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    substituted + rest
+}
+
+/// One variant of `steps` per combination of values in the order of [`combinations`],
+/// with every placeholder replaced by its value in every program and argument.
+pub fn variants(sweeps: &[Sweep], steps: &[Step]) -> Vec<Variant> {
+    combinations(sweeps)
+        .into_iter()
+        .map(|combination| Variant {
+            label: label(sweeps, &combination),
+            steps: steps
+                .iter()
+                .map(|step| Step {
+                    exec: substitute(&step.exec, sweeps, &combination),
+                    args: step
+                        .args
+                        .iter()
+                        .map(|arg| substitute(arg, sweeps, &combination))
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// The totals of the runs of one variant.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VariantReport {
+    /// What sets the variant apart, e.g. `threads=4`.
+    pub label: String,
+    /// The totals of its runs.
+    #[serde(flatten)]
+    pub report: SessionReport,
+}
+
+/// An observer adding up the runs of every variant on its own. Run `i` is attributed to
+/// variant `i % variants`, as the runner picks them.
+pub struct VariantReports {
+    labels: Vec<String>,
+    reports: Vec<Mutex<SessionReport>>,
+}
+
+impl VariantReports {
+    /// Add up the runs of `variants`, whose iterations have `steps` steps.
+    pub fn new(variants: &[Variant], steps: usize) -> Self {
+        VariantReports {
+            labels: variants
+                .iter()
+                .map(|variant| variant.label.clone())
+                .collect(),
+            reports: variants
+                .iter()
+                .map(|_| Mutex::new(SessionReport::new(steps)))
+                .collect(),
+        }
+    }
+
+    /// The totals so far, in the order of the variants.
+    pub fn reports(&self) -> Vec<VariantReport> {
+        self.labels
+            .iter()
+            .zip(&self.reports)
+            .map(|(label, report)| VariantReport {
+                label: label.clone(),
+                report: report.lock().unwrap().clone(),
+            })
+            .collect()
+    }
+}
+
+impl RunObserver for VariantReports {
+    fn on_run(&self, result: &RunResult) {
+        let variant = result.index % self.reports.len().max(1) as u64;
+        if let Some(report) = self.reports.get(variant as usize) {
+            report.lock().unwrap().record(result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sweeps() -> Vec<Sweep> {
+        vec![parse("threads=1,4").unwrap(), parse("algo=a,b,c").unwrap()]
+    }
+
+    #[test]
+    fn parse_sweeps() {
+        assert_eq!(
+            parse("my-param_2=x,y"),
+            Ok(Sweep {
+                name: "my-param_2".to_string(),
+                values: vec!["x".to_string(), "y".to_string()],
+            })
+        );
+        assert_eq!(parse("n=1").unwrap().values, ["1"]);
+        for invalid in ["n", "=1,2", "a b=1", "n{=1", "n=", "n=1,,2", "n=1,2,1"] {
+            assert!(parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn combination_order() {
+        let sweeps = sweeps();
+        assert_eq!(combination_count(&sweeps), Some(6));
+        assert_eq!(
+            combinations(&sweeps),
+            [
+                ["1", "a"],
+                ["1", "b"],
+                ["1", "c"],
+                ["4", "a"],
+                ["4", "b"],
+                ["4", "c"],
+            ]
+        );
+        assert_eq!(label(&sweeps, &["4", "b"]), "threads=4 algo=b");
+        assert_eq!(combinations(&[]), [Vec::<&str>::new()]);
+        let huge = Sweep {
+            name: "n".to_string(),
+            values: vec!["v".to_string(); 1 << 16],
+        };
+        assert_eq!(combination_count(&vec![huge; 4]), None);
+    }
+
+    #[test]
+    fn substitution() {
+        let sweeps = sweeps();
+        assert_eq!(
+            substitute("--threads={threads}", &sweeps, &["4", "a"]),
+            "--threads=4"
+        );
+        assert_eq!(
+            substitute("{algo}{threads}{algo}", &sweeps, &["4", "b"]),
+            "b4b"
+        );
+        // Unknown and unclosed placeholders are kept, values are not substituted into.
+        assert_eq!(substitute("{other} {", &sweeps, &["4", "a"]), "{other} {");
+        assert_eq!(substitute("{threads}", &sweeps, &["{algo}", "a"]), "{algo}");
+        assert_eq!(substitute("{{algo}}", &sweeps, &["1", "c"]), "{c}");
+    }
+
+    #[test]
+    fn step_variants() {
+        let sweeps = sweeps();
+        let steps = [Step {
+            exec: "./bench-{algo}".to_string(),
+            args: vec!["-j".to_string(), "{threads}".to_string()],
+        }];
+        assert!(sweeps.iter().all(|sweep| sweep.is_used(&steps)));
+        assert!(!parse("unused=1").unwrap().is_used(&steps));
+        let variants = variants(&sweeps, &steps);
+        assert_eq!(variants.len(), 6);
+        assert_eq!(variants[5].label, "threads=4 algo=c");
+        assert_eq!(variants[5].steps[0].exec, "./bench-c");
+        assert_eq!(variants[5].steps[0].args, ["-j", "4"]);
+    }
+}
@@ -0,0 +1,140 @@
+//! When every worker thread was busy during a session, to tune `-p` and spot a tail of runs
+//! left to a single thread, see `--timeline`.
+
+use crate::{RunObserver, RunResult};
+use serde::{Deserialize, Serialize};
+use std::{sync::Mutex, time::Instant};
+
+/// The characters drawn for a bucket of a thread busy none, a quarter, half, three quarters
+/// or all of the time.
+const SHADES: [char; 5] = [' ', '.', '-', '=', '#'];
+
+/// A stretch of time one worker thread spent on runs, in seconds since the session started.
+/// A single run at first; when there are too many, consecutive ones are merged into one with
+/// the idle time between them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    /// Index of the worker thread.
+    pub thread: usize,
+    /// Run index of the first run.
+    pub index: u64,
+    /// Number of runs merged into it.
+    pub runs: u64,
+    /// When the first run started, with its setup.
+    pub start: f64,
+    /// When the last run finished, with its teardown.
+    pub end: f64,
+    /// Seconds of it spent running, `end - start` unless runs were merged.
+    pub busy: f64,
+}
+
+impl Interval {
+    /// The two consecutive intervals of one thread as one.
+    fn merge(self, next: Interval) -> Interval {
+        Interval {
+            runs: self.runs + next.runs,
+            end: next.end,
+            busy: self.busy + next.busy,
+            ..self
+        }
+    }
+}
+
+/// An observer recording the interval of every run, one list per worker thread so threads
+/// never wait for each other. A list reaching its share of the limit is halved by merging
+/// pairs of intervals, so memory stays bounded however many runs there are.
+pub struct Timeline {
+    started: Instant,
+    max_per_thread: usize,
+    threads: Vec<Mutex<Vec<Interval>>>,
+}
+
+impl Timeline {
+    /// Record the runs of `threads` worker threads of a session that started at `started`,
+    /// keeping about `max_intervals` intervals at most.
+    pub fn new(started: Instant, threads: usize, max_intervals: usize) -> Self {
+        let threads = threads.max(1);
+        Timeline {
+            started,
+            max_per_thread: (max_intervals / threads).max(2),
+            threads: (0..threads).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    /// The intervals so far, by thread and then in time order.
+    pub fn intervals(&self) -> Vec<Interval> {
+        self.threads
+            .iter()
+            .flat_map(|intervals| intervals.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+impl RunObserver for Timeline {
+    fn on_run(&self, result: &RunResult) {
+        let start = result.started.saturating_duration_since(self.started);
+        let end = self.started.elapsed().max(start);
+        let interval = Interval {
+            thread: result.thread,
+            index: result.index,
+            runs: 1,
+            start: start.as_secs_f64(),
+            end: end.as_secs_f64(),
+            busy: (end - start).as_secs_f64(),
+        };
+        let mut intervals = self.threads[result.thread % self.threads.len()]
+            .lock()
+            .unwrap();
+        intervals.push(interval);
+        if intervals.len() > self.max_per_thread {
+            let merged = std::mem::take(&mut *intervals)
+                .chunks(2)
+                .filter_map(|pair| pair.iter().cloned().reduce(Interval::merge))
+                .collect();
+            *intervals = merged;
+        }
+    }
+}
+
+/// Draw one row of `width` characters per thread of `threads` for the `intervals` of a session
+/// that took `duration` seconds, each character shaded by how busy the thread was at that
+/// time, followed by the share of the session the thread was busy.
+pub fn render(intervals: &[Interval], threads: usize, duration: f64, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let bucket = duration.max(f64::MIN_POSITIVE) / width as f64;
+    let mut busy = vec![vec![0.0; width]; threads.max(1)];
+    for interval in intervals {
+        let Some(row) = busy.get_mut(interval.thread) else {
+            continue;
+        };
+        let length = interval.end - interval.start;
+        // Merged intervals are spread evenly over their length.
+        let density = if length > 0.0 {
+            interval.busy / length
+        } else {
+            1.0
+        };
+        let first = ((interval.start / bucket) as usize).min(width - 1);
+        let last = ((interval.end / bucket) as usize).min(width - 1);
+        for (column, time) in row.iter_mut().enumerate().take(last + 1).skip(first) {
+            let from = interval.start.max(column as f64 * bucket);
+            let to = interval.end.min((column + 1) as f64 * bucket);
+            *time += (to - from).max(0.0) * density;
+        }
+    }
+    busy.iter()
+        .map(|row| {
+            let chart = row
+                .iter()
+                .map(|time| {
+                    let share = (time / bucket).clamp(0.0, 1.0);
+                    // Any work at all shows.
+                    let shade = (share * (SHADES.len() - 1) as f64).round() as usize;
+                    SHADES[if share > 0.0 { shade.max(1) } else { 0 }]
+                })
+                .collect::<String>();
+            let total = row.iter().sum::<f64>() / (bucket * width as f64);
+            format!("|{}| {:>3.0}%", chart, (total * 100.0).clamp(0.0, 100.0))
+        })
+        .collect()
+}
@@ -0,0 +1,88 @@
+//! A scratch directory for every run, nested in one directory per session so parallel runs
+//! never share their scratch files.
+
+use crate::{command::Step, environment::Environment};
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+/// The environment variable holding the path of the run's directory.
+pub const TMPDIR_ENV: &str = "TESTER_TMPDIR";
+/// The placeholder replaced by the path of the run's directory in a command's arguments.
+pub const TMPDIR_PLACEHOLDER: &str = "{tmpdir}";
+
+/// The directory holding the run directories of a session, removed when dropped.
+#[derive(Debug)]
+pub struct SessionDir {
+    path: PathBuf,
+    keep_failed: bool,
+}
+
+impl SessionDir {
+    /// Create a fresh directory under the system's temporary directory. With `keep_failed`,
+    /// the directories of failed runs are left behind, and so is this one if there are any.
+    pub fn create(keep_failed: bool) -> io::Result<Self> {
+        let temp_dir = env::temp_dir();
+        for attempt in 0.. {
+            let path = temp_dir.join(format!("tester-{}-{}", process::id(), attempt));
+            match fs::create_dir(&path) {
+                Ok(()) => return Ok(SessionDir { path, keep_failed }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        unreachable!("ran out of session directory names")
+    }
+
+    /// Where the directory is.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SessionDir {
+    fn drop(&mut self) {
+        let removed = if self.keep_failed {
+            // Only the directories of failed runs are left, if any.
+            fs::remove_dir(&self.path)
+        } else {
+            fs::remove_dir_all(&self.path)
+        };
+        if let Err(err) = removed {
+            if self.keep_failed && err.kind() == io::ErrorKind::DirectoryNotEmpty {
+                return;
+            }
+            warn!(
+                "#tester cannot remove the temporary directory {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+}
+
+/// The directory of run `index` in the session directory `session_dir`.
+pub fn run_dir(session_dir: &Path, index: u64) -> PathBuf {
+    session_dir.join(format!("run-{}", index))
+}
+
+/// A [`Command`] running `step` in the context of a run: in `env`, with
+/// [`TMPDIR_PLACEHOLDER`] replaced by `dir` in its arguments and [`TMPDIR_ENV`] set to it.
+pub fn command_in(step: &Step, dir: Option<&Path>, env: &Environment) -> Command {
+    let Some(dir) = dir else {
+        let mut command = step.command();
+        env.apply(&mut command);
+        return command;
+    };
+    let path = dir.display().to_string();
+    let mut step = step.clone();
+    for arg in &mut step.args {
+        *arg = arg.replace(TMPDIR_PLACEHOLDER, &path);
+    }
+    let mut command = step.command();
+    env.apply(&mut command);
+    command.env(TMPDIR_ENV, dir);
+    command
+}
@@ -0,0 +1,418 @@
+//! Lenient parsing of counts (`10_000`, `250k`, `1M`), durations (`1.5s`, `2h30m`) and sizes
+//! (`64MiB`), and the normalized forms they are displayed back in, along with scores.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+/// Decimal places of displayed scores and durations, `usize::MAX` for as many as needed.
+static PRECISION: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// Whether displayed numbers group the digits of their integer part by thousands.
+static THOUSANDS_SEPARATORS: AtomicBool = AtomicBool::new(false);
+/// What displayed scores are measured in, e.g. `MB/s`, empty for nothing.
+static SCORE_LABEL: RwLock<String> = RwLock::new(String::new());
+
+const COUNT_SUFFIXES: [(&str, u64); 4] = [
+    ("", 1),
+    ("k", 1_000),
+    ("M", 1_000_000),
+    ("G", 1_000_000_000),
+];
+const SIZE_UNITS: [(&str, u64); 7] = [
+    ("", 1),
+    ("B", 1),
+    ("KiB", 1 << 10),
+    ("MiB", 1 << 20),
+    ("GiB", 1 << 30),
+    ("K", 1 << 10),
+    ("M", 1 << 20),
+];
+const DURATION_UNITS: [(&str, f64); 8] = [
+    ("ns", 1e-9),
+    ("us", 1e-6),
+    ("µs", 1e-6),
+    ("ms", 1e-3),
+    ("s", 1.0),
+    ("m", 60.0),
+    ("h", 3600.0),
+    ("d", 86400.0),
+];
+
+/// Split `s` into a leading decimal number (underscores allowed) and the rest.
+fn split_number(s: &str) -> Option<(f64, &str)> {
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '_' || c == '.'))
+        .unwrap_or(s.len());
+    let (number, rest) = s.split_at(end);
+    let number = number.replace('_', "");
+    if number.is_empty() || number.starts_with('.') || number.ends_with('.') {
+        return None;
+    }
+    number.parse().ok().map(|number| (number, rest))
+}
+
+/// Parse a count such as `10`, `10_000`, `2k`, `1.5M` or `1G`.
+pub fn parse_count(s: &str) -> Result<u64, String> {
+    let expected = || format!("invalid count `{}`: expected a count like 10, 2k, 1M", s);
+    // Plain integers are parsed exactly, beyond the 2^53 an f64 holds without rounding.
+    if let Ok(count) = s.trim().replace('_', "").parse::<u64>() {
+        return Ok(count);
+    }
+    let (number, suffix) = split_number(s.trim()).ok_or_else(expected)?;
+    let (_, multiplier) = COUNT_SUFFIXES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(suffix))
+        .ok_or_else(expected)?;
+    let count = number * *multiplier as f64;
+    if count.fract() != 0.0 {
+        return Err(format!("invalid count `{}`: not a whole number", s));
+    }
+    if count >= u64::MAX as f64 {
+        return Err(format!(
+            "invalid count `{}`: at most {} is supported",
+            s,
+            u64::MAX
+        ));
+    }
+    Ok(count as u64)
+}
+
+/// Parse a size in bytes such as `4096`, `512K` or `64MiB`. Suffixes are binary.
+pub fn parse_size(s: &str) -> Result<usize, String> {
+    let expected = || {
+        format!(
+            "invalid size `{}`: expected a size like 4096, 512K, 64MiB",
+            s
+        )
+    };
+    let (number, suffix) = split_number(s.trim()).ok_or_else(expected)?;
+    let (_, multiplier) = SIZE_UNITS
+        .iter()
+        .find(|(name, _)| *name == suffix.trim_start())
+        .ok_or_else(expected)?;
+    let size = number * *multiplier as f64;
+    if size.fract() != 0.0 || size > usize::MAX as f64 {
+        return Err(expected());
+    }
+    Ok(size as usize)
+}
+
+/// Parse a rate such as `0.5%` or `0.005`, between 0 and 1.
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    let expected = || format!("invalid rate `{}`: expected a rate like 0.5%, 0.005", s);
+    let (number, suffix) = split_number(s.trim()).ok_or_else(expected)?;
+    let rate = match suffix.trim_start() {
+        "" => number,
+        "%" => number / 100.0,
+        _ => return Err(expected()),
+    };
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(format!("invalid rate `{}`: not between 0% and 100%", s));
+    }
+    Ok(rate)
+}
+
+/// Display `rate` as a percentage with up to 3 significant decimals, e.g. `0.123%`.
+pub fn format_rate(rate: f64) -> String {
+    let percent = rate * 100.0;
+    let decimals = if percent == 0.0 || percent >= 10.0 {
+        1
+    } else {
+        (2 - percent.log10().floor() as i32).clamp(1, 6) as usize
+    };
+    let formatted = format!("{:.*}", decimals, percent);
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    format!("{}%", formatted)
+}
+
+/// Parse a duration such as `10` (seconds), `1.5s`, `250ms` or `2h30m`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let expected = || {
+        format!(
+            "invalid duration `{}`: expected a duration like 30, 1.5s, 250ms, 2h30m",
+            s
+        )
+    };
+    let too_long = |_| format!("invalid duration `{}`: too long", s);
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(expected());
+    }
+    if let Some((seconds, "")) = split_number(rest) {
+        return Duration::try_from_secs_f64(seconds).map_err(too_long);
+    }
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let (number, after_number) = split_number(rest).ok_or_else(expected)?;
+        let unit_len = after_number
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(after_number.len());
+        let (unit, after_unit) = after_number.split_at(unit_len);
+        let (_, unit_seconds) = DURATION_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .ok_or_else(expected)?;
+        seconds += number * unit_seconds;
+        rest = after_unit;
+    }
+    Duration::try_from_secs_f64(seconds).map_err(too_long)
+}
+
+/// Show scores and durations with `decimals` decimal places from now on, or with as many as
+/// needed to tell them apart for `None`, the default.
+pub fn set_precision(decimals: Option<usize>) {
+    PRECISION.store(decimals.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// The decimal places set with [`set_precision`].
+pub fn precision() -> Option<usize> {
+    Some(PRECISION.load(Ordering::Relaxed)).filter(|&decimals| decimals != usize::MAX)
+}
+
+/// Group the digits of displayed numbers by thousands from now on, e.g. `12,345`.
+pub fn set_thousands_separators(enabled: bool) {
+    THOUSANDS_SEPARATORS.store(enabled, Ordering::Relaxed);
+}
+
+/// Follow every displayed score with `label`, e.g. `MB/s`, from now on, or with nothing for
+/// `None`, the default.
+pub fn set_score_label(label: Option<&str>) {
+    *SCORE_LABEL.write().unwrap() = label.unwrap_or_default().to_string();
+}
+
+/// `formatted` with a `,` between every three digits of its integer part if enabled with
+/// [`set_thousands_separators`].
+fn group_thousands(formatted: String) -> String {
+    if !THOUSANDS_SEPARATORS.load(Ordering::Relaxed) {
+        return formatted;
+    }
+    insert_separators(formatted)
+}
+
+/// `formatted` with a `,` between every three digits of its integer part.
+fn insert_separators(formatted: String) -> String {
+    let digits_start = usize::from(formatted.starts_with('-'));
+    let digits_end = formatted[digits_start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(formatted.len(), |end| digits_start + end);
+    let digits = &formatted[digits_start..digits_end];
+    let mut grouped = formatted[..digits_start].to_string();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped + &formatted[digits_end..]
+}
+
+/// Display a score or another measured `value` with the [precision](set_precision) set,
+/// never as `-0`.
+pub fn format_number(value: f64) -> String {
+    format_with(
+        value,
+        precision(),
+        THOUSANDS_SEPARATORS.load(Ordering::Relaxed),
+    )
+}
+
+/// [`format_number`] with `decimals` decimal places, or as many as needed for `None`, and
+/// thousands separators if `separators`.
+fn format_with(value: f64, decimals: Option<usize>, separators: bool) -> String {
+    let formatted = match decimals {
+        Some(decimals) => format!("{:.*}", decimals, value),
+        None => value.to_string(),
+    };
+    // Rounding a small negative value leaves only zeros.
+    let formatted = match formatted.strip_prefix('-') {
+        Some(zero) if zero.chars().all(|c| c == '0' || c == '.') => zero.to_string(),
+        _ => formatted,
+    };
+    if separators {
+        insert_separators(formatted)
+    } else {
+        formatted
+    }
+}
+
+/// Display a score, or a statistic or change of scores, with [`format_number`] and the
+/// [label](set_score_label) set.
+pub fn format_score(value: f64) -> String {
+    let label = SCORE_LABEL.read().unwrap();
+    if label.is_empty() {
+        format_number(value)
+    } else {
+        format!("{} {}", format_number(value), label)
+    }
+}
+
+/// Display an exact count of things, e.g. runs, with thousands separators if enabled.
+pub fn format_integer(count: u64) -> String {
+    group_thousands(count.to_string())
+}
+
+/// Display `count` with the largest suffix that represents it exactly, e.g. `250k`.
+pub fn format_count(count: u64) -> String {
+    COUNT_SUFFIXES
+        .iter()
+        .rev()
+        .find(|(_, multiplier)| count.is_multiple_of(*multiplier) && count >= *multiplier)
+        .map(|(suffix, multiplier)| format!("{}{}", format_integer(count / multiplier), suffix))
+        .unwrap_or_else(|| format_integer(count))
+}
+
+/// Display `duration` as hours, minutes and seconds, e.g. `2h30m` or `1.5s`, or in
+/// milliseconds below a second. Seconds and milliseconds have 3 decimals at most, or the
+/// [precision](set_precision) set.
+pub fn format_duration(duration: Duration) -> String {
+    let decimals = |value: f64| match precision() {
+        Some(decimals) => format!("{:.*}", decimals, value),
+        None => ((value * 1e3).round() / 1e3).to_string(),
+    };
+    let seconds = duration.as_secs_f64();
+    if seconds < 1.0 {
+        return format!("{}ms", decimals(seconds * 1e3));
+    }
+    let hours = duration.as_secs() / 3600;
+    let minutes = duration.as_secs() % 3600 / 60;
+    let seconds = seconds - (hours * 3600 + minutes * 60) as f64;
+    let mut formatted = String::new();
+    if hours > 0 {
+        formatted += &format!("{}h", hours);
+    }
+    if minutes > 0 {
+        formatted += &format!("{}m", minutes);
+    }
+    if seconds > 0.0 || formatted.is_empty() {
+        formatted += &format!("{}s", decimals(seconds));
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts() {
+        assert_eq!(parse_count("10"), Ok(10));
+        assert_eq!(parse_count("10_000"), Ok(10_000));
+        assert_eq!(parse_count("2k"), Ok(2_000));
+        assert_eq!(parse_count("1.5M"), Ok(1_500_000));
+        assert_eq!(parse_count("1g"), Ok(1_000_000_000));
+        assert_eq!(parse_count(" 3 "), Ok(3));
+        // Beyond what an f64 holds exactly.
+        assert_eq!(parse_count("18446744073709551615"), Ok(u64::MAX));
+        for invalid in ["", "k", "1.5", "1.0001k", "2T", "-1", ".5k", "20000000000G"] {
+            assert!(parse_count(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("100B"), Ok(100));
+        assert_eq!(parse_size("512K"), Ok(512 << 10));
+        assert_eq!(parse_size("64MiB"), Ok(64 << 20));
+        assert_eq!(parse_size("1 GiB"), Ok(1 << 30));
+        assert_eq!(parse_size("1.5KiB"), Ok(1536));
+        for invalid in ["", "1.5", "64MB", "1kib", "KiB"] {
+            assert!(parse_size(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn rates() {
+        assert_eq!(parse_rate("0.5%"), Ok(0.005));
+        assert_eq!(parse_rate("0.005"), Ok(0.005));
+        assert_eq!(parse_rate("100%"), Ok(1.0));
+        for invalid in ["150%", "2", "5 percent", "%"] {
+            assert!(parse_rate(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(format_rate(0.00123), "0.123%");
+        assert_eq!(format_rate(0.5), "50%");
+        assert_eq!(format_rate(0.0), "0%");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("10"), Ok(Duration::from_secs(10)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2h30m"), Ok(Duration::from_secs(9000)));
+        assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+        for invalid in ["", "s", "1.5x", "10 s", "-1s", "1h-5m"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn durations_too_long() {
+        for huge in ["100000000000000000000", "999999999999999999d"] {
+            let err = parse_duration(huge).unwrap_err();
+            assert!(err.ends_with("too long"), "{}: {}", huge, err);
+        }
+    }
+
+    #[test]
+    fn formatted_durations() {
+        assert_eq!(format_duration(Duration::ZERO), "0ms");
+        assert_eq!(format_duration(Duration::from_micros(1500)), "1.5ms");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.5s");
+        assert_eq!(format_duration(Duration::from_secs(9000)), "2h30m");
+        assert_eq!(format_duration(Duration::from_secs(3661)), "1h1m1s");
+        for text in ["250ms", "1.5s", "2h30m", "1h1m1s"] {
+            assert_eq!(format_duration(parse_duration(text).unwrap()), text);
+        }
+    }
+
+    #[test]
+    fn formatted_numbers() {
+        let cases: [(f64, Option<usize>, bool, &str); 16] = [
+            // Rounded up into the next integer.
+            (0.9995, Some(3), false, "1.000"),
+            (0.9994, Some(3), false, "0.999"),
+            (2.5, None, false, "2.5"),
+            (-2.5, None, false, "-2.5"),
+            (-0.0004, Some(3), false, "0.000"),
+            (-0.0, None, false, "0"),
+            (-1234.5, Some(1), true, "-1,234.5"),
+            (1e15, None, false, "1000000000000000"),
+            (1e15, None, true, "1,000,000,000,000,000"),
+            (1.5e15, Some(2), true, "1,500,000,000,000,000.00"),
+            (1e21, None, true, "1,000,000,000,000,000,000,000"),
+            (999.0, None, true, "999"),
+            (1000.0, None, true, "1,000"),
+            (1000.0, None, false, "1000"),
+            (1_000_000.0, Some(0), true, "1,000,000"),
+            (1_000_000.25, None, true, "1,000,000.25"),
+        ];
+        for (value, decimals, separators, formatted) in cases {
+            assert_eq!(
+                format_with(value, decimals, separators),
+                formatted,
+                "{} {:?} {}",
+                value,
+                decimals,
+                separators
+            );
+        }
+    }
+
+    #[test]
+    fn formatted_counts() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(1_500), "1500");
+        assert_eq!(format_count(250_000), "250k");
+        assert_eq!(format_count(2_000_000), "2M");
+        assert_eq!(format_count(3_000_000_000), "3G");
+        for text in ["7", "250k", "2M"] {
+            assert_eq!(format_count(parse_count(text).unwrap()), text);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Noticing when files change, see `--watch`, by polling their modification time and size so
+//! it works the same everywhere.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread::sleep,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the files are looked at.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What a file looked like, `None` if it did not exist.
+type Stamp = Option<(Option<SystemTime>, u64)>;
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
+
+/// Some files and what they looked like when last seen.
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    stamps: Vec<Stamp>,
+}
+
+impl Watcher {
+    /// Watch `paths` for changes from now on.
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        let stamps = paths.iter().map(|path| stamp(path)).collect();
+        Watcher { paths, stamps }
+    }
+
+    /// The watched files.
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Block until some files changed and then stayed the same for `debounce`, so a build
+    /// writing a file several times counts once, and return them. Files missing for a
+    /// while, e.g. a binary being relinked, are waited for. Returns `None` as soon as `stop` is
+    /// set.
+    pub fn wait(&mut self, debounce: Duration, stop: &AtomicBool) -> Option<Vec<PathBuf>> {
+        // What the files look like now and since when, while they differ from the last stamps.
+        let mut changing: Option<(Vec<Stamp>, Instant)> = None;
+        loop {
+            if stop.load(Ordering::Relaxed) {
+                return None;
+            }
+            let current = self
+                .paths
+                .iter()
+                .map(|path| stamp(path))
+                .collect::<Vec<_>>();
+            match &changing {
+                _ if current == self.stamps => changing = None,
+                Some((seen, since)) if *seen == current => {
+                    if current.iter().all(Option::is_some) && since.elapsed() >= debounce {
+                        let changed = self
+                            .paths
+                            .iter()
+                            .zip(self.stamps.iter().zip(&current))
+                            .filter(|(_, (old, new))| old != new)
+                            .map(|(path, _)| path.clone())
+                            .collect();
+                        self.stamps = current;
+                        return Some(changed);
+                    }
+                }
+                _ => changing = Some((current, Instant::now())),
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
@@ -0,0 +1,470 @@
+//! Running the `tester` binary on small shell commands and checking how sessions end.
+
+#![cfg(unix)]
+
+use std::{
+    fs,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// A command failing its first `failures` runs and passing the others, counting them in a
+/// file of the working directory. Needs `-p 1`, the default.
+fn fails_first(failures: u32) -> String {
+    format!(
+        "n=$(cat count 2>/dev/null || echo 0); echo $((n + 1)) > count; [ $n -ge {} ]",
+        failures
+    )
+}
+
+/// An empty working directory for one test, removed when dropped.
+struct WorkDir(PathBuf);
+
+impl WorkDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("tester-cli-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        WorkDir(path)
+    }
+
+    fn tester(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_tester"));
+        command.current_dir(&self.0).args(args);
+        command
+    }
+
+    /// Run tester with `args` until it exits.
+    fn run(&self, args: &[&str]) -> Output {
+        self.tester(args).output().unwrap()
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// What tester printed on stdout and stderr.
+fn printed(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned() + &String::from_utf8_lossy(&output.stderr)
+}
+
+#[test]
+fn exit_status() {
+    let dir = WorkDir::new("exit-status");
+    let passed = dir.run(&["run", "-n", "5", "--", "true"]);
+    assert_eq!(passed.status.code(), Some(0), "{}", printed(&passed));
+    assert!(printed(&passed).contains("No failure in 5 runs"));
+
+    let failed = dir.run(&["run", "-n", "4", "--", "false"]);
+    assert_eq!(failed.status.code(), Some(1), "{}", printed(&failed));
+    assert!(printed(&failed).contains("Failed 4 / 4"));
+
+    let unusable = dir.run(&["run", "--timeout", "", "--", "true"]);
+    assert_eq!(unusable.status.code(), Some(2), "{}", printed(&unusable));
+
+    let after_all = dir.run(&["run", "-n", "1", "--after-all", "false", "--", "true"]);
+    assert_eq!(after_all.status.code(), Some(4), "{}", printed(&after_all));
+}
+
+#[test]
+fn unusable_arguments() {
+    let dir = WorkDir::new("unusable");
+    fs::write(dir.0.join("file"), "").unwrap();
+    let cases: [(&[&str], &str); 4] = [
+        (
+            &["--", "no-such-binary-for-tester"],
+            "executable `no-such-binary-for-tester` not found",
+        ),
+        // Made unwritable by a file in place of a directory, which holds even for root.
+        (
+            &["--csv", "file/runs.csv", "--", "true"],
+            "cannot write CSV file file/runs.csv",
+        ),
+        (
+            &[
+                "--gen",
+                "echo 1",
+                "--save-failures",
+                "file/failures",
+                "--",
+                "true",
+            ],
+            "cannot save failing inputs to file/failures",
+        ),
+        (
+            &["--score", "--score-regex", "(", "--", "true"],
+            "invalid regex `(`",
+        ),
+    ];
+    for (flags, message) in cases {
+        let mut args = vec!["run", "-n", "1"];
+        args.extend_from_slice(flags);
+        let output = dir.run(&args);
+        assert_eq!(output.status.code(), Some(2), "{}", printed(&output));
+        assert!(printed(&output).contains(message), "{}", printed(&output));
+    }
+}
+
+#[test]
+fn without_subcommand() {
+    let dir = WorkDir::new("without-subcommand");
+    let output = dir.run(&["-n", "2", "--", "true"]);
+    assert_eq!(output.status.code(), Some(0), "{}", printed(&output));
+    assert!(printed(&output).contains("deprecated"));
+}
+
+#[test]
+fn timeout() {
+    let dir = WorkDir::new("timeout");
+    let started = Instant::now();
+    let output = dir.run(&["run", "-n", "3", "--timeout", "200ms", "--", "sleep", "10"]);
+    assert!(started.elapsed() < Duration::from_secs(8));
+    assert_eq!(output.status.code(), Some(1), "{}", printed(&output));
+    assert!(printed(&output).contains("Failed 3 / 3 (3 timed out)"));
+}
+
+#[test]
+fn timeout_kills_grandchildren() {
+    let dir = WorkDir::new("timeout-grandchildren");
+    let output = dir.run(&[
+        "run",
+        "-n",
+        "1",
+        "--timeout",
+        "300ms",
+        "--",
+        "sh",
+        "-c",
+        "sleep 30 & echo $! > pid; sleep 30",
+    ]);
+    assert_eq!(output.status.code(), Some(1), "{}", printed(&output));
+    let pid: libc::pid_t = fs::read_to_string(dir.0.join("pid"))
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    // Until its parent, now init, reaped it the killed grandchild may still be found.
+    let started = Instant::now();
+    // SAFETY: kill has no memory safety requirements, and signal 0 sends nothing.
+    while unsafe { libc::kill(pid, 0) } == 0 {
+        assert!(
+            started.elapsed() < Duration::from_secs(2),
+            "the grandchild {} outlived the time limit",
+            pid
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn timeout_partial_output() {
+    let dir = WorkDir::new("timeout-partial");
+    for silent in [&[][..], &["--silent"][..]] {
+        let mut args = vec!["run", "-n", "1", "--timeout", "300ms"];
+        args.extend_from_slice(silent);
+        args.extend_from_slice(&["--", "sh", "-c", "echo printed-$((40 + 2)); sleep 10"]);
+        let output = dir.run(&args);
+        assert_eq!(output.status.code(), Some(1), "{}", printed(&output));
+        assert!(
+            printed(&output).contains("(partial)"),
+            "{}",
+            printed(&output)
+        );
+        assert_eq!(
+            printed(&output).matches("printed-42").count(),
+            1,
+            "{}",
+            printed(&output)
+        );
+    }
+}
+
+#[test]
+fn ctrl_c() {
+    let dir = WorkDir::new("ctrl-c");
+    let child = dir
+        .tester(&["run", "-n", "100", "-p", "2", "--", "sleep", "10"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+    let started = Instant::now();
+    // SAFETY: kill has no memory safety requirements, the pid is that of our child, which
+    // is not reaped before wait_with_output.
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) },
+        0
+    );
+    let output = child.wait_with_output().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(output.status.code(), Some(130), "{}", printed(&output));
+    assert!(printed(&output).contains("interrupted by SIGINT"));
+}
+
+#[test]
+fn ctrl_c_before_all() {
+    let dir = WorkDir::new("ctrl-c-before-all");
+    let child = dir
+        .tester(&[
+            "run",
+            "-n",
+            "1",
+            "--before-all",
+            "sleep 5",
+            "--after-all",
+            "touch after-all",
+            "--",
+            "true",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(500));
+    let started = Instant::now();
+    // SAFETY: as in `ctrl_c`.
+    assert_eq!(
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) },
+        0
+    );
+    let output = child.wait_with_output().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(4));
+    assert_eq!(output.status.code(), Some(130), "{}", printed(&output));
+    assert!(printed(&output).contains("nothing was run"));
+    assert!(dir.0.join("after-all").exists(), "{}", printed(&output));
+}
+
+#[test]
+fn score_after_invalid_utf8() {
+    let dir = WorkDir::new("score-utf8");
+    for parser in [
+        &["--score-last-line"][..],
+        &["--score-regex", r"\n(\S+)"][..],
+    ] {
+        let mut args = vec!["run", "-n", "2", "--score"];
+        args.extend_from_slice(parser);
+        args.extend_from_slice(&["--", "printf", r"\377\376\n0.5\n"]);
+        let output = dir.run(&args);
+        assert_eq!(output.status.code(), Some(0), "{}", printed(&output));
+        assert!(
+            printed(&output).contains("average score: 0.5."),
+            "{}",
+            printed(&output)
+        );
+    }
+}
+
+#[test]
+fn skip_exit_codes() {
+    let dir = WorkDir::new("skip");
+    let skipped = dir.run(&[
+        "run",
+        "-n",
+        "6",
+        "--skip-exit-codes",
+        "77",
+        "--",
+        "sh",
+        "-c",
+        "n=$(cat count 2>/dev/null || echo 0); echo $((n + 1)) > count; [ $n -ge 2 ] || exit 77",
+    ]);
+    assert_eq!(skipped.status.code(), Some(0), "{}", printed(&skipped));
+    assert!(printed(&skipped).contains("2 runs were skipped by their exit code"));
+
+    let too_many = dir.run(&[
+        "run",
+        "-n",
+        "4",
+        "--skip-exit-codes",
+        "77",
+        "--max-skipped",
+        "1",
+        "--",
+        "sh",
+        "-c",
+        "exit 77",
+    ]);
+    assert_eq!(too_many.status.code(), Some(3), "{}", printed(&too_many));
+    assert!(printed(&too_many).contains("more than the 1 allowed"));
+}
+
+#[test]
+fn allowed_failures() {
+    let dir = WorkDir::new("allowed");
+    let command = fails_first(2);
+    let within = dir.run(&[
+        "run",
+        "-n",
+        "20",
+        "--allowed-failures",
+        "2",
+        "--",
+        "sh",
+        "-c",
+        &command,
+    ]);
+    assert_eq!(within.status.code(), Some(0), "{}", printed(&within));
+
+    fs::remove_file(dir.0.join("count")).unwrap();
+    let over = dir.run(&[
+        "run",
+        "-n",
+        "20",
+        "--allowed-failures",
+        "1",
+        "--",
+        "sh",
+        "-c",
+        &command,
+    ]);
+    assert_eq!(over.status.code(), Some(1), "{}", printed(&over));
+    assert!(printed(&over).contains("more failures than allowed"));
+}
+
+#[test]
+fn judge_by_target() {
+    let dir = WorkDir::new("target");
+    let few = fails_first(2);
+    let half = fails_first(20);
+    let args = |rate, command| {
+        [
+            "run",
+            "-n",
+            "40",
+            "--judge-by-target",
+            "--target-failure-rate",
+            rate,
+            "--",
+            "sh",
+            "-c",
+            command,
+        ]
+    };
+    let consistent = dir.run(&args("0.5", &few));
+    assert_eq!(
+        consistent.status.code(),
+        Some(0),
+        "{}",
+        printed(&consistent)
+    );
+    assert!(printed(&consistent).contains("consistent with the target failure rate"));
+
+    fs::remove_file(dir.0.join("count")).unwrap();
+    let exceeded = dir.run(&args("0.01", &half));
+    assert_eq!(exceeded.status.code(), Some(1), "{}", printed(&exceeded));
+    assert!(printed(&exceeded).contains("exceeds the target"));
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite() {
+    let dir = WorkDir::new("sqlite");
+    let passed = dir.run(&["run", "-n", "5", "--sqlite", "runs.db", "--", "true"]);
+    assert_eq!(passed.status.code(), Some(0), "{}", printed(&passed));
+    let failed = dir.run(&["run", "-n", "3", "--sqlite", "runs.db", "--", "false"]);
+    assert_eq!(failed.status.code(), Some(1), "{}", printed(&failed));
+
+    let query = |sql: &str| {
+        let output = Command::new("sqlite3")
+            .current_dir(&dir.0)
+            .args(["runs.db", sql])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", printed(&output));
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    };
+    assert_eq!(query("SELECT version FROM schema_version"), "1");
+    assert_eq!(
+        query("SELECT run_times, fail_times FROM sessions ORDER BY id"),
+        "5|0\n3|3"
+    );
+    assert_eq!(
+        query("SELECT session_id, count(*) FROM runs GROUP BY session_id ORDER BY session_id"),
+        "1|5\n2|3"
+    );
+}
+
+#[test]
+fn compare_with_tolerance() {
+    let dir = WorkDir::new("compare");
+    let args = |actual| {
+        [
+            "run",
+            "-n",
+            "2",
+            "--gen",
+            "echo 1",
+            "--compare-with",
+            "echo 0.5",
+            "--compare-float-epsilon",
+            "1e-3",
+            "--",
+            "echo",
+            actual,
+        ]
+    };
+    let within = dir.run(&args("0.5004"));
+    assert_eq!(within.status.code(), Some(0), "{}", printed(&within));
+
+    let beyond = dir.run(&args("0.51"));
+    assert_eq!(beyond.status.code(), Some(1), "{}", printed(&beyond));
+    assert!(printed(&beyond).contains(
+        "wrong answer, first difference at line 1, `0.51` for `0.5`, off by 1.00e-2, not within 1e-3"
+    ));
+    assert!(printed(&beyond).contains("-0.5\n+0.51\n"));
+    let diff = fs::read_to_string(dir.0.join("failing_diff_0.txt")).unwrap();
+    assert!(diff.contains("rejected: `0.51` for `0.5`"), "{}", diff);
+}
+
+#[test]
+fn baseline_regression() {
+    let dir = WorkDir::new("baseline");
+    let baseline = dir.run(&[
+        "run",
+        "-n",
+        "3",
+        "--score",
+        "--json",
+        "baseline.json",
+        "--",
+        "echo",
+        "100",
+    ]);
+    assert_eq!(baseline.status.code(), Some(0), "{}", printed(&baseline));
+    let regressed = dir.run(&[
+        "run",
+        "-n",
+        "3",
+        "--score",
+        "--baseline",
+        "baseline.json",
+        "--",
+        "echo",
+        "80",
+    ]);
+    assert_eq!(regressed.status.code(), Some(5), "{}", printed(&regressed));
+    assert!(printed(&regressed).contains("mean score: 100 -> 80 (-20.0%) REGRESSION"));
+}
+
+/// Many short runs on many threads, for the cost of accumulating their results. Run with
+/// `cargo test --release -- --ignored --nocapture throughput`.
+#[test]
+#[ignore]
+fn throughput() {
+    let dir = WorkDir::new("throughput");
+    let started = Instant::now();
+    let output = dir.run(&["run", "-n", "20000", "-p", "32", "-q", "--", "true"]);
+    let elapsed = started.elapsed();
+    assert_eq!(output.status.code(), Some(0), "{}", printed(&output));
+    println!(
+        "20000 runs in {:.2?}, {:.0} runs/s",
+        elapsed,
+        20000.0 / elapsed.as_secs_f64()
+    );
+}
@@ -0,0 +1,130 @@
+//! Driving a [`TestRunner`] from the library on small shell commands.
+
+#![cfg(unix)]
+
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use tester::{command::Step, Error, RunConfig, RunResult, RunStatus, TestRunner};
+
+/// A step running `script` with `sh -c`.
+fn sh(script: &str) -> Step {
+    Step {
+        exec: "sh".to_string(),
+        args: vec!["-c".to_string(), script.to_string()],
+    }
+}
+
+#[test]
+fn passed_and_failed() {
+    let mut config = RunConfig::new(vec![sh("exit 0")], 6);
+    config.threads = 3;
+    let report = TestRunner::new(config).run().unwrap();
+    assert_eq!((report.run_times, report.fail_times), (6, 0));
+    assert_eq!(report.steps[0].run_times, 6);
+
+    let failing = RunConfig::new(vec![sh("true"), sh("exit 3"), sh("true")], 4);
+    let report = TestRunner::new(failing).run().unwrap();
+    assert_eq!((report.run_times, report.fail_times), (4, 4));
+    // A failing step ends its iteration.
+    let steps = report
+        .steps
+        .iter()
+        .map(|step| (step.run_times, step.fail_times))
+        .collect::<Vec<_>>();
+    assert_eq!(steps, [(4, 0), (4, 4), (0, 0)]);
+    assert_eq!(report.failed_runs.iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+}
+
+#[test]
+fn scores() {
+    let mut config = RunConfig::new(vec![sh("echo 2.5")], 4);
+    config.score_step = Some(0);
+    let report = TestRunner::new(config).run().unwrap();
+    assert_eq!(report.fail_times, 0);
+    assert_eq!(report.scores.count, 4);
+    assert_eq!(report.scores.mean(), Some(2.5));
+}
+
+#[test]
+fn observers() {
+    let seen = Arc::new(Mutex::new(vec![]));
+    let mut config = RunConfig::new(vec![sh("echo out; echo err >&2; exit 1")], 5);
+    config.threads = 2;
+    let recorded = Arc::clone(&seen);
+    let runner = TestRunner::new(config).observe(move |result: &RunResult| {
+        recorded.lock().unwrap().push((
+            result.index,
+            result.status,
+            result.exit_code,
+            result.stdout.clone(),
+            result.stderr.clone(),
+        ));
+    });
+    let report = runner.run().unwrap();
+    assert_eq!(report.run_times, 5);
+    let mut seen = seen.lock().unwrap().clone();
+    seen.sort_by_key(|(index, ..)| *index);
+    let expected = (0..5)
+        .map(|index| {
+            let (stdout, stderr) = (b"out\n".to_vec(), b"err\n".to_vec());
+            (index, RunStatus::Failed, Some(1), stdout, stderr)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(seen, expected);
+}
+
+#[test]
+fn timeout() {
+    let mut config = RunConfig::new(vec![sh("sleep 10")], 3);
+    config.threads = 3;
+    config.timeout = Some(Duration::from_millis(200));
+    let started = Instant::now();
+    let report = TestRunner::new(config).run().unwrap();
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        started.elapsed()
+    );
+    assert_eq!(report.fail_times, 3);
+    assert_eq!(report.timeout_times, 3);
+}
+
+#[test]
+fn cancelled() {
+    let mut config = RunConfig::new(vec![sh("sleep 10")], 100);
+    config.threads = 2;
+    let runner = TestRunner::new(config);
+    let token = runner.cancellation_token();
+    let started = Instant::now();
+    let report = thread::scope(|scope| {
+        let session = scope.spawn(|| runner.run());
+        thread::sleep(Duration::from_millis(300));
+        token.cancel();
+        runner.interrupt_running();
+        session.join().unwrap()
+    })
+    .unwrap();
+    assert!(
+        started.elapsed() < Duration::from_secs(5),
+        "{:?}",
+        started.elapsed()
+    );
+    // The runs killed by the interrupt are not reported.
+    assert_eq!(report.run_times, 0);
+    assert_eq!(runner.pending().len(), 100);
+}
+
+#[test]
+fn missing_executable() {
+    let step = Step {
+        exec: "no-such-binary-for-tester".to_string(),
+        args: vec![],
+    };
+    match TestRunner::new(RunConfig::new(vec![step], 3)).run() {
+        Err(Error::Spawn { step, .. }) => assert_eq!(step, "no-such-binary-for-tester"),
+        other => panic!("expected a spawn error, got {:?}", other),
+    }
+}